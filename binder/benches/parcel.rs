@@ -0,0 +1,158 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Reading large byte arrays and strings by copying them out of the parcel,
+//! and by borrowing them with `read_bytes_ref` and `read_str_ref`.
+//!
+//! Only host builds borrow; on Android the `_ref` reads copy too.
+//!
+//! Before timing, this prints how many allocations and bytes each kind of read
+//! takes, counted by the global allocator.
+
+use binder_ndk::binder_impl::Parcel;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const SIZES: [usize; 3] = [64 << 10, 1 << 20, 8 << 20];
+
+/// A parcel holding a byte array and a string, each of `size` bytes.
+fn parcel(size: usize) -> Parcel {
+    let mut parcel = Parcel::new();
+    parcel.write(&vec![0x5au8; size]).unwrap();
+    parcel.write(&"a".repeat(size / 2)).unwrap();
+    parcel
+}
+
+fn rewind(parcel: &Parcel) {
+    unsafe {
+        // Safety: 0 is always a valid position.
+        parcel.set_data_position(0).unwrap();
+    }
+}
+
+/// Print the allocations `read` makes.
+fn report(name: &str, size: usize, parcel: &Parcel, read: impl Fn(&Parcel)) {
+    rewind(parcel);
+    let (allocations, bytes) =
+        (ALLOCATIONS.load(Ordering::Relaxed), ALLOCATED_BYTES.load(Ordering::Relaxed));
+    read(parcel);
+    println!(
+        "{}/{}: {} allocations, {} bytes",
+        name,
+        size,
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes
+    );
+}
+
+fn read_bytes(parcel: &Parcel) {
+    black_box(parcel.read::<Vec<u8>>().unwrap());
+}
+
+fn read_bytes_ref(parcel: &Parcel) {
+    black_box(parcel.read_bytes_ref().unwrap());
+}
+
+/// Skip the byte array in front of the string, the same way for both string
+/// reads so that only the string read differs between them.
+fn skip_bytes(parcel: &Parcel) {
+    parcel.read_bytes_ref().unwrap();
+}
+
+fn read_string(parcel: &Parcel) {
+    skip_bytes(parcel);
+    black_box(parcel.read::<String>().unwrap().len());
+}
+
+fn read_str_ref(parcel: &Parcel) {
+    skip_bytes(parcel);
+    // Still look at every character, as most uses of the string would.
+    black_box(parcel.read_str_ref().unwrap().chars().count());
+}
+
+fn bytes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("read_bytes");
+    for size in SIZES {
+        let parcel = parcel(size);
+        report("read::<Vec<u8>>", size, &parcel, read_bytes);
+        report("read_bytes_ref", size, &parcel, read_bytes_ref);
+
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("read::<Vec<u8>>", size), &parcel, |b, parcel| {
+            b.iter(|| {
+                rewind(parcel);
+                read_bytes(parcel)
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("read_bytes_ref", size), &parcel, |b, parcel| {
+            b.iter(|| {
+                rewind(parcel);
+                read_bytes_ref(parcel)
+            })
+        });
+    }
+    group.finish();
+}
+
+fn strings(c: &mut Criterion) {
+    let mut group = c.benchmark_group("read_string");
+    for size in SIZES {
+        let parcel = parcel(size);
+        report("read::<String>", size, &parcel, read_string);
+        report("read_str_ref", size, &parcel, read_str_ref);
+
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("read::<String>", size), &parcel, |b, parcel| {
+            b.iter(|| {
+                rewind(parcel);
+                read_string(parcel)
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("read_str_ref", size), &parcel, |b, parcel| {
+            b.iter(|| {
+                rewind(parcel);
+                read_str_ref(parcel)
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bytes, strings);
+criterion_main!(benches);
@@ -28,7 +28,7 @@ use std::ffi::{c_void, CStr, CString};
 use std::fmt;
 use std::fs::File;
 use std::marker::PhantomData;
-use std::ops::Deref;
+use std::ops::{Deref, RangeInclusive};
 use std::os::raw::c_char;
 use std::os::unix::io::AsRawFd;
 use std::ptr;
@@ -61,9 +61,26 @@ pub trait Interface: Send + Sync {
     ///
     /// This handler is a no-op by default and should be implemented for each
     /// Binder service struct that wishes to respond to dump transactions.
+    /// [`Dumper`](crate::Dumper) parses the usual `dumpsys` flags and formats
+    /// the output.
     fn dump(&self, _file: &File, _args: &[&CStr]) -> Result<()> {
         Ok(())
     }
+
+    /// Shell command handler for this Binder object, run by
+    /// `cmd <service> <args>...`.
+    ///
+    /// Output goes to `stdout` and `stderr`. [`ShellArgs`](crate::ShellArgs)
+    /// helps with parsing `args`. Fails with `INVALID_OPERATION` by default.
+    fn handle_shell_command(
+        &self,
+        _stdin: &File,
+        _stdout: &File,
+        _stderr: &File,
+        _args: &[&CStr],
+    ) -> Result<()> {
+        Err(StatusCode::INVALID_OPERATION)
+    }
 }
 
 /// Implemented by sync interfaces to specify what the associated async interface is.
@@ -97,22 +114,60 @@ where
 
 /// Interface stability promise
 ///
-/// An interface can promise to be a stable vendor interface ([`Vintf`]), or
-/// makes no stability guarantees ([`Local`]). [`Local`] is
-/// currently the default stability.
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// An interface can promise to be stable within the vendor partition
+/// ([`Vendor`]), within the system partition ([`System`]), or between both as a
+/// stable vendor interface ([`Vintf`]). Otherwise it makes no stability
+/// guarantees ([`Local`]), which is currently the default stability.
+///
+/// `Ord` follows the declaration order, so that stabilities can be sorted and
+/// used as map keys, but [`Vendor`] and [`System`] don't promise more than one
+/// another. Use [`Stability::can_be_called_from`] to check whether a binder
+/// may be called from a partition.
+///
+/// [`Local`]: Stability::Local
+/// [`Vendor`]: Stability::Vendor
+/// [`System`]: Stability::System
+/// [`Vintf`]: Stability::Vintf
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Stability {
     /// Default stability, visible to other modules in the same compilation
     /// context (e.g. modules on system.img)
+    #[default]
     Local,
 
+    /// Stable within the vendor partition (vendor.img)
+    Vendor,
+
+    /// Stable within the system partition (system.img)
+    System,
+
     /// A Vendor Interface Object, which promises to be stable
     Vintf,
 }
 
-impl Default for Stability {
-    fn default() -> Self {
-        Stability::Local
+impl Stability {
+    /// The partitions this stability promises to be stable in, as in
+    /// libbinder's `Stability::Level`.
+    fn level(self) -> u8 {
+        match self {
+            Stability::Local => 0,
+            Stability::Vendor => 0b000011,
+            Stability::System => 0b001100,
+            Stability::Vintf => 0b111111,
+        }
+    }
+
+    /// Whether a binder of this stability promises to be stable wherever
+    /// `required` does, so that it can be called from there: [`Vintf`]
+    /// binders can be called from anywhere, [`Vendor`] and [`System`] ones
+    /// only from their own partition, and [`Local`] ones only locally.
+    ///
+    /// [`Local`]: Stability::Local
+    /// [`Vendor`]: Stability::Vendor
+    /// [`System`]: Stability::System
+    /// [`Vintf`]: Stability::Vintf
+    pub fn can_be_called_from(self, required: Stability) -> bool {
+        self.level() & required.level() == required.level()
     }
 }
 
@@ -120,7 +175,9 @@ impl From<Stability> for i32 {
     fn from(stability: Stability) -> i32 {
         use Stability::*;
         match stability {
-            Local => 0,
+            // Parcelables are only ever local or VINTF on the wire, so vendor
+            // and system stability are both written as local.
+            Local | Vendor | System => 0,
             Vintf => 1,
         }
     }
@@ -138,6 +195,71 @@ impl TryFrom<i32> for Stability {
     }
 }
 
+/// Linux scheduling policy for handling incoming transactions, see
+/// `sched(7)`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SchedulerPolicy {
+    /// `SCHED_NORMAL`, with a nice value in `-20..=19`.
+    Normal,
+
+    /// `SCHED_FIFO`, with a realtime priority in `1..=99`.
+    Fifo,
+
+    /// `SCHED_RR`, with a realtime priority in `1..=99`.
+    RoundRobin,
+}
+
+impl SchedulerPolicy {
+    /// The priorities valid for this policy.
+    pub fn priority_range(self) -> RangeInclusive<i32> {
+        match self {
+            SchedulerPolicy::Normal => -20..=19,
+            SchedulerPolicy::Fifo | SchedulerPolicy::RoundRobin => 1..=99,
+        }
+    }
+}
+
+impl From<SchedulerPolicy> for i32 {
+    fn from(policy: SchedulerPolicy) -> i32 {
+        match policy {
+            SchedulerPolicy::Normal => libc::SCHED_OTHER,
+            SchedulerPolicy::Fifo => libc::SCHED_FIFO,
+            SchedulerPolicy::RoundRobin => libc::SCHED_RR,
+        }
+    }
+}
+
+/// A scheduler policy together with a priority valid for it, which transactions
+/// to a local binder are at least handled with.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MinSchedulerPolicy {
+    policy: SchedulerPolicy,
+    priority: i32,
+}
+
+impl MinSchedulerPolicy {
+    /// Create a minimum scheduler policy, or fail with `BAD_VALUE` if
+    /// `priority` is outside of [`SchedulerPolicy::priority_range`].
+    pub fn new(policy: SchedulerPolicy, priority: i32) -> Result<Self> {
+        if policy.priority_range().contains(&priority) {
+            Ok(MinSchedulerPolicy { policy, priority })
+        } else {
+            Err(StatusCode::BAD_VALUE)
+        }
+    }
+
+    /// The scheduling policy.
+    pub fn policy(&self) -> SchedulerPolicy {
+        self.policy
+    }
+
+    /// The nice value for [`SchedulerPolicy::Normal`], or the realtime priority
+    /// otherwise.
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+}
+
 /// A local service that can be remotable via Binder.
 ///
 /// An object that implement this interface made be made into a Binder service
@@ -169,6 +291,18 @@ pub trait Remotable: Send + Sync {
     /// object.
     fn on_dump(&self, file: &File, args: &[&CStr]) -> Result<()>;
 
+    /// Handle a shell command sent to this object. Fails with
+    /// `INVALID_OPERATION` unless overridden.
+    fn on_shell_command(
+        &self,
+        _stdin: &File,
+        _stdout: &File,
+        _stderr: &File,
+        _args: &[&CStr],
+    ) -> Result<()> {
+        Err(StatusCode::INVALID_OPERATION)
+    }
+
     /// Retrieve the class of this remote object.
     ///
     /// This method should always return the same InterfaceClass for the same
@@ -211,6 +345,19 @@ pub trait IBinderInternal: IBinder {
     /// Create a Parcel that can be used with `submit_transact`.
     fn prepare_transact(&self) -> Result<Parcel>;
 
+    /// Create a Parcel that can be used with `submit_transact` and rejects
+    /// file descriptors, for transactions that mustn't carry any. Writing one
+    /// fails with `FDS_NOT_ALLOWED`.
+    ///
+    /// Only available on the host: libbinder_ndk can't restrict parcels, see
+    /// [`Parcel::set_allow_fds`].
+    #[cfg(feature = "host")]
+    fn prepare_transact_without_fds(&self) -> Result<Parcel> {
+        let mut parcel = self.prepare_transact()?;
+        parcel.set_allow_fds(false)?;
+        Ok(parcel)
+    }
+
     /// Perform a generic operation with the object.
     ///
     /// The provided [`Parcel`] must have been created by a call to
@@ -302,11 +449,10 @@ impl InterfaceClass {
             // Safety: `AIBinder_Class_define` expects a valid C string, and
             // three valid callback functions, all non-null pointers. The C
             // string is copied and need not be valid for longer than the call,
-            // so we can drop it after the call. We can safely assign null to
-            // the onDump and handleShellCommand callbacks as long as the class
-            // pointer was non-null. Rust None for a Option<fn> is guaranteed to
-            // be a NULL pointer. Rust retains ownership of the pointer after it
-            // is defined.
+            // so we can drop it after the call. The onDump and
+            // handleShellCommand callbacks can be set as long as the class
+            // pointer was non-null. Rust retains ownership of the pointer after
+            // it is defined.
             let class = sys::AIBinder_Class_define(
                 descriptor.as_ptr(),
                 Some(I::on_create),
@@ -317,7 +463,7 @@ impl InterfaceClass {
                 panic!("Expected non-null class pointer from AIBinder_Class_define!");
             }
             sys::AIBinder_Class_setOnDump(class, Some(I::on_dump));
-            sys::AIBinder_Class_setHandleShellCommand(class, None);
+            sys::AIBinder_Class_setHandleShellCommand(class, Some(I::on_shell_command));
             class
         };
         InterfaceClass(ptr)
@@ -616,6 +762,23 @@ pub trait InterfaceClassMethods {
         args: *mut *const c_char,
         num_args: u32,
     ) -> status_t;
+
+    /// Called to handle a shell command.
+    ///
+    /// # Safety
+    ///
+    /// Must be called with a non-null, valid pointer to a local `AIBinder` that
+    /// contains a `T` pointer in its user data. The fds should be non-owned
+    /// file descriptors, and argv must be an array of null-terminated string
+    /// pointers with length argc.
+    unsafe extern "C" fn on_shell_command(
+        binder: *mut sys::AIBinder,
+        in_: i32,
+        out: i32,
+        err: i32,
+        argv: *mut *const c_char,
+        argc: u32,
+    ) -> status_t;
 }
 
 /// Interface for transforming a generic SpIBinder into a specific remote
@@ -688,6 +851,13 @@ pub struct BinderFeatures {
     /// for `ThreadState::with_calling_sid` to work.
     #[cfg(not(android_vndk))]
     pub set_requesting_sid: bool,
+    /// The minimum scheduler policy to handle incoming transactions with, if any.
+    #[cfg(not(android_vndk))]
+    pub min_scheduler_policy: Option<MinSchedulerPolicy>,
+    /// Indicates that incoming transactions may inherit realtime scheduling policies from their
+    /// callers.
+    #[cfg(not(android_vndk))]
+    pub inherit_rt: bool,
     // Ensure that clients include a ..BinderFeatures::default() to preserve backwards compatibility
     // when new fields are added. #[non_exhaustive] doesn't work because it prevents struct
     // expressions entirely.
@@ -891,6 +1061,12 @@ macro_rules! declare_binder_interface {
                 let mut binder = $crate::binder_impl::Binder::new_with_stability($native(Box::new(inner)), $stability);
                 #[cfg(not(android_vndk))]
                 $crate::binder_impl::IBinderInternal::set_requesting_sid(&mut binder, features.set_requesting_sid);
+                #[cfg(not(android_vndk))]
+                if let Some(policy) = features.min_scheduler_policy {
+                    binder.set_min_scheduler_policy(policy);
+                }
+                #[cfg(not(android_vndk))]
+                binder.set_inherit_rt(features.inherit_rt);
                 $crate::Strong::new(Box::new(binder))
             }
         }
@@ -918,6 +1094,10 @@ macro_rules! declare_binder_interface {
                 self.0.dump(file, args)
             }
 
+            fn on_shell_command(&self, stdin: &std::fs::File, stdout: &std::fs::File, stderr: &std::fs::File, args: &[&std::ffi::CStr]) -> std::result::Result<(), $crate::StatusCode> {
+                self.0.handle_shell_command(stdin, stdout, stderr, args)
+            }
+
             fn get_class() -> $crate::binder_impl::InterfaceClass {
                 static CLASS_INIT: std::sync::Once = std::sync::Once::new();
                 static mut CLASS: Option<$crate::binder_impl::InterfaceClass> = None;
@@ -1141,3 +1321,116 @@ macro_rules! declare_binder_enum {
         }
     };
 }
+
+/// Declare an AIDL union.
+///
+/// The union becomes an enum with a variant for each field, and `$tag` a
+/// module with the `i32` tag of each field. A union is written as the tag of
+/// its field followed by the field's value, and reading an unknown tag fails
+/// with `BAD_VALUE`. The default value is the first field, with its default
+/// if one is given.
+///
+/// This is mainly used internally by the AIDL compiler.
+///
+/// # Examples
+///
+/// ```
+/// binder_ndk::declare_binder_union! {
+///     #[derive(Debug, Clone, PartialEq)]
+///     Value["com.example.Value"] {
+///         tag: ValueTag,
+///         fields: {
+///             number = 0 => Number(i32) = 1,
+///             text = 1 => Text(String),
+///         },
+///     }
+/// }
+///
+/// assert_eq!(Value::default(), Value::Number(1));
+/// assert_eq!(ValueTag::text, 1);
+/// ```
+#[macro_export]
+macro_rules! declare_binder_union {
+    (@default) => {
+        Default::default()
+    };
+
+    (@default $default:expr) => {
+        $default
+    };
+
+    {
+        $( #[$attr:meta] )*
+        $union:ident[$descriptor:expr] {
+            tag: $tag:ident,
+            fields: {
+                $( #[$first_attr:meta] )*
+                $first_name:ident = $first_value:expr => $first:ident($first_ty:ty) $( = $default:expr )?,
+                $( $( #[$field_attr:meta] )* $name:ident = $value:expr => $variant:ident($ty:ty), )*
+            },
+            $( stability: $stability:expr, )?
+        }
+    } => {
+        $( #[$attr] )*
+        pub enum $union {
+            $( #[$first_attr] )*
+            $first($first_ty),
+            $( $( #[$field_attr] )* $variant($ty), )*
+        }
+
+        /// The tags written before the value of each field of the union.
+        #[allow(missing_docs, non_snake_case, non_upper_case_globals)]
+        pub mod $tag {
+            pub const $first_name: i32 = $first_value;
+            $( pub const $name: i32 = $value; )*
+        }
+
+        impl Default for $union {
+            fn default() -> Self {
+                Self::$first($crate::declare_binder_union!(@default $( $default )?))
+            }
+        }
+
+        impl $crate::Parcelable for $union {
+            fn write_to_parcel(&self, parcel: &mut $crate::binder_impl::BorrowedParcel<'_>) -> std::result::Result<(), $crate::StatusCode> {
+                match self {
+                    Self::$first(value) => {
+                        parcel.write(&$tag::$first_name)?;
+                        parcel.write(value)
+                    }
+                    $(
+                        Self::$variant(value) => {
+                            parcel.write(&$tag::$name)?;
+                            parcel.write(value)
+                        }
+                    )*
+                }
+            }
+
+            fn read_from_parcel(&mut self, parcel: &$crate::binder_impl::BorrowedParcel<'_>) -> std::result::Result<(), $crate::StatusCode> {
+                let tag: i32 = parcel.read()?;
+                match tag {
+                    $tag::$first_name => *self = Self::$first(parcel.read()?),
+                    $( $tag::$name => *self = Self::$variant(parcel.read()?), )*
+                    _ => return Err($crate::StatusCode::BAD_VALUE),
+                }
+                Ok(())
+            }
+        }
+
+        $crate::impl_serialize_for_parcelable!($union);
+        $crate::impl_deserialize_for_parcelable!($union);
+
+        impl $crate::binder_impl::ParcelableMetadata for $union {
+            fn get_descriptor() -> &'static str {
+                $descriptor
+            }
+
+            $(
+                fn get_stability(&self) -> $crate::binder_impl::Stability {
+                    $stability
+                }
+            )?
+        }
+    };
+}
@@ -0,0 +1,264 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! A list of remote callbacks that forgets the ones whose process dies, like
+//! Java's `android.os.RemoteCallbackList`.
+
+use crate::binder::{FromIBinder, IBinder, IBinderInternal, Strong};
+use crate::error::{Result, Status, StatusCode};
+use crate::proxy::{DeathRecipient, SpIBinder};
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, Weak};
+
+/// Callbacks registered with a service, such as listeners, keyed by binder
+/// identity.
+///
+/// Each callback is linked to death when it is registered, and dropped from
+/// the list when its process dies, so services don't have to manage
+/// [`DeathRecipient`]s themselves. A cookie of type `C` can be kept with each
+/// callback.
+///
+/// ```
+/// use binder_ndk::{RemoteCallbackList, Result, Strong};
+/// # use binder_ndk::binder_impl::{Binder, BorrowedParcel, TransactionCode};
+/// # use binder_ndk::{declare_binder_interface, Interface, StatusCode};
+/// #
+/// # pub trait IListener: Interface {
+/// #     fn on_event(&self, event: i32) -> Result<()>;
+/// # }
+/// #
+/// # declare_binder_interface! {
+/// #     IListener["IListener"] {
+/// #         native: BnListener(on_transact),
+/// #         proxy: BpListener,
+/// #     }
+/// # }
+/// #
+/// # fn on_transact(
+/// #     _service: &dyn IListener,
+/// #     _code: TransactionCode,
+/// #     _data: &BorrowedParcel,
+/// #     _reply: &mut BorrowedParcel,
+/// # ) -> std::result::Result<(), StatusCode> {
+/// #     Err(StatusCode::UNKNOWN_TRANSACTION)
+/// # }
+/// #
+/// # impl IListener for BpListener {
+/// #     fn on_event(&self, _event: i32) -> Result<()> {
+/// #         Ok(())
+/// #     }
+/// # }
+/// #
+/// # impl IListener for Binder<BnListener> {
+/// #     fn on_event(&self, event: i32) -> Result<()> {
+/// #         self.0.on_event(event)
+/// #     }
+/// # }
+///
+/// struct Service {
+///     listeners: RemoteCallbackList<dyn IListener, String>,
+/// }
+///
+/// impl Service {
+///     fn add_listener(&self, listener: Strong<dyn IListener>, name: String) -> Result<()> {
+///         Ok(self.listeners.register_with_cookie(listener, name)?)
+///     }
+///
+///     fn notify(&self, event: i32) {
+///         for (_listener, name, status) in
+///             self.listeners.broadcast(|listener, _| listener.on_event(event))
+///         {
+///             eprintln!("Listener {} failed: {}", name, status);
+///         }
+///     }
+/// }
+/// ```
+pub struct RemoteCallbackList<I: FromIBinder + ?Sized, C = ()> {
+    callbacks: Arc<Mutex<Callbacks<I, C>>>,
+}
+
+type Callbacks<I, C> = BTreeMap<SpIBinder, Registration<I, C>>;
+
+struct Registration<I: FromIBinder + ?Sized, C> {
+    callback: Arc<(Strong<I>, C)>,
+    /// `None` for local callbacks, which can't die.
+    recipient: Option<DeathRecipient>,
+}
+
+impl<I: FromIBinder + ?Sized, C> Drop for Registration<I, C> {
+    fn drop(&mut self) {
+        if let Some(recipient) = &mut self.recipient {
+            // The binder may already be dead, in which case there is nothing
+            // to unlink.
+            let _ = self.callback.0.as_binder().unlink_to_death(recipient);
+        }
+    }
+}
+
+impl<I, C> RemoteCallbackList<I, C>
+where
+    I: FromIBinder + ?Sized + 'static,
+    C: Send + Sync + 'static,
+{
+    /// Create an empty list.
+    pub fn new() -> Self {
+        Self { callbacks: Arc::new(Mutex::new(BTreeMap::new())) }
+    }
+
+    /// Add a callback with a cookie, replacing its registration if it is
+    /// already in the list.
+    ///
+    /// Fails with `DEAD_OBJECT` if the callback's process has already died.
+    pub fn register_with_cookie(&self, callback: Strong<I>, cookie: C) -> Result<()> {
+        let mut binder = callback.as_binder();
+        let recipient = if binder.is_remote() {
+            let callbacks = Arc::downgrade(&self.callbacks);
+            let mut recipient = DeathRecipient::new(move || Self::remove_dead(&callbacks));
+            binder.link_to_death(&mut recipient)?;
+            Some(recipient)
+        } else {
+            None
+        };
+        let registration = Registration { callback: Arc::new((callback, cookie)), recipient };
+        let replaced = self.callbacks.lock().unwrap().insert(binder, registration);
+        // Unlink the old registration after releasing the lock.
+        drop(replaced);
+        Ok(())
+    }
+
+    /// Remove a callback, returning its cookie if it was in the list.
+    pub fn unregister(&self, callback: &Strong<I>) -> Option<C>
+    where
+        C: Clone,
+    {
+        let registration = self.callbacks.lock().unwrap().remove(&callback.as_binder())?;
+        Some(registration.callback.1.clone())
+    }
+
+    /// Whether `callback` is in the list.
+    pub fn contains(&self, callback: &Strong<I>) -> bool {
+        self.callbacks.lock().unwrap().contains_key(&callback.as_binder())
+    }
+
+    /// The number of callbacks in the list.
+    pub fn len(&self) -> usize {
+        self.callbacks.lock().unwrap().len()
+    }
+
+    /// Whether the list is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove every callback.
+    pub fn clear(&self) {
+        let callbacks = std::mem::take(&mut *self.callbacks.lock().unwrap());
+        drop(callbacks);
+    }
+
+    /// Call `f` for every callback in the list, and return the callbacks it
+    /// failed for with their cookies and errors.
+    ///
+    /// Callbacks that fail with `DEAD_OBJECT` are removed from the list rather
+    /// than returned, in case their death notification hasn't arrived yet.
+    /// The list isn't locked while `f` runs, so `f` may register and
+    /// unregister callbacks.
+    pub fn broadcast<F>(&self, mut f: F) -> Vec<(Strong<I>, C, Status)>
+    where
+        F: FnMut(&Strong<I>, &C) -> crate::Result<()>,
+        C: Clone,
+    {
+        let snapshot: Vec<_> = self
+            .callbacks
+            .lock()
+            .unwrap()
+            .values()
+            .map(|registration| registration.callback.clone())
+            .collect();
+        let mut errors = Vec::new();
+        for callback in snapshot {
+            let (strong, cookie) = &*callback;
+            match f(strong, cookie) {
+                Ok(()) => {}
+                Err(status) if status.transaction_error() == StatusCode::DEAD_OBJECT => {
+                    self.remove_registration(&callback);
+                }
+                Err(status) => errors.push((strong.clone(), cookie.clone(), status)),
+            }
+        }
+        errors
+    }
+
+    /// Remove `callback` if it is still the one registered for its binder.
+    fn remove_registration(&self, callback: &Arc<(Strong<I>, C)>) {
+        let binder = callback.0.as_binder();
+        let mut callbacks = self.callbacks.lock().unwrap();
+        let removed = match callbacks.get(&binder) {
+            Some(registration) if Arc::ptr_eq(&registration.callback, callback) => {
+                callbacks.remove(&binder)
+            }
+            _ => None,
+        };
+        drop(callbacks);
+        drop(removed);
+    }
+
+    /// Death notification: forget every callback whose binder is dead.
+    fn remove_dead(callbacks: &Weak<Mutex<Callbacks<I, C>>>) {
+        let Some(callbacks) = callbacks.upgrade() else {
+            return;
+        };
+        let mut callbacks = callbacks.lock().unwrap();
+        let dead: Vec<SpIBinder> =
+            callbacks.keys().filter(|binder| !binder.is_binder_alive()).cloned().collect();
+        let removed: Vec<_> = dead.iter().filter_map(|binder| callbacks.remove(binder)).collect();
+        drop(callbacks);
+        drop(removed);
+    }
+}
+
+impl<I> RemoteCallbackList<I>
+where
+    I: FromIBinder + ?Sized + 'static,
+{
+    /// Add a callback without a cookie, replacing its registration if it is
+    /// already in the list.
+    ///
+    /// Fails with `DEAD_OBJECT` if the callback's process has already died.
+    pub fn register(&self, callback: Strong<I>) -> Result<()> {
+        self.register_with_cookie(callback, ())
+    }
+}
+
+impl<I, C> Default for RemoteCallbackList<I, C>
+where
+    I: FromIBinder + ?Sized + 'static,
+    C: Send + Sync + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I: FromIBinder + ?Sized, C> fmt::Debug for RemoteCallbackList<I, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemoteCallbackList")
+            .field("len", &self.callbacks.lock().unwrap().len())
+            .finish()
+    }
+}
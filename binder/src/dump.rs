@@ -0,0 +1,529 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Structured output for `dumpsys`, built on [`Interface::dump`].
+//!
+//! A service describes its state as named sections of fields, and [`Dumper`]
+//! picks the sections and the format from the dump arguments:
+//!
+//! * `--section <name>` only dumps the named sections, and may be repeated.
+//! * `-a` asks for everything, see [`DumpSection::all`].
+//! * `--json` renders JSON rather than indented text.
+//! * `--proto` is recognised, but fails with `INVALID_OPERATION` as there is
+//!   no schema to encode sections with.
+//!
+//! Other arguments are left to the service, in [`DumpArgs::rest`].
+//!
+//! [`Interface::dump`]: crate::Interface::dump
+
+use crate::binder::IBinderInternal;
+use crate::error::{status_result, Result, StatusCode};
+use crate::proxy::SpIBinder;
+
+use std::ffi::CStr;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::FromRawFd;
+use std::thread;
+
+/// How a dump is rendered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// Indented `name: value` lines, for people.
+    #[default]
+    Text,
+    /// A JSON object with a member per section.
+    Json,
+    /// A protobuf message, which [`Dumper`] doesn't support.
+    Proto,
+}
+
+/// Dump arguments, parsed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DumpArgs {
+    format: DumpFormat,
+    all: bool,
+    sections: Vec<String>,
+    rest: Vec<String>,
+}
+
+impl DumpArgs {
+    /// Parse the arguments passed to [`Interface::dump`].
+    ///
+    /// Fails with `BAD_VALUE` if an argument is not UTF-8, `--section` has no
+    /// name, or more than one format is asked for.
+    ///
+    /// [`Interface::dump`]: crate::Interface::dump
+    pub fn parse(args: &[&CStr]) -> Result<Self> {
+        let mut parsed = Self::default();
+        let mut format = None;
+        let mut args = args.iter().map(|arg| arg.to_str().or(Err(StatusCode::BAD_VALUE)));
+        while let Some(arg) = args.next() {
+            let arg = arg?;
+            let arg_format = match arg {
+                "--json" => DumpFormat::Json,
+                "--proto" => DumpFormat::Proto,
+                "-a" => {
+                    parsed.all = true;
+                    continue;
+                }
+                "--section" => {
+                    let name = args.next().ok_or(StatusCode::BAD_VALUE)??;
+                    parsed.sections.push(name.to_owned());
+                    continue;
+                }
+                _ => {
+                    parsed.rest.push(arg.to_owned());
+                    continue;
+                }
+            };
+            if format.replace(arg_format).map_or(false, |format| format != arg_format) {
+                return Err(StatusCode::BAD_VALUE);
+            }
+        }
+        parsed.format = format.unwrap_or_default();
+        Ok(parsed)
+    }
+
+    /// The format asked for.
+    pub fn format(&self) -> DumpFormat {
+        self.format
+    }
+
+    /// Whether `-a` was passed.
+    pub fn all(&self) -> bool {
+        self.all
+    }
+
+    /// The sections asked for with `--section`, or empty for all of them.
+    pub fn sections(&self) -> &[String] {
+        &self.sections
+    }
+
+    /// The arguments that aren't flags of the dump framework.
+    pub fn rest(&self) -> &[String] {
+        &self.rest
+    }
+
+    fn wants(&self, section: &str) -> bool {
+        self.sections.is_empty() || self.sections.iter().any(|s| s == section)
+    }
+}
+
+/// A value of a field in a dump.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DumpValue {
+    /// No value, such as `None`.
+    Null,
+    /// A boolean.
+    Bool(bool),
+    /// A signed integer.
+    Int(i64),
+    /// An unsigned integer.
+    UInt(u64),
+    /// A floating point number. Non-finite numbers are `null` in JSON.
+    Float(f64),
+    /// A string.
+    Str(String),
+    /// A list of values.
+    List(Vec<DumpValue>),
+    /// Named fields, in order.
+    Fields(Vec<(String, DumpValue)>),
+}
+
+macro_rules! impl_dump_value_from {
+    ($variant:ident($as:ty): $($ty:ty),*) => {
+        $(
+            impl From<$ty> for DumpValue {
+                fn from(value: $ty) -> Self {
+                    DumpValue::$variant(value as $as)
+                }
+            }
+        )*
+    };
+}
+
+impl_dump_value_from!(Int(i64): i8, i16, i32, i64, isize);
+impl_dump_value_from!(UInt(u64): u8, u16, u32, u64, usize);
+impl_dump_value_from!(Float(f64): f32, f64);
+
+impl From<bool> for DumpValue {
+    fn from(value: bool) -> Self {
+        DumpValue::Bool(value)
+    }
+}
+
+impl From<&str> for DumpValue {
+    fn from(value: &str) -> Self {
+        DumpValue::Str(value.to_owned())
+    }
+}
+
+impl From<String> for DumpValue {
+    fn from(value: String) -> Self {
+        DumpValue::Str(value)
+    }
+}
+
+impl<T: Into<DumpValue>> From<Vec<T>> for DumpValue {
+    fn from(value: Vec<T>) -> Self {
+        DumpValue::List(value.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<DumpValue>> From<Option<T>> for DumpValue {
+    /// `None` is dumped as `null`.
+    fn from(value: Option<T>) -> Self {
+        match value {
+            Some(value) => value.into(),
+            None => DumpValue::Null,
+        }
+    }
+}
+
+/// The fields of a section of a dump, or of a group within one.
+#[derive(Debug)]
+pub struct DumpSection {
+    all: bool,
+    fields: Vec<(String, DumpValue)>,
+}
+
+impl DumpSection {
+    /// Whether `-a` was passed, so the section should include details it
+    /// leaves out by default.
+    pub fn all(&self) -> bool {
+        self.all
+    }
+
+    /// Add a field.
+    pub fn field(&mut self, name: &str, value: impl Into<DumpValue>) -> &mut Self {
+        self.fields.push((name.to_owned(), value.into()));
+        self
+    }
+
+    /// Add a group of fields, nested under `name`.
+    pub fn group(&mut self, name: &str, f: impl FnOnce(&mut DumpSection)) -> &mut Self {
+        let mut group = DumpSection { all: self.all, fields: vec![] };
+        f(&mut group);
+        self.fields.push((name.to_owned(), DumpValue::Fields(group.fields)));
+        self
+    }
+}
+
+/// Collects the sections of a dump and writes them out in the format asked
+/// for.
+///
+/// ```
+/// # use binder_ndk::{Dumper, Interface, StatusCode};
+/// # use std::ffi::CStr;
+/// # use std::fs::File;
+/// # struct MyService { clients: Vec<String> }
+/// impl Interface for MyService {
+///     fn dump(&self, file: &File, args: &[&CStr]) -> Result<(), StatusCode> {
+///         Dumper::new(args)?
+///             .section("clients", |s| {
+///                 s.field("count", self.clients.len());
+///                 if s.all() {
+///                     s.field("names", self.clients.clone());
+///                 }
+///             })
+///             .dump(file)
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Dumper {
+    args: DumpArgs,
+    sections: Vec<(String, Vec<(String, DumpValue)>)>,
+    unknown: Vec<String>,
+}
+
+impl Dumper {
+    /// Start a dump with the arguments passed to [`Interface::dump`].
+    ///
+    /// [`Interface::dump`]: crate::Interface::dump
+    pub fn new(args: &[&CStr]) -> Result<Self> {
+        Ok(Self::with_args(DumpArgs::parse(args)?))
+    }
+
+    /// Start a dump with arguments that were already parsed.
+    pub fn with_args(args: DumpArgs) -> Self {
+        let unknown = args.sections.clone();
+        Self { args, sections: vec![], unknown }
+    }
+
+    /// The parsed dump arguments.
+    pub fn args(&self) -> &DumpArgs {
+        &self.args
+    }
+
+    /// Add a section, unless `--section` left it out, in which case `f` isn't
+    /// called.
+    pub fn section(mut self, name: &str, f: impl FnOnce(&mut DumpSection)) -> Self {
+        if self.args.wants(name) {
+            self.unknown.retain(|s| s != name);
+            let mut section = DumpSection { all: self.args.all, fields: vec![] };
+            f(&mut section);
+            self.sections.push((name.to_owned(), section.fields));
+        }
+        self
+    }
+
+    /// Write the dump to `file`.
+    ///
+    /// Fails with `BAD_VALUE` if `--section` named a section that wasn't
+    /// added, and with `INVALID_OPERATION` for `--proto`.
+    pub fn dump(self, mut file: &File) -> Result<()> {
+        let output = self.render()?;
+        file.write_all(output.as_bytes()).or(Err(StatusCode::UNKNOWN_ERROR))
+    }
+
+    /// Render the dump, as [`Dumper::dump`] would write it.
+    pub fn render(&self) -> Result<String> {
+        if !self.unknown.is_empty() {
+            return Err(StatusCode::BAD_VALUE);
+        }
+        let mut out = String::new();
+        match self.args.format {
+            DumpFormat::Text => {
+                for (name, fields) in &self.sections {
+                    out.push_str(name);
+                    out.push_str(":\n");
+                    write_text_fields(&mut out, fields, 1);
+                }
+            }
+            DumpFormat::Json => {
+                let sections = self
+                    .sections
+                    .iter()
+                    .map(|(name, fields)| (name.clone(), DumpValue::Fields(fields.clone())))
+                    .collect();
+                write_json(&mut out, &DumpValue::Fields(sections));
+                out.push('\n');
+            }
+            DumpFormat::Proto => return Err(StatusCode::INVALID_OPERATION),
+        }
+        Ok(out)
+    }
+}
+
+fn write_text_fields(out: &mut String, fields: &[(String, DumpValue)], depth: usize) {
+    for (name, value) in fields {
+        out.extend(std::iter::repeat("  ").take(depth));
+        out.push_str(name);
+        out.push(':');
+        if let DumpValue::Fields(fields) = value {
+            out.push('\n');
+            write_text_fields(out, fields, depth + 1);
+        } else {
+            out.push(' ');
+            write_text_value(out, value);
+            out.push('\n');
+        }
+    }
+}
+
+fn write_text_value(out: &mut String, value: &DumpValue) {
+    match value {
+        DumpValue::Null => out.push_str("null"),
+        DumpValue::Bool(value) => write!(out, "{}", value).unwrap(),
+        DumpValue::Int(value) => write!(out, "{}", value).unwrap(),
+        DumpValue::UInt(value) => write!(out, "{}", value).unwrap(),
+        DumpValue::Float(value) => write!(out, "{}", value).unwrap(),
+        DumpValue::Str(value) => out.push_str(value),
+        DumpValue::List(values) => {
+            out.push('[');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_text_value(out, value);
+            }
+            out.push(']');
+        }
+        DumpValue::Fields(fields) => {
+            out.push('{');
+            for (i, (name, value)) in fields.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write!(out, "{}: ", name).unwrap();
+                write_text_value(out, value);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn write_json(out: &mut String, value: &DumpValue) {
+    match value {
+        DumpValue::Null => out.push_str("null"),
+        DumpValue::Bool(value) => write!(out, "{}", value).unwrap(),
+        DumpValue::Int(value) => write!(out, "{}", value).unwrap(),
+        DumpValue::UInt(value) => write!(out, "{}", value).unwrap(),
+        DumpValue::Float(value) if value.is_finite() => write!(out, "{:?}", value).unwrap(),
+        DumpValue::Float(_) => out.push_str("null"),
+        DumpValue::Str(value) => write_json_string(out, value),
+        DumpValue::List(values) => {
+            out.push('[');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_json(out, value);
+            }
+            out.push(']');
+        }
+        DumpValue::Fields(fields) => {
+            out.push('{');
+            for (i, (name, value)) in fields.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_json_string(out, name);
+                out.push(':');
+                write_json(out, value);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn write_json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Dump `binder` with `args`, and return what it wrote.
+///
+/// The dump is written to a pipe that is read on another thread, so it may be
+/// larger than the pipe's buffer. Output that isn't UTF-8 is converted
+/// lossily.
+pub fn dump_to_string(binder: &SpIBinder, args: &[&str]) -> Result<String> {
+    let mut fds = [0; 2];
+    status_result(unsafe {
+        // Safety: `fds` has room for the two descriptors `pipe2` writes.
+        if libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) == 0 {
+            0
+        } else {
+            -io::Error::last_os_error().raw_os_error().unwrap_or(libc::EINVAL)
+        }
+    })?;
+    // Safety: `pipe2` succeeded, so these are open descriptors that nothing
+    // else owns.
+    let (mut read_end, write_end) =
+        unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+
+    let reader = thread::spawn(move || {
+        let mut output = Vec::new();
+        read_end.read_to_end(&mut output).map(|_| output)
+    });
+    let result = binder.clone().dump(&write_end, args);
+    // Close our end, so the reader sees the end of the dump once the service
+    // has closed its copy too.
+    drop(write_end);
+    let output = reader.join().unwrap().or(Err(StatusCode::UNKNOWN_ERROR))?;
+    result?;
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&'static [u8]]) -> Vec<&'static CStr> {
+        args.iter().map(|arg| CStr::from_bytes_with_nul(arg).unwrap()).collect()
+    }
+
+    fn dump(args: &[&CStr]) -> Result<String> {
+        Dumper::new(args)?
+            .section("config", |s| {
+                s.field("name", "test \"service\"").field("level", 3u8);
+                s.group("limits", |s| {
+                    s.field("max", -1).field("ratio", 0.5);
+                });
+            })
+            .section("clients", |s| {
+                s.field("count", 2usize);
+                if s.all() {
+                    s.field("pids", vec![10, 11]).field("owner", None::<&str>);
+                }
+            })
+            .render()
+    }
+
+    #[test]
+    fn test_dump_args() {
+        let parsed = DumpArgs::parse(&args(&[
+            b"-a\0",
+            b"--section\0",
+            b"foo\0",
+            b"--json\0",
+            b"verbose\0",
+            b"--json\0",
+        ]))
+        .unwrap();
+        assert_eq!(parsed.format(), DumpFormat::Json);
+        assert!(parsed.all());
+        assert_eq!(parsed.sections(), ["foo"]);
+        assert_eq!(parsed.rest(), ["verbose"]);
+
+        assert_eq!(DumpArgs::parse(&[]).unwrap(), DumpArgs::default());
+        assert_eq!(DumpArgs::parse(&args(&[b"--section\0"])), Err(StatusCode::BAD_VALUE));
+        assert_eq!(
+            DumpArgs::parse(&args(&[b"--json\0", b"--proto\0"])),
+            Err(StatusCode::BAD_VALUE)
+        );
+        assert_eq!(DumpArgs::parse(&args(&[b"\xff\0"])), Err(StatusCode::BAD_VALUE));
+    }
+
+    #[test]
+    fn test_dump_text() {
+        assert_eq!(
+            dump(&[]).unwrap(),
+            "config:\n  name: test \"service\"\n  level: 3\n  limits:\n    max: -1\n    ratio: 0.5\n\
+             clients:\n  count: 2\n"
+        );
+        assert_eq!(
+            dump(&args(&[b"-a\0", b"--section\0", b"clients\0"])).unwrap(),
+            "clients:\n  count: 2\n  pids: [10, 11]\n  owner: null\n"
+        );
+        assert_eq!(dump(&args(&[b"--section\0", b"nope\0"])), Err(StatusCode::BAD_VALUE));
+    }
+
+    #[test]
+    fn test_dump_json() {
+        assert_eq!(
+            dump(&args(&[b"--json\0", b"-a\0"])).unwrap(),
+            "{\"config\":{\"name\":\"test \\\"service\\\"\",\"level\":3,\
+             \"limits\":{\"max\":-1,\"ratio\":0.5}},\
+             \"clients\":{\"count\":2,\"pids\":[10,11],\"owner\":null}}\n"
+        );
+        assert_eq!(dump(&args(&[b"--proto\0"])), Err(StatusCode::INVALID_OPERATION));
+    }
+}
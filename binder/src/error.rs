@@ -20,6 +20,7 @@ use crate::sys;
 use std::error;
 use std::ffi::{CStr, CString};
 use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::io;
 use std::result;
 
 pub use sys::binder_status_t as status_t;
@@ -92,7 +93,14 @@ fn parse_exception_code(code: i32) -> ExceptionCode {
 /// track of and chain binder errors along with service specific errors.
 ///
 /// Used in AIDL transactions to represent failed transactions.
-pub struct Status(*mut sys::AStatus);
+///
+/// A `Status` made locally can hold the Rust error that caused it, as its
+/// [`source`](error::Error::source). Only the codes and message are sent to
+/// the other side of a transaction.
+pub struct Status {
+    ptr: *mut sys::AStatus,
+    source: Option<Box<dyn error::Error + Send + Sync + 'static>>,
+}
 
 // Safety: The `AStatus` that the `Status` points to must have an entirely thread-safe API for the
 // duration of the `Status` object's lifetime. We ensure this by not allowing mutation of a `Status`
@@ -101,7 +109,7 @@ pub struct Status(*mut sys::AStatus);
 unsafe impl Sync for Status {}
 
 // Safety: `Status` always contains an owning pointer to a global, immutable, interned `AStatus`.
-// A thread-local `AStatus` would not be valid.
+// A thread-local `AStatus` would not be valid. The source is `Send` and `Sync` itself.
 unsafe impl Send for Status {}
 
 fn to_cstring<T: AsRef<str>>(message: T) -> Option<CString> {
@@ -119,7 +127,7 @@ impl Status {
             // Rust takes ownership of the returned pointer.
             sys::AStatus_newOk()
         };
-        Self(ptr)
+        Self { ptr, source: None }
     }
 
     /// Create a status object from a service specific error
@@ -147,7 +155,7 @@ impl Status {
                 sys::AStatus_fromServiceSpecificError(err)
             }
         };
-        Self(ptr)
+        Self { ptr, source: None }
     }
 
     /// Creates a status object from a service specific error.
@@ -161,7 +169,7 @@ impl Status {
             let ptr = unsafe {
                 sys::AStatus_fromExceptionCodeWithMessage(exception as i32, message.as_ptr())
             };
-            Self(ptr)
+            Self { ptr, source: None }
         } else {
             exception.into()
         }
@@ -175,13 +183,37 @@ impl Status {
         Self::new_exception(exception, message.and_then(to_cstring).as_deref())
     }
 
+    /// Creates the `ILLEGAL_ARGUMENT` exception Java throws when a file
+    /// descriptor is written to a parcel that doesn't allow them.
+    ///
+    /// Writing the fd itself fails with a bare `FDS_NOT_ALLOWED`, which
+    /// converts into a transaction error like any other [`StatusCode`]. Return
+    /// this instead where the failure should read as the caller's mistake.
+    pub fn fds_not_allowed() -> Status {
+        Self::new_exception_str(
+            ExceptionCode::ILLEGAL_ARGUMENT,
+            Some("Not allowed to write file descriptors here"),
+        )
+    }
+
+    /// Start building a status from an exception or service specific error,
+    /// a message and a source error.
+    pub fn builder() -> StatusBuilder {
+        StatusBuilder {
+            exception: ExceptionCode::ILLEGAL_STATE,
+            service_specific_error: 0,
+            message: None,
+            source: None,
+        }
+    }
+
     /// Create a status object from a raw `AStatus` pointer.
     ///
     /// # Safety
     ///
     /// This constructor is safe iff `ptr` is a valid pointer to an `AStatus`.
     pub(crate) unsafe fn from_ptr(ptr: *mut sys::AStatus) -> Self {
-        Self(ptr)
+        Self { ptr, source: None }
     }
 
     /// Returns `true` if this status represents a successful transaction.
@@ -222,6 +254,23 @@ impl Status {
         description
     }
 
+    /// Returns the message of the status, without the codes that
+    /// [`get_description`](Self::get_description) adds, or `None` if it has
+    /// none.
+    pub fn message(&self) -> Option<String> {
+        let message = unsafe {
+            // Safety: `Status` always contains a valid `AStatus` pointer, so we
+            // are always passing a valid pointer to `AStatus_getMessage` here.
+            //
+            // `AStatus_getMessage` always returns a valid pointer to a null
+            // terminated C string, which lives as long as the `AStatus`. We
+            // copy it into an owned string before returning.
+            CStr::from_ptr(sys::AStatus_getMessage(self.as_native()))
+        };
+        let message = message.to_string_lossy();
+        (!message.is_empty()).then(|| message.into_owned())
+    }
+
     /// Returns the exception code of the status.
     pub fn exception_code(&self) -> ExceptionCode {
         let code = unsafe {
@@ -276,7 +325,11 @@ impl Status {
     }
 }
 
-impl error::Error for Status {}
+impl error::Error for Status {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.source.as_deref().map(|source| source as &(dyn error::Error + 'static))
+    }
+}
 
 impl Display for Status {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
@@ -326,7 +379,7 @@ impl From<status_t> for Status {
             // UNKNOWN_ERROR.
             sys::AStatus_fromStatus(status)
         };
-        Self(ptr)
+        Self { ptr, source: None }
     }
 }
 
@@ -338,7 +391,94 @@ impl From<ExceptionCode> for Status {
             // Unknown values will be coerced into EX_TRANSACTION_FAILED.
             sys::AStatus_fromExceptionCode(code as i32)
         };
-        Self(ptr)
+        Self { ptr, source: None }
+    }
+}
+
+/// The exception for an I/O error, as the Java equivalent would throw.
+fn io_exception_code(kind: io::ErrorKind) -> ExceptionCode {
+    match kind {
+        io::ErrorKind::PermissionDenied => ExceptionCode::SECURITY,
+        io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => ExceptionCode::ILLEGAL_ARGUMENT,
+        io::ErrorKind::Unsupported => ExceptionCode::UNSUPPORTED_OPERATION,
+        _ => ExceptionCode::ILLEGAL_STATE,
+    }
+}
+
+/// `PermissionDenied` becomes a `SECURITY` exception, `InvalidInput` and
+/// `InvalidData` `ILLEGAL_ARGUMENT`, `Unsupported` `UNSUPPORTED_OPERATION` and
+/// anything else `ILLEGAL_STATE`. The error is kept as the source.
+impl From<io::Error> for Status {
+    fn from(err: io::Error) -> Status {
+        Status::builder().exception(io_exception_code(err.kind())).source(err).build()
+    }
+}
+
+/// Recovers a `Status` that was wrapped in the error, and otherwise converts it
+/// as for `io::Error` if it is one, or into `ILLEGAL_STATE` if not. The message
+/// includes the error's whole chain of causes.
+#[cfg(feature = "anyhow")]
+impl From<anyhow::Error> for Status {
+    fn from(err: anyhow::Error) -> Status {
+        let err = match err.downcast::<Status>() {
+            Ok(status) => return status,
+            Err(err) => err,
+        };
+        let exception = err
+            .downcast_ref::<io::Error>()
+            .map_or(ExceptionCode::ILLEGAL_STATE, |err| io_exception_code(err.kind()));
+        Status::builder().exception(exception).message(format!("{:#}", err)).source(err).build()
+    }
+}
+
+/// Builder for a [`Status`], from [`Status::builder`].
+#[derive(Debug)]
+pub struct StatusBuilder {
+    exception: ExceptionCode,
+    service_specific_error: i32,
+    message: Option<String>,
+    source: Option<Box<dyn error::Error + Send + Sync + 'static>>,
+}
+
+impl StatusBuilder {
+    /// Set the exception code, which defaults to `ILLEGAL_STATE`.
+    pub fn exception(mut self, exception: ExceptionCode) -> Self {
+        self.exception = exception;
+        self
+    }
+
+    /// Make the status a service specific error with code `err`.
+    pub fn service_specific_error(mut self, err: i32) -> Self {
+        self.exception = ExceptionCode::SERVICE_SPECIFIC;
+        self.service_specific_error = err;
+        self
+    }
+
+    /// Set the message sent with the status. Messages can't contain NUL
+    /// characters, and are dropped if they do.
+    pub fn message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    /// Keep `source` as the cause of the status. If no message is set, the
+    /// source's is sent instead.
+    pub fn source(mut self, source: impl Into<Box<dyn error::Error + Send + Sync>>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Build the status.
+    pub fn build(self) -> Status {
+        let message = self.message.or_else(|| self.source.as_ref().map(|e| e.to_string()));
+        let mut status = match self.exception {
+            ExceptionCode::SERVICE_SPECIFIC => {
+                Status::new_service_specific_error_str(self.service_specific_error, message)
+            }
+            exception => Status::new_exception_str(exception, message),
+        };
+        status.source = self.source;
+        status
     }
 }
 
@@ -367,7 +507,7 @@ impl Drop for Status {
             // pointee, so we need to delete it here. We know that the pointer
             // will be valid here since `Status` always contains a valid pointer
             // while it is alive.
-            sys::AStatus_delete(self.0);
+            sys::AStatus_delete(self.ptr);
         }
     }
 }
@@ -381,11 +521,11 @@ impl Drop for Status {
 /// `Status` object is still alive.
 unsafe impl AsNative<sys::AStatus> for Status {
     fn as_native(&self) -> *const sys::AStatus {
-        self.0
+        self.ptr
     }
 
     fn as_native_mut(&mut self) -> *mut sys::AStatus {
-        self.0
+        self.ptr
     }
 }
 
@@ -425,4 +565,77 @@ mod tests {
         assert_eq!(status.service_specific_error(), 0);
         assert_eq!(status.get_description(), "Status(-5, EX_ILLEGAL_STATE): ''".to_string());
     }
+
+    #[test]
+    fn status_message() {
+        let status = Status::new_exception_str(ExceptionCode::ILLEGAL_STATE, Some("message"));
+        assert_eq!(status.message().as_deref(), Some("message"));
+        assert_eq!(Status::from(ExceptionCode::ILLEGAL_STATE).message(), None);
+        assert_eq!(Status::ok().message(), None);
+    }
+
+    #[test]
+    fn fds_not_allowed() {
+        let status = Status::fds_not_allowed();
+        assert_eq!(status.exception_code(), ExceptionCode::ILLEGAL_ARGUMENT);
+        assert_eq!(status.message().as_deref(), Some("Not allowed to write file descriptors here"));
+
+        let status = Status::from(StatusCode::FDS_NOT_ALLOWED);
+        assert_eq!(status.exception_code(), ExceptionCode::TRANSACTION_FAILED);
+        assert_eq!(status.transaction_error(), StatusCode::FDS_NOT_ALLOWED);
+    }
+
+    #[test]
+    fn build_status() {
+        let status = Status::builder().service_specific_error(-42).message("message").build();
+        assert_eq!(status.exception_code(), ExceptionCode::SERVICE_SPECIFIC);
+        assert_eq!(status.service_specific_error(), -42);
+        assert_eq!(status.message().as_deref(), Some("message"));
+        assert!(error::Error::source(&status).is_none());
+
+        let status = Status::builder()
+            .exception(ExceptionCode::ILLEGAL_ARGUMENT)
+            .source(io::Error::new(io::ErrorKind::Other, "cause"))
+            .build();
+        assert_eq!(status.exception_code(), ExceptionCode::ILLEGAL_ARGUMENT);
+        assert_eq!(status.message().as_deref(), Some("cause"));
+        let source = error::Error::source(&status).unwrap();
+        assert_eq!(source.to_string(), "cause");
+        assert!(source.downcast_ref::<io::Error>().is_some());
+    }
+
+    #[test]
+    fn status_from_io_error() {
+        for (kind, exception) in [
+            (io::ErrorKind::PermissionDenied, ExceptionCode::SECURITY),
+            (io::ErrorKind::InvalidInput, ExceptionCode::ILLEGAL_ARGUMENT),
+            (io::ErrorKind::Unsupported, ExceptionCode::UNSUPPORTED_OPERATION),
+            (io::ErrorKind::NotFound, ExceptionCode::ILLEGAL_STATE),
+        ] {
+            let status = Status::from(io::Error::new(kind, "io"));
+            assert_eq!(status.exception_code(), exception);
+            assert_eq!(status.message().as_deref(), Some("io"));
+            assert!(error::Error::source(&status).is_some());
+        }
+    }
+
+    #[cfg(feature = "anyhow")]
+    #[test]
+    fn status_from_anyhow() {
+        use anyhow::Context;
+
+        let err = Err::<(), _>(io::Error::new(io::ErrorKind::PermissionDenied, "denied"))
+            .context("opening file")
+            .unwrap_err();
+        let status = Status::from(err);
+        assert_eq!(status.exception_code(), ExceptionCode::SECURITY);
+        assert_eq!(status.message().as_deref(), Some("opening file: denied"));
+
+        let status = Status::from(anyhow::anyhow!("oops"));
+        assert_eq!(status.exception_code(), ExceptionCode::ILLEGAL_STATE);
+
+        let status = Status::from(anyhow::Error::new(Status::from(ExceptionCode::SECURITY)));
+        assert_eq!(status.exception_code(), ExceptionCode::SECURITY);
+        assert_eq!(status.message(), None);
+    }
 }
@@ -96,26 +96,45 @@
 #[macro_use]
 mod binder;
 mod binder_async;
+mod callback_list;
+mod dump;
 mod error;
 mod native;
 mod parcel;
 mod proxy;
+mod shell;
 mod state;
 
 use binder_ndk_sys as sys;
 
 pub use crate::binder_async::{BinderAsyncPool, BoxFuture};
-pub use binder::{BinderFeatures, FromIBinder, IBinder, Interface, Strong, Weak};
-pub use error::{ExceptionCode, Status, StatusCode};
+#[cfg(feature = "derive")]
+pub use async_trait::async_trait;
+pub use binder::{
+    BinderFeatures, FromIBinder, IBinder, Interface, MinSchedulerPolicy, SchedulerPolicy, Strong,
+    Weak,
+};
+#[cfg(feature = "derive")]
+pub use binder_derive::{interface, Parcelable};
+pub use callback_list::RemoteCallbackList;
+pub use dump::{dump_to_string, DumpArgs, DumpFormat, DumpSection, DumpValue, Dumper};
+pub use error::{ExceptionCode, Status, StatusBuilder, StatusCode};
 pub use native::{
-    add_service, force_lazy_services_persist, is_handling_transaction, register_lazy_service,
+    add_service, force_lazy_services_persist, is_handling_transaction, re_register_lazy_services,
+    register_lazy_service, set_active_services_callback, try_unregister_lazy_services,
     LazyServiceGuard,
 };
-pub use parcel::{ParcelFileDescriptor, Parcelable, ParcelableHolder};
+pub use parcel::{ParcelFileDescriptor, Parcelable, ParcelableHolder, SharedMemory};
 pub use proxy::{
-    get_declared_instances, get_interface, get_service, is_declared, wait_for_interface,
-    wait_for_service, DeathRecipient, SpIBinder, WpIBinder,
+    check_interface, check_service, get_declared_instances, get_interface, get_service,
+    get_updatable_apex_name, is_declared, is_updatable_via_apex,
+    register_for_interface_notifications, register_for_service_notifications, wait_for_interface,
+    wait_for_service, DeathRecipient, ServiceHandle, ServiceHandleBuilder,
+    ServiceNotificationRegistration, SpIBinder, WpIBinder,
 };
+pub use shell::ShellArgs;
+#[cfg(feature = "host")]
+pub use state::ServiceProcess;
 pub use state::{ProcessState, ThreadState};
 
 /// Binder result containing a [`Status`] on error.
@@ -133,11 +152,13 @@ pub mod binder_impl {
     pub use crate::error::status_t;
     pub use crate::native::Binder;
     pub use crate::parcel::{
-        BorrowedParcel, Deserialize, DeserializeArray, DeserializeOption, Parcel,
+        BorrowedParcel, Deserialize, DeserializeArray, DeserializeOption, Parcel, ParcelStr,
         ParcelableMetadata, Serialize, SerializeArray, SerializeOption, NON_NULL_PARCELABLE_FLAG,
         NULL_PARCELABLE_FLAG,
     };
     pub use crate::proxy::{AssociateClass, Proxy};
+    #[cfg(feature = "derive")]
+    pub use binder_derive::{Deserialize, Serialize};
 }
 
 /// Unstable, in-development API that only allowlisted clients are allowed to use.
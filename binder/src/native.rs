@@ -15,7 +15,8 @@
  */
 
 use crate::binder::{
-    AsNative, Interface, InterfaceClassMethods, Remotable, Stability, TransactionCode,
+    AsNative, Interface, InterfaceClassMethods, MinSchedulerPolicy, Remotable, Stability,
+    TransactionCode,
 };
 use crate::error::{status_result, status_t, Result, StatusCode};
 use crate::parcel::{BorrowedParcel, Serialize};
@@ -188,6 +189,36 @@ impl<T: Remotable> Binder<T> {
         status_result(status)
     }
 
+    /// Handle incoming transactions with at least the given scheduler policy
+    /// and priority. This must be called before the object is sent to another
+    /// process.
+    #[cfg(not(android_vndk))]
+    pub fn set_min_scheduler_policy(&mut self, policy: MinSchedulerPolicy) {
+        unsafe {
+            // Safety: `AIBinder_setMinSchedulerPolicy` expects a valid, local
+            // `AIBinder` pointer, which `self` always contains. It aborts on
+            // invalid values, but `MinSchedulerPolicy` only holds priorities
+            // valid for its policy.
+            sys::AIBinder_setMinSchedulerPolicy(
+                self.as_native_mut(),
+                policy.policy().into(),
+                policy.priority(),
+            );
+        }
+    }
+
+    /// Allow incoming transactions to inherit realtime scheduling policies
+    /// from their callers, which they don't by default. This must be called
+    /// before the object is sent to another process.
+    #[cfg(not(android_vndk))]
+    pub fn set_inherit_rt(&mut self, inherit_rt: bool) {
+        unsafe {
+            // Safety: `AIBinder_setInheritRt` expects a valid, local `AIBinder`
+            // pointer, which `self` always contains.
+            sys::AIBinder_setInheritRt(self.as_native_mut(), inherit_rt);
+        }
+    }
+
     /// Retrieve the interface descriptor string for this object's Binder
     /// interface.
     pub fn get_descriptor() -> &'static str {
@@ -198,6 +229,20 @@ impl<T: Remotable> Binder<T> {
     fn mark_stability(&mut self, stability: Stability) {
         match stability {
             Stability::Local => self.mark_local_stability(),
+            Stability::Vendor => {
+                unsafe {
+                    // Safety: Self always contains a valid `AIBinder` pointer, so
+                    // we can always call this C API safely.
+                    sys::AIBinder_markVendorStability(self.as_native_mut());
+                }
+            }
+            Stability::System => {
+                unsafe {
+                    // Safety: Self always contains a valid `AIBinder` pointer, so
+                    // we can always call this C API safely.
+                    sys::AIBinder_markSystemStability(self.as_native_mut());
+                }
+            }
             Stability::Vintf => {
                 unsafe {
                     // Safety: Self always contains a valid `AIBinder` pointer, so
@@ -333,22 +378,53 @@ impl<T: Remotable> InterfaceClassMethods for Binder<T> {
         // We don't own this file, so we need to be careful not to drop it.
         let file = ManuallyDrop::new(File::from_raw_fd(fd));
 
-        if args.is_null() && num_args != 0 {
-            return StatusCode::UNEXPECTED_NULL as status_t;
+        let args = match args_from_raw(args, num_args) {
+            Some(args) => args,
+            None => return StatusCode::UNEXPECTED_NULL as status_t,
+        };
+
+        let object = sys::AIBinder_getUserData(binder);
+        let binder: &T = &*(object as *const T);
+        let res = binder.on_dump(&file, &args);
+
+        match res {
+            Ok(()) => 0,
+            Err(e) => e as status_t,
         }
+    }
 
-        let args = if args.is_null() || num_args == 0 {
-            vec![]
-        } else {
-            slice::from_raw_parts(args, num_args as usize)
-                .iter()
-                .map(|s| CStr::from_ptr(*s))
-                .collect()
+    /// Called to handle a shell command.
+    ///
+    /// # Safety
+    ///
+    /// Must be called with a non-null, valid pointer to a local `AIBinder` that
+    /// contains a `T` pointer in its user data. The fds should be non-owned
+    /// file descriptors, and argv must be an array of null-terminated string
+    /// pointers with length argc.
+    unsafe extern "C" fn on_shell_command(
+        binder: *mut sys::AIBinder,
+        in_: i32,
+        out: i32,
+        err: i32,
+        argv: *mut *const c_char,
+        argc: u32,
+    ) -> status_t {
+        if in_ < 0 || out < 0 || err < 0 {
+            return StatusCode::UNEXPECTED_NULL as status_t;
+        }
+        // We don't own these files, so we need to be careful not to drop them.
+        let stdin = ManuallyDrop::new(File::from_raw_fd(in_));
+        let stdout = ManuallyDrop::new(File::from_raw_fd(out));
+        let stderr = ManuallyDrop::new(File::from_raw_fd(err));
+
+        let args = match args_from_raw(argv, argc) {
+            Some(args) => args,
+            None => return StatusCode::UNEXPECTED_NULL as status_t,
         };
 
         let object = sys::AIBinder_getUserData(binder);
         let binder: &T = &*(object as *const T);
-        let res = binder.on_dump(&file, &args);
+        let res = binder.on_shell_command(&stdin, &stdout, &stderr, &args);
 
         match res {
             Ok(()) => 0,
@@ -357,6 +433,22 @@ impl<T: Remotable> InterfaceClassMethods for Binder<T> {
     }
 }
 
+/// Borrow the arguments of a dump or shell command, or `None` if they are null
+/// but there should be some.
+///
+/// # Safety
+///
+/// `args` must be null or point to `num_args` pointers to null-terminated
+/// strings, which outlive the returned references.
+unsafe fn args_from_raw<'a>(args: *mut *const c_char, num_args: u32) -> Option<Vec<&'a CStr>> {
+    if args.is_null() {
+        return (num_args == 0).then(Vec::new);
+    }
+    Some(
+        slice::from_raw_parts(args, num_args as usize).iter().map(|s| CStr::from_ptr(*s)).collect(),
+    )
+}
+
 impl<T: Remotable> Drop for Binder<T> {
     // This causes C++ to decrease the strong ref count of the `AIBinder`
     // object. We specifically do not drop the `rust_object` here. When C++
@@ -548,6 +640,67 @@ impl Default for LazyServiceGuard {
     }
 }
 
+/// Set a callback to be told when the lazy services of this process gain their
+/// first client, or lose their last one.
+///
+/// The callback receives whether any service has clients. It returns whether
+/// it handled the change itself. Otherwise, once no service has clients, the
+/// process unregisters its services and exits. A callback which handles the
+/// change can clean up with [`try_unregister_lazy_services`] before exiting, or
+/// call [`re_register_lazy_services`] to keep running.
+///
+/// Only one callback can be set for a process. Setting another replaces it,
+/// but the callbacks are never freed.
+pub fn set_active_services_callback<F>(callback: F)
+where
+    F: Fn(bool) -> bool + Send + Sync + 'static,
+{
+    unsafe extern "C" fn on_active_services_changed<F>(
+        has_clients: bool,
+        context: *mut c_void,
+    ) -> bool
+    where
+        F: Fn(bool) -> bool + Send + Sync + 'static,
+    {
+        // Safety: the context is the callback leaked below, and is never freed.
+        let callback = (context as *const F).as_ref().unwrap();
+        callback(has_clients)
+    }
+
+    let callback: &'static F = Box::leak(Box::new(callback));
+    unsafe {
+        // Safety: the context is a valid callback for the function pointer,
+        // and lives forever, as the callback may be called at any time.
+        sys::AServiceManager_setActiveServicesCallback(
+            Some(on_active_services_changed::<F>),
+            callback as *const F as *mut c_void,
+        )
+    }
+}
+
+/// Try to unregister all the lazy services of this process, failing if any
+/// has clients.
+///
+/// Returns `true` if all services were unregistered. If cleaning up after this
+/// fails, call [`re_register_lazy_services`] to register them again.
+pub fn try_unregister_lazy_services() -> bool {
+    unsafe {
+        // Safety: No borrowing or transfer of ownership occurs here.
+        sys::AServiceManager_tryUnregister()
+    }
+}
+
+/// Register again the lazy services unregistered by
+/// [`try_unregister_lazy_services`].
+///
+/// This must be called on the same thread as `try_unregister_lazy_services`.
+pub fn re_register_lazy_services() {
+    unsafe {
+        // Safety: No borrowing or transfer of ownership occurs here.
+        sys::AServiceManager_reRegister()
+    }
+}
+
 /// Tests often create a base BBinder instance; so allowing the unit
 /// type to be remotable translates nicely to Binder::new(()).
 impl Remotable for () {
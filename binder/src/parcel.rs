@@ -28,15 +28,19 @@ use std::mem::ManuallyDrop;
 use std::ptr::{self, NonNull};
 
 mod file_descriptor;
+mod in_place;
 mod parcelable;
 mod parcelable_holder;
+mod shared_memory;
 
 pub use self::file_descriptor::ParcelFileDescriptor;
+pub use self::in_place::ParcelStr;
 pub use self::parcelable::{
     Deserialize, DeserializeArray, DeserializeOption, Parcelable, Serialize, SerializeArray,
     SerializeOption, NON_NULL_PARCELABLE_FLAG, NULL_PARCELABLE_FLAG,
 };
 pub use self::parcelable_holder::{ParcelableHolder, ParcelableMetadata};
+pub use self::shared_memory::SharedMemory;
 
 /// Container for a message (data and object references) that can be sent
 /// through Binder.
@@ -209,6 +213,42 @@ impl<'a> BorrowedParcel<'a> {
         }
     }
 
+    /// Whether file descriptors can be written to the parcel. Parcels that
+    /// don't allow them, like those for RPC binders without fd support, fail
+    /// with `FDS_NOT_ALLOWED`. Services that want to report that as Java does
+    /// can return [`Status::fds_not_allowed`](crate::Status::fds_not_allowed).
+    pub fn allows_fds(&self) -> bool {
+        unsafe {
+            // Safety: `BorrowedParcel` always contains a valid pointer to an
+            // `AParcel`, and this call is otherwise safe.
+            sys::AParcel_getAllowFds(self.as_native())
+        }
+    }
+
+    /// Allow or disallow writing file descriptors to the parcel, e.g. to make
+    /// sure a transaction can't carry any.
+    ///
+    /// libbinder_ndk can only query this, so on Android changing it fails with
+    /// `INVALID_OPERATION`. The host backend supports it.
+    pub fn set_allow_fds(&mut self, allow_fds: bool) -> Result<()> {
+        if self.allows_fds() == allow_fds {
+            return Ok(());
+        }
+        #[cfg(feature = "host")]
+        {
+            unsafe {
+                // Safety: `BorrowedParcel` always contains a valid pointer to
+                // an `AParcel`, and this call is otherwise safe.
+                sys::AParcel_setAllowFds(self.as_native_mut(), allow_fds)
+            }
+            Ok(())
+        }
+        #[cfg(not(feature = "host"))]
+        {
+            Err(StatusCode::INVALID_OPERATION)
+        }
+    }
+
     /// Write a type that implements [`Serialize`] to the parcel.
     pub fn write<S: Serialize + ?Sized>(&mut self, parcelable: &S) -> Result<()> {
         parcelable.serialize(self)
@@ -333,6 +373,24 @@ impl<'a> BorrowedParcel<'a> {
         let size = unsafe { sys::AParcel_getDataSize(other.as_native()) };
         self.append_from(other, 0, size)
     }
+
+    /// Copy out the raw bytes of the parcel, which [`Parcel::unmarshal`] turns
+    /// back into a parcel.
+    ///
+    /// Fails with `INVALID_OPERATION` if the parcel contains binders or file
+    /// descriptors. The bytes are laid out like libbinder's `Parcel`, which is
+    /// not guaranteed to stay compatible between platform versions.
+    pub fn marshal(&self) -> Result<Vec<u8>> {
+        let mut buffer = vec![0; self.get_data_size() as usize];
+        let status = unsafe {
+            // Safety: `BorrowedParcel` always contains a valid pointer to an
+            // `AParcel`, and `buffer` is valid for writes of the `len` bytes
+            // `AParcel_marshal` copies to it.
+            sys::AParcel_marshal(self.as_native(), buffer.as_mut_ptr(), 0, buffer.len())
+        };
+        status_result(status)?;
+        Ok(buffer)
+    }
 }
 
 /// A segment of a writable parcel, used for [`BorrowedParcel::sized_write`].
@@ -351,6 +409,23 @@ impl Parcel {
         self.borrowed().mark_sensitive()
     }
 
+    /// Whether file descriptors can be written to the parcel. Parcels that
+    /// don't allow them, like those for RPC binders without fd support, fail
+    /// with `FDS_NOT_ALLOWED`. Services that want to report that as Java does
+    /// can return [`Status::fds_not_allowed`](crate::Status::fds_not_allowed).
+    pub fn allows_fds(&self) -> bool {
+        self.borrowed_ref().allows_fds()
+    }
+
+    /// Allow or disallow writing file descriptors to the parcel, e.g. to make
+    /// sure a transaction can't carry any.
+    ///
+    /// libbinder_ndk can only query this, so on Android changing it fails with
+    /// `INVALID_OPERATION`. The host backend supports it.
+    pub fn set_allow_fds(&mut self, allow_fds: bool) -> Result<()> {
+        self.borrowed().set_allow_fds(allow_fds)
+    }
+
     /// Write a type that implements [`Serialize`] to the parcel.
     pub fn write<S: Serialize + ?Sized>(&mut self, parcelable: &S) -> Result<()> {
         self.borrowed().write(parcelable)
@@ -438,6 +513,38 @@ impl Parcel {
     pub fn append_all_from(&mut self, other: &impl AsNative<sys::AParcel>) -> Result<()> {
         self.borrowed().append_all_from(other)
     }
+
+    /// Copy out the raw bytes of the parcel, see [`BorrowedParcel::marshal`].
+    pub fn marshal(&self) -> Result<Vec<u8>> {
+        self.borrowed_ref().marshal()
+    }
+
+    /// Create a parcel holding the raw bytes `data` from
+    /// [`marshal`](Parcel::marshal), positioned to read them from the start.
+    pub fn unmarshal(data: &[u8]) -> Result<Parcel> {
+        let mut parcel = Parcel::new();
+        let status = unsafe {
+            // Safety: `Parcel` always contains a valid pointer to an `AParcel`,
+            // and `data` is valid for reads of its whole length.
+            sys::AParcel_unmarshal(parcel.as_native_mut(), data.as_ptr(), data.len())
+        };
+        status_result(status)?;
+        unsafe {
+            // Safety: 0 is always a valid position.
+            parcel.set_data_position(0)?;
+        }
+        Ok(parcel)
+    }
+
+    /// Clear the parcel, releasing any objects it holds, to reuse it.
+    pub fn reset(&mut self) -> Result<()> {
+        let status = unsafe {
+            // Safety: `Parcel` always contains a valid pointer to an `AParcel`,
+            // which we own.
+            sys::AParcel_reset(self.as_native_mut())
+        };
+        status_result(status)
+    }
 }
 
 // Data deserialization methods
@@ -569,6 +676,12 @@ impl<'a> ReadableSubParcel<'a> {
         D::deserialize(&self.parcel)
     }
 
+    /// Read a type that implements [`Deserialize`] from the sub-parcel onto
+    /// an existing value.
+    pub fn read_onto<D: Deserialize>(&self, x: &mut D) -> Result<()> {
+        self.parcel.read_onto(x)
+    }
+
     /// Check if the sub-parcel has more data to read
     pub fn has_more_data(&self) -> bool {
         self.parcel.get_data_position() < self.end_position
@@ -883,6 +996,30 @@ fn test_sized_write() {
     assert_eq!(parcel.read::<Vec<i32>>().unwrap(), &arr,);
 }
 
+#[test]
+fn test_marshal() {
+    let mut parcel = Parcel::new();
+    parcel.write(&42i32).unwrap();
+    parcel.write("Hello, Binder!").unwrap();
+
+    let data = parcel.marshal().unwrap();
+    assert_eq!(data.len(), parcel.get_data_size() as usize);
+
+    let parcel = Parcel::unmarshal(&data).unwrap();
+    assert_eq!(parcel.get_data_size() as usize, data.len());
+    assert_eq!(parcel.read::<i32>(), Ok(42));
+    assert_eq!(parcel.read::<String>().as_deref(), Ok("Hello, Binder!"));
+    assert_eq!(parcel.marshal(), Ok(data));
+
+    let mut parcel = Parcel::new();
+    parcel.write(&ParcelFileDescriptor::new(std::fs::File::open("/dev/null").unwrap())).unwrap();
+    assert_eq!(parcel.marshal(), Err(StatusCode::INVALID_OPERATION));
+
+    assert_eq!(parcel.reset(), Ok(()));
+    assert_eq!(parcel.get_data_size(), 0);
+    assert_eq!(parcel.marshal(), Ok(vec![]));
+}
+
 #[test]
 fn test_append_from() {
     let mut parcel1 = Parcel::new();
@@ -23,7 +23,8 @@ use crate::error::{status_result, Result, StatusCode};
 use crate::sys;
 
 use std::fs::File;
-use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixStream;
 
 /// Rust version of the Java class android.os.ParcelFileDescriptor
 #[derive(Debug)]
@@ -48,6 +49,36 @@ impl From<ParcelFileDescriptor> for File {
     }
 }
 
+impl From<OwnedFd> for ParcelFileDescriptor {
+    fn from(fd: OwnedFd) -> Self {
+        Self(fd.into())
+    }
+}
+
+impl From<ParcelFileDescriptor> for OwnedFd {
+    fn from(file: ParcelFileDescriptor) -> OwnedFd {
+        file.0.into()
+    }
+}
+
+impl From<UnixStream> for ParcelFileDescriptor {
+    fn from(stream: UnixStream) -> Self {
+        OwnedFd::from(stream).into()
+    }
+}
+
+impl From<ParcelFileDescriptor> for UnixStream {
+    fn from(file: ParcelFileDescriptor) -> UnixStream {
+        OwnedFd::from(file).into()
+    }
+}
+
+impl AsFd for ParcelFileDescriptor {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
 impl AsRawFd for ParcelFileDescriptor {
     fn as_raw_fd(&self) -> RawFd {
         self.0.as_raw_fd()
@@ -72,25 +103,57 @@ impl Eq for ParcelFileDescriptor {}
 
 impl Serialize for ParcelFileDescriptor {
     fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
-        let fd = self.0.as_raw_fd();
+        self.as_fd().serialize(parcel)
+    }
+}
+
+impl SerializeArray for ParcelFileDescriptor {}
+
+impl SerializeOption for ParcelFileDescriptor {
+    fn serialize_option(this: Option<&Self>, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        SerializeOption::serialize_option(this.map(|f| f.as_fd()).as_ref(), parcel)
+    }
+}
+
+impl DeserializeOption for ParcelFileDescriptor {
+    fn deserialize_option(parcel: &BorrowedParcel<'_>) -> Result<Option<Self>> {
+        OwnedFd::deserialize_option(parcel).map(|fd| fd.map(Self::from))
+    }
+}
+
+impl Deserialize for ParcelFileDescriptor {
+    fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
+        Deserialize::deserialize(parcel).transpose().unwrap_or(Err(StatusCode::UNEXPECTED_NULL))
+    }
+}
+
+impl DeserializeArray for ParcelFileDescriptor {}
+
+impl Serialize for BorrowedFd<'_> {
+    fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        // libbinder only finds out after writing the start of the
+        // `ParcelFileDescriptor`, so check first rather than leave half of it
+        // in the parcel.
+        if !parcel.allows_fds() {
+            return Err(StatusCode::FDS_NOT_ALLOWED);
+        }
         let status = unsafe {
             // Safety: `Parcel` always contains a valid pointer to an
-            // `AParcel`. Likewise, `ParcelFileDescriptor` always contains a
-            // valid file, so we can borrow a valid file
+            // `AParcel`, and a `BorrowedFd` is always a valid file
             // descriptor. `AParcel_writeParcelFileDescriptor` does NOT take
             // ownership of the fd, so we need not duplicate it first.
-            sys::AParcel_writeParcelFileDescriptor(parcel.as_native_mut(), fd)
+            sys::AParcel_writeParcelFileDescriptor(parcel.as_native_mut(), self.as_raw_fd())
         };
         status_result(status)
     }
 }
 
-impl SerializeArray for ParcelFileDescriptor {}
+impl SerializeArray for BorrowedFd<'_> {}
 
-impl SerializeOption for ParcelFileDescriptor {
+impl SerializeOption for BorrowedFd<'_> {
     fn serialize_option(this: Option<&Self>, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
-        if let Some(f) = this {
-            f.serialize(parcel)
+        if let Some(fd) = this {
+            fd.serialize(parcel)
         } else {
             let status = unsafe {
                 // Safety: `Parcel` always contains a valid pointer to an
@@ -104,7 +167,21 @@ impl SerializeOption for ParcelFileDescriptor {
     }
 }
 
-impl DeserializeOption for ParcelFileDescriptor {
+impl Serialize for OwnedFd {
+    fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        self.as_fd().serialize(parcel)
+    }
+}
+
+impl SerializeArray for OwnedFd {}
+
+impl SerializeOption for OwnedFd {
+    fn serialize_option(this: Option<&Self>, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        SerializeOption::serialize_option(this.map(|fd| fd.as_fd()).as_ref(), parcel)
+    }
+}
+
+impl DeserializeOption for OwnedFd {
     fn deserialize_option(parcel: &BorrowedParcel<'_>) -> Result<Option<Self>> {
         let mut fd = -1i32;
         unsafe {
@@ -120,21 +197,83 @@ impl DeserializeOption for ParcelFileDescriptor {
         if fd < 0 {
             Ok(None)
         } else {
-            let file = unsafe {
+            let fd = unsafe {
                 // Safety: At this point, we know that the file descriptor was
                 // not -1, so must be a valid, owned file descriptor which we
-                // can safely turn into a `File`.
-                File::from_raw_fd(fd)
+                // can safely turn into an `OwnedFd`.
+                OwnedFd::from_raw_fd(fd)
             };
-            Ok(Some(ParcelFileDescriptor::new(file)))
+            Ok(Some(fd))
         }
     }
 }
 
-impl Deserialize for ParcelFileDescriptor {
+impl Deserialize for OwnedFd {
     fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
         Deserialize::deserialize(parcel).transpose().unwrap_or(Err(StatusCode::UNEXPECTED_NULL))
     }
 }
 
-impl DeserializeArray for ParcelFileDescriptor {}
+impl DeserializeArray for OwnedFd {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parcel::Parcel;
+
+    use std::io::{Read, Write};
+
+    #[test]
+    fn test_fd_types() {
+        let (mut local, remote) = UnixStream::pair().unwrap();
+        let remote = OwnedFd::from(remote);
+
+        let mut parcel = Parcel::new();
+        assert!(parcel.write(&remote.as_fd()).is_ok());
+        assert!(parcel.write(&None::<BorrowedFd<'_>>).is_ok());
+        assert!(parcel.write(&remote).is_ok());
+        assert!(parcel.write(&ParcelFileDescriptor::from(remote)).is_ok());
+        assert!(parcel.write(&None::<ParcelFileDescriptor>).is_ok());
+        unsafe {
+            assert!(parcel.set_data_position(0).is_ok());
+        }
+
+        // Every type reads what the others wrote.
+        let file = parcel.read::<ParcelFileDescriptor>().unwrap();
+        assert!(parcel.read::<Option<OwnedFd>>().unwrap().is_none());
+        let fd = parcel.read::<OwnedFd>().unwrap();
+        let mut stream = UnixStream::from(parcel.read::<ParcelFileDescriptor>().unwrap());
+        assert_eq!(parcel.read::<OwnedFd>().err(), Some(StatusCode::UNEXPECTED_NULL));
+
+        for (i, fd) in [OwnedFd::from(file), fd].into_iter().enumerate() {
+            assert!(UnixStream::from(fd).write_all(&[i as u8]).is_ok());
+        }
+        assert!(stream.write_all(&[2]).is_ok());
+        let mut buf = [0; 3];
+        assert!(local.read_exact(&mut buf).is_ok());
+        assert_eq!(buf, [0, 1, 2]);
+    }
+
+    #[cfg(feature = "host")]
+    #[test]
+    fn test_fds_not_allowed() {
+        let (stream, _) = UnixStream::pair().unwrap();
+        let mut parcel = Parcel::new();
+        assert!(parcel.allows_fds());
+        assert!(parcel.write(&stream.as_fd()).is_ok());
+
+        let mut restricted = Parcel::new();
+        assert!(restricted.set_allow_fds(false).is_ok());
+        assert!(!restricted.allows_fds());
+        let file = ParcelFileDescriptor::from(stream);
+        assert_eq!(restricted.write(&file), Err(StatusCode::FDS_NOT_ALLOWED));
+        assert_eq!(restricted.get_data_size(), 0);
+        assert_eq!(restricted.write(&[file]), Err(StatusCode::FDS_NOT_ALLOWED));
+        // Null file descriptors are still fine.
+        assert!(restricted.write(&None::<OwnedFd>).is_ok());
+        assert_eq!(restricted.append_all_from(&parcel), Err(StatusCode::FDS_NOT_ALLOWED));
+
+        assert!(restricted.set_allow_fds(true).is_ok());
+        assert!(restricted.append_all_from(&parcel).is_ok());
+    }
+}
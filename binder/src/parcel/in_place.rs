@@ -0,0 +1,307 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Reads that borrow from the parcel instead of copying out of it, on host
+//! builds only.
+//!
+//! The host backend hands out the parcel's own buffer, like
+//! `Parcel::readInplace`. libbinder_ndk only reads by copying into buffers
+//! from an allocator, so on Android every one of these reads copies the whole
+//! array or string, exactly as `read::<Vec<u8>>()` or `read::<String>()`
+//! would. They are still useful there to share code with the host, but save
+//! nothing.
+
+use super::{BorrowedParcel, Parcel};
+#[cfg(feature = "host")]
+use crate::binder::AsNative;
+use crate::error::{Result, StatusCode};
+#[cfg(feature = "host")]
+use crate::sys;
+
+use std::borrow::Cow;
+use std::char;
+use std::fmt;
+#[cfg(feature = "host")]
+use std::slice;
+
+/// A string read from a parcel. Host builds keep it as it was written and
+/// only decode it on use; on Android libbinder_ndk has already decoded it.
+///
+/// Comparing it with a `str` doesn't allocate.
+#[derive(Clone)]
+pub struct ParcelStr<'a>(Repr<'a>);
+
+#[derive(Clone)]
+enum Repr<'a> {
+    /// Little-endian UTF-16, without the NUL terminator.
+    Utf16(&'a [u8]),
+    /// Already decoded by libbinder_ndk.
+    #[cfg_attr(feature = "host", allow(dead_code))]
+    Utf8(String),
+}
+
+impl<'a> ParcelStr<'a> {
+    /// The length of the string in UTF-16 code units, as written to the
+    /// parcel.
+    pub fn len_utf16(&self) -> usize {
+        match &self.0 {
+            Repr::Utf16(bytes) => bytes.len() / 2,
+            Repr::Utf8(string) => string.encode_utf16().count(),
+        }
+    }
+
+    /// Whether the string is empty.
+    pub fn is_empty(&self) -> bool {
+        match &self.0 {
+            Repr::Utf16(bytes) => bytes.is_empty(),
+            Repr::Utf8(string) => string.is_empty(),
+        }
+    }
+
+    /// The UTF-16 code units of the string.
+    pub fn encode_utf16(&self) -> impl Iterator<Item = u16> + '_ {
+        let (utf16, utf8) = match &self.0 {
+            Repr::Utf16(bytes) => (Some(bytes.chunks_exact(2)), None),
+            Repr::Utf8(string) => (None, Some(string.encode_utf16())),
+        };
+        let utf16 = utf16.into_iter().flatten().map(|unit| u16::from_le_bytes([unit[0], unit[1]]));
+        utf16.chain(utf8.into_iter().flatten())
+    }
+
+    /// The characters of the string, failing with `BAD_VALUE` at invalid
+    /// UTF-16.
+    pub fn chars(&self) -> impl Iterator<Item = Result<char>> + '_ {
+        char::decode_utf16(self.encode_utf16()).map(|c| c.or(Err(StatusCode::BAD_VALUE)))
+    }
+
+    /// Decode the string, failing with `BAD_VALUE` if it isn't valid UTF-16
+    /// as for `String`'s `Deserialize`.
+    pub fn decode(&self) -> Result<String> {
+        match &self.0 {
+            Repr::Utf16(_) => self.chars().collect(),
+            Repr::Utf8(string) => Ok(string.clone()),
+        }
+    }
+}
+
+impl PartialEq<str> for ParcelStr<'_> {
+    fn eq(&self, other: &str) -> bool {
+        match &self.0 {
+            Repr::Utf16(_) => self.encode_utf16().eq(other.encode_utf16()),
+            Repr::Utf8(string) => string == other,
+        }
+    }
+}
+
+impl PartialEq<&str> for ParcelStr<'_> {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl fmt::Debug for ParcelStr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lossy: String =
+            char::decode_utf16(self.encode_utf16()).map(|c| c.unwrap_or('\u{fffd}')).collect();
+        fmt::Debug::fmt(&lossy, f)
+    }
+}
+
+impl<'a> BorrowedParcel<'a> {
+    /// Read a byte array, as written for `[u8]`. Host builds borrow it from
+    /// the parcel; on Android it is copied.
+    ///
+    /// Fails with `UNEXPECTED_NULL` for a null array.
+    pub fn read_bytes_ref(&self) -> Result<Cow<'_, [u8]>> {
+        self.read_nullable_bytes_ref()?.ok_or(StatusCode::UNEXPECTED_NULL)
+    }
+
+    /// Read a byte array that may be null, as written for `Option<&[u8]>`.
+    /// Host builds borrow it from the parcel; on Android it is copied.
+    pub fn read_nullable_bytes_ref(&self) -> Result<Option<Cow<'_, [u8]>>> {
+        #[cfg(feature = "host")]
+        {
+            let Some(len) = self.read_len()? else {
+                return Ok(None);
+            };
+            self.read_inplace(len).map(|bytes| Some(Cow::Borrowed(bytes)))
+        }
+        #[cfg(not(feature = "host"))]
+        {
+            self.read::<Option<Vec<u8>>>().map(|bytes| bytes.map(Cow::Owned))
+        }
+    }
+
+    /// Read a string without decoding it. Host builds borrow it from the
+    /// parcel; on Android it is copied and decoded.
+    ///
+    /// Fails with `UNEXPECTED_NULL` for a null string.
+    pub fn read_str_ref(&self) -> Result<ParcelStr<'_>> {
+        self.read_nullable_str_ref()?.ok_or(StatusCode::UNEXPECTED_NULL)
+    }
+
+    /// Read a string that may be null without decoding it. Host builds borrow
+    /// it from the parcel; on Android it is copied and decoded.
+    pub fn read_nullable_str_ref(&self) -> Result<Option<ParcelStr<'_>>> {
+        #[cfg(feature = "host")]
+        {
+            let Some(len) = self.read_len()? else {
+                return Ok(None);
+            };
+            // Strings are NUL terminated.
+            let bytes = len
+                .checked_add(1)
+                .and_then(|len| len.checked_mul(2))
+                .ok_or(StatusCode::BAD_VALUE)
+                .and_then(|size| self.read_inplace(size))?;
+            let (string, nul) = bytes.split_at(len * 2);
+            if nul != [0, 0] {
+                return Err(StatusCode::BAD_VALUE);
+            }
+            Ok(Some(ParcelStr(Repr::Utf16(string))))
+        }
+        #[cfg(not(feature = "host"))]
+        {
+            self.read::<Option<String>>().map(|string| string.map(|s| ParcelStr(Repr::Utf8(s))))
+        }
+    }
+
+    /// Read an array length, where -1 means null.
+    #[cfg(feature = "host")]
+    fn read_len(&self) -> Result<Option<usize>> {
+        match self.read::<i32>()? {
+            -1 => Ok(None),
+            len => usize::try_from(len).map(Some).or(Err(StatusCode::BAD_VALUE)),
+        }
+    }
+
+    /// Borrow the next `len` bytes, and skip past them and their padding.
+    #[cfg(feature = "host")]
+    fn read_inplace(&self, len: usize) -> Result<&[u8]> {
+        let data = unsafe {
+            // Safety: `BorrowedParcel` always contains a valid pointer to an
+            // `AParcel`.
+            sys::AParcel_readInplace(self.as_native(), len)
+        };
+        if data.is_null() {
+            return Err(StatusCode::NOT_ENOUGH_DATA);
+        }
+        Ok(unsafe {
+            // Safety: `data` points to `len` bytes of the parcel's data, which
+            // stay valid until the parcel is written to or reset. That needs
+            // a mutable borrow, so can't happen while `self` is borrowed.
+            slice::from_raw_parts(data as *const u8, len)
+        })
+    }
+}
+
+impl Parcel {
+    /// Read a byte array, as written for `[u8]`. Host builds borrow it from
+    /// the parcel; on Android it is copied.
+    ///
+    /// Fails with `UNEXPECTED_NULL` for a null array.
+    pub fn read_bytes_ref(&self) -> Result<Cow<'_, [u8]>> {
+        self.borrowed_ref().read_bytes_ref()
+    }
+
+    /// Read a byte array that may be null, as written for `Option<&[u8]>`.
+    /// Host builds borrow it from the parcel; on Android it is copied.
+    pub fn read_nullable_bytes_ref(&self) -> Result<Option<Cow<'_, [u8]>>> {
+        self.borrowed_ref().read_nullable_bytes_ref()
+    }
+
+    /// Read a string without decoding it. Host builds borrow it from the
+    /// parcel; on Android it is copied and decoded.
+    ///
+    /// Fails with `UNEXPECTED_NULL` for a null string.
+    pub fn read_str_ref(&self) -> Result<ParcelStr<'_>> {
+        self.borrowed_ref().read_str_ref()
+    }
+
+    /// Read a string that may be null without decoding it. Host builds borrow
+    /// it from the parcel; on Android it is copied and decoded.
+    pub fn read_nullable_str_ref(&self) -> Result<Option<ParcelStr<'_>>> {
+        self.borrowed_ref().read_nullable_str_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_bytes_ref() {
+        let mut parcel = Parcel::new();
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert!(parcel.write(&bytes).is_ok());
+        assert!(parcel.write(&None::<Vec<u8>>).is_ok());
+        assert!(parcel.write(&[1u8, 2, 3][..]).is_ok());
+        assert!(parcel.write(&7i32).is_ok());
+        assert!(parcel.write(&None::<Vec<u8>>).is_ok());
+        unsafe {
+            assert!(parcel.set_data_position(0).is_ok());
+        }
+
+        assert_eq!(parcel.read_bytes_ref().unwrap(), &bytes[..]);
+        assert_eq!(parcel.read_nullable_bytes_ref().unwrap(), None);
+        // The padding after the bytes is skipped.
+        assert_eq!(parcel.read_bytes_ref().unwrap(), &[1, 2, 3][..]);
+        assert_eq!(parcel.read::<i32>().unwrap(), 7);
+        assert_eq!(parcel.read_bytes_ref(), Err(StatusCode::UNEXPECTED_NULL));
+
+        let mut parcel = Parcel::new();
+        assert!(parcel.write(&16i32).is_ok());
+        assert!(parcel.write(&0i32).is_ok());
+        unsafe {
+            assert!(parcel.set_data_position(0).is_ok());
+        }
+        assert!(parcel.read_bytes_ref().is_err());
+    }
+
+    #[cfg(feature = "host")]
+    #[test]
+    fn test_read_bytes_ref_borrows() {
+        let mut parcel = Parcel::new();
+        assert!(parcel.write(&[1u8, 2, 3][..]).is_ok());
+        unsafe {
+            assert!(parcel.set_data_position(0).is_ok());
+        }
+        assert!(matches!(parcel.read_bytes_ref().unwrap(), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_read_str_ref() {
+        let mut parcel = Parcel::new();
+        assert!(parcel.write("héllo, 世界 🦀").is_ok());
+        assert!(parcel.write(&None::<String>).is_ok());
+        assert!(parcel.write("").is_ok());
+        assert!(parcel.write(&1i32).is_ok());
+        unsafe {
+            assert!(parcel.set_data_position(0).is_ok());
+        }
+
+        let string = parcel.read_str_ref().unwrap();
+        assert_eq!(string, "héllo, 世界 🦀");
+        assert_ne!(string, "hello");
+        assert_eq!(string.len_utf16(), 12);
+        assert_eq!(string.chars().nth(1), Some(Ok('é')));
+        assert_eq!(string.decode().unwrap(), "héllo, 世界 🦀");
+        assert_eq!(format!("{:?}", string), "\"héllo, 世界 🦀\"");
+        assert!(parcel.read_nullable_str_ref().unwrap().is_none());
+        assert!(parcel.read_str_ref().unwrap().is_empty());
+        assert_eq!(parcel.read::<i32>().unwrap(), 1);
+    }
+}
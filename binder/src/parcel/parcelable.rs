@@ -20,8 +20,10 @@ use crate::parcel::BorrowedParcel;
 use crate::proxy::SpIBinder;
 use crate::sys;
 
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::{TryFrom, TryInto};
 use std::ffi::c_void;
+use std::hash::{BuildHasher, Hash};
 use std::mem::{self, ManuallyDrop, MaybeUninit};
 use std::os::raw::c_char;
 use std::ptr;
@@ -657,6 +659,229 @@ impl<T: DeserializeArray, const N: usize> DeserializeOption for [T; N] {
 
 impl<T: DeserializeArray, const N: usize> DeserializeArray for [T; N] {}
 
+impl<T: SerializeArray> SerializeArray for Vec<T> {}
+
+impl<T: DeserializeArray> DeserializeArray for Vec<T> {}
+
+/// Write the size of a map or set, which must fit in an `i32`.
+fn write_collection_len(len: usize, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+    let len = i32::try_from(len).or(Err(StatusCode::BAD_VALUE))?;
+    parcel.write(&len)
+}
+
+/// Read the size of a map or set, where a negative size means `null`.
+fn read_collection_len(parcel: &BorrowedParcel<'_>) -> Result<Option<usize>> {
+    let len: i32 = parcel.read()?;
+    Ok(usize::try_from(len).ok())
+}
+
+// Maps are written like typed `Map`s in Java: their size, or -1 for `null`,
+// followed by each key and then its value.
+fn serialize_map<'a, K, V>(
+    len: usize,
+    entries: impl Iterator<Item = (&'a K, &'a V)>,
+    parcel: &mut BorrowedParcel<'_>,
+) -> Result<()>
+where
+    K: Serialize + 'a,
+    V: Serialize + 'a,
+{
+    write_collection_len(len, parcel)?;
+    for (key, value) in entries {
+        parcel.write(key)?;
+        parcel.write(value)?;
+    }
+    Ok(())
+}
+
+fn deserialize_map<K, V, M>(parcel: &BorrowedParcel<'_>) -> Result<Option<M>>
+where
+    K: Deserialize,
+    V: Deserialize,
+    M: FromIterator<(K, V)>,
+{
+    let Some(len) = read_collection_len(parcel)? else {
+        return Ok(None);
+    };
+    (0..len).map(|_| Ok((parcel.read()?, parcel.read()?))).collect::<Result<M>>().map(Some)
+}
+
+// Sets are written like lists: their size, or -1 for `null`, followed by each
+// element.
+fn serialize_set<'a, T: Serialize + 'a>(
+    len: usize,
+    elements: impl Iterator<Item = &'a T>,
+    parcel: &mut BorrowedParcel<'_>,
+) -> Result<()> {
+    write_collection_len(len, parcel)?;
+    for element in elements {
+        parcel.write(element)?;
+    }
+    Ok(())
+}
+
+fn deserialize_set<T: Deserialize, S: FromIterator<T>>(
+    parcel: &BorrowedParcel<'_>,
+) -> Result<Option<S>> {
+    let Some(len) = read_collection_len(parcel)? else {
+        return Ok(None);
+    };
+    (0..len).map(|_| parcel.read()).collect::<Result<S>>().map(Some)
+}
+
+impl<K: Serialize, V: Serialize, H> Serialize for HashMap<K, V, H> {
+    fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        serialize_map(self.len(), self.iter(), parcel)
+    }
+}
+
+impl<K: Serialize, V: Serialize, H> SerializeOption for HashMap<K, V, H> {
+    fn serialize_option(this: Option<&Self>, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        match this {
+            Some(map) => map.serialize(parcel),
+            None => parcel.write(&-1i32),
+        }
+    }
+}
+
+impl<K: Serialize, V: Serialize, H> SerializeArray for HashMap<K, V, H> {}
+
+impl<K, V, H> Deserialize for HashMap<K, V, H>
+where
+    K: Deserialize + Eq + Hash,
+    V: Deserialize,
+    H: BuildHasher + Default,
+{
+    fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
+        deserialize_map(parcel)?.ok_or(StatusCode::UNEXPECTED_NULL)
+    }
+}
+
+impl<K, V, H> DeserializeOption for HashMap<K, V, H>
+where
+    K: Deserialize + Eq + Hash,
+    V: Deserialize,
+    H: BuildHasher + Default,
+{
+    fn deserialize_option(parcel: &BorrowedParcel<'_>) -> Result<Option<Self>> {
+        deserialize_map(parcel)
+    }
+}
+
+impl<K, V, H> DeserializeArray for HashMap<K, V, H>
+where
+    K: Deserialize + Eq + Hash,
+    V: Deserialize,
+    H: BuildHasher + Default,
+{
+}
+
+impl<K: Serialize, V: Serialize> Serialize for BTreeMap<K, V> {
+    fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        serialize_map(self.len(), self.iter(), parcel)
+    }
+}
+
+impl<K: Serialize, V: Serialize> SerializeOption for BTreeMap<K, V> {
+    fn serialize_option(this: Option<&Self>, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        match this {
+            Some(map) => map.serialize(parcel),
+            None => parcel.write(&-1i32),
+        }
+    }
+}
+
+impl<K: Serialize, V: Serialize> SerializeArray for BTreeMap<K, V> {}
+
+impl<K: Deserialize + Ord, V: Deserialize> Deserialize for BTreeMap<K, V> {
+    fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
+        deserialize_map(parcel)?.ok_or(StatusCode::UNEXPECTED_NULL)
+    }
+}
+
+impl<K: Deserialize + Ord, V: Deserialize> DeserializeOption for BTreeMap<K, V> {
+    fn deserialize_option(parcel: &BorrowedParcel<'_>) -> Result<Option<Self>> {
+        deserialize_map(parcel)
+    }
+}
+
+impl<K: Deserialize + Ord, V: Deserialize> DeserializeArray for BTreeMap<K, V> {}
+
+impl<T: Serialize, H> Serialize for HashSet<T, H> {
+    fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        serialize_set(self.len(), self.iter(), parcel)
+    }
+}
+
+impl<T: Serialize, H> SerializeOption for HashSet<T, H> {
+    fn serialize_option(this: Option<&Self>, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        match this {
+            Some(set) => set.serialize(parcel),
+            None => parcel.write(&-1i32),
+        }
+    }
+}
+
+impl<T: Serialize, H> SerializeArray for HashSet<T, H> {}
+
+impl<T, H> Deserialize for HashSet<T, H>
+where
+    T: Deserialize + Eq + Hash,
+    H: BuildHasher + Default,
+{
+    fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
+        deserialize_set(parcel)?.ok_or(StatusCode::UNEXPECTED_NULL)
+    }
+}
+
+impl<T, H> DeserializeOption for HashSet<T, H>
+where
+    T: Deserialize + Eq + Hash,
+    H: BuildHasher + Default,
+{
+    fn deserialize_option(parcel: &BorrowedParcel<'_>) -> Result<Option<Self>> {
+        deserialize_set(parcel)
+    }
+}
+
+impl<T, H> DeserializeArray for HashSet<T, H>
+where
+    T: Deserialize + Eq + Hash,
+    H: BuildHasher + Default,
+{
+}
+
+impl<T: Serialize> Serialize for BTreeSet<T> {
+    fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        serialize_set(self.len(), self.iter(), parcel)
+    }
+}
+
+impl<T: Serialize> SerializeOption for BTreeSet<T> {
+    fn serialize_option(this: Option<&Self>, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        match this {
+            Some(set) => set.serialize(parcel),
+            None => parcel.write(&-1i32),
+        }
+    }
+}
+
+impl<T: Serialize> SerializeArray for BTreeSet<T> {}
+
+impl<T: Deserialize + Ord> Deserialize for BTreeSet<T> {
+    fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
+        deserialize_set(parcel)?.ok_or(StatusCode::UNEXPECTED_NULL)
+    }
+}
+
+impl<T: Deserialize + Ord> DeserializeOption for BTreeSet<T> {
+    fn deserialize_option(parcel: &BorrowedParcel<'_>) -> Result<Option<Self>> {
+        deserialize_set(parcel)
+    }
+}
+
+impl<T: Deserialize + Ord> DeserializeArray for BTreeSet<T> {}
+
 impl Serialize for Stability {
     fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
         i32::from(*self).serialize(parcel)
@@ -1176,4 +1401,152 @@ mod tests {
 
         assert_eq!(vec, strs);
     }
+
+    #[test]
+    fn test_nullable_parcelables() {
+        let mut parcel = Parcel::new();
+        let start = parcel.get_data_position();
+
+        assert!(parcel.write(&None::<String>).is_ok());
+        assert!(parcel.write(&None::<Vec<i32>>).is_ok());
+        assert!(parcel.write(&None::<SpIBinder>).is_ok());
+        assert!(parcel.write(&Some("ab")).is_ok());
+
+        unsafe {
+            assert!(parcel.set_data_position(start).is_ok());
+        }
+
+        assert_eq!(parcel.read::<i32>().unwrap(), -1); // null string
+        assert_eq!(parcel.read::<i32>().unwrap(), -1); // null array
+        assert_eq!(parcel.read::<u32>().unwrap(), 0x73622a85); // BINDER_TYPE_BINDER
+        assert_eq!(parcel.read::<u32>().unwrap(), 0); // flags
+        assert_eq!(parcel.read::<u64>().unwrap(), 0); // binder
+        assert_eq!(parcel.read::<u64>().unwrap(), 0); // cookie
+        assert_eq!(parcel.read::<i32>().unwrap(), 0); // stability
+        assert_eq!(parcel.read::<i32>().unwrap(), 2); // 2 chars
+        assert_eq!(parcel.read::<u32>().unwrap(), 0x00620061); // "ab"
+        assert_eq!(parcel.read::<u32>().unwrap(), 0); // NUL and padding
+
+        unsafe {
+            assert!(parcel.set_data_position(start).is_ok());
+        }
+
+        assert_eq!(parcel.read::<Option<String>>().unwrap(), None);
+        assert_eq!(parcel.read::<Option<Vec<i32>>>().unwrap(), None);
+        assert!(parcel.read::<Option<SpIBinder>>().unwrap().is_none());
+        assert_eq!(parcel.read::<Option<String>>().unwrap().as_deref(), Some("ab"));
+
+        let status = Status::new_service_specific_error_str(-42, Some("message"));
+
+        unsafe {
+            assert!(parcel.set_data_position(start).is_ok());
+        }
+        assert!(parcel.write(&status).is_ok());
+        unsafe {
+            assert!(parcel.set_data_position(start).is_ok());
+        }
+
+        let status = parcel.read::<Status>().unwrap();
+        assert_eq!(status.service_specific_error(), -42);
+        assert_eq!(status.get_description(), "Status(-8, EX_SERVICE_SPECIFIC): '-42: message'");
+    }
+
+    #[test]
+    fn test_maps_and_sets() {
+        let mut parcel = Parcel::new();
+        let start = parcel.get_data_position();
+
+        let map = BTreeMap::from([("a".to_string(), 1), ("b".to_string(), 2)]);
+        let hash_map: HashMap<_, _> = map.clone().into_iter().collect();
+        let set = BTreeSet::from([3i64, 1, 2]);
+        let hash_set: HashSet<_> = set.iter().copied().collect();
+
+        assert!(parcel.write(&map).is_ok());
+        assert!(parcel.write(&hash_map).is_ok());
+        assert!(parcel.write(&set).is_ok());
+        assert!(parcel.write(&hash_set).is_ok());
+        assert!(parcel.write(&None::<BTreeMap<String, i32>>).is_ok());
+        assert!(parcel.write(&None::<HashSet<i64>>).is_ok());
+
+        unsafe {
+            assert!(parcel.set_data_position(start).is_ok());
+        }
+
+        assert_eq!(parcel.read::<i32>().unwrap(), 2); // 2 entries
+        assert_eq!(parcel.read::<String>().unwrap(), "a");
+        assert_eq!(parcel.read::<i32>().unwrap(), 1);
+        assert_eq!(parcel.read::<String>().unwrap(), "b");
+        assert_eq!(parcel.read::<i32>().unwrap(), 2);
+
+        unsafe {
+            assert!(parcel.set_data_position(start).is_ok());
+        }
+
+        assert_eq!(parcel.read::<BTreeMap<String, i32>>().unwrap(), map);
+        assert_eq!(parcel.read::<HashMap<String, i32>>().unwrap(), hash_map);
+        assert_eq!(parcel.read::<BTreeSet<i64>>().unwrap(), set);
+        assert_eq!(parcel.read::<HashSet<i64>>().unwrap(), hash_set);
+        assert_eq!(parcel.read::<Option<HashMap<String, i32>>>().unwrap(), None);
+        assert_eq!(parcel.read::<Option<BTreeSet<i64>>>().unwrap(), None);
+
+        unsafe {
+            assert!(parcel.set_data_position(start).is_ok());
+        }
+        assert!(parcel.write(&-1i32).is_ok());
+        unsafe {
+            assert!(parcel.set_data_position(start).is_ok());
+        }
+        assert_eq!(parcel.read::<BTreeMap<String, i32>>(), Err(StatusCode::UNEXPECTED_NULL));
+    }
+
+    #[test]
+    fn test_nested_vecs() {
+        let mut parcel = Parcel::new();
+        let start = parcel.get_data_position();
+
+        let vecs = vec![vec![1, 2], vec![], vec![3]];
+        let strings = vec![Some(vec!["a".to_string()]), None];
+        let maps = vec![HashMap::from([(1u8, vec![2i8])])];
+
+        assert!(parcel.write(&vecs).is_ok());
+        assert!(parcel.write(&strings).is_ok());
+        assert!(parcel.write(&maps).is_ok());
+
+        unsafe {
+            assert!(parcel.set_data_position(start).is_ok());
+        }
+
+        assert_eq!(parcel.read::<i32>().unwrap(), 3); // 3 vecs
+        assert_eq!(parcel.read::<Vec<i32>>().unwrap(), [1, 2]);
+
+        unsafe {
+            assert!(parcel.set_data_position(start).is_ok());
+        }
+
+        assert_eq!(parcel.read::<Vec<Vec<i32>>>().unwrap(), vecs);
+        assert_eq!(parcel.read::<Vec<Option<Vec<String>>>>().unwrap(), strings);
+        assert_eq!(parcel.read::<Vec<HashMap<u8, Vec<i8>>>>().unwrap(), maps);
+    }
+
+    #[test]
+    fn test_stability() {
+        let mut parcel = Parcel::new();
+        let start = parcel.get_data_position();
+
+        for stability in [Stability::Local, Stability::Vendor, Stability::System, Stability::Vintf]
+        {
+            assert!(parcel.write(&stability).is_ok());
+        }
+        assert!(parcel.write(&2i32).is_ok());
+
+        unsafe {
+            assert!(parcel.set_data_position(start).is_ok());
+        }
+
+        assert_eq!(parcel.read::<i32>().unwrap(), 0); // local
+        assert_eq!(parcel.read::<i32>().unwrap(), 0); // vendor is local
+        assert_eq!(parcel.read::<i32>().unwrap(), 0); // system is local
+        assert_eq!(parcel.read::<Stability>().unwrap(), Stability::Vintf);
+        assert_eq!(parcel.read::<Stability>(), Err(StatusCode::BAD_VALUE));
+    }
 }
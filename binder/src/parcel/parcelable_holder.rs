@@ -91,7 +91,8 @@ impl ParcelableHolder {
     where
         T: Any + Parcelable + ParcelableMetadata + std::fmt::Debug + Send + Sync,
     {
-        if self.stability > p.get_stability() {
+        // The parcelable must be at least as stable as this holder promises.
+        if !p.get_stability().can_be_called_from(self.stability) {
             return Err(StatusCode::BAD_VALUE);
         }
 
@@ -179,6 +180,15 @@ impl Deserialize for ParcelableHolder {
             Ok(parcelable)
         }
     }
+
+    fn deserialize_from(&mut self, parcel: &BorrowedParcel<'_>) -> Result<(), StatusCode> {
+        let status: i32 = parcel.read()?;
+        if status == NULL_PARCELABLE_FLAG {
+            Err(StatusCode::UNEXPECTED_NULL)
+        } else {
+            self.read_from_parcel(parcel)
+        }
+    }
 }
 
 impl Parcelable for ParcelableHolder {
@@ -219,7 +229,8 @@ impl Parcelable for ParcelableHolder {
     }
 
     fn read_from_parcel(&mut self, parcel: &BorrowedParcel<'_>) -> Result<(), StatusCode> {
-        if self.stability != parcel.read()? {
+        // Compared on the wire, where vendor and system stability are local.
+        if i32::from(self.stability) != parcel.read::<i32>()? {
             return Err(StatusCode::BAD_VALUE);
         }
 
@@ -254,3 +265,86 @@ impl Parcelable for ParcelableHolder {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A parcelable of any stability, which writes nothing else.
+    #[derive(Debug, Default)]
+    struct Tagged(Stability);
+
+    impl Parcelable for Tagged {
+        fn write_to_parcel(&self, _parcel: &mut BorrowedParcel<'_>) -> Result<(), StatusCode> {
+            Ok(())
+        }
+
+        fn read_from_parcel(&mut self, _parcel: &BorrowedParcel<'_>) -> Result<(), StatusCode> {
+            Ok(())
+        }
+    }
+
+    impl ParcelableMetadata for Tagged {
+        fn get_descriptor() -> &'static str {
+            "Tagged"
+        }
+
+        fn get_stability(&self) -> Stability {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_set_parcelable_stability() {
+        let mut vendor = ParcelableHolder::new(Stability::Vendor);
+        assert!(vendor.set_parcelable(Arc::new(Tagged(Stability::Vendor))).is_ok());
+        assert!(vendor.set_parcelable(Arc::new(Tagged(Stability::Vintf))).is_ok());
+        assert_eq!(
+            vendor.set_parcelable(Arc::new(Tagged(Stability::System))),
+            Err(StatusCode::BAD_VALUE)
+        );
+        assert_eq!(
+            vendor.set_parcelable(Arc::new(Tagged(Stability::Local))),
+            Err(StatusCode::BAD_VALUE)
+        );
+
+        let mut system = ParcelableHolder::new(Stability::System);
+        assert!(system.set_parcelable(Arc::new(Tagged(Stability::System))).is_ok());
+        assert_eq!(
+            system.set_parcelable(Arc::new(Tagged(Stability::Vendor))),
+            Err(StatusCode::BAD_VALUE)
+        );
+
+        let mut local = ParcelableHolder::new(Stability::Local);
+        assert!(local.set_parcelable(Arc::new(Tagged(Stability::Vendor))).is_ok());
+    }
+
+    #[test]
+    fn test_deserialize_from() {
+        let mut parcel = Parcel::new();
+        assert!(parcel.write(&NULL_PARCELABLE_FLAG).is_ok());
+        let mut vintf = ParcelableHolder::new(Stability::Vintf);
+        assert!(vintf.set_parcelable(Arc::new(Tagged(Stability::Vintf))).is_ok());
+        assert!(parcel.write(&vintf).is_ok());
+        assert!(parcel.write(&vintf).is_ok());
+        unsafe {
+            assert!(parcel.set_data_position(0).is_ok());
+        }
+
+        let mut holder = ParcelableHolder::new(Stability::Vintf);
+        assert_eq!(
+            holder.deserialize_from(parcel.borrowed_ref()),
+            Err(StatusCode::UNEXPECTED_NULL)
+        );
+
+        // The holder is read in place, keeping its stability, where replacing
+        // it with a deserialized one would have local stability.
+        assert!(holder.deserialize_from(parcel.borrowed_ref()).is_ok());
+        assert_eq!(holder.get_stability(), Stability::Vintf);
+        assert!(holder.get_parcelable::<Tagged>().unwrap().is_some());
+
+        let mut local = ParcelableHolder::new(Stability::Local);
+        assert_eq!(local.deserialize_from(parcel.borrowed_ref()), Err(StatusCode::BAD_VALUE));
+        assert_eq!(local.get_stability(), Stability::Local);
+    }
+}
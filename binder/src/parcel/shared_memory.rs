@@ -0,0 +1,432 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use super::{
+    BorrowedParcel, Deserialize, DeserializeArray, DeserializeOption, Serialize, SerializeArray,
+    SerializeOption,
+};
+use crate::error::{status_result, Result, StatusCode};
+
+use std::ffi::CString;
+use std::fmt;
+use std::io;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::ptr::{self, NonNull};
+use std::slice;
+
+/// Seals every `SharedMemory` carries, so that its size can be trusted.
+const SIZE_SEALS: libc::c_int = libc::F_SEAL_SHRINK | libc::F_SEAL_GROW;
+
+/// A block of shared memory, backed by a memfd, for passing payloads too big
+/// for a transaction's buffer.
+///
+/// Only the file descriptor and size go into the parcel. The memfd is sealed
+/// against resizing, and the receiving side maps it read-only after checking
+/// those seals and the size, so the sender can't truncate it under the
+/// receiver. Call [`seal`](Self::seal) before sending to also stop the sender
+/// changing the contents.
+///
+/// Until it is sealed, any process sharing the memory can change it at any
+/// time, so its contents can only be copied in and out with
+/// [`read_at`](Self::read_at) and [`write_at`](Self::write_at). Sealed contents
+/// can be borrowed with [`as_sealed`](Self::as_sealed).
+pub struct SharedMemory {
+    fd: OwnedFd,
+    ptr: NonNull<u8>,
+    len: usize,
+    writable: bool,
+}
+
+// Safety: `SharedMemory` owns its mapping. It only hands out references to it
+// once the memfd is sealed against writes, and otherwise only copies in and out
+// of it through raw pointers, which other mappings writing concurrently can't
+// invalidate.
+unsafe impl Send for SharedMemory {}
+unsafe impl Sync for SharedMemory {}
+
+/// The `StatusCode` for the last failed libc call.
+fn last_error() -> StatusCode {
+    let errno = io::Error::last_os_error().raw_os_error().unwrap_or(libc::EINVAL);
+    status_result(-errno).err().unwrap_or(StatusCode::UNKNOWN_ERROR)
+}
+
+/// Map `len` bytes of `fd`, or nothing if `len` is zero.
+fn map(fd: BorrowedFd<'_>, len: usize, writable: bool) -> Result<NonNull<u8>> {
+    if len == 0 {
+        return Ok(NonNull::dangling());
+    }
+    // A shared mapping, even a read-only one, could be made writable, so the
+    // kernel won't add `F_SEAL_WRITE` while one exists. Read-only mappings are
+    // private instead, which still sees the other side's writes.
+    let (prot, flags) = if writable {
+        (libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED)
+    } else {
+        (libc::PROT_READ, libc::MAP_PRIVATE)
+    };
+    let ptr = unsafe {
+        // Safety: Mapping a file we have a descriptor for at a new address
+        // doesn't touch any existing memory.
+        libc::mmap(ptr::null_mut(), len, prot, flags, fd.as_raw_fd(), 0)
+    };
+    if ptr == libc::MAP_FAILED {
+        return Err(last_error());
+    }
+    NonNull::new(ptr as *mut u8).ok_or(StatusCode::NO_MEMORY)
+}
+
+/// The seals on `fd`, or `BAD_VALUE` if it isn't a memfd.
+fn seals(fd: BorrowedFd<'_>) -> Result<libc::c_int> {
+    let seals = unsafe {
+        // Safety: `F_GET_SEALS` only reads the seals of a valid descriptor.
+        libc::fcntl(fd.as_raw_fd(), libc::F_GET_SEALS)
+    };
+    if seals < 0 {
+        return Err(StatusCode::BAD_VALUE);
+    }
+    Ok(seals)
+}
+
+impl SharedMemory {
+    /// Create zeroed, writable shared memory of `size` bytes. `name` only
+    /// shows up in `/proc/<pid>/fd` for debugging.
+    pub fn new(name: &str, size: usize) -> Result<Self> {
+        let name = CString::new(name).or(Err(StatusCode::BAD_VALUE))?;
+        let size_off = libc::off_t::try_from(size).or(Err(StatusCode::BAD_VALUE))?;
+        // Bionic only has the memfd_create wrapper from API level 30.
+        let fd = unsafe {
+            // Safety: `name` is a valid NUL-terminated string.
+            libc::syscall(
+                libc::SYS_memfd_create,
+                name.as_ptr(),
+                libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING,
+            )
+        };
+        if fd < 0 {
+            return Err(last_error());
+        }
+        let fd = unsafe {
+            // Safety: memfd_create returned a new descriptor which nothing
+            // else owns.
+            OwnedFd::from_raw_fd(fd as RawFd)
+        };
+        unsafe {
+            // Safety: These only change the size and seals of our own memfd.
+            if libc::ftruncate(fd.as_raw_fd(), size_off) < 0
+                || libc::fcntl(fd.as_raw_fd(), libc::F_ADD_SEALS, SIZE_SEALS) < 0
+            {
+                return Err(last_error());
+            }
+        }
+        let ptr = map(fd.as_fd(), size, true)?;
+        Ok(Self { fd, ptr, len: size, writable: true })
+    }
+
+    /// Create shared memory holding a copy of `data`.
+    pub fn from_slice(name: &str, data: &[u8]) -> Result<Self> {
+        let mut memory = Self::new(name, data.len())?;
+        memory.write_at(0, data)?;
+        Ok(memory)
+    }
+
+    /// The size of the memory in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the memory is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Copy the contents from `offset` into `buf`.
+    ///
+    /// Unless the memory is sealed, another process sharing it can change the
+    /// contents at any time, even during the copy, so validate the copy rather
+    /// than reading again. Fails with `BAD_INDEX` if `buf` doesn't fit.
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<()> {
+        self.check_range(offset, buf.len())?;
+        unsafe {
+            // Safety: `check_range` made sure the mapping holds `buf.len()`
+            // bytes from `offset`. Copying through raw pointers doesn't
+            // assert that nothing else writes to them.
+            ptr::copy_nonoverlapping(self.ptr.as_ptr().add(offset), buf.as_mut_ptr(), buf.len());
+        }
+        Ok(())
+    }
+
+    /// Copy the whole contents out, as for [`read_at`](Self::read_at).
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut contents = vec![0; self.len];
+        self.read_at(0, &mut contents).expect("the contents fit their own size");
+        contents
+    }
+
+    /// Copy `data` into the memory at `offset`.
+    ///
+    /// Fails with `INVALID_OPERATION` if the memory isn't mapped writable
+    /// here, as for memory read from a parcel or sealed, and with `BAD_INDEX`
+    /// if `data` doesn't fit.
+    pub fn write_at(&mut self, offset: usize, data: &[u8]) -> Result<()> {
+        if !self.writable {
+            return Err(StatusCode::INVALID_OPERATION);
+        }
+        self.check_range(offset, data.len())?;
+        unsafe {
+            // Safety: As for `read_at`, and the mapping is writable.
+            ptr::copy_nonoverlapping(data.as_ptr(), self.ptr.as_ptr().add(offset), data.len());
+        }
+        Ok(())
+    }
+
+    fn check_range(&self, offset: usize, len: usize) -> Result<()> {
+        match offset.checked_add(len) {
+            Some(end) if end <= self.len => Ok(()),
+            _ => Err(StatusCode::BAD_INDEX),
+        }
+    }
+
+    /// Seal the contents against writes from any process, and map them
+    /// read-only here.
+    ///
+    /// Fails with `INVALID_OPERATION` if the memory was received from a
+    /// parcel, as only its creator can seal it.
+    pub fn seal(&mut self) -> Result<()> {
+        if self.is_sealed()? {
+            return Ok(());
+        }
+        if !self.writable {
+            return Err(StatusCode::INVALID_OPERATION);
+        }
+        // Swap our writable mapping for a read-only one first, see `map`.
+        let ptr = map(self.fd.as_fd(), self.len, false)?;
+        self.unmap();
+        self.ptr = ptr;
+        self.writable = false;
+        let status = unsafe {
+            // Safety: This only changes the seals of our own memfd.
+            libc::fcntl(
+                self.fd.as_raw_fd(),
+                libc::F_ADD_SEALS,
+                libc::F_SEAL_WRITE | libc::F_SEAL_SEAL,
+            )
+        };
+        if status < 0 {
+            return Err(last_error());
+        }
+        Ok(())
+    }
+
+    /// Whether the contents are sealed against writes.
+    pub fn is_sealed(&self) -> Result<bool> {
+        Ok(seals(self.fd.as_fd())? & libc::F_SEAL_WRITE != 0)
+    }
+
+    /// The contents of the memory, if they are sealed against writes, so that
+    /// they can be validated and then used without another process changing
+    /// them in between.
+    ///
+    /// Fails with `INVALID_OPERATION` if the memory isn't sealed.
+    pub fn as_sealed(&self) -> Result<&[u8]> {
+        if !self.is_sealed()? {
+            return Err(StatusCode::INVALID_OPERATION);
+        }
+        Ok(unsafe {
+            // Safety: `ptr` is mapped for `len` bytes, or dangling if `len` is
+            // zero, for as long as `self` lives. The seal can't be removed, so
+            // nothing can write to the memfd while the slice is alive.
+            slice::from_raw_parts(self.ptr.as_ptr(), self.len)
+        })
+    }
+
+    fn unmap(&mut self) {
+        if self.len > 0 {
+            unsafe {
+                // Safety: `ptr` is our own mapping of `len` bytes, and no
+                // slices of it can outlive the `&mut self` borrow.
+                libc::munmap(self.ptr.as_ptr() as *mut libc::c_void, self.len);
+            }
+        }
+    }
+}
+
+impl Drop for SharedMemory {
+    fn drop(&mut self) {
+        self.unmap();
+    }
+}
+
+impl AsFd for SharedMemory {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl AsRawFd for SharedMemory {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl fmt::Debug for SharedMemory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedMemory")
+            .field("fd", &self.fd)
+            .field("len", &self.len)
+            .field("writable", &self.writable)
+            .finish()
+    }
+}
+
+impl Serialize for SharedMemory {
+    fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        parcel.write(&self.fd)?;
+        // `new` checked that the size fits in an `off_t`.
+        parcel.write(&(self.len as i64))
+    }
+}
+
+impl SerializeArray for SharedMemory {}
+
+impl SerializeOption for SharedMemory {
+    fn serialize_option(this: Option<&Self>, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        match this {
+            Some(memory) => memory.serialize(parcel),
+            None => OwnedFd::serialize_option(None, parcel),
+        }
+    }
+}
+
+impl DeserializeOption for SharedMemory {
+    fn deserialize_option(parcel: &BorrowedParcel<'_>) -> Result<Option<Self>> {
+        let Some(fd) = parcel.read::<Option<OwnedFd>>()? else {
+            return Ok(None);
+        };
+        let len = usize::try_from(parcel.read::<i64>()?).or(Err(StatusCode::BAD_VALUE))?;
+
+        // Without these seals the sender could shrink the memfd after we map
+        // it, and reading past its end would raise SIGBUS.
+        if seals(fd.as_fd())? & SIZE_SEALS != SIZE_SEALS {
+            return Err(StatusCode::BAD_VALUE);
+        }
+        let mut stat = std::mem::MaybeUninit::<libc::stat>::uninit();
+        let stat = unsafe {
+            // Safety: `fstat` fills in `stat` if it succeeds.
+            if libc::fstat(fd.as_raw_fd(), stat.as_mut_ptr()) < 0 {
+                return Err(last_error());
+            }
+            stat.assume_init()
+        };
+        if usize::try_from(stat.st_size) != Ok(len) {
+            return Err(StatusCode::BAD_VALUE);
+        }
+
+        let ptr = map(fd.as_fd(), len, false)?;
+        Ok(Some(Self { fd, ptr, len, writable: false }))
+    }
+}
+
+impl Deserialize for SharedMemory {
+    fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
+        Deserialize::deserialize(parcel).transpose().unwrap_or(Err(StatusCode::UNEXPECTED_NULL))
+    }
+}
+
+impl DeserializeArray for SharedMemory {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parcel::Parcel;
+
+    use std::fs::File;
+    use std::io::Write;
+
+    fn rewind(parcel: &Parcel) {
+        unsafe {
+            assert!(parcel.set_data_position(0).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_shared_memory() {
+        let data: Vec<u8> = (0..4096u32).map(|i| i as u8).collect();
+        let mut memory = SharedMemory::from_slice("test", &data).unwrap();
+        assert_eq!(memory.to_vec(), data);
+        assert_eq!(memory.as_sealed(), Err(StatusCode::INVALID_OPERATION));
+        assert!(!memory.is_sealed().unwrap());
+
+        let mut parcel = Parcel::new();
+        assert!(parcel.write(&memory).is_ok());
+        assert!(parcel.write(&None::<SharedMemory>).is_ok());
+        assert!(parcel.write(&SharedMemory::new("empty", 0).unwrap()).is_ok());
+        rewind(&parcel);
+
+        let mut received = parcel.read::<SharedMemory>().unwrap();
+        assert_eq!(received.to_vec(), data);
+        assert_eq!(received.as_sealed(), Err(StatusCode::INVALID_OPERATION));
+        assert_eq!(received.write_at(0, &[0]), Err(StatusCode::INVALID_OPERATION));
+        assert_eq!(received.seal(), Err(StatusCode::INVALID_OPERATION));
+        assert!(parcel.read::<Option<SharedMemory>>().unwrap().is_none());
+        assert!(parcel.read::<SharedMemory>().unwrap().is_empty());
+
+        // Unsealed memory is still shared with the sender.
+        assert!(memory.write_at(4094, &[0xff, 0xfe]).is_ok());
+        let mut end = [0; 2];
+        assert!(received.read_at(4094, &mut end).is_ok());
+        assert_eq!(end, [0xff, 0xfe]);
+        assert_eq!(memory.write_at(4095, &[0, 0]), Err(StatusCode::BAD_INDEX));
+        assert_eq!(received.read_at(usize::MAX, &mut end), Err(StatusCode::BAD_INDEX));
+
+        assert!(memory.seal().is_ok());
+        assert!(memory.is_sealed().unwrap());
+        assert!(received.is_sealed().unwrap());
+        assert_eq!(received.as_sealed().unwrap()[4094], 0xff);
+        assert_eq!(memory.write_at(0, &[0]), Err(StatusCode::INVALID_OPERATION));
+        assert_eq!(memory.as_sealed().unwrap()[4095], 0xfe);
+        assert!(memory.seal().is_ok());
+    }
+
+    #[test]
+    fn test_shared_memory_validation() {
+        let memory = SharedMemory::new("test", 16).unwrap();
+
+        // The size must match the memfd's.
+        let mut parcel = Parcel::new();
+        let fd = memory.as_fd().try_clone_to_owned().unwrap();
+        assert!(parcel.write(&fd).is_ok());
+        assert!(parcel.write(&32i64).is_ok());
+        rewind(&parcel);
+        assert_eq!(parcel.read::<SharedMemory>().err(), Some(StatusCode::BAD_VALUE));
+
+        // Files that can be resized are refused.
+        let mut parcel = Parcel::new();
+        let mut file: File = tempfile();
+        assert!(file.write_all(&[0; 16]).is_ok());
+        assert!(parcel.write(&OwnedFd::from(file)).is_ok());
+        assert!(parcel.write(&16i64).is_ok());
+        rewind(&parcel);
+        assert_eq!(parcel.read::<SharedMemory>().err(), Some(StatusCode::BAD_VALUE));
+    }
+
+    /// An unsealed memfd.
+    fn tempfile() -> File {
+        let fd = unsafe {
+            libc::syscall(libc::SYS_memfd_create, b"unsealed\0".as_ptr(), libc::MFD_CLOEXEC)
+        };
+        assert!(fd >= 0);
+        unsafe { File::from_raw_fd(fd as RawFd) }
+    }
+}
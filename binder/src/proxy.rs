@@ -20,7 +20,7 @@ use crate::binder::{
     AsNative, FromIBinder, IBinder, IBinderInternal, Interface, InterfaceClass, Strong,
     TransactionCode, TransactionFlags,
 };
-use crate::error::{status_result, Result, StatusCode};
+use crate::error::{status_result, Result, Status, StatusCode};
 use crate::parcel::{
     BorrowedParcel, Deserialize, DeserializeArray, DeserializeOption, Parcel, Serialize,
     SerializeArray, SerializeOption,
@@ -35,7 +35,9 @@ use std::mem;
 use std::os::raw::c_char;
 use std::os::unix::io::AsRawFd;
 use std::ptr;
-use std::sync::Arc;
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::{mpsc, Arc, Mutex, Weak};
+use std::thread;
 
 /// A strong reference to a Binder remote object.
 ///
@@ -131,6 +133,87 @@ impl SpIBinder {
     pub fn downgrade(&mut self) -> WpIBinder {
         WpIBinder::new(self)
     }
+
+    /// Downgrade the stability of this local binder to that of the vendor
+    /// partition, to use an interface within the partition without the
+    /// requirements of its higher stability, e.g. being in the VINTF manifest.
+    ///
+    /// Keep this `SpIBinder` around while using the downgraded binder. Only
+    /// local binders can be downgraded, otherwise this fails with
+    /// `INVALID_OPERATION`.
+    pub fn force_downgrade_to_vendor_stability(&mut self) -> Result<()> {
+        if self.is_remote() {
+            return Err(StatusCode::INVALID_OPERATION);
+        }
+        unsafe {
+            // Safety: `SpIBinder` guarantees that it always contains a valid
+            // `AIBinder` pointer, which we checked is local.
+            sys::AIBinder_forceDowngradeToVendorStability(self.as_native_mut());
+        }
+        Ok(())
+    }
+
+    /// Downgrade the stability of this local binder to that of the system
+    /// partition, see [`SpIBinder::force_downgrade_to_vendor_stability`].
+    pub fn force_downgrade_to_system_stability(&mut self) -> Result<()> {
+        if self.is_remote() {
+            return Err(StatusCode::INVALID_OPERATION);
+        }
+        unsafe {
+            // Safety: `SpIBinder` guarantees that it always contains a valid
+            // `AIBinder` pointer, which we checked is local.
+            sys::AIBinder_forceDowngradeToSystemStability(self.as_native_mut());
+        }
+        Ok(())
+    }
+
+    /// Downgrade the stability of this local binder to that of the partition
+    /// we are building for, see
+    /// [`SpIBinder::force_downgrade_to_vendor_stability`].
+    pub fn force_downgrade_to_local_stability(&mut self) -> Result<()> {
+        if cfg!(any(vendor_ndk, android_vndk)) {
+            self.force_downgrade_to_vendor_stability()
+        } else {
+            self.force_downgrade_to_system_stability()
+        }
+    }
+
+    /// Run a shell command on this binder, as `cmd` would, and return its
+    /// result.
+    ///
+    /// Only available on the host: on Android, shell commands are sent by
+    /// `cmd`.
+    #[cfg(feature = "host")]
+    pub fn shell_command(
+        &mut self,
+        stdin: &impl AsRawFd,
+        stdout: &impl AsRawFd,
+        stderr: &impl AsRawFd,
+        args: &[&str],
+    ) -> Result<()> {
+        let args = args
+            .iter()
+            .map(|a| CString::new(*a).or(Err(StatusCode::UNEXPECTED_NULL)))
+            .collect::<Result<Vec<_>>>()?;
+        let mut arg_ptrs: Vec<_> = args.iter().map(|a| a.as_ptr()).collect();
+        let status = unsafe {
+            // Safety: `SpIBinder` guarantees that `self` always contains a
+            // valid pointer to an `AIBinder`. `AsRawFd` guarantees that the
+            // file descriptors are valid open files. `arg_ptrs` points to C
+            // strings that live for the whole function scope.
+            //
+            // This call does not take ownership of any of its parameters.
+            sys::AIBinder_shellCommand(
+                self.as_native_mut(),
+                stdin.as_raw_fd(),
+                stdout.as_raw_fd(),
+                stderr.as_raw_fd(),
+                arg_ptrs.as_mut_ptr(),
+                arg_ptrs.len().try_into().unwrap(),
+            )
+        };
+        status_result(status)
+    }
 }
 
 fn interface_cast<T: FromIBinder + ?Sized>(service: Option<SpIBinder>) -> Result<Strong<T>> {
@@ -328,7 +411,10 @@ impl<T: AsNative<sys::AIBinder>> IBinderInternal for T {
     }
 
     fn dump<F: AsRawFd>(&mut self, fp: &F, args: &[&str]) -> Result<()> {
-        let args: Vec<_> = args.iter().map(|a| CString::new(*a).unwrap()).collect();
+        let args = args
+            .iter()
+            .map(|a| CString::new(*a).or(Err(StatusCode::UNEXPECTED_NULL)))
+            .collect::<Result<Vec<_>>>()?;
         let mut arg_ptrs: Vec<_> = args.iter().map(|a| a.as_ptr()).collect();
         let status = unsafe {
             // Safety: `SpIBinder` guarantees that `self` always contains a
@@ -773,6 +859,18 @@ pub fn get_service(name: &str) -> Option<SpIBinder> {
     }
 }
 
+/// Retrieve an existing service, returning `None` right away if it isn't
+/// registered.
+pub fn check_service(name: &str) -> Option<SpIBinder> {
+    let name = CString::new(name).ok()?;
+    unsafe {
+        // Safety: `AServiceManager_checkService` returns either a null pointer
+        // or a valid pointer to an owned `AIBinder`. Either of these values is
+        // safe to pass to `SpIBinder::from_raw`.
+        SpIBinder::from_raw(sys::AServiceManager_checkService(name.as_ptr()))
+    }
+}
+
 /// Retrieve an existing service, or start it if it is configured as a dynamic
 /// service and isn't yet started.
 pub fn wait_for_service(name: &str) -> Option<SpIBinder> {
@@ -791,12 +889,416 @@ pub fn get_interface<T: FromIBinder + ?Sized>(name: &str) -> Result<Strong<T>> {
     interface_cast(get_service(name))
 }
 
+/// Retrieve an existing service for a particular interface, failing with
+/// `StatusCode::NAME_NOT_FOUND` right away if it isn't registered.
+pub fn check_interface<T: FromIBinder + ?Sized>(name: &str) -> Result<Strong<T>> {
+    interface_cast(check_service(name))
+}
+
 /// Retrieve an existing service for a particular interface, or start it if it
 /// is configured as a dynamic service and isn't yet started.
 pub fn wait_for_interface<T: FromIBinder + ?Sized>(name: &str) -> Result<Strong<T>> {
     interface_cast(wait_for_service(name))
 }
 
+/// A client's handle to a service for a particular interface, which connects on
+/// first use and reconnects after the service dies.
+///
+/// The service is resolved with [`wait_for_interface`] and cached. A death
+/// recipient drops the cached service when its process dies, so the next use
+/// connects again. Clones share the cached service. Use
+/// [`ServiceHandle::builder`] to configure how the handle reconnects.
+pub struct ServiceHandle<T: FromIBinder + ?Sized> {
+    inner: Arc<ServiceHandleInner<T>>,
+}
+
+struct ServiceHandleInner<T: FromIBinder + ?Sized> {
+    name: String,
+    retry_on_dead_object: bool,
+    on_reconnect: Option<ReconnectCallback<T>>,
+    state: Arc<Mutex<ServiceHandleState<T>>>,
+}
+
+type ReconnectCallback<T> = Box<dyn Fn(&Strong<T>) + Send + Sync>;
+
+/// Builder for a [`ServiceHandle`], from [`ServiceHandle::builder`].
+pub struct ServiceHandleBuilder<T: FromIBinder + ?Sized> {
+    name: String,
+    retry_on_dead_object: bool,
+    on_reconnect: Option<ReconnectCallback<T>>,
+}
+
+impl<T: FromIBinder + ?Sized + 'static> ServiceHandleBuilder<T> {
+    /// Whether [`ServiceHandle::call`] reconnects and calls again, once, when
+    /// the service dies during the call. Only enable this for calls which are
+    /// safe to repeat. Disabled by default.
+    pub fn retry_on_dead_object(mut self, retry: bool) -> Self {
+        self.retry_on_dead_object = retry;
+        self
+    }
+
+    /// Call `callback` with the new service every time the handle reconnects
+    /// after losing the service, for example to register listeners again. It
+    /// isn't called for the first connection.
+    pub fn on_reconnect<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Strong<T>) + Send + Sync + 'static,
+    {
+        self.on_reconnect = Some(Box::new(callback));
+        self
+    }
+
+    /// Build the handle, without connecting to the service yet.
+    pub fn build(self) -> ServiceHandle<T> {
+        ServiceHandle {
+            inner: Arc::new(ServiceHandleInner {
+                name: self.name,
+                retry_on_dead_object: self.retry_on_dead_object,
+                on_reconnect: self.on_reconnect,
+                state: Arc::new(Mutex::new(ServiceHandleState { service: None, generation: 0 })),
+            }),
+        }
+    }
+}
+
+impl<T: FromIBinder + ?Sized> fmt::Debug for ServiceHandleBuilder<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServiceHandleBuilder")
+            .field("name", &self.name)
+            .field("retry_on_dead_object", &self.retry_on_dead_object)
+            .field("on_reconnect", &self.on_reconnect.is_some())
+            .finish()
+    }
+}
+
+struct ServiceHandleState<T: FromIBinder + ?Sized> {
+    /// The cached service, and its death recipient unless it is local.
+    service: Option<(Strong<T>, Option<DeathRecipient>)>,
+    /// Incremented for every connection, so that a late death notification
+    /// doesn't drop a newer connection.
+    generation: u64,
+}
+
+impl<T: FromIBinder + ?Sized + 'static> ServiceHandle<T> {
+    /// Create a handle to the service `name`, without connecting to it yet.
+    pub fn new(name: &str) -> Self {
+        Self::builder(name).build()
+    }
+
+    /// Start building a handle to the service `name`.
+    pub fn builder(name: &str) -> ServiceHandleBuilder<T> {
+        ServiceHandleBuilder {
+            name: name.to_owned(),
+            retry_on_dead_object: false,
+            on_reconnect: None,
+        }
+    }
+
+    /// The name of the service.
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    /// The service, if the handle is connected to it.
+    pub fn cached(&self) -> Option<Strong<T>> {
+        let state = self.inner.state.lock().unwrap();
+        state.service.as_ref().map(|(service, _)| service.clone())
+    }
+
+    /// The service, connecting to it first if needed. This blocks until the
+    /// service is available, or starts it if it is a dynamic service.
+    pub fn get(&self) -> Result<Strong<T>> {
+        match self.cached() {
+            Some(service) => Ok(service),
+            None => self.connect(wait_for_interface(&self.inner.name)?),
+        }
+    }
+
+    /// Cache `service`, which was just resolved, unless another thread
+    /// connected first, in which case use theirs.
+    fn connect(&self, service: Strong<T>) -> Result<Strong<T>> {
+        let mut state = self.inner.state.lock().unwrap();
+        if let Some((cached, _)) = &state.service {
+            return Ok(cached.clone());
+        }
+        state.generation += 1;
+        let reconnected = state.generation > 1;
+        let recipient = self.link_to_death(&service, state.generation)?;
+        state.service = Some((service.clone(), recipient));
+        drop(state);
+
+        if reconnected {
+            if let Some(on_reconnect) = &self.inner.on_reconnect {
+                on_reconnect(&service);
+            }
+        }
+        Ok(service)
+    }
+
+    fn link_to_death(
+        &self,
+        service: &Strong<T>,
+        generation: u64,
+    ) -> Result<Option<DeathRecipient>> {
+        let state = Arc::downgrade(&self.inner.state);
+        let mut recipient = DeathRecipient::new(move || {
+            Self::forget(&state, |state| state.generation == generation);
+        });
+        match service.as_binder().link_to_death(&mut recipient) {
+            Ok(()) => Ok(Some(recipient)),
+            // Local services never die.
+            Err(StatusCode::INVALID_OPERATION) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Drop the cached service if `is_stale` says so.
+    fn forget(
+        state: &Weak<Mutex<ServiceHandleState<T>>>,
+        is_stale: impl FnOnce(&ServiceHandleState<T>) -> bool,
+    ) {
+        let Some(state) = state.upgrade() else {
+            return;
+        };
+        let mut state = state.lock().unwrap();
+        let service = if is_stale(&state) { state.service.take() } else { None };
+        // Drop the death recipient after releasing the lock.
+        drop(state);
+        drop(service);
+    }
+
+    /// Drop `service` if it is the cached service, so that the next use
+    /// reconnects. For calls that failed with `DEAD_OBJECT` before the death
+    /// notification arrived.
+    pub fn invalidate(&self, service: &Strong<T>) {
+        let binder = service.as_binder();
+        Self::forget(&Arc::downgrade(&self.inner.state), |state| {
+            state.service.as_ref().map_or(false, |(cached, _)| cached.as_binder() == binder)
+        });
+    }
+
+    /// Call `f` with the service, connecting to it first if needed.
+    ///
+    /// If the service dies during the call, the handle reconnects on next use,
+    /// or straight away to call `f` again if
+    /// [`ServiceHandleBuilder::retry_on_dead_object`] is enabled.
+    pub fn call<R, F>(&self, f: F) -> crate::Result<R>
+    where
+        F: Fn(&Strong<T>) -> crate::Result<R>,
+    {
+        match self.call_once(&f) {
+            Err(status) if self.inner.retry_on_dead_object && is_dead_object(&status) => {
+                self.call_once(&f)
+            }
+            result => result,
+        }
+    }
+
+    fn call_once<R>(&self, f: impl Fn(&Strong<T>) -> crate::Result<R>) -> crate::Result<R> {
+        let service = self.get()?;
+        let result = f(&service);
+        if matches!(&result, Err(status) if is_dead_object(status)) {
+            self.invalidate(&service);
+        }
+        result
+    }
+
+    /// Whether [`ServiceHandle::call`] retries calls that failed with
+    /// `DEAD_OBJECT`.
+    pub fn retries_on_dead_object(&self) -> bool {
+        self.inner.retry_on_dead_object
+    }
+}
+
+fn is_dead_object(status: &Status) -> bool {
+    status.transaction_error() == StatusCode::DEAD_OBJECT
+}
+
+impl<T: FromIBinder + ?Sized> Clone for ServiceHandle<T> {
+    fn clone(&self) -> Self {
+        Self { inner: self.inner.clone() }
+    }
+}
+
+impl<T: FromIBinder + ?Sized> fmt::Debug for ServiceHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let connected = self.inner.state.lock().unwrap().service.is_some();
+        f.debug_struct("ServiceHandle")
+            .field("name", &self.inner.name)
+            .field("connected", &connected)
+            .finish()
+    }
+}
+
+/// A registration for notifications about a service, created by
+/// [`register_for_service_notifications`] or
+/// [`register_for_interface_notifications`].
+///
+/// Dropping the registration unregisters it. This blocks until any callback in
+/// progress has returned, after which the callback is never called again. The
+/// callback of an interface registration may drop its own registration, which
+/// then doesn't wait for it.
+pub struct ServiceNotificationRegistration {
+    registration: ptr::NonNull<sys::AServiceManager_NotificationRegistration>,
+    callback: *mut ServiceNotificationCallback,
+    /// The thread calling the callback of an interface registration, and a
+    /// flag telling it to stop.
+    worker: Option<(thread::JoinHandle<()>, Arc<AtomicBool>)>,
+}
+
+type ServiceNotificationCallback = Box<dyn Fn(SpIBinder) + Send + Sync>;
+
+/// # Safety
+///
+/// A `ServiceNotificationRegistration` owns an
+/// `AServiceManager_NotificationRegistration`, which is threadsafe, and a
+/// callback which is `Send` and `Sync`.
+unsafe impl Send for ServiceNotificationRegistration {}
+
+/// # Safety
+///
+/// A `ServiceNotificationRegistration` owns an
+/// `AServiceManager_NotificationRegistration`, which is threadsafe, and a
+/// callback which is `Send` and `Sync`.
+unsafe impl Sync for ServiceNotificationRegistration {}
+
+impl ServiceNotificationRegistration {
+    /// Callback invoked from C++ when the service is registered.
+    ///
+    /// # Safety
+    ///
+    /// The `cookie` parameter must be the callback of a live registration, and
+    /// `registered` an owned `AIBinder` pointer or null.
+    unsafe extern "C" fn on_register(
+        _instance: *const c_char,
+        registered: *mut sys::AIBinder,
+        cookie: *mut c_void,
+    ) {
+        // Safety: we own a reference to `registered`, which is passed on to
+        // the callback.
+        if let Some(binder) = SpIBinder::from_raw(registered) {
+            let callback = (cookie as *const ServiceNotificationCallback).as_ref().unwrap();
+            callback(binder);
+        }
+    }
+}
+
+impl fmt::Debug for ServiceNotificationRegistration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("ServiceNotificationRegistration")
+    }
+}
+
+impl Drop for ServiceNotificationRegistration {
+    fn drop(&mut self) {
+        unsafe {
+            // Safety: `self.registration` is the owned registration returned by
+            // `AServiceManager_registerForServiceNotifications`, and is only
+            // deleted here. Once this returns, the callback is not running and
+            // will never be called again.
+            sys::AServiceManager_NotificationRegistration_delete(self.registration.as_ptr());
+
+            // Safety: the callback was leaked when `self` was created, and
+            // nothing refers to it anymore.
+            drop(Box::from_raw(self.callback));
+        }
+        // Dropping the callback disconnected the worker, so it only has the
+        // callback in progress to finish. It skips notifications already sent.
+        if let Some((worker, stopped)) = self.worker.take() {
+            stopped.store(true, atomic::Ordering::SeqCst);
+            // The callback may drop its own registration, and a thread can't
+            // join itself.
+            if worker.thread().id() != thread::current().id() {
+                let _ = worker.join();
+            }
+        }
+    }
+}
+
+/// Call `callback` with the service `name` every time it is registered,
+/// including right away if it is already registered.
+///
+/// This allows clients to reconnect to a service after it restarts, without
+/// polling. Prefer [`wait_for_service`] to wait for a service to start.
+///
+/// The callback is called on a binder thread, with a lock held by
+/// libbinder_ndk, so it must not make synchronous binder calls, including
+/// casting the service to an interface. It must not drop the returned
+/// registration either, which waits for the callback to return.
+pub fn register_for_service_notifications<F>(
+    name: &str,
+    callback: F,
+) -> Result<ServiceNotificationRegistration>
+where
+    F: Fn(SpIBinder) + Send + Sync + 'static,
+{
+    let name = CString::new(name).or(Err(StatusCode::UNEXPECTED_NULL))?;
+    let callback: ServiceNotificationCallback = Box::new(callback);
+    let callback = Box::into_raw(Box::new(callback));
+    let registration = unsafe {
+        // Safety: `name` is a valid null-terminated C-style string, which is
+        // only borrowed for the duration of the call. The cookie is a valid
+        // callback for `on_register`, and outlives the registration, which it
+        // is freed after.
+        sys::AServiceManager_registerForServiceNotifications(
+            name.as_ptr(),
+            Some(ServiceNotificationRegistration::on_register),
+            callback as *mut c_void,
+        )
+    };
+    match ptr::NonNull::new(registration) {
+        Some(registration) => {
+            Ok(ServiceNotificationRegistration { registration, callback, worker: None })
+        }
+        None => {
+            // Safety: the callback was leaked above, and as registering failed
+            // nothing else refers to it.
+            drop(unsafe { Box::from_raw(callback) });
+            Err(StatusCode::UNKNOWN_ERROR)
+        }
+    }
+}
+
+/// Call `callback` with the service `name` for a particular interface every
+/// time it is registered, including right away if it is already registered.
+///
+/// Casting a service may need a binder call, which the notification callback
+/// can't make, so the service is cast and `callback` called on a thread of
+/// the registration's own. Unlike for [`register_for_service_notifications`],
+/// `callback` may make binder calls, and may drop the returned registration,
+/// e.g. to be notified only once. Registrations of a service which does not
+/// implement the interface are skipped.
+pub fn register_for_interface_notifications<T, F>(
+    name: &str,
+    callback: F,
+) -> Result<ServiceNotificationRegistration>
+where
+    T: FromIBinder + ?Sized + 'static,
+    F: Fn(Strong<T>) + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel::<SpIBinder>();
+    // `Sender` isn't `Sync` on every supported toolchain.
+    let sender = Mutex::new(sender);
+    let mut registration = register_for_service_notifications(name, move |service| {
+        // The worker only stops once the registration is dropped.
+        let _ = sender.lock().unwrap().send(service);
+    })?;
+    let stopped = Arc::new(AtomicBool::new(false));
+    let worker_stopped = stopped.clone();
+    let worker = thread::spawn(move || {
+        for service in receiver {
+            if worker_stopped.load(atomic::Ordering::SeqCst) {
+                break;
+            }
+            if let Ok(service) = FromIBinder::try_from(service) {
+                callback(service);
+            }
+        }
+    });
+    registration.worker = Some((worker, stopped));
+    Ok(registration)
+}
+
 /// Check if a service is declared (e.g. in a VINTF manifest)
 pub fn is_declared(interface: &str) -> Result<bool> {
     let interface = CString::new(interface).or(Err(StatusCode::UNEXPECTED_NULL))?;
@@ -852,6 +1354,52 @@ pub fn get_declared_instances(interface: &str) -> Result<Vec<String>> {
         })
 }
 
+/// Check if a service is updatable via an APEX module.
+pub fn is_updatable_via_apex(instance: &str) -> Result<bool> {
+    let instance = CString::new(instance).or(Err(StatusCode::UNEXPECTED_NULL))?;
+
+    unsafe {
+        // Safety: `instance` is a valid null-terminated C-style string and is
+        // only borrowed for the lifetime of the call.
+        Ok(sys::AServiceManager_isUpdatableViaApex(instance.as_ptr()))
+    }
+}
+
+/// Retrieve the name of the APEX module a service is declared as updatable
+/// via, if any.
+pub fn get_updatable_apex_name(instance: &str) -> Result<Option<String>> {
+    unsafe extern "C" fn callback(apex_name: *const c_char, opaque: *mut c_void) {
+        // Safety: opaque was a mutable pointer created below from an Option
+        // of CString, and outlives this callback.
+        if let Some(name) = opaque.cast::<Option<CString>>().as_mut() {
+            // Safety: apex_name is a valid null-terminated C string with a
+            // lifetime at least as long as this function, and we immediately
+            // copy it into an owned CString.
+            *name = Some(CStr::from_ptr(apex_name).to_owned());
+        } else {
+            eprintln!("Opaque pointer was null in get_updatable_apex_name callback!");
+        }
+    }
+
+    let instance = CString::new(instance).or(Err(StatusCode::UNEXPECTED_NULL))?;
+    let mut name: Option<CString> = None;
+    unsafe {
+        // Safety: `instance` and `name` are borrowed for the length of this
+        // call and both outlive the call. `instance` is guaranteed to be a
+        // valid null-terminated C-style string.
+        sys::AServiceManager_getUpdatableApexName(
+            instance.as_ptr(),
+            &mut name as *mut _ as *mut c_void,
+            Some(callback),
+        );
+    }
+
+    name.map(CString::into_string).transpose().map_err(|e| {
+        eprintln!("An APEX name was not a valid UTF-8 string: {}", e);
+        StatusCode::BAD_VALUE
+    })
+}
+
 /// # Safety
 ///
 /// `SpIBinder` guarantees that `binder` always contains a valid pointer to an
@@ -0,0 +1,139 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Parsing of the arguments to `cmd <service>`.
+
+use crate::error::{Result, StatusCode};
+
+use std::ffi::CStr;
+use std::str::FromStr;
+
+/// The arguments of a shell command, consumed front to back.
+///
+/// Every failure is `BAD_VALUE`, which `cmd` reports to the user.
+///
+/// ```
+/// # use binder_ndk::{ShellArgs, StatusCode};
+/// # use std::ffi::CStr;
+/// # use std::fs::File;
+/// # use std::io::Write;
+/// fn handle_shell_command(
+///     stdout: &File,
+///     args: &[&CStr],
+/// ) -> Result<(), StatusCode> {
+///     let mut args = ShellArgs::new(args);
+///     match args.subcommand()? {
+///         Some("set-level") => {
+///             let verbose = args.flag("--verbose");
+///             let level: u32 = args.parse()?;
+///             args.finish()?;
+///             writeln!(&*stdout, "level {} (verbose: {})", level, verbose)
+///                 .map_err(|_| StatusCode::UNKNOWN_ERROR)
+///         }
+///         _ => Err(StatusCode::BAD_VALUE),
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ShellArgs<'a> {
+    args: &'a [&'a CStr],
+}
+
+impl<'a> ShellArgs<'a> {
+    /// Wrap the arguments passed to `handle_shell_command`.
+    pub fn new(args: &'a [&'a CStr]) -> Self {
+        Self { args }
+    }
+
+    /// Take the subcommand, or `None` if there are no arguments left.
+    ///
+    /// Fails if the argument is not UTF-8.
+    pub fn subcommand(&mut self) -> Result<Option<&'a str>> {
+        let Some((first, rest)) = self.args.split_first() else {
+            return Ok(None);
+        };
+        let arg = first.to_str().or(Err(StatusCode::BAD_VALUE))?;
+        self.args = rest;
+        Ok(Some(arg))
+    }
+
+    /// Take a required argument, failing if there are none left or it is not
+    /// UTF-8.
+    pub fn arg(&mut self) -> Result<&'a str> {
+        self.subcommand()?.ok_or(StatusCode::BAD_VALUE)
+    }
+
+    /// Take a required argument and parse it.
+    pub fn parse<T: FromStr>(&mut self) -> Result<T> {
+        self.arg()?.parse().or(Err(StatusCode::BAD_VALUE))
+    }
+
+    /// Take the next argument if it is `name`, returning whether it was.
+    pub fn flag(&mut self, name: &str) -> bool {
+        match self.args.split_first() {
+            Some((first, rest)) if first.to_bytes() == name.as_bytes() => {
+                self.args = rest;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The arguments not taken yet.
+    pub fn rest(&self) -> &'a [&'a CStr] {
+        self.args
+    }
+
+    /// Check that every argument was taken.
+    pub fn finish(self) -> Result<()> {
+        if self.args.is_empty() {
+            Ok(())
+        } else {
+            Err(StatusCode::BAD_VALUE)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_args() {
+        let args = [
+            CStr::from_bytes_with_nul(b"set\0").unwrap(),
+            CStr::from_bytes_with_nul(b"--force\0").unwrap(),
+            CStr::from_bytes_with_nul(b"42\0").unwrap(),
+            CStr::from_bytes_with_nul(b"name\0").unwrap(),
+            CStr::from_bytes_with_nul(b"\xff\0").unwrap(),
+        ];
+        let mut args = ShellArgs::new(&args);
+        assert_eq!(args.subcommand(), Ok(Some("set")));
+        assert!(!args.flag("--dry-run"));
+        assert!(args.flag("--force"));
+        assert_eq!(args.clone().parse::<bool>(), Err(StatusCode::BAD_VALUE));
+        assert_eq!(args.parse::<u32>(), Ok(42));
+        assert_eq!(args.rest().len(), 2);
+        assert_eq!(args.arg(), Ok("name"));
+        assert_eq!(args.clone().arg(), Err(StatusCode::BAD_VALUE));
+        assert_eq!(args.clone().finish(), Err(StatusCode::BAD_VALUE));
+
+        let mut args = ShellArgs::new(&[]);
+        assert_eq!(args.subcommand(), Ok(None));
+        assert_eq!(args.arg(), Err(StatusCode::BAD_VALUE));
+        assert_eq!(args.finish(), Ok(()));
+    }
+}
@@ -14,9 +14,11 @@
  * limitations under the License.
  */
 
+use crate::error::{status_result, Result, StatusCode};
 use crate::sys;
 
 use libc::{pid_t, uid_t};
+use std::os::unix::io::{BorrowedFd, OwnedFd};
 
 /// Static utility functions to manage Binder process state.
 pub struct ProcessState;
@@ -49,6 +51,73 @@ impl ProcessState {
             sys::ABinderProcess_joinThreadPool();
         }
     }
+
+    /// Set up the current thread to handle Binder IPC by polling, rather than
+    /// in a thread pool.
+    ///
+    /// Returns a duplicate of the binder file descriptor, which becomes readable
+    /// whenever [`handle_polled_commands`](Self::handle_polled_commands) has
+    /// commands to handle. This is meant for single-threaded processes waiting
+    /// on several file descriptors, which should not also start a thread pool.
+    pub fn setup_polling() -> Result<OwnedFd> {
+        let mut fd = -1;
+        status_result(unsafe {
+            // Safety: `fd` is a valid pointer to write the file descriptor to.
+            sys::ABinderProcess_setupPolling(&mut fd)
+        })?;
+        // Safety: the file descriptor stays open for the lifetime of the
+        // process, so it is valid while we duplicate it.
+        let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+        fd.try_clone_to_owned().or(Err(StatusCode::UNKNOWN_ERROR))
+    }
+
+    /// Handle all pending Binder IPC commands of the current thread.
+    ///
+    /// This should be called whenever the file descriptor returned by
+    /// [`setup_polling`](Self::setup_polling) is readable, from the same thread.
+    /// It blocks until a command arrives if none is pending.
+    pub fn handle_polled_commands() -> Result<()> {
+        status_result(unsafe {
+            // Safety: Safe FFI
+            sys::ABinderProcess_handlePolledCommands()
+        })
+    }
+}
+
+/// A service process of the loopback transport used by the `host` feature.
+///
+/// On hosts without `/dev/binder`, every "process" runs on a thread of the
+/// current one. Services registered with [`add_service`](crate::add_service)
+/// from the function a `ServiceProcess` runs belong to it, and are only ever
+/// reached through proxies by everyone else.
+///
+/// Dropping a `ServiceProcess` kills it: its services are unregistered, and
+/// death notifications are delivered for all of its binders, just as if a
+/// real process had died.
+#[cfg(feature = "host")]
+#[derive(Debug)]
+pub struct ServiceProcess(sys::HostProcess);
+
+#[cfg(feature = "host")]
+impl ServiceProcess {
+    /// Start a process running `main`.
+    ///
+    /// This returns once `main` calls [`ProcessState::join_thread_pool`], which
+    /// blocks until the process is killed, or returns, which kills it.
+    pub fn spawn<F: FnOnce() + Send + 'static>(main: F) -> Self {
+        Self(sys::HostProcess::spawn(main))
+    }
+
+    /// Returns `true` until the process has been killed.
+    pub fn is_alive(&self) -> bool {
+        self.0.is_alive()
+    }
+
+    /// Kill the process and wait until all death notifications for it have
+    /// been delivered.
+    pub fn kill(mut self) {
+        self.0.kill();
+    }
 }
 
 /// Static utility functions to manage Binder thread state.
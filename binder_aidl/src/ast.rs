@@ -0,0 +1,175 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The parsed form of AIDL files.
+
+/// A parsed `.aidl` file.
+#[derive(Debug)]
+pub(crate) struct Document {
+    pub(crate) package: Vec<String>,
+    pub(crate) imports: Vec<Vec<String>>,
+    pub(crate) decls: Vec<Decl>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Annotation {
+    pub(crate) name: String,
+    pub(crate) params: Vec<(String, Expr)>,
+}
+
+/// What all declarations have in common.
+#[derive(Debug)]
+pub(crate) struct DeclHeader {
+    pub(crate) doc: Option<String>,
+    pub(crate) annotations: Vec<Annotation>,
+    pub(crate) name: String,
+    pub(crate) line: usize,
+}
+
+impl DeclHeader {
+    pub(crate) fn annotation(&self, name: &str) -> Option<&Annotation> {
+        self.annotations.iter().find(|annotation| annotation.name == name)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum Decl {
+    Interface(Interface),
+    Parcelable(Parcelable),
+    /// A parcelable without fields, defined by a `rust_type`.
+    ForeignParcelable(DeclHeader, Option<String>),
+    Enum(Enum),
+    Union(Parcelable),
+}
+
+impl Decl {
+    pub(crate) fn header(&self) -> &DeclHeader {
+        match self {
+            Decl::Interface(interface) => &interface.header,
+            Decl::Parcelable(parcelable) | Decl::Union(parcelable) => &parcelable.header,
+            Decl::ForeignParcelable(header, _) => header,
+            Decl::Enum(enumeration) => &enumeration.header,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Interface {
+    pub(crate) header: DeclHeader,
+    pub(crate) oneway: bool,
+    pub(crate) methods: Vec<Method>,
+    pub(crate) constants: Vec<Constant>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Method {
+    pub(crate) doc: Option<String>,
+    pub(crate) oneway: bool,
+    pub(crate) ret: Type,
+    pub(crate) name: String,
+    pub(crate) args: Vec<Argument>,
+    pub(crate) id: Option<i64>,
+    pub(crate) line: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Direction {
+    In,
+    Out,
+    InOut,
+}
+
+#[derive(Debug)]
+pub(crate) struct Argument {
+    pub(crate) direction: Direction,
+    pub(crate) ty: Type,
+    pub(crate) name: String,
+}
+
+/// A structured parcelable, or a union.
+#[derive(Debug)]
+pub(crate) struct Parcelable {
+    pub(crate) header: DeclHeader,
+    pub(crate) fields: Vec<Field>,
+    pub(crate) constants: Vec<Constant>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Field {
+    pub(crate) doc: Option<String>,
+    pub(crate) ty: Type,
+    pub(crate) name: String,
+    pub(crate) default: Option<Expr>,
+    pub(crate) line: usize,
+}
+
+#[derive(Debug)]
+pub(crate) struct Constant {
+    pub(crate) doc: Option<String>,
+    pub(crate) ty: Type,
+    pub(crate) name: String,
+    pub(crate) value: Expr,
+    pub(crate) line: usize,
+}
+
+#[derive(Debug)]
+pub(crate) struct Enum {
+    pub(crate) header: DeclHeader,
+    pub(crate) enumerators: Vec<Enumerator>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Enumerator {
+    pub(crate) doc: Option<String>,
+    pub(crate) name: String,
+    pub(crate) value: Option<Expr>,
+    pub(crate) line: usize,
+}
+
+/// A type, as written.
+#[derive(Clone, Debug)]
+pub(crate) struct Type {
+    /// The possibly qualified name, e.g. `["List"]` or `["android", "os", "Foo"]`.
+    pub(crate) name: Vec<String>,
+    pub(crate) args: Vec<Type>,
+    pub(crate) array: Option<Array>,
+    pub(crate) nullable: bool,
+    pub(crate) line: usize,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum Array {
+    /// `T[]`
+    Dynamic,
+    /// `T[N]`, or `T[N][M]` for nested arrays.
+    Fixed(Vec<Expr>),
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum Expr {
+    Int(String),
+    Float(String),
+    Bool(bool),
+    Str(String),
+    Char(char),
+    /// A reference to a constant or enumerator, possibly qualified.
+    Name(Vec<String>),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Ternary(Box<Expr>, Box<Expr>, Box<Expr>),
+    /// `{ a, b, c }`
+    Array(Vec<Expr>),
+}
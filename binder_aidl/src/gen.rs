@@ -0,0 +1,1651 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Generating Rust code from parsed AIDL files, following what the AIDL Rust
+//! backend generates.
+
+use crate::ast::*;
+use crate::parser::parse_int;
+use crate::Error;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+/// A parsed AIDL file.
+pub(crate) struct Source {
+    pub(crate) path: PathBuf,
+    pub(crate) document: Document,
+}
+
+pub(crate) struct Options<'a> {
+    pub(crate) krate: &'a str,
+    pub(crate) async_server: bool,
+}
+
+/// Types which aren't declared in AIDL files.
+const BUILTIN_TYPES: &[&str] = &[
+    "void",
+    "boolean",
+    "byte",
+    "char",
+    "int",
+    "long",
+    "float",
+    "double",
+    "String",
+    "IBinder",
+    "ParcelFileDescriptor",
+    "ParcelableHolder",
+    "List",
+    "Map",
+];
+
+/// Imports of builtin types, which don't need a file.
+const BUILTIN_IMPORTS: &[&str] = &[
+    "android.os.IBinder",
+    "android.os.ParcelFileDescriptor",
+    "android.os.ParcelableHolder",
+    "java.util.List",
+    "java.util.Map",
+];
+
+/// Lints the generated code doesn't bother with.
+const ALLOW: &str = "#[allow(clippy::all, dead_code, missing_docs, non_camel_case_types, \
+                     non_snake_case, non_upper_case_globals, unused_imports, unused_mut)]";
+
+/// The qualified names of the types `document` may need from other files.
+pub(crate) fn dependencies(document: &Document) -> Vec<Vec<String>> {
+    let mut names: Vec<Vec<String>> = document
+        .imports
+        .iter()
+        .filter(|import| !BUILTIN_IMPORTS.contains(&import.join(".").as_str()))
+        .cloned()
+        .collect();
+    let mut visit = |name: &[String]| {
+        let is_builtin = name.len() == 1 && BUILTIN_TYPES.contains(&name[0].as_str());
+        let is_imported = document.imports.iter().any(|import| import.last() == name.first());
+        if is_builtin || is_imported {
+            return;
+        }
+        let mut qualified = if name.len() == 1 { document.package.clone() } else { Vec::new() };
+        qualified.extend(name.iter().cloned());
+        if !names.contains(&qualified) {
+            names.push(qualified);
+        }
+    };
+    for decl in &document.decls {
+        match decl {
+            Decl::Interface(interface) => {
+                for method in &interface.methods {
+                    visit_type(&method.ret, &mut visit);
+                    for arg in &method.args {
+                        visit_type(&arg.ty, &mut visit);
+                    }
+                }
+                visit_constants(&interface.constants, &mut visit);
+            }
+            Decl::Parcelable(parcelable) | Decl::Union(parcelable) => {
+                for field in &parcelable.fields {
+                    visit_type(&field.ty, &mut visit);
+                    if let Some(default) = &field.default {
+                        visit_expr(default, &mut visit);
+                    }
+                }
+                visit_constants(&parcelable.constants, &mut visit);
+            }
+            Decl::Enum(enumeration) => {
+                for value in enumeration.enumerators.iter().filter_map(|e| e.value.as_ref()) {
+                    visit_expr(value, &mut visit);
+                }
+            }
+            Decl::ForeignParcelable(..) => {}
+        }
+    }
+    names
+}
+
+fn visit_constants(constants: &[Constant], visit: &mut impl FnMut(&[String])) {
+    for constant in constants {
+        visit_type(&constant.ty, visit);
+        visit_expr(&constant.value, visit);
+    }
+}
+
+fn visit_type(ty: &Type, visit: &mut impl FnMut(&[String])) {
+    visit(&ty.name);
+    for arg in &ty.args {
+        visit_type(arg, visit);
+    }
+    if let Some(Array::Fixed(sizes)) = &ty.array {
+        for size in sizes {
+            visit_expr(size, visit);
+        }
+    }
+}
+
+/// Visit the types named by qualified constant names in `expr`.
+fn visit_expr(expr: &Expr, visit: &mut impl FnMut(&[String])) {
+    match expr {
+        Expr::Name(name) if name.len() > 1 => visit(&name[..name.len() - 1]),
+        Expr::Unary(_, operand) => visit_expr(operand, visit),
+        Expr::Binary(_, lhs, rhs) => {
+            visit_expr(lhs, visit);
+            visit_expr(rhs, visit);
+        }
+        Expr::Ternary(condition, then, otherwise) => {
+            visit_expr(condition, visit);
+            visit_expr(then, visit);
+            visit_expr(otherwise, visit);
+        }
+        Expr::Array(elements) => {
+            for element in elements {
+                visit_expr(element, visit);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Generate the code for all declarations in `sources`.
+pub(crate) fn generate(sources: &[Source], options: &Options<'_>) -> Result<String, Error> {
+    let mut generator = Generator { sources, options, decls: HashMap::new() };
+    for (source_index, source) in sources.iter().enumerate() {
+        for (decl_index, decl) in source.document.decls.iter().enumerate() {
+            let header = decl.header();
+            let name = qualified_name(&source.document.package, &header.name);
+            let id = DeclId { source: source_index, decl: decl_index };
+            if generator.decls.insert(name.clone(), id).is_some() {
+                return Err(Error::Invalid {
+                    path: source.path.clone(),
+                    line: header.line,
+                    message: format!("{} is declared more than once", name),
+                });
+            }
+        }
+    }
+    generator.run().map_err(|invalid| Error::Invalid {
+        path: sources[invalid.source].path.clone(),
+        line: invalid.line,
+        message: invalid.message,
+    })
+}
+
+fn qualified_name(package: &[String], name: &str) -> String {
+    package.iter().map(String::as_str).chain([name]).collect::<Vec<_>>().join(".")
+}
+
+/// An error in the file `sources[source]`.
+struct Invalid {
+    source: usize,
+    line: usize,
+    message: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct DeclId {
+    source: usize,
+    decl: usize,
+}
+
+/// A resolved AIDL type.
+#[derive(Clone)]
+enum Ty {
+    Bool,
+    Byte,
+    Char,
+    Int,
+    Long,
+    Float,
+    Double,
+    String,
+    IBinder,
+    FileDescriptor,
+    ParcelableHolder,
+    /// The Rust path of an interface trait.
+    Interface(String),
+    /// The Rust path of a parcelable or union.
+    Parcelable(String),
+    /// The Rust path of an enum.
+    Enum(String),
+    /// `T[]`, or `List<T>` if not `is_array`.
+    Vec(Box<Ty>, bool),
+    Fixed(Box<Ty>, usize),
+    /// `Map<String, V>`, with the value type.
+    Map(Box<Ty>),
+    Option(Box<Ty>),
+}
+
+impl Ty {
+    fn is_primitive(&self) -> bool {
+        matches!(
+            self,
+            Ty::Bool
+                | Ty::Byte
+                | Ty::Char
+                | Ty::Int
+                | Ty::Long
+                | Ty::Float
+                | Ty::Double
+                | Ty::Enum(_)
+        )
+    }
+
+    fn can_be_null(&self) -> bool {
+        matches!(
+            self,
+            Ty::String
+                | Ty::IBinder
+                | Ty::FileDescriptor
+                | Ty::Interface(_)
+                | Ty::Parcelable(_)
+                | Ty::Vec(..)
+                | Ty::Fixed(..)
+                | Ty::Map(_)
+        )
+    }
+
+    /// Whether the type has no `Default`, and so needs to be an `Option` in
+    /// parcelables.
+    fn needs_option(&self) -> bool {
+        matches!(self, Ty::IBinder | Ty::FileDescriptor | Ty::Interface(_))
+    }
+}
+
+/// A constant value.
+#[derive(Clone)]
+enum Value {
+    Bool(bool),
+    Int(i128),
+    Float(f64),
+    Str(String),
+    Array(Vec<Value>),
+    /// The Rust path of an enumerator, and its value.
+    Enumerator(String, i128),
+}
+
+impl Value {
+    fn as_int(&self) -> Option<i128> {
+        match self {
+            Value::Int(value) | Value::Enumerator(_, value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Float(value) => Some(*value),
+            _ => self.as_int().map(|value| value as f64),
+        }
+    }
+}
+
+/// Constants referring to each other this deep are assumed to be circular.
+const MAX_DEPTH: usize = 64;
+
+struct MethodInfo<'a> {
+    method: &'a Method,
+    name: String,
+    oneway: bool,
+    ret: Option<Ty>,
+    args: Vec<ArgInfo>,
+    code: i64,
+}
+
+struct ArgInfo {
+    name: String,
+    ty: Ty,
+    direction: Direction,
+}
+
+impl ArgInfo {
+    fn is_mut(&self) -> bool {
+        self.direction != Direction::In
+    }
+}
+
+struct Generator<'a> {
+    sources: &'a [Source],
+    options: &'a Options<'a>,
+    /// Declarations by qualified name.
+    decls: HashMap<String, DeclId>,
+}
+
+/// The declarations of a package, and its subpackages.
+#[derive(Default)]
+struct Module {
+    children: BTreeMap<String, Module>,
+    decls: BTreeMap<String, DeclId>,
+}
+
+impl<'a> Generator<'a> {
+    fn decl(&self, id: DeclId) -> &'a Decl {
+        &self.sources[id.source].document.decls[id.decl]
+    }
+
+    fn package(&self, id: DeclId) -> &'a [String] {
+        &self.sources[id.source].document.package
+    }
+
+    fn invalid<T>(
+        &self,
+        id: DeclId,
+        line: usize,
+        message: impl Into<String>,
+    ) -> Result<T, Invalid> {
+        Err(Invalid { source: id.source, line, message: message.into() })
+    }
+
+    fn run(&self) -> Result<String, Invalid> {
+        let mut root = Module::default();
+        for &id in self.decls.values() {
+            let mut module = &mut root;
+            for segment in self.package(id) {
+                module = module.children.entry(segment.clone()).or_default();
+            }
+            module.decls.insert(self.decl(id).header().name.clone(), id);
+        }
+        let mut w = Writer::default();
+        w.line("// Generated by binder_aidl. Do not edit.");
+        self.module(&mut w, &root, true)?;
+        Ok(w.out)
+    }
+
+    fn module(&self, w: &mut Writer, module: &Module, top: bool) -> Result<(), Invalid> {
+        for &id in module.decls.values() {
+            if let Decl::ForeignParcelable(..) = self.decl(id) {
+                continue;
+            }
+            if top {
+                w.line(ALLOW);
+            }
+            w.line(format!("pub mod {} {{", ident(&self.decl(id).header().name)));
+            match self.decl(id) {
+                Decl::Interface(interface) => self.interface(w, id, interface)?,
+                Decl::Parcelable(parcelable) => self.parcelable(w, id, parcelable)?,
+                Decl::Union(union) => self.union(w, id, union)?,
+                Decl::Enum(enumeration) => self.enumeration(w, id, enumeration)?,
+                Decl::ForeignParcelable(..) => unreachable!(),
+            }
+            w.line("}");
+        }
+        for (name, child) in &module.children {
+            if top {
+                w.line(ALLOW);
+            }
+            w.line(format!("pub mod {} {{", ident(name)));
+            self.module(w, child, false)?;
+            w.line("}");
+        }
+        Ok(())
+    }
+
+    /// The declaration `name` refers to from `from`.
+    fn resolve(&self, from: DeclId, name: &[String]) -> Option<DeclId> {
+        let document = &self.sources[from.source].document;
+        if let [name] = name {
+            if let Some(import) = document.imports.iter().find(|import| import.last() == Some(name))
+            {
+                return self.decls.get(&import.join(".")).copied();
+            }
+            return self.decls.get(&qualified_name(&document.package, name)).copied();
+        }
+        self.decls.get(&name.join(".")).copied()
+    }
+
+    /// The Rust path of the type `to` from the module of `from`.
+    fn path(&self, from: DeclId, to: DeclId) -> String {
+        let name = ident(&self.decl(to).header().name);
+        let to_package = self.package(to);
+        let from_package = self.package(from);
+        if from_package == to_package {
+            return format!("super::{0}::{0}", name);
+        }
+        let mut path = "super::".repeat(from_package.len() + 1);
+        for segment in to_package {
+            path.push_str(&ident(segment));
+            path.push_str("::");
+        }
+        format!("{0}{1}::{1}", path, name)
+    }
+
+    fn resolve_type(&self, id: DeclId, ty: &Type) -> Result<Ty, Invalid> {
+        let simple = match ty.name.as_slice() {
+            [name] => Some(name.as_str()),
+            _ => None,
+        };
+        if !ty.args.is_empty() && !matches!(simple, Some("List" | "Map")) {
+            return self.invalid(id, ty.line, "only List and Map take type arguments");
+        }
+        let base = match simple {
+            Some("boolean") => Ty::Bool,
+            Some("byte") => Ty::Byte,
+            Some("char") => Ty::Char,
+            Some("int") => Ty::Int,
+            Some("long") => Ty::Long,
+            Some("float") => Ty::Float,
+            Some("double") => Ty::Double,
+            Some("String") => Ty::String,
+            Some("IBinder") => Ty::IBinder,
+            Some("ParcelFileDescriptor") => Ty::FileDescriptor,
+            Some("ParcelableHolder") => Ty::ParcelableHolder,
+            Some("List") => {
+                let [elem] = ty.args.as_slice() else {
+                    return self.invalid(id, ty.line, "List takes one type argument");
+                };
+                if ty.array.is_some() {
+                    return self.invalid(id, ty.line, "arrays of lists are not supported");
+                }
+                let elem = self.resolve_type(id, elem)?;
+                return Ok(self.vec(elem, false, ty.nullable));
+            }
+            Some("Map") => {
+                let [key, value] = ty.args.as_slice() else {
+                    return self.invalid(id, ty.line, "only Map<String, V> is supported");
+                };
+                if key.name != ["String"] || key.array.is_some() || key.nullable {
+                    return self.invalid(id, key.line, "Map keys must be String");
+                }
+                if ty.array.is_some() {
+                    return self.invalid(id, ty.line, "arrays of maps are not supported");
+                }
+                let map = Ty::Map(Box::new(self.resolve_type(id, value)?));
+                return Ok(if ty.nullable { Ty::Option(Box::new(map)) } else { map });
+            }
+            Some("void") => {
+                return self.invalid(id, ty.line, "void can only be the return type of a method")
+            }
+            _ => self.user_type(id, ty)?,
+        };
+        match &ty.array {
+            Some(Array::Dynamic) => Ok(self.vec(base, true, ty.nullable)),
+            Some(Array::Fixed(sizes)) => {
+                let mut fixed = base;
+                for size in sizes.iter().rev() {
+                    let size = self.eval(id, id, size, ty.line, 0)?;
+                    let Some(size) = size.as_int().and_then(|size| usize::try_from(size).ok())
+                    else {
+                        return self.invalid(id, ty.line, "invalid array size");
+                    };
+                    fixed = Ty::Fixed(Box::new(fixed), size);
+                }
+                Ok(if ty.nullable { Ty::Option(Box::new(fixed)) } else { fixed })
+            }
+            None if ty.nullable && !base.can_be_null() => {
+                self.invalid(id, ty.line, format!("{} can't be @nullable", ty.name.join(".")))
+            }
+            None if ty.nullable => Ok(Ty::Option(Box::new(base))),
+            None => Ok(base),
+        }
+    }
+
+    /// `T[]` or `List<T>`, where nullable arrays have nullable elements.
+    fn vec(&self, elem: Ty, is_array: bool, nullable: bool) -> Ty {
+        let elem = if nullable && elem.can_be_null() { Ty::Option(Box::new(elem)) } else { elem };
+        let vec = Ty::Vec(Box::new(elem), is_array);
+        if nullable {
+            Ty::Option(Box::new(vec))
+        } else {
+            vec
+        }
+    }
+
+    fn user_type(&self, id: DeclId, ty: &Type) -> Result<Ty, Invalid> {
+        let name = ty.name.join(".");
+        let Some(target) = self.resolve(id, &ty.name) else {
+            return self.invalid(id, ty.line, format!("unknown type {}", name));
+        };
+        match self.decl(target) {
+            Decl::Interface(_) => Ok(Ty::Interface(self.path(id, target))),
+            Decl::Parcelable(_) | Decl::Union(_) => Ok(Ty::Parcelable(self.path(id, target))),
+            Decl::Enum(_) => Ok(Ty::Enum(self.path(id, target))),
+            Decl::ForeignParcelable(_, Some(rust_type)) => Ok(Ty::Parcelable(rust_type.clone())),
+            Decl::ForeignParcelable(_, None) => {
+                self.invalid(id, ty.line, format!("parcelable {} has no rust_type", name))
+            }
+        }
+    }
+
+    /// The type of a local, field or return value.
+    fn owned(&self, ty: &Ty) -> String {
+        let krate = self.options.krate;
+        match ty {
+            Ty::Bool => "bool".to_owned(),
+            Ty::Byte => "i8".to_owned(),
+            Ty::Char => "u16".to_owned(),
+            Ty::Int => "i32".to_owned(),
+            Ty::Long => "i64".to_owned(),
+            Ty::Float => "f32".to_owned(),
+            Ty::Double => "f64".to_owned(),
+            Ty::String => "String".to_owned(),
+            Ty::IBinder => format!("{}::SpIBinder", krate),
+            Ty::FileDescriptor => format!("{}::ParcelFileDescriptor", krate),
+            Ty::ParcelableHolder => format!("{}::ParcelableHolder", krate),
+            Ty::Interface(path) => format!("{}::Strong<dyn {}>", krate, path),
+            Ty::Parcelable(path) | Ty::Enum(path) => path.clone(),
+            Ty::Vec(elem, _) => format!("Vec<{}>", self.element(elem)),
+            Ty::Fixed(elem, size) => format!("[{}; {}]", self.element(elem), size),
+            Ty::Map(value) => {
+                format!("std::collections::HashMap<String, {}>", self.owned(value))
+            }
+            Ty::Option(inner) => format!("Option<{}>", self.owned(inner)),
+        }
+    }
+
+    /// The type of array elements, where bytes are unsigned.
+    fn element(&self, ty: &Ty) -> String {
+        match ty {
+            Ty::Byte => "u8".to_owned(),
+            _ => self.owned(ty),
+        }
+    }
+
+    /// The type of `in` arguments, with references of `lifetime`.
+    fn borrowed(&self, ty: &Ty, lifetime: &str) -> String {
+        match ty {
+            _ if ty.is_primitive() => self.owned(ty),
+            Ty::String => format!("&{}str", lifetime),
+            Ty::Vec(elem, _) => format!("&{}[{}]", lifetime, self.element(elem)),
+            Ty::Option(inner) => match &**inner {
+                Ty::String => format!("Option<&{}str>", lifetime),
+                Ty::Vec(elem, _) => format!("Option<&{}[{}]>", lifetime, self.element(elem)),
+                _ => format!("Option<&{}{}>", lifetime, self.owned(inner)),
+            },
+            _ => format!("&{}{}", lifetime, self.owned(ty)),
+        }
+    }
+
+    fn params(&self, method: &MethodInfo<'_>, lifetime: &str) -> Vec<String> {
+        method
+            .args
+            .iter()
+            .map(|arg| {
+                let ty = if arg.is_mut() {
+                    format!("&{}mut {}", lifetime, self.owned(&arg.ty))
+                } else {
+                    self.borrowed(&arg.ty, lifetime)
+                };
+                format!("{}: {}", arg.name, ty)
+            })
+            .collect()
+    }
+
+    fn ret(&self, method: &MethodInfo<'_>) -> String {
+        method.ret.as_ref().map_or_else(|| "()".to_owned(), |ret| self.owned(ret))
+    }
+
+    fn stability(&self, header: &DeclHeader) -> String {
+        let stability =
+            if header.annotation("VintfStability").is_some() { "Vintf" } else { "Local" };
+        format!("{}::binder_impl::Stability::{}", self.options.krate, stability)
+    }
+
+    fn constants(&self, w: &mut Writer, id: DeclId, constants: &[Constant]) -> Result<(), Invalid> {
+        for constant in constants {
+            let ty = self.resolve_type(id, &constant.ty)?;
+            let rust_ty = match ty {
+                Ty::String => "&str".to_owned(),
+                _ if ty.is_primitive() => self.owned(&ty),
+                _ => {
+                    return self.invalid(
+                        id,
+                        constant.line,
+                        "constants must be primitives or strings",
+                    )
+                }
+            };
+            let value = self.eval(id, id, &constant.value, constant.line, 0)?;
+            let value = self.literal(id, constant.line, &value, &ty, false)?;
+            w.doc(&constant.doc);
+            w.line(format!("pub const {}: {} = {};", ident(&constant.name), rust_ty, value));
+        }
+        Ok(())
+    }
+
+    fn methods(
+        &self,
+        id: DeclId,
+        interface: &'a Interface,
+    ) -> Result<Vec<MethodInfo<'a>>, Invalid> {
+        let has_ids = interface.methods.iter().filter(|method| method.id.is_some()).count();
+        if has_ids != 0 && has_ids != interface.methods.len() {
+            let line = interface.header.line;
+            return self.invalid(id, line, "either all methods or none must have an id");
+        }
+        let mut methods: Vec<MethodInfo<'_>> = Vec::new();
+        for (index, method) in interface.methods.iter().enumerate() {
+            let line = method.line;
+            let code = method.id.unwrap_or(index as i64);
+            if !(0..0x00ff_ffff).contains(&code) {
+                return self.invalid(id, line, format!("invalid method id {}", code));
+            }
+            if methods.iter().any(|other| other.code == code) {
+                return self.invalid(id, line, format!("method id {} is used twice", code));
+            }
+            if methods.iter().any(|other| other.method.name == method.name) {
+                return self.invalid(id, line, format!("{} is declared twice", method.name));
+            }
+            let ret = match method.ret.name.as_slice() {
+                [name] if name == "void" && method.ret.array.is_none() => None,
+                _ => Some(self.resolve_type(id, &method.ret)?),
+            };
+            let mut args = Vec::new();
+            for arg in &method.args {
+                let ty = self.resolve_type(id, &arg.ty)?;
+                let ty_line = arg.ty.line;
+                if let Ty::ParcelableHolder = ty {
+                    return self.invalid(id, ty_line, "ParcelableHolder can only be a field");
+                }
+                let can_be_out = match &ty {
+                    Ty::Option(inner) => {
+                        matches!(**inner, Ty::Vec(..) | Ty::Fixed(..) | Ty::Map(_))
+                    }
+                    _ => matches!(ty, Ty::Vec(..) | Ty::Fixed(..) | Ty::Map(_) | Ty::Parcelable(_)),
+                };
+                if arg.direction != Direction::In && !can_be_out {
+                    let message = format!("{} can't be an out or inout argument", arg.name);
+                    return self.invalid(id, ty_line, message);
+                }
+                args.push(ArgInfo {
+                    name: format!("_arg_{}", arg.name),
+                    ty,
+                    direction: arg.direction,
+                });
+            }
+            if let Some(Ty::ParcelableHolder) = ret {
+                return self.invalid(id, line, "ParcelableHolder can only be a field");
+            }
+            let oneway = interface.oneway || method.oneway;
+            if oneway && ret.is_some() {
+                return self.invalid(id, line, "oneway methods must return void");
+            }
+            if oneway && args.iter().any(ArgInfo::is_mut) {
+                return self.invalid(id, line, "oneway methods can't have out or inout arguments");
+            }
+            methods.push(MethodInfo { method, name: ident(&method.name), oneway, ret, args, code });
+        }
+        Ok(methods)
+    }
+
+    fn interface(&self, w: &mut Writer, id: DeclId, interface: &Interface) -> Result<(), Invalid> {
+        let krate = self.options.krate;
+        let header = &interface.header;
+        let methods = self.methods(id, interface)?;
+        let name = ident(&header.name);
+        let base = match header.name.strip_prefix('I') {
+            Some(base) if base.starts_with(|c: char| c.is_ascii_uppercase()) => base,
+            _ => &header.name,
+        };
+        let native = format!("Bn{}", base);
+        let proxy = format!("Bp{}", base);
+        let descriptor = format!("{:?}", qualified_name(self.package(id), &header.name));
+        let get_descriptor =
+            format!("fn get_descriptor() -> &'static str where Self: Sized {{ {} }}", descriptor);
+        let is_vintf = header.annotation("VintfStability").is_some();
+        // Only VINTF interfaces may be called from the other partition.
+        let private_local = if is_vintf {
+            "0".to_owned()
+        } else {
+            format!("{}::binder_impl::FLAG_PRIVATE_LOCAL", krate)
+        };
+        let flags = |method: &MethodInfo<'_>| {
+            if method.oneway {
+                format!("{}::binder_impl::FLAG_ONEWAY | {}", krate, private_local)
+            } else {
+                private_local.clone()
+            }
+        };
+
+        w.line(format!("use {}::binder_impl::IBinderInternal;", krate));
+        w.doc(&header.doc);
+        w.line(format!("pub trait {}: {}::Interface + Send {{", name, krate));
+        w.line(&get_descriptor);
+        for method in &methods {
+            let params = ["&self".to_owned()].into_iter().chain(self.params(method, ""));
+            w.doc(&method.method.doc);
+            w.line(format!(
+                "fn {}({}) -> {}::Result<{}>;",
+                method.name,
+                params.collect::<Vec<_>>().join(", "),
+                krate,
+                self.ret(method)
+            ));
+        }
+        w.line("}");
+
+        w.line(format!("pub trait {}Async<P>: {}::Interface + Send {{", name, krate));
+        w.line(&get_descriptor);
+        for method in &methods {
+            let params = ["&'a self".to_owned()].into_iter().chain(self.params(method, "'a "));
+            w.doc(&method.method.doc);
+            w.line(format!(
+                "fn {}<'a>({}) -> {}::BoxFuture<'a, {}::Result<{}>>;",
+                method.name,
+                params.collect::<Vec<_>>().join(", "),
+                krate,
+                krate,
+                self.ret(method)
+            ));
+        }
+        w.line("}");
+
+        if self.options.async_server {
+            w.line(format!("#[{}::async_trait]", krate));
+            w.line(format!("pub trait {}AsyncServer: {}::Interface + Send {{", name, krate));
+            w.line(&get_descriptor);
+            for method in &methods {
+                let params = ["&self".to_owned()].into_iter().chain(self.params(method, ""));
+                w.doc(&method.method.doc);
+                w.line(format!(
+                    "async fn {}({}) -> {}::Result<{}>;",
+                    method.name,
+                    params.collect::<Vec<_>>().join(", "),
+                    krate,
+                    self.ret(method)
+                ));
+            }
+            w.line("}");
+        }
+
+        w.line(format!("{}::declare_binder_interface! {{", krate));
+        w.line(format!("{}[{}] {{", name, descriptor));
+        w.line(format!("native: {}(on_transact),", native));
+        w.line(format!("proxy: {},", proxy));
+        w.line(format!("async: {}Async,", name));
+        w.line(format!("stability: {},", self.stability(header)));
+        w.line("}");
+        w.line("}");
+
+        w.line("pub mod transactions {");
+        for method in &methods {
+            w.line(format!(
+                "pub const {}: {}::binder_impl::TransactionCode = \
+                 {}::binder_impl::FIRST_CALL_TRANSACTION + {};",
+                method.name, krate, krate, method.code
+            ));
+        }
+        w.line("}");
+
+        self.constants(w, id, &interface.constants)?;
+
+        if self.options.async_server {
+            self.new_async_binder(w, &name, &native, &methods);
+        }
+
+        w.line(format!("impl {} {{", proxy));
+        for method in &methods {
+            self.proxy_helpers(w, method);
+        }
+        w.line("}");
+
+        w.line(format!("impl {} for {} {{", name, proxy));
+        for method in &methods {
+            let aidl_name = &method.method.name;
+            let params = ["&self".to_owned()].into_iter().chain(self.params(method, ""));
+            let names = method.args.iter().map(|arg| arg.name.as_str());
+            let mut_names = method.args.iter().filter(|arg| arg.is_mut()).map(|arg| &arg.name);
+            w.line(format!(
+                "fn {}({}) -> {}::Result<{}> {{",
+                method.name,
+                params.collect::<Vec<_>>().join(", "),
+                krate,
+                self.ret(method)
+            ));
+            w.line(format!(
+                "let _aidl_data = self.build_parcel_{}({})?;",
+                aidl_name,
+                names.collect::<Vec<_>>().join(", ")
+            ));
+            w.line(format!(
+                "let _aidl_reply = self.binder.submit_transact(transactions::{}, _aidl_data, {});",
+                method.name,
+                flags(method)
+            ));
+            w.line(format!(
+                "self.read_response_{}({})",
+                aidl_name,
+                mut_names
+                    .chain(["_aidl_reply".to_owned()].iter())
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+            w.line("}");
+        }
+        w.line("}");
+
+        w.line(format!("impl<P: {}::BinderAsyncPool> {}Async<P> for {} {{", krate, name, proxy));
+        for method in &methods {
+            let aidl_name = &method.method.name;
+            let params = ["&'a self".to_owned()].into_iter().chain(self.params(method, "'a "));
+            let names = method.args.iter().map(|arg| arg.name.as_str()).collect::<Vec<_>>();
+            let mut read_args: Vec<_> =
+                method.args.iter().filter(|arg| arg.is_mut()).map(|arg| arg.name.clone()).collect();
+            read_args.push("_aidl_reply".to_owned());
+            w.line(format!(
+                "fn {}<'a>({}) -> {}::BoxFuture<'a, {}::Result<{}>> {{",
+                method.name,
+                params.collect::<Vec<_>>().join(", "),
+                krate,
+                krate,
+                self.ret(method)
+            ));
+            w.line(format!(
+                "let _aidl_data = match self.build_parcel_{}({}) {{",
+                aidl_name,
+                names.join(", ")
+            ));
+            w.line("Ok(_aidl_data) => _aidl_data,");
+            w.line("Err(err) => return Box::pin(std::future::ready(Err(err))),");
+            w.line("};");
+            if method.oneway {
+                // Oneway transactions don't block, so don't need another thread.
+                w.line(format!(
+                    "let _aidl_reply = self.binder.submit_transact(transactions::{}, _aidl_data, {});",
+                    method.name,
+                    flags(method)
+                ));
+                w.line(format!(
+                    "Box::pin(std::future::ready(self.read_response_{}(_aidl_reply)))",
+                    aidl_name
+                ));
+            } else {
+                w.line("let binder = self.binder.clone();");
+                w.line("P::spawn(");
+                w.line(format!(
+                    "move || binder.submit_transact(transactions::{}, _aidl_data, {}),",
+                    method.name,
+                    flags(method)
+                ));
+                w.line("move |_aidl_reply| async move {");
+                w.line(format!("self.read_response_{}({})", aidl_name, read_args.join(", ")));
+                w.line("},");
+                w.line(")");
+            }
+            w.line("}");
+        }
+        w.line("}");
+
+        w.line(format!("impl {} for {}::binder_impl::Binder<{}> {{", name, krate, native));
+        for method in &methods {
+            let params = ["&self".to_owned()].into_iter().chain(self.params(method, ""));
+            let names = method.args.iter().map(|arg| arg.name.as_str()).collect::<Vec<_>>();
+            w.line(format!(
+                "fn {}({}) -> {}::Result<{}> {{ self.0.{}({}) }}",
+                method.name,
+                params.collect::<Vec<_>>().join(", "),
+                krate,
+                self.ret(method),
+                method.name,
+                names.join(", ")
+            ));
+        }
+        w.line("}");
+
+        self.on_transact(w, &name, &methods);
+        Ok(())
+    }
+
+    fn proxy_helpers(&self, w: &mut Writer, method: &MethodInfo<'_>) {
+        let krate = self.options.krate;
+        let aidl_name = &method.method.name;
+        let params = ["&self".to_owned()].into_iter().chain(self.params(method, ""));
+        w.line(format!(
+            "fn build_parcel_{}({}) -> {}::Result<{}::binder_impl::Parcel> {{",
+            aidl_name,
+            params.collect::<Vec<_>>().join(", "),
+            krate,
+            krate
+        ));
+        w.line("let mut _aidl_data = self.binder.prepare_transact()?;");
+        for arg in &method.args {
+            let name = &arg.name;
+            match arg.direction {
+                Direction::In if arg.ty.is_primitive() || matches!(arg.ty, Ty::Option(_)) => {
+                    w.line(format!("_aidl_data.write(&{})?;", name))
+                }
+                Direction::In => w.line(format!("_aidl_data.write({})?;", name)),
+                Direction::InOut => w.line(format!("_aidl_data.write(&*{})?;", name)),
+                // The service allocates arrays of the size the client has.
+                Direction::Out => match &arg.ty {
+                    Ty::Vec(_, true) => {
+                        w.line(format!("_aidl_data.write_slice_size(Some({}.as_slice()))?;", name))
+                    }
+                    Ty::Option(inner) if matches!(**inner, Ty::Vec(_, true)) => {
+                        w.line(format!("_aidl_data.write_slice_size({}.as_deref())?;", name))
+                    }
+                    _ => {}
+                },
+            }
+        }
+        w.line("Ok(_aidl_data)");
+        w.line("}");
+
+        let mut params: Vec<_> = ["&self".to_owned()].into();
+        params.extend(
+            method
+                .args
+                .iter()
+                .filter(|arg| arg.is_mut())
+                .map(|arg| format!("{}: &mut {}", arg.name, self.owned(&arg.ty))),
+        );
+        params.push(format!(
+            "_aidl_reply: std::result::Result<{}::binder_impl::Parcel, {}::StatusCode>",
+            krate, krate
+        ));
+        w.line(format!(
+            "fn read_response_{}({}) -> {}::Result<{}> {{",
+            aidl_name,
+            params.join(", "),
+            krate,
+            self.ret(method)
+        ));
+        if method.oneway {
+            w.line("_aidl_reply?;");
+            w.line("Ok(())");
+        } else {
+            w.line("let _aidl_reply = _aidl_reply?;");
+            w.line(format!("let _aidl_status: {}::Status = _aidl_reply.read()?;", krate));
+            w.line("if !_aidl_status.is_ok() {");
+            w.line("return Err(_aidl_status);");
+            w.line("}");
+            if let Some(ret) = &method.ret {
+                w.line(format!("let _aidl_return: {} = _aidl_reply.read()?;", self.owned(ret)));
+            }
+            for arg in method.args.iter().filter(|arg| arg.is_mut()) {
+                w.line(format!("_aidl_reply.read_onto({})?;", arg.name));
+            }
+            w.line(if method.ret.is_some() { "Ok(_aidl_return)" } else { "Ok(())" });
+        }
+        w.line("}");
+    }
+
+    fn on_transact(&self, w: &mut Writer, name: &str, methods: &[MethodInfo<'_>]) {
+        let krate = self.options.krate;
+        w.line(format!(
+            "fn on_transact(_aidl_service: &dyn {}, _aidl_code: {}::binder_impl::TransactionCode, \
+             _aidl_data: &{}::binder_impl::BorrowedParcel<'_>, \
+             _aidl_reply: &mut {}::binder_impl::BorrowedParcel<'_>) \
+             -> std::result::Result<(), {}::StatusCode> {{",
+            name, krate, krate, krate, krate
+        ));
+        w.line("match _aidl_code {");
+        for method in methods {
+            w.line(format!("transactions::{} => {{", method.name));
+            let mut passes = Vec::new();
+            for arg in &method.args {
+                let (name, ty) = (&arg.name, self.owned(&arg.ty));
+                match arg.direction {
+                    Direction::In => {
+                        w.line(format!("let {}: {} = _aidl_data.read()?;", name, ty));
+                        passes.push(match &arg.ty {
+                            _ if arg.ty.is_primitive() => name.clone(),
+                            Ty::Option(inner) if matches!(**inner, Ty::String | Ty::Vec(..)) => {
+                                format!("{}.as_deref()", name)
+                            }
+                            Ty::Option(_) => format!("{}.as_ref()", name),
+                            _ => format!("&{}", name),
+                        });
+                        continue;
+                    }
+                    Direction::InOut => {
+                        w.line(format!("let mut {}: {} = _aidl_data.read()?;", name, ty));
+                    }
+                    Direction::Out => {
+                        w.line(format!("let mut {}: {} = Default::default();", name, ty));
+                        match &arg.ty {
+                            Ty::Vec(_, true) => {
+                                w.line(format!("_aidl_data.resize_out_vec(&mut {})?;", name))
+                            }
+                            Ty::Option(inner) if matches!(**inner, Ty::Vec(_, true)) => w.line(
+                                format!("_aidl_data.resize_nullable_out_vec(&mut {})?;", name),
+                            ),
+                            _ => {}
+                        }
+                    }
+                }
+                passes.push(format!("&mut {}", name));
+            }
+            w.line(format!(
+                "let _aidl_return = _aidl_service.{}({});",
+                method.name,
+                passes.join(", ")
+            ));
+            if !method.oneway {
+                w.line("match &_aidl_return {");
+                w.line("Ok(_aidl_return) => {");
+                w.line(format!(
+                    "_aidl_reply.write(&{}::Status::from({}::StatusCode::OK))?;",
+                    krate, krate
+                ));
+                if method.ret.is_some() {
+                    w.line("_aidl_reply.write(_aidl_return)?;");
+                }
+                for arg in method.args.iter().filter(|arg| arg.is_mut()) {
+                    w.line(format!("_aidl_reply.write(&{})?;", arg.name));
+                }
+                w.line("}");
+                w.line("Err(_aidl_status) => _aidl_reply.write(_aidl_status)?,");
+                w.line("}");
+            }
+            w.line("Ok(())");
+            w.line("}");
+        }
+        w.line(format!("_ => Err({}::StatusCode::UNKNOWN_TRANSACTION),", krate));
+        w.line("}");
+        w.line("}");
+    }
+
+    fn new_async_binder(
+        &self,
+        w: &mut Writer,
+        name: &str,
+        native: &str,
+        methods: &[MethodInfo<'_>],
+    ) {
+        let krate = self.options.krate;
+        w.line(format!("impl {} {{", native));
+        w.line("/// Create a new binder service out of an async service, which");
+        w.line("/// handles each transaction by blocking on `rt`.");
+        w.line(format!(
+            "pub fn new_async_binder<T, R>(inner: T, rt: R, features: {}::BinderFeatures) \
+             -> {}::Strong<dyn {}>",
+            krate, krate, name
+        ));
+        w.line("where");
+        w.line(format!("T: {}AsyncServer + {}::Interface + Send + Sync + 'static,", name, krate));
+        w.line(format!("R: {}::binder_impl::BinderAsyncRuntime + Send + Sync + 'static,", krate));
+        w.line("{");
+        w.line("struct Wrapper<T, R> {");
+        w.line("_inner: T,");
+        w.line("_rt: R,");
+        w.line("}");
+        w.line(format!(
+            "impl<T, R> {}::Interface for Wrapper<T, R> where T: {}::Interface, R: Send + Sync {{",
+            krate, krate
+        ));
+        w.line(format!(
+            "fn as_binder(&self) -> {}::SpIBinder {{ self._inner.as_binder() }}",
+            krate
+        ));
+        w.line(format!(
+            "fn dump(&self, file: &std::fs::File, args: &[&std::ffi::CStr]) \
+             -> std::result::Result<(), {}::StatusCode> {{ self._inner.dump(file, args) }}",
+            krate
+        ));
+        w.line(format!(
+            "fn handle_shell_command(&self, stdin: &std::fs::File, stdout: &std::fs::File, \
+             stderr: &std::fs::File, args: &[&std::ffi::CStr]) \
+             -> std::result::Result<(), {}::StatusCode> {{ \
+             self._inner.handle_shell_command(stdin, stdout, stderr, args) }}",
+            krate
+        ));
+        w.line("}");
+        w.line(format!("impl<T, R> {} for Wrapper<T, R>", name));
+        w.line("where");
+        w.line(format!("T: {}AsyncServer + Send + Sync + 'static,", name));
+        w.line(format!("R: {}::binder_impl::BinderAsyncRuntime + Send + Sync + 'static,", krate));
+        w.line("{");
+        for method in methods {
+            let params = ["&self".to_owned()].into_iter().chain(self.params(method, ""));
+            let names = method.args.iter().map(|arg| arg.name.as_str()).collect::<Vec<_>>();
+            w.line(format!(
+                "fn {}({}) -> {}::Result<{}> {{",
+                method.name,
+                params.collect::<Vec<_>>().join(", "),
+                krate,
+                self.ret(method)
+            ));
+            w.line(format!("self._rt.block_on(self._inner.{}({}))", method.name, names.join(", ")));
+            w.line("}");
+        }
+        w.line("}");
+        w.line("Self::new_binder(Wrapper { _inner: inner, _rt: rt }, features)");
+        w.line("}");
+        w.line("}");
+    }
+
+    /// The type of a parcelable or union field.
+    fn field_type(&self, id: DeclId, field: &Field) -> Result<Ty, Invalid> {
+        let ty = self.resolve_type(id, &field.ty)?;
+        Ok(if ty.needs_option() { Ty::Option(Box::new(ty)) } else { ty })
+    }
+
+    /// The initial value of a field.
+    fn field_default(
+        &self,
+        id: DeclId,
+        header: &DeclHeader,
+        field: &Field,
+        ty: &Ty,
+    ) -> Result<String, Invalid> {
+        match (&field.default, ty) {
+            (Some(default), _) => {
+                let value = self.eval(id, id, default, field.line, 0)?;
+                self.literal(id, field.line, &value, ty, true)
+            }
+            (None, Ty::ParcelableHolder) => Ok(format!(
+                "{}::ParcelableHolder::new({})",
+                self.options.krate,
+                self.stability(header)
+            )),
+            (None, _) => Ok("Default::default()".to_owned()),
+        }
+    }
+
+    fn derives(&self, header: &DeclHeader) -> String {
+        let mut derives = vec!["Debug".to_owned()];
+        if let Some(annotation) = header.annotation("RustDerive") {
+            for (name, value) in &annotation.params {
+                if let Expr::Bool(true) = value {
+                    derives.push(name.clone());
+                }
+            }
+        }
+        format!("#[derive({})]", derives.join(", "))
+    }
+
+    fn metadata(&self, w: &mut Writer, id: DeclId, header: &DeclHeader) {
+        let krate = self.options.krate;
+        let name = ident(&header.name);
+        w.line(format!("{}::impl_serialize_for_parcelable!({});", krate, name));
+        w.line(format!("{}::impl_deserialize_for_parcelable!({});", krate, name));
+        w.line(format!("impl {}::binder_impl::ParcelableMetadata for {} {{", krate, name));
+        w.line(format!(
+            "fn get_descriptor() -> &'static str {{ {:?} }}",
+            qualified_name(self.package(id), &header.name)
+        ));
+        if header.annotation("VintfStability").is_some() {
+            w.line(format!(
+                "fn get_stability(&self) -> {}::binder_impl::Stability {{ {} }}",
+                krate,
+                self.stability(header)
+            ));
+        }
+        w.line("}");
+    }
+
+    fn parcelable(
+        &self,
+        w: &mut Writer,
+        id: DeclId,
+        parcelable: &Parcelable,
+    ) -> Result<(), Invalid> {
+        let krate = self.options.krate;
+        let header = &parcelable.header;
+        let name = ident(&header.name);
+        let mut fields = Vec::new();
+        for field in &parcelable.fields {
+            let ty = self.field_type(id, field)?;
+            let default = self.field_default(id, header, field, &ty)?;
+            fields.push((field, ident(&field.name), ty, default));
+        }
+
+        w.doc(&header.doc);
+        w.line(self.derives(header));
+        w.line(format!("pub struct {} {{", name));
+        for (field, name, ty, _) in &fields {
+            w.doc(&field.doc);
+            w.line(format!("pub {}: {},", name, self.owned(ty)));
+        }
+        w.line("}");
+
+        self.constants(w, id, &parcelable.constants)?;
+
+        w.line(format!("impl Default for {} {{", name));
+        w.line("fn default() -> Self {");
+        w.line("Self {");
+        for (_, name, _, default) in &fields {
+            w.line(format!("{}: {},", name, default));
+        }
+        w.line("}");
+        w.line("}");
+        w.line("}");
+
+        // Don't leave an unused closure argument behind for empty parcelables.
+        let subparcel = if fields.is_empty() { "_" } else { "subparcel" };
+        w.line(format!("impl {}::Parcelable for {} {{", krate, name));
+        w.line(format!(
+            "fn write_to_parcel(&self, parcel: &mut {}::binder_impl::BorrowedParcel<'_>) \
+             -> std::result::Result<(), {}::StatusCode> {{",
+            krate, krate
+        ));
+        w.line(format!("parcel.sized_write(|{}| {{", subparcel));
+        for (_, name, _, _) in &fields {
+            w.line(format!("subparcel.write(&self.{})?;", name));
+        }
+        w.line("Ok(())");
+        w.line("})");
+        w.line("}");
+        w.line(format!(
+            "fn read_from_parcel(&mut self, parcel: &{}::binder_impl::BorrowedParcel<'_>) \
+             -> std::result::Result<(), {}::StatusCode> {{",
+            krate, krate
+        ));
+        w.line(format!("parcel.sized_read(|{}| {{", subparcel));
+        for (_, name, ty, _) in &fields {
+            w.line("if subparcel.has_more_data() {");
+            // A ParcelableHolder keeps the stability it was created with.
+            if let Ty::ParcelableHolder = ty {
+                w.line(format!("subparcel.read_onto(&mut self.{})?;", name));
+            } else {
+                w.line(format!("self.{} = subparcel.read()?;", name));
+            }
+            w.line("}");
+        }
+        w.line("Ok(())");
+        w.line("})");
+        w.line("}");
+        w.line("}");
+
+        self.metadata(w, id, header);
+        Ok(())
+    }
+
+    fn union(&self, w: &mut Writer, id: DeclId, union: &Parcelable) -> Result<(), Invalid> {
+        let krate = self.options.krate;
+        let header = &union.header;
+        let name = ident(&header.name);
+        let Some(first) = union.fields.first() else {
+            return self.invalid(id, header.line, "unions must have at least one field");
+        };
+        let mut fields = Vec::new();
+        for field in &union.fields {
+            let ty = self.field_type(id, field)?;
+            let mut chars = field.name.chars();
+            let variant: String =
+                chars.next().into_iter().flat_map(char::to_uppercase).chain(chars).collect();
+            fields.push((field, ident(&variant), ident(&field.name), ty));
+        }
+
+        // Only the first field's default is used, as the union's.
+        let default = self.field_default(id, header, first, &fields[0].3)?;
+
+        w.line(format!("{}::declare_binder_union! {{", krate));
+        w.doc(&header.doc);
+        w.line(self.derives(header));
+        w.line(format!("{}[{:?}] {{", name, qualified_name(self.package(id), &header.name)));
+        w.line("tag: Tag,");
+        w.line("fields: {");
+        for (index, (field, variant, tag, ty)) in fields.iter().enumerate() {
+            let default = if index == 0 { format!(" = {}", default) } else { String::new() };
+            w.doc(&field.doc);
+            w.line(format!("{} = {} => {}({}){},", tag, index, variant, self.owned(ty), default));
+        }
+        w.line("},");
+        if header.annotation("VintfStability").is_some() {
+            w.line(format!("stability: {},", self.stability(header)));
+        }
+        w.line("}");
+        w.line("}");
+
+        self.constants(w, id, &union.constants)?;
+        Ok(())
+    }
+
+    fn enumeration(&self, w: &mut Writer, id: DeclId, enumeration: &Enum) -> Result<(), Invalid> {
+        let header = &enumeration.header;
+        let backing = match header.annotation("Backing").and_then(|backing| backing.params.first())
+        {
+            None => Ty::Byte,
+            Some((_, Expr::Str(ty))) if ty == "byte" => Ty::Byte,
+            Some((_, Expr::Str(ty))) if ty == "int" => Ty::Int,
+            Some((_, Expr::Str(ty))) if ty == "long" => Ty::Long,
+            Some(_) => {
+                let message = "@Backing type must be \"byte\", \"int\" or \"long\"";
+                return self.invalid(id, header.line, message);
+            }
+        };
+        w.line(format!("{}::declare_binder_enum! {{", self.options.krate));
+        w.doc(&header.doc);
+        w.line(format!(
+            "{}: [{}; {}] {{",
+            ident(&header.name),
+            self.owned(&backing),
+            enumeration.enumerators.len()
+        ));
+        for (index, enumerator) in enumeration.enumerators.iter().enumerate() {
+            let value = Value::Int(self.enumerator_value(id, index, 0)?);
+            let value = self.literal(id, enumerator.line, &value, &backing, false)?;
+            w.doc(&enumerator.doc);
+            w.line(format!("{} = {},", ident(&enumerator.name), value));
+        }
+        w.line("}");
+        w.line("}");
+        Ok(())
+    }
+
+    fn enumerator_value(&self, id: DeclId, index: usize, depth: usize) -> Result<i128, Invalid> {
+        let Decl::Enum(enumeration) = self.decl(id) else { unreachable!() };
+        // Enumerators without a value follow the previous one.
+        let enumerators = &enumeration.enumerators[..=index];
+        let Some(explicit) = enumerators.iter().rposition(|enumerator| enumerator.value.is_some())
+        else {
+            return Ok(index as i128);
+        };
+        let enumerator = &enumerators[explicit];
+        let line = enumerator.line;
+        let value = self.eval(id, id, enumerator.value.as_ref().unwrap(), line, depth + 1)?;
+        match value.as_int() {
+            Some(value) => Ok(value + (index - explicit) as i128),
+            None => self.invalid(id, line, "enumerators must be integers"),
+        }
+    }
+
+    /// Evaluate `expr` in the scope of `scope`, for code in the module of
+    /// `from`.
+    fn eval(
+        &self,
+        scope: DeclId,
+        from: DeclId,
+        expr: &Expr,
+        line: usize,
+        depth: usize,
+    ) -> Result<Value, Invalid> {
+        if depth > MAX_DEPTH {
+            return self.invalid(scope, line, "constant expression is circular");
+        }
+        let eval = |expr| self.eval(scope, from, expr, line, depth);
+        let value = match expr {
+            Expr::Int(literal) => match parse_int(literal) {
+                Some(value) => Value::Int(value.into()),
+                None => return self.invalid(scope, line, format!("invalid integer {}", literal)),
+            },
+            Expr::Float(literal) => Value::Float(literal.parse().unwrap()),
+            Expr::Bool(value) => Value::Bool(*value),
+            Expr::Str(value) => Value::Str(value.clone()),
+            Expr::Char(value) => Value::Int((*value as u32).into()),
+            Expr::Array(elements) => {
+                Value::Array(elements.iter().map(eval).collect::<Result<_, _>>()?)
+            }
+            Expr::Name(name) => {
+                let (member, prefix) = name.split_last().unwrap();
+                let target = if prefix.is_empty() {
+                    scope
+                } else {
+                    match self.resolve(scope, prefix) {
+                        Some(target) => target,
+                        None => {
+                            let message = format!("unknown type {}", prefix.join("."));
+                            return self.invalid(scope, line, message);
+                        }
+                    }
+                };
+                return self.member(scope, target, from, member, line, depth + 1);
+            }
+            Expr::Unary(operator, operand) => match (*operator, eval(operand)?) {
+                ("-", value) if value.as_int().is_some() => Value::Int(-value.as_int().unwrap()),
+                ("-", Value::Float(value)) => Value::Float(-value),
+                ("+", value @ (Value::Int(_) | Value::Float(_))) => value,
+                ("~", value) if value.as_int().is_some() => Value::Int(!value.as_int().unwrap()),
+                ("!", Value::Bool(value)) => Value::Bool(!value),
+                _ => return self.invalid(scope, line, format!("invalid operand for {}", operator)),
+            },
+            Expr::Binary(operator, lhs, rhs) => match binary(operator, eval(lhs)?, eval(rhs)?) {
+                Ok(value) => value,
+                Err(message) => return self.invalid(scope, line, message),
+            },
+            Expr::Ternary(condition, then, otherwise) => match eval(condition)? {
+                Value::Bool(true) => eval(then)?,
+                Value::Bool(false) => eval(otherwise)?,
+                _ => return self.invalid(scope, line, "conditions must be booleans"),
+            },
+        };
+        Ok(value)
+    }
+
+    /// The value of the constant or enumerator `member` of `target`.
+    fn member(
+        &self,
+        scope: DeclId,
+        target: DeclId,
+        from: DeclId,
+        member: &str,
+        line: usize,
+        depth: usize,
+    ) -> Result<Value, Invalid> {
+        let constants = match self.decl(target) {
+            Decl::Enum(enumeration) => {
+                let Some(index) = enumeration.enumerators.iter().position(|e| e.name == member)
+                else {
+                    return self.invalid(scope, line, format!("unknown enumerator {}", member));
+                };
+                let value = self.enumerator_value(target, index, depth)?;
+                let path = format!("{}::{}", self.path(from, target), ident(member));
+                return Ok(Value::Enumerator(path, value));
+            }
+            Decl::Interface(interface) => &interface.constants,
+            Decl::Parcelable(parcelable) | Decl::Union(parcelable) => &parcelable.constants,
+            Decl::ForeignParcelable(..) => &[][..],
+        };
+        let Some(constant) = constants.iter().find(|constant| constant.name == member) else {
+            return self.invalid(scope, line, format!("unknown constant {}", member));
+        };
+        let value = self.eval(target, from, &constant.value, constant.line, depth)?;
+        // Integer constants wrap around, as in `const int ALL = 0xffffffff;`.
+        let bits = match constant.ty.name.as_slice() {
+            [name] if constant.ty.array.is_none() && name == "byte" => 8,
+            [name] if constant.ty.array.is_none() && name == "int" => 32,
+            [name] if constant.ty.array.is_none() && name == "long" => 64,
+            _ => return Ok(value),
+        };
+        match value.as_int().map(|value| wrap(value, bits)) {
+            Some(Some(value)) => Ok(Value::Int(value)),
+            _ => Ok(value),
+        }
+    }
+
+    /// `value` as a Rust expression of type `ty`, with `String`s rather than
+    /// `&str`s if `owned`.
+    fn literal(
+        &self,
+        id: DeclId,
+        line: usize,
+        value: &Value,
+        ty: &Ty,
+        owned: bool,
+    ) -> Result<String, Invalid> {
+        let literal = match (ty, value) {
+            (Ty::Bool, Value::Bool(value)) => Some(value.to_string()),
+            (Ty::Byte, value) => {
+                value.as_int().and_then(|value| wrap(value, 8)).map(|v| v.to_string())
+            }
+            (Ty::Int, value) => {
+                value.as_int().and_then(|value| wrap(value, 32)).map(|v| v.to_string())
+            }
+            (Ty::Long, value) => {
+                value.as_int().and_then(|value| wrap(value, 64)).map(|v| v.to_string())
+            }
+            (Ty::Char, value) => {
+                value.as_int().and_then(|value| u16::try_from(value).ok()).map(|v| v.to_string())
+            }
+            (Ty::Float, value) => value
+                .as_float()
+                .filter(|value| value.is_finite())
+                .map(|v| format!("{:?}", v as f32)),
+            (Ty::Double, value) => {
+                value.as_float().filter(|value| value.is_finite()).map(|v| format!("{:?}", v))
+            }
+            (Ty::String, Value::Str(value)) if owned => Some(format!("{:?}.into()", value)),
+            (Ty::String, Value::Str(value)) => Some(format!("{:?}", value)),
+            (Ty::Enum(_), Value::Enumerator(path, _)) => Some(path.clone()),
+            (Ty::Enum(path), Value::Int(value)) => Some(format!("{}({})", path, value)),
+            (Ty::Option(inner), value) => {
+                Some(format!("Some({})", self.literal(id, line, value, inner, owned)?))
+            }
+            (Ty::Vec(elem, _), Value::Array(elements)) => {
+                let elements = self.literals(id, line, elements, elem, owned)?;
+                Some(format!("vec![{}]", elements))
+            }
+            (Ty::Fixed(elem, size), Value::Array(elements)) if elements.len() == *size => {
+                Some(format!("[{}]", self.literals(id, line, elements, elem, owned)?))
+            }
+            _ => None,
+        };
+        match literal {
+            Some(literal) => Ok(literal),
+            None => self.invalid(id, line, format!("invalid value for {}", self.owned(ty))),
+        }
+    }
+
+    fn literals(
+        &self,
+        id: DeclId,
+        line: usize,
+        values: &[Value],
+        ty: &Ty,
+        owned: bool,
+    ) -> Result<String, Invalid> {
+        values
+            .iter()
+            .map(|value| match (ty, value.as_int()) {
+                // Arrays of bytes are `u8`s.
+                (Ty::Byte, Some(value)) => match wrap(value, 8) {
+                    Some(value) => Ok((value as u8).to_string()),
+                    None => self.invalid(id, line, "invalid value for u8"),
+                },
+                _ => self.literal(id, line, value, ty, owned),
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(|literals| literals.join(", "))
+    }
+}
+
+/// `value` as a signed integer of `bits`, accepting unsigned values as in two's
+/// complement.
+fn wrap(value: i128, bits: u32) -> Option<i128> {
+    let min = -(1i128 << (bits - 1));
+    let max = (1i128 << (bits - 1)) - 1;
+    let unsigned_max = (1i128 << bits) - 1;
+    match value {
+        _ if (min..=max).contains(&value) => Some(value),
+        _ if (0..=unsigned_max).contains(&value) => Some(value - (1i128 << bits)),
+        _ => None,
+    }
+}
+
+fn binary(operator: &str, lhs: Value, rhs: Value) -> Result<Value, String> {
+    let invalid = || format!("invalid operands for {}", operator);
+    let value = match (lhs, rhs) {
+        (Value::Bool(lhs), Value::Bool(rhs)) => match operator {
+            "&&" => Value::Bool(lhs && rhs),
+            "||" => Value::Bool(lhs || rhs),
+            "==" => Value::Bool(lhs == rhs),
+            "!=" => Value::Bool(lhs != rhs),
+            _ => return Err(invalid()),
+        },
+        (Value::Str(lhs), Value::Str(rhs)) => match operator {
+            "+" => Value::Str(lhs + &rhs),
+            "==" => Value::Bool(lhs == rhs),
+            "!=" => Value::Bool(lhs != rhs),
+            _ => return Err(invalid()),
+        },
+        (lhs, rhs) if lhs.as_int().is_some() && rhs.as_int().is_some() => {
+            let (lhs, rhs) = (lhs.as_int().unwrap(), rhs.as_int().unwrap());
+            let value = match operator {
+                "+" => lhs.checked_add(rhs),
+                "-" => lhs.checked_sub(rhs),
+                "*" => lhs.checked_mul(rhs),
+                "/" => lhs.checked_div(rhs),
+                "%" => lhs.checked_rem(rhs),
+                "<<" => u32::try_from(rhs).ok().filter(|&rhs| rhs < 64).map(|rhs| lhs << rhs),
+                ">>" => u32::try_from(rhs).ok().filter(|&rhs| rhs < 64).map(|rhs| lhs >> rhs),
+                "&" => Some(lhs & rhs),
+                "|" => Some(lhs | rhs),
+                "^" => Some(lhs ^ rhs),
+                _ => return Ok(Value::Bool(compare(operator, lhs, rhs).ok_or_else(invalid)?)),
+            };
+            Value::Int(value.ok_or_else(|| format!("{} overflows", operator))?)
+        }
+        (lhs, rhs) => {
+            let (Some(lhs), Some(rhs)) = (lhs.as_float(), rhs.as_float()) else {
+                return Err(invalid());
+            };
+            match operator {
+                "+" => Value::Float(lhs + rhs),
+                "-" => Value::Float(lhs - rhs),
+                "*" => Value::Float(lhs * rhs),
+                "/" => Value::Float(lhs / rhs),
+                _ => Value::Bool(compare(operator, lhs, rhs).ok_or_else(invalid)?),
+            }
+        }
+    };
+    Ok(value)
+}
+
+fn compare<T: PartialOrd>(operator: &str, lhs: T, rhs: T) -> Option<bool> {
+    match operator {
+        "==" => Some(lhs == rhs),
+        "!=" => Some(lhs != rhs),
+        "<" => Some(lhs < rhs),
+        ">" => Some(lhs > rhs),
+        "<=" => Some(lhs <= rhs),
+        ">=" => Some(lhs >= rhs),
+        _ => None,
+    }
+}
+
+/// `name` as a Rust identifier.
+fn ident(name: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do",
+        "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let",
+        "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+        "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+        "virtual", "where", "while", "yield",
+    ];
+    match name {
+        // These can't be raw identifiers.
+        "self" | "Self" | "super" | "crate" => format!("{}_", name),
+        _ if KEYWORDS.contains(&name) => format!("r#{}", name),
+        _ => name.to_owned(),
+    }
+}
+
+/// Writes code, indented by its braces.
+#[derive(Default)]
+struct Writer {
+    out: String,
+    indent: usize,
+}
+
+impl Writer {
+    fn line(&mut self, code: impl AsRef<str>) {
+        for line in code.as_ref().lines() {
+            let line = line.trim();
+            let is_code = !line.starts_with("//");
+            if is_code && line.starts_with(['}', ')', ']']) {
+                self.indent = self.indent.saturating_sub(1);
+            }
+            if !line.is_empty() {
+                self.out.push_str(&"    ".repeat(self.indent));
+                self.out.push_str(line);
+            }
+            self.out.push('\n');
+            if is_code && line.ends_with(['{', '(', '[']) {
+                self.indent += 1;
+            }
+        }
+    }
+
+    fn doc(&mut self, doc: &Option<String>) {
+        for line in doc.iter().flat_map(|doc| doc.lines()) {
+            if line.is_empty() {
+                self.line("///");
+            } else {
+                self.line(format!("/// {}", line));
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Splitting AIDL source into tokens.
+
+use crate::ParseError;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum TokenKind {
+    Ident(String),
+    /// An integer literal, as written, without its `l` or `L` suffix.
+    Int(String),
+    /// A floating point literal, as written, without its `f` suffix.
+    Float(String),
+    Str(String),
+    Char(char),
+    /// Punctuation, with `<<`, `>>`, `&&`, `||`, `==`, `!=`, `<=` and `>=`
+    /// as single tokens.
+    Punct(&'static str),
+    Eof,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Token {
+    pub(crate) kind: TokenKind,
+    pub(crate) line: usize,
+    /// The `/** ... */` comment right before the token, if any.
+    pub(crate) doc: Option<String>,
+}
+
+const PUNCTS: &[&str] = &[
+    "<<", ">>", "&&", "||", "==", "!=", "<=", ">=", "(", ")", "{", "}", "[", "]", "<", ">", ",",
+    ";", "=", "@", ".", "+", "-", "*", "/", "%", "&", "|", "^", "~", "!", "?", ":",
+];
+
+/// Split `source` into tokens, ending with `TokenKind::Eof`.
+pub(crate) fn tokenize(source: &str) -> Result<Vec<Token>, ParseError> {
+    let mut lexer = Lexer { rest: source, line: 1, doc: None, tokens: Vec::new() };
+    lexer.run()?;
+    Ok(lexer.tokens)
+}
+
+struct Lexer<'a> {
+    rest: &'a str,
+    line: usize,
+    doc: Option<String>,
+    tokens: Vec<Token>,
+}
+
+impl<'a> Lexer<'a> {
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError { line: self.line, message: message.into() }
+    }
+
+    fn advance(&mut self, len: usize) -> &'a str {
+        let (taken, rest) = self.rest.split_at(len);
+        self.line += taken.matches('\n').count();
+        self.rest = rest;
+        taken
+    }
+
+    fn push(&mut self, kind: TokenKind, line: usize) {
+        self.tokens.push(Token { kind, line, doc: self.doc.take() });
+    }
+
+    fn run(&mut self) -> Result<(), ParseError> {
+        loop {
+            let trimmed = self.rest.trim_start();
+            self.advance(self.rest.len() - trimmed.len());
+            let line = self.line;
+            let Some(c) = self.rest.chars().next() else {
+                self.push(TokenKind::Eof, line);
+                return Ok(());
+            };
+
+            if self.rest.starts_with("//") {
+                let len = self.rest.find('\n').unwrap_or(self.rest.len());
+                self.advance(len);
+            } else if self.rest.starts_with("/*") {
+                let Some(end) = self.rest[2..].find("*/") else {
+                    return Err(self.error("unterminated comment"));
+                };
+                let comment = self.advance(end + 4);
+                if comment.starts_with("/**") && comment.len() > 4 {
+                    self.doc = Some(doc_text(&comment[3..comment.len() - 2]));
+                }
+            } else if c.is_ascii_alphabetic() || c == '_' {
+                let len = self
+                    .rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(self.rest.len());
+                let ident = self.advance(len).to_owned();
+                self.push(TokenKind::Ident(ident), line);
+            } else if c.is_ascii_digit() {
+                let kind = self.number()?;
+                self.push(kind, line);
+            } else if c == '"' {
+                let value = self.string()?;
+                self.push(TokenKind::Str(value), line);
+            } else if c == '\'' {
+                let value = self.char()?;
+                self.push(TokenKind::Char(value), line);
+            } else if let Some(punct) = PUNCTS.iter().find(|punct| self.rest.starts_with(**punct)) {
+                self.advance(punct.len());
+                self.push(TokenKind::Punct(punct), line);
+            } else {
+                return Err(self.error(format!("unexpected character {:?}", c)));
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<TokenKind, ParseError> {
+        let hex = self.rest.starts_with("0x") || self.rest.starts_with("0X");
+        let len = self
+            .rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '.'))
+            .unwrap_or(self.rest.len());
+        let literal = self.advance(len);
+        let is_float = !hex && (literal.contains('.') || literal.ends_with(['f', 'F']));
+        if is_float {
+            let digits = literal.trim_end_matches(['f', 'F']);
+            if digits.parse::<f64>().is_err() {
+                return Err(self.error(format!("invalid number {}", literal)));
+            }
+            Ok(TokenKind::Float(digits.to_owned()))
+        } else {
+            let digits = literal.trim_end_matches(['l', 'L']);
+            Ok(TokenKind::Int(digits.to_owned()))
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        let mut value = String::new();
+        let mut chars = self.rest.char_indices().skip(1);
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.advance(index + 1);
+                    return Ok(value);
+                }
+                '\\' => match chars.next() {
+                    Some((_, c)) => value.push(unescape(c).ok_or_else(|| {
+                        self.error(format!("unsupported escape sequence \\{}", c))
+                    })?),
+                    None => break,
+                },
+                '\n' => break,
+                c => value.push(c),
+            }
+        }
+        Err(self.error("unterminated string"))
+    }
+
+    fn char(&mut self) -> Result<char, ParseError> {
+        let mut chars = self.rest.chars().skip(1);
+        let (value, len) = match chars.next() {
+            Some('\\') => {
+                let c = chars.next().and_then(unescape);
+                (c, 4)
+            }
+            Some(c) => (Some(c), 2 + c.len_utf8()),
+            None => (None, 0),
+        };
+        match value {
+            Some(value) if self.rest.get(..len).map_or(false, |quoted| quoted.ends_with('\'')) => {
+                self.advance(len);
+                Ok(value)
+            }
+            _ => Err(self.error("invalid character literal")),
+        }
+    }
+}
+
+fn unescape(c: char) -> Option<char> {
+    match c {
+        'n' => Some('\n'),
+        't' => Some('\t'),
+        'r' => Some('\r'),
+        '0' => Some('\0'),
+        '\\' | '\'' | '"' => Some(c),
+        _ => None,
+    }
+}
+
+/// The text of a doc comment, without the leading `*` of each line.
+fn doc_text(comment: &str) -> String {
+    let lines: Vec<&str> = comment
+        .lines()
+        .map(|line| {
+            let line = line.trim();
+            line.strip_prefix('*').map_or(line, |line| line.strip_prefix(' ').unwrap_or(line))
+        })
+        .collect();
+    lines.join("\n").trim().to_owned()
+}
@@ -0,0 +1,219 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! An AIDL compiler for build scripts, generating Rust code for the `binder`
+//! crate without the Android SDK's `aidl` tool.
+//!
+//! ```no_run
+//! // In build.rs:
+//! binder_aidl::Builder::new()
+//!     .source("aidl/com/example/IRemoteService.aidl")
+//!     .include_dir("aidl")
+//!     .generate()
+//!     .unwrap();
+//! ```
+//!
+//! The generated code goes to `$OUT_DIR/aidl.rs` by default, with a module for
+//! each package and, like the AIDL Rust backend, one for each type:
+//!
+//! ```text
+//! mod aidl {
+//!     include!(concat!(env!("OUT_DIR"), "/aidl.rs"));
+//! }
+//! use aidl::com::example::IRemoteService::{BnRemoteService, IRemoteService};
+//! ```
+//!
+//! Supported are interfaces (including `oneway` ones and methods with `in`,
+//! `out` and `inout` arguments), structured parcelables, unions, enums,
+//! constants and `@nullable` types. `Map<String, V>` becomes a `HashMap`.
+//! Unstructured parcelables need a `rust_type`, as in
+//! `parcelable Foo rust_type "crate::Foo";`. Untyped `Map`s and nested types
+//! aren't supported yet.
+//!
+//! Parcelable fields of interface, `IBinder` and `ParcelFileDescriptor` types
+//! are `Option`s even when not `@nullable`, as they have no default value.
+
+mod ast;
+mod gen;
+mod lexer;
+mod parser;
+
+use std::collections::{HashSet, VecDeque};
+use std::env;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A syntax error, before the file it is in is known.
+#[derive(Debug)]
+pub(crate) struct ParseError {
+    pub(crate) line: usize,
+    pub(crate) message: String,
+}
+
+/// Why code couldn't be generated.
+#[derive(Debug)]
+pub enum Error {
+    /// An AIDL file couldn't be read, or the output couldn't be written.
+    Io(PathBuf, io::Error),
+    /// An AIDL file is invalid, or uses something this crate doesn't support.
+    Invalid {
+        /// The file with the error.
+        path: PathBuf,
+        /// The line with the error, starting at 1.
+        line: usize,
+        /// What is wrong.
+        message: String,
+    },
+    /// No output path was given, and `OUT_DIR` isn't set as it is for build
+    /// scripts.
+    NoOutDir,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            Error::Invalid { path, line, message } => {
+                write!(f, "{}:{}: {}", path.display(), line, message)
+            }
+            Error::NoOutDir => f.write_str("OUT_DIR is not set, and no output was given"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(_, error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Compiles AIDL files into a Rust source file.
+#[derive(Debug)]
+pub struct Builder {
+    sources: Vec<PathBuf>,
+    include_dirs: Vec<PathBuf>,
+    output: Option<PathBuf>,
+    crate_path: String,
+    async_server: bool,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Builder {
+    /// Create a builder without any sources.
+    pub fn new() -> Self {
+        Builder {
+            sources: Vec::new(),
+            include_dirs: Vec::new(),
+            output: None,
+            crate_path: "binder".to_owned(),
+            async_server: false,
+        }
+    }
+
+    /// Generate code for the AIDL file at `path`.
+    pub fn source(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.sources.push(path.as_ref().to_owned());
+        self
+    }
+
+    /// Look for imported types in `path`, where `com.example.IFoo` is
+    /// `path/com/example/IFoo.aidl`. Imported files get code generated for
+    /// them as well.
+    ///
+    /// Types from the same package don't need to be imported.
+    pub fn include_dir(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.include_dirs.push(path.as_ref().to_owned());
+        self
+    }
+
+    /// Write the generated code to `path` instead of `$OUT_DIR/aidl.rs`.
+    pub fn output(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.output = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// The path the generated code uses for the binder crate, `binder` by
+    /// default.
+    pub fn crate_path(&mut self, path: impl Into<String>) -> &mut Self {
+        self.crate_path = path.into();
+        self
+    }
+
+    /// Also generate `IFooAsyncServer` traits and `BnFoo::new_async_binder`
+    /// for async services. These need the `derive` feature of the binder
+    /// crate, for `binder::async_trait`.
+    pub fn async_server(&mut self, enable: bool) -> &mut Self {
+        self.async_server = enable;
+        self
+    }
+
+    /// Generate the code, and return the path it was written to.
+    ///
+    /// This prints `cargo:rerun-if-changed` for every file read, so that the
+    /// build script reruns when one of them changes.
+    pub fn generate(&self) -> Result<PathBuf, Error> {
+        let output = match &self.output {
+            Some(output) => output.clone(),
+            None => PathBuf::from(env::var_os("OUT_DIR").ok_or(Error::NoOutDir)?).join("aidl.rs"),
+        };
+        let sources = self.load()?;
+        let options = gen::Options { krate: &self.crate_path, async_server: self.async_server };
+        let code = gen::generate(&sources, &options)?;
+        fs::write(&output, code).map_err(|error| Error::Io(output.clone(), error))?;
+        Ok(output)
+    }
+
+    /// Parse the sources, and the files of the types they use.
+    fn load(&self) -> Result<Vec<gen::Source>, Error> {
+        let mut sources = Vec::new();
+        let mut loaded = HashSet::new();
+        let mut queue: VecDeque<PathBuf> = self.sources.iter().cloned().collect();
+        while let Some(path) = queue.pop_front() {
+            let canonical =
+                fs::canonicalize(&path).map_err(|error| Error::Io(path.clone(), error))?;
+            if !loaded.insert(canonical) {
+                continue;
+            }
+            println!("cargo:rerun-if-changed={}", path.display());
+            let text = fs::read_to_string(&path).map_err(|error| Error::Io(path.clone(), error))?;
+            let document = parser::parse(&text).map_err(|error| Error::Invalid {
+                path: path.clone(),
+                line: error.line,
+                message: error.message,
+            })?;
+            queue.extend(gen::dependencies(&document).iter().filter_map(|name| self.find(name)));
+            sources.push(gen::Source { path, document });
+        }
+        Ok(sources)
+    }
+
+    /// The file declaring the type with the qualified `name`, if any.
+    fn find(&self, name: &[String]) -> Option<PathBuf> {
+        let relative = format!("{}.aidl", name.join("/"));
+        self.include_dirs.iter().map(|dir| dir.join(&relative)).find(|path| path.is_file())
+    }
+}
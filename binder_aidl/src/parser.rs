@@ -0,0 +1,572 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Parsing AIDL files.
+
+use crate::ast::*;
+use crate::lexer::{tokenize, Token, TokenKind};
+use crate::ParseError;
+
+/// Parse the contents of an `.aidl` file.
+pub(crate) fn parse(source: &str) -> Result<Document, ParseError> {
+    let mut parser = Parser { tokens: tokenize(source)?, pos: 0 };
+    parser.document()
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+/// Binary operators by increasing precedence.
+const BINARY_OPERATORS: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", ">", "<=", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+impl Parser {
+    fn peek(&self) -> &TokenKind {
+        &self.tokens[self.pos].kind
+    }
+
+    fn peek_at(&self, offset: usize) -> &TokenKind {
+        &self.tokens[(self.pos + offset).min(self.tokens.len() - 1)].kind
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.pos].line
+    }
+
+    fn doc(&self) -> Option<String> {
+        self.tokens[self.pos].doc.clone()
+    }
+
+    fn next(&mut self) -> TokenKind {
+        let kind = self.tokens[self.pos].kind.clone();
+        if kind != TokenKind::Eof {
+            self.pos += 1;
+        }
+        kind
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, ParseError> {
+        Err(ParseError { line: self.line(), message: message.into() })
+    }
+
+    fn unexpected<T>(&self, expected: &str) -> Result<T, ParseError> {
+        let found = match self.peek() {
+            TokenKind::Ident(ident) => format!("`{}`", ident),
+            TokenKind::Int(literal) | TokenKind::Float(literal) => literal.clone(),
+            TokenKind::Str(literal) => format!("{:?}", literal),
+            TokenKind::Char(literal) => format!("{:?}", literal),
+            TokenKind::Punct(punct) => format!("`{}`", punct),
+            TokenKind::Eof => "end of file".to_owned(),
+        };
+        self.error(format!("expected {}, found {}", expected, found))
+    }
+
+    fn is_punct(&self, punct: &str) -> bool {
+        matches!(self.peek(), TokenKind::Punct(p) if *p == punct)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), TokenKind::Ident(ident) if ident == keyword)
+    }
+
+    fn eat_punct(&mut self, punct: &str) -> bool {
+        let found = self.is_punct(punct);
+        if found {
+            self.next();
+        }
+        found
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.next();
+        }
+        found
+    }
+
+    fn expect_punct(&mut self, punct: &str) -> Result<(), ParseError> {
+        if self.eat_punct(punct) {
+            Ok(())
+        } else {
+            self.unexpected(&format!("`{}`", punct))
+        }
+    }
+
+    /// Expect the `>` closing a type argument list, which may be the first
+    /// half of a `>>` token.
+    fn expect_close_angle(&mut self) -> Result<(), ParseError> {
+        if self.is_punct(">>") {
+            self.tokens[self.pos].kind = TokenKind::Punct(">");
+            Ok(())
+        } else {
+            self.expect_punct(">")
+        }
+    }
+
+    fn expect_ident(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            TokenKind::Ident(_) => match self.next() {
+                TokenKind::Ident(ident) => Ok(ident),
+                _ => unreachable!(),
+            },
+            _ => self.unexpected("an identifier"),
+        }
+    }
+
+    fn expect_string(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            TokenKind::Str(_) => match self.next() {
+                TokenKind::Str(value) => Ok(value),
+                _ => unreachable!(),
+            },
+            _ => self.unexpected("a string"),
+        }
+    }
+
+    fn qualified_name(&mut self) -> Result<Vec<String>, ParseError> {
+        let mut name = vec![self.expect_ident()?];
+        while self.eat_punct(".") {
+            name.push(self.expect_ident()?);
+        }
+        Ok(name)
+    }
+
+    fn document(&mut self) -> Result<Document, ParseError> {
+        let mut package = Vec::new();
+        if self.eat_keyword("package") {
+            package = self.qualified_name()?;
+            self.expect_punct(";")?;
+        }
+        let mut imports = Vec::new();
+        while self.eat_keyword("import") {
+            imports.push(self.qualified_name()?);
+            self.expect_punct(";")?;
+        }
+        let mut decls = Vec::new();
+        while *self.peek() != TokenKind::Eof {
+            decls.push(self.decl()?);
+        }
+        if decls.is_empty() {
+            return self.error("no declarations");
+        }
+        Ok(Document { package, imports, decls })
+    }
+
+    fn annotations(&mut self) -> Result<Vec<Annotation>, ParseError> {
+        let mut annotations = Vec::new();
+        while self.eat_punct("@") {
+            let name = self.expect_ident()?;
+            let mut params = Vec::new();
+            if self.eat_punct("(") {
+                while !self.eat_punct(")") {
+                    if matches!(self.peek(), TokenKind::Ident(_))
+                        && matches!(self.peek_at(1), TokenKind::Punct("="))
+                    {
+                        let key = self.expect_ident()?;
+                        self.expect_punct("=")?;
+                        params.push((key, self.expr()?));
+                    } else {
+                        params.push(("value".to_owned(), self.expr()?));
+                    }
+                    if !self.is_punct(")") {
+                        self.expect_punct(",")?;
+                    }
+                }
+            }
+            annotations.push(Annotation { name, params });
+        }
+        Ok(annotations)
+    }
+
+    fn decl(&mut self) -> Result<Decl, ParseError> {
+        let doc = self.doc();
+        let annotations = self.annotations()?;
+        let doc = doc.or_else(|| self.doc());
+        let oneway = self.eat_keyword("oneway");
+        let line = self.line();
+        let kind = self.expect_ident()?;
+        let name = self.expect_ident()?;
+        let header = DeclHeader { doc, annotations, name, line };
+        if self.is_punct("<") {
+            return self.error("generic parcelables are not supported");
+        }
+        if oneway && kind != "interface" {
+            return self.error("only interfaces can be oneway");
+        }
+        match kind.as_str() {
+            "interface" => self.interface(header, oneway).map(Decl::Interface),
+            "parcelable" if self.eat_punct(";") => Ok(Decl::ForeignParcelable(header, None)),
+            "parcelable" if !self.is_punct("{") => {
+                let mut rust_type = None;
+                while !self.eat_punct(";") {
+                    match self.expect_ident()?.as_str() {
+                        "rust_type" => rust_type = Some(self.expect_string()?),
+                        "cpp_header" | "ndk_header" => {
+                            self.expect_string()?;
+                        }
+                        _ => return self.unexpected("`rust_type` or `;`"),
+                    }
+                }
+                Ok(Decl::ForeignParcelable(header, rust_type))
+            }
+            "parcelable" => self.parcelable(header).map(Decl::Parcelable),
+            "union" => self.parcelable(header).map(Decl::Union),
+            "enum" => self.enumeration(header).map(Decl::Enum),
+            _ => {
+                self.pos -= 2;
+                self.unexpected("`interface`, `parcelable`, `enum` or `union`")
+            }
+        }
+    }
+
+    /// Reject nested types, which aren't supported.
+    fn check_not_nested(&self) -> Result<(), ParseError> {
+        let is_type_keyword =
+            ["interface", "parcelable", "enum", "union"].iter().any(|kind| self.is_keyword(kind));
+        if is_type_keyword && matches!(self.peek_at(1), TokenKind::Ident(_)) {
+            if let TokenKind::Punct("{") | TokenKind::Punct(";") = self.peek_at(2) {
+                return self.error("nested types are not supported");
+            }
+        }
+        Ok(())
+    }
+
+    fn interface(&mut self, header: DeclHeader, oneway: bool) -> Result<Interface, ParseError> {
+        let mut interface =
+            Interface { header, oneway, methods: Vec::new(), constants: Vec::new() };
+        self.expect_punct("{")?;
+        while !self.eat_punct("}") {
+            let doc = self.doc();
+            let annotations = self.annotations()?;
+            let doc = doc.or_else(|| self.doc());
+            self.check_not_nested()?;
+            if self.eat_keyword("const") {
+                interface.constants.push(self.constant(doc, annotations)?);
+                continue;
+            }
+            let oneway = self.eat_keyword("oneway");
+            let line = self.line();
+            let ret = self.ty(annotations)?;
+            let name = self.expect_ident()?;
+            self.expect_punct("(")?;
+            let mut args = Vec::new();
+            while !self.eat_punct(")") {
+                args.push(self.argument()?);
+                if !self.is_punct(")") {
+                    self.expect_punct(",")?;
+                }
+            }
+            let id = if self.eat_punct("=") {
+                match self.next() {
+                    TokenKind::Int(id) => Some(parse_int(&id).ok_or_else(|| ParseError {
+                        line,
+                        message: format!("invalid transaction id {}", id),
+                    })?),
+                    _ => return self.error("expected a transaction id"),
+                }
+            } else {
+                None
+            };
+            self.expect_punct(";")?;
+            interface.methods.push(Method { doc, oneway, ret, name, args, id, line });
+        }
+        Ok(interface)
+    }
+
+    fn argument(&mut self) -> Result<Argument, ParseError> {
+        let mut annotations = self.annotations()?;
+        let direction = if self.eat_keyword("in") {
+            Direction::In
+        } else if self.eat_keyword("out") {
+            Direction::Out
+        } else if self.eat_keyword("inout") {
+            Direction::InOut
+        } else {
+            Direction::In
+        };
+        annotations.extend(self.annotations()?);
+        let ty = self.ty(annotations)?;
+        let name = self.expect_ident()?;
+        Ok(Argument { direction, ty, name })
+    }
+
+    fn constant(
+        &mut self,
+        doc: Option<String>,
+        annotations: Vec<Annotation>,
+    ) -> Result<Constant, ParseError> {
+        let line = self.line();
+        let ty = self.ty(annotations)?;
+        let name = self.expect_ident()?;
+        self.expect_punct("=")?;
+        let value = self.expr()?;
+        self.expect_punct(";")?;
+        Ok(Constant { doc, ty, name, value, line })
+    }
+
+    fn parcelable(&mut self, header: DeclHeader) -> Result<Parcelable, ParseError> {
+        let mut parcelable = Parcelable { header, fields: Vec::new(), constants: Vec::new() };
+        self.expect_punct("{")?;
+        while !self.eat_punct("}") {
+            let doc = self.doc();
+            let annotations = self.annotations()?;
+            let doc = doc.or_else(|| self.doc());
+            self.check_not_nested()?;
+            if self.eat_keyword("const") {
+                parcelable.constants.push(self.constant(doc, annotations)?);
+                continue;
+            }
+            let line = self.line();
+            let ty = self.ty(annotations)?;
+            let name = self.expect_ident()?;
+            let default = if self.eat_punct("=") { Some(self.expr()?) } else { None };
+            self.expect_punct(";")?;
+            parcelable.fields.push(Field { doc, ty, name, default, line });
+        }
+        Ok(parcelable)
+    }
+
+    fn enumeration(&mut self, header: DeclHeader) -> Result<Enum, ParseError> {
+        let mut enumerators = Vec::new();
+        self.expect_punct("{")?;
+        while !self.eat_punct("}") {
+            let doc = self.doc();
+            self.annotations()?;
+            let line = self.line();
+            let name = self.expect_ident()?;
+            let value = if self.eat_punct("=") { Some(self.expr()?) } else { None };
+            enumerators.push(Enumerator { doc, name, value, line });
+            if !self.is_punct("}") {
+                self.expect_punct(",")?;
+            }
+        }
+        Ok(Enum { header, enumerators })
+    }
+
+    fn ty(&mut self, mut annotations: Vec<Annotation>) -> Result<Type, ParseError> {
+        annotations.extend(self.annotations()?);
+        let line = self.line();
+        let name = self.qualified_name()?;
+        let mut args = Vec::new();
+        if self.eat_punct("<") {
+            loop {
+                args.push(self.ty(Vec::new())?);
+                if !self.eat_punct(",") {
+                    break;
+                }
+            }
+            self.expect_close_angle()?;
+        }
+        let mut array = None;
+        let mut sizes = Vec::new();
+        while self.eat_punct("[") {
+            if self.eat_punct("]") {
+                if array.is_some() || !sizes.is_empty() {
+                    return self.error("arrays of dynamic arrays are not supported");
+                }
+                array = Some(Array::Dynamic);
+            } else {
+                sizes.push(self.expr()?);
+                self.expect_punct("]")?;
+            }
+        }
+        if !sizes.is_empty() {
+            if array.is_some() {
+                return self.error("arrays of dynamic arrays are not supported");
+            }
+            array = Some(Array::Fixed(sizes));
+        }
+        let nullable = annotations.iter().any(|annotation| annotation.name == "nullable");
+        Ok(Type { name, args, array, nullable, line })
+    }
+
+    fn expr(&mut self) -> Result<Expr, ParseError> {
+        let condition = self.binary(0)?;
+        if self.eat_punct("?") {
+            let then = self.expr()?;
+            self.expect_punct(":")?;
+            let otherwise = self.expr()?;
+            return Ok(Expr::Ternary(Box::new(condition), Box::new(then), Box::new(otherwise)));
+        }
+        Ok(condition)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, ParseError> {
+        let Some(operators) = BINARY_OPERATORS.get(level) else { return self.unary() };
+        let mut lhs = self.binary(level + 1)?;
+        loop {
+            let operator = match self.peek() {
+                TokenKind::Punct(punct) if operators.contains(punct) => *punct,
+                _ => return Ok(lhs),
+            };
+            self.next();
+            let rhs = self.binary(level + 1)?;
+            lhs = Expr::Binary(operator, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        for operator in ["-", "+", "~", "!"] {
+            if self.eat_punct(operator) {
+                return Ok(Expr::Unary(operator, Box::new(self.unary()?)));
+            }
+        }
+        match self.peek().clone() {
+            TokenKind::Int(literal) => {
+                self.next();
+                Ok(Expr::Int(literal))
+            }
+            TokenKind::Float(literal) => {
+                self.next();
+                Ok(Expr::Float(literal))
+            }
+            TokenKind::Str(literal) => {
+                self.next();
+                Ok(Expr::Str(literal))
+            }
+            TokenKind::Char(literal) => {
+                self.next();
+                Ok(Expr::Char(literal))
+            }
+            TokenKind::Ident(ident) if ident == "true" || ident == "false" => {
+                self.next();
+                Ok(Expr::Bool(ident == "true"))
+            }
+            TokenKind::Ident(_) => Ok(Expr::Name(self.qualified_name()?)),
+            TokenKind::Punct("(") => {
+                self.next();
+                let expr = self.expr()?;
+                self.expect_punct(")")?;
+                Ok(expr)
+            }
+            TokenKind::Punct("{") => {
+                self.next();
+                let mut elements = Vec::new();
+                while !self.eat_punct("}") {
+                    elements.push(self.expr()?);
+                    if !self.is_punct("}") {
+                        self.expect_punct(",")?;
+                    }
+                }
+                Ok(Expr::Array(elements))
+            }
+            _ => self.unexpected("an expression"),
+        }
+    }
+}
+
+/// Parse an integer literal, in decimal or hexadecimal.
+///
+/// Hexadecimal literals are two's complement, so `0xffffffff` is `-1` for
+/// `int` constants; this returns them as unsigned, and leaves truncation to
+/// the type they are used for.
+pub(crate) fn parse_int(literal: &str) -> Option<i64> {
+    match literal.strip_prefix("0x").or_else(|| literal.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok().map(|value| value as i64),
+        None => literal.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interface() {
+        let document = parse(
+            r#"
+            package com.example;
+
+            import com.example.Other;
+
+            /** An interface. */
+            @VintfStability
+            interface IFoo {
+                const int ANSWER = 6 * 7;
+                /** Does things. */
+                @nullable String[] foo(in int[] a, out List<String> b, inout @nullable Other c);
+                oneway void bar(in Map<String, List<List<String>>> d) = 10;
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(document.package, ["com", "example"]);
+        assert_eq!(document.imports, [["com", "example", "Other"]]);
+        let Decl::Interface(interface) = &document.decls[0] else { panic!() };
+        assert_eq!(interface.header.doc.as_deref(), Some("An interface."));
+        assert!(interface.header.annotation("VintfStability").is_some());
+        assert_eq!(interface.constants[0].name, "ANSWER");
+
+        let foo = &interface.methods[0];
+        assert_eq!(foo.doc.as_deref(), Some("Does things."));
+        assert!(foo.ret.nullable);
+        assert!(matches!(foo.ret.array, Some(Array::Dynamic)));
+        let directions: Vec<_> = foo.args.iter().map(|arg| arg.direction).collect();
+        assert_eq!(directions, [Direction::In, Direction::Out, Direction::InOut]);
+        assert!(foo.args[2].ty.nullable);
+
+        let bar = &interface.methods[1];
+        assert!(bar.oneway);
+        assert_eq!(bar.id, Some(10));
+        assert_eq!(bar.args[0].ty.args[1].args[0].name, ["List"]);
+    }
+
+    #[test]
+    fn parcelable_enum_union() {
+        let document = parse(
+            r#"
+            parcelable Point { int x = 1 << 4; float y = -1.5f; }
+            @Backing(type="byte") enum Color { RED, GREEN = RED + 2, }
+            union Shape { Point point; int[3] triangle; }
+            parcelable Native rust_type "crate::Native";
+            "#,
+        )
+        .unwrap();
+        assert!(document.package.is_empty());
+        let Decl::Parcelable(point) = &document.decls[0] else { panic!() };
+        assert_eq!(point.fields.len(), 2);
+        let Decl::Enum(color) = &document.decls[1] else { panic!() };
+        assert_eq!(color.enumerators.len(), 2);
+        assert!(color.header.annotation("Backing").is_some());
+        let Decl::Union(shape) = &document.decls[2] else { panic!() };
+        assert!(matches!(shape.fields[1].ty.array, Some(Array::Fixed(_))));
+        let Decl::ForeignParcelable(_, rust_type) = &document.decls[3] else { panic!() };
+        assert_eq!(rust_type.as_deref(), Some("crate::Native"));
+    }
+
+    #[test]
+    fn errors() {
+        let error = parse("interface IFoo {\n  void foo(int a\n}").unwrap_err();
+        assert_eq!(error.line, 3);
+        assert_eq!(error.message, "expected `,`, found `}`");
+        let error = parse("interface IFoo { parcelable Bar {} }").unwrap_err();
+        assert_eq!(error.message, "nested types are not supported");
+        assert!(parse("package foo;").is_err());
+    }
+}
@@ -0,0 +1,788 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! `#[interface]`, following the code the AIDL Rust backend generates.
+
+use crate::parse_stability;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::visit_mut::{self, VisitMut};
+use syn::{
+    parse_quote, Attribute, FnArg, GenericArgument, Ident, ItemTrait, LitStr, Pat, Path,
+    PathArguments, ReturnType, Token, TraitItem, TraitItemFn, Type, TypeParamBound, TypeReference,
+};
+
+/// The arguments of `#[interface(...)]`.
+pub(crate) struct InterfaceArgs {
+    descriptor: LitStr,
+    stability: Option<Ident>,
+    krate: Path,
+}
+
+impl Parse for InterfaceArgs {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let mut args = InterfaceArgs {
+            descriptor: input.parse()?,
+            stability: None,
+            krate: parse_quote!(::binder),
+        };
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            // `crate` is a keyword.
+            let key = input.call(Ident::parse_any)?;
+            input.parse::<Token![=]>()?;
+            let value: LitStr = input.parse()?;
+            if key == "stability" {
+                args.stability = Some(parse_stability(&value)?);
+            } else if key == "crate" {
+                args.krate = value.parse()?;
+            } else {
+                return Err(syn::Error::new(key.span(), "unknown interface argument"));
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// How an argument is passed, and so how it travels in parcels.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    /// By value: written, and read into a local on the service side.
+    Value,
+    /// As `&T`: written, and read into an owned local which is borrowed.
+    Ref,
+    /// As `Option<&T>`, for nullable arguments; `deref` if `T` is unsized.
+    OptionRef { deref: bool },
+    /// As `&mut T`: written, and read back from the reply.
+    InOut,
+    /// As `#[out] &mut T`: starts out as `T::default()` on the service side,
+    /// and is read back from the reply. Arrays are resized to the length the
+    /// proxy has, which is all that is written.
+    Out,
+}
+
+struct Arg {
+    /// The name of the argument in generated code, which can't clash with
+    /// locals of the generated code.
+    name: Ident,
+    ty: Type,
+    /// The type of the local the service side reads the argument into.
+    owned_ty: Type,
+    direction: Direction,
+}
+
+impl Arg {
+    fn is_mut(&self) -> bool {
+        matches!(self.direction, Direction::InOut | Direction::Out)
+    }
+
+    /// Write the argument to `_aidl_data`, on the proxy side.
+    fn write(&self) -> Option<TokenStream> {
+        let name = &self.name;
+        match self.direction {
+            Direction::Value | Direction::OptionRef { .. } => {
+                Some(quote! { _aidl_data.write(&#name)?; })
+            }
+            Direction::Ref => Some(quote! { _aidl_data.write(#name)?; }),
+            Direction::InOut => Some(quote! { _aidl_data.write(&*#name)?; }),
+            Direction::Out => match OutArray::of(&self.owned_ty)? {
+                OutArray::Vec => Some(quote! { _aidl_data.write_slice_size(Some(&**#name))?; }),
+                OutArray::Nullable => {
+                    Some(quote! { _aidl_data.write_slice_size(#name.as_deref())?; })
+                }
+            },
+        }
+    }
+
+    /// Declare the local holding the argument, on the service side.
+    fn read(&self) -> TokenStream {
+        let name = &self.name;
+        let owned_ty = &self.owned_ty;
+        match self.direction {
+            Direction::InOut => quote! { let mut #name: #owned_ty = _aidl_data.read()?; },
+            Direction::Out => {
+                let resize = match OutArray::of(owned_ty) {
+                    Some(OutArray::Vec) => quote! { _aidl_data.resize_out_vec(&mut #name)?; },
+                    Some(OutArray::Nullable) => {
+                        quote! { _aidl_data.resize_nullable_out_vec(&mut #name)?; }
+                    }
+                    None => quote!(),
+                };
+                quote! {
+                    let mut #name: #owned_ty = ::std::default::Default::default();
+                    #resize
+                }
+            }
+            _ => quote! { let #name: #owned_ty = _aidl_data.read()?; },
+        }
+    }
+
+    /// Pass the local holding the argument to the service.
+    fn pass(&self) -> TokenStream {
+        let name = &self.name;
+        match self.direction {
+            Direction::Value => quote!(#name),
+            Direction::Ref => quote!(&#name),
+            Direction::OptionRef { deref: true } => quote!(#name.as_deref()),
+            Direction::OptionRef { deref: false } => quote!(#name.as_ref()),
+            Direction::InOut | Direction::Out => quote!(&mut #name),
+        }
+    }
+}
+
+/// An out argument which is an array, whose length the proxy writes.
+enum OutArray {
+    /// `Vec<T>`
+    Vec,
+    /// `Option<Vec<T>>`
+    Nullable,
+}
+
+impl OutArray {
+    fn of(ty: &Type) -> Option<Self> {
+        if type_argument(ty, "Vec").is_some() {
+            Some(OutArray::Vec)
+        } else if type_argument(type_argument(ty, "Option")?, "Vec").is_some() {
+            Some(OutArray::Nullable)
+        } else {
+            None
+        }
+    }
+}
+
+struct Method {
+    attrs: Vec<Attribute>,
+    name: Ident,
+    code: Ident,
+    oneway: bool,
+    args: Vec<Arg>,
+    output: ReturnType,
+    /// `T` of the `binder::Result<T>` the method returns.
+    result: Type,
+}
+
+impl Method {
+    fn parse(method: &mut TraitItemFn) -> syn::Result<Self> {
+        let sig = &mut method.sig;
+        if !sig.generics.params.is_empty() || sig.generics.where_clause.is_some() {
+            return Err(syn::Error::new_spanned(
+                &sig.generics,
+                "binder interface methods can't be generic",
+            ));
+        }
+        if let Some(asyncness) = &sig.asyncness {
+            return Err(syn::Error::new_spanned(
+                asyncness,
+                "binder interface methods can't be async, see the generated async traits",
+            ));
+        }
+        match sig.inputs.first() {
+            Some(FnArg::Receiver(receiver))
+                if receiver.reference.is_some() && receiver.mutability.is_none() => {}
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &*sig,
+                    "binder interface methods must take `&self`",
+                ))
+            }
+        }
+
+        let mut oneway = false;
+        method.attrs.retain(|attr| {
+            let is_oneway = attr.path().is_ident("oneway");
+            oneway |= is_oneway;
+            !is_oneway
+        });
+
+        let mut args = Vec::new();
+        for input in sig.inputs.iter_mut().skip(1) {
+            let FnArg::Typed(input) = input else { unreachable!() };
+            let Pat::Ident(pat) = &*input.pat else {
+                return Err(syn::Error::new_spanned(&input.pat, "expected an argument name"));
+            };
+            let mut out = false;
+            input.attrs.retain(|attr| {
+                let is_out = attr.path().is_ident("out");
+                out |= is_out;
+                !is_out
+            });
+            let ty = (*input.ty).clone();
+            let (owned_ty, direction) = match &ty {
+                Type::Reference(reference) if reference.mutability.is_some() => {
+                    let direction = if out { Direction::Out } else { Direction::InOut };
+                    ((*reference.elem).clone(), direction)
+                }
+                _ if out => {
+                    return Err(syn::Error::new_spanned(&ty, "out arguments must be `&mut`"));
+                }
+                Type::Reference(reference) => (owned_type(&reference.elem), Direction::Ref),
+                _ => match option_ref(&ty) {
+                    Some(inner) => {
+                        let owned_inner = owned_type(inner);
+                        let deref = is_unsized(inner);
+                        (
+                            parse_quote!(::std::option::Option<#owned_inner>),
+                            Direction::OptionRef { deref },
+                        )
+                    }
+                    None => (ty.clone(), Direction::Value),
+                },
+            };
+            args.push(Arg { name: format_ident!("_arg_{}", pat.ident), ty, owned_ty, direction });
+        }
+
+        let result = match &sig.output {
+            ReturnType::Type(_, ty) => result_type(ty),
+            ReturnType::Default => None,
+        };
+        let Some(result) = result else {
+            return Err(syn::Error::new_spanned(
+                &sig.output,
+                "binder interface methods must return `binder::Result`",
+            ));
+        };
+        if oneway {
+            if !is_unit(&result) {
+                return Err(syn::Error::new_spanned(
+                    &sig.output,
+                    "oneway methods must return `binder::Result<()>`",
+                ));
+            }
+            if let Some(arg) = args.iter().find(|arg| arg.is_mut()) {
+                return Err(syn::Error::new_spanned(
+                    &arg.ty,
+                    "oneway methods can't have inout or out arguments",
+                ));
+            }
+        }
+
+        Ok(Method {
+            attrs: method.attrs.clone(),
+            name: sig.ident.clone(),
+            code: format_ident!("TRANSACTION_{}", sig.ident),
+            oneway,
+            args,
+            output: sig.output.clone(),
+            result,
+        })
+    }
+
+    /// The arguments, as in the declaration of the sync method.
+    fn params(&self) -> TokenStream {
+        let params = self.args.iter().map(|Arg { name, ty, .. }| quote!(#name: #ty));
+        quote!(#(#params),*)
+    }
+
+    /// The arguments, as in the declaration of an async method returning a
+    /// future with lifetime `'a`.
+    fn async_params(&self) -> TokenStream {
+        let params = self.args.iter().map(|Arg { name, ty, .. }| {
+            let mut ty = ty.clone();
+            AsyncLifetime.visit_type_mut(&mut ty);
+            quote!(#name: #ty)
+        });
+        quote!(#(#params),*)
+    }
+
+    fn arg_names(&self) -> impl Iterator<Item = &Ident> {
+        self.args.iter().map(|arg| &arg.name)
+    }
+
+    /// The `&mut` arguments, which are read back from the reply.
+    fn mut_args(&self) -> impl Iterator<Item = &Arg> {
+        self.args.iter().filter(|arg| arg.is_mut())
+    }
+}
+
+/// Give all references the lifetime `'a`.
+struct AsyncLifetime;
+
+impl VisitMut for AsyncLifetime {
+    fn visit_type_reference_mut(&mut self, reference: &mut TypeReference) {
+        reference.lifetime = Some(parse_quote!('a));
+        visit_mut::visit_type_reference_mut(self, reference);
+    }
+}
+
+/// The type a borrowed `ty` is read into: `String` for `str`, `Vec<T>` for
+/// `[T]`, and `ty` itself otherwise.
+fn owned_type(ty: &Type) -> Type {
+    match ty {
+        Type::Slice(slice) => {
+            let elem = &slice.elem;
+            parse_quote!(::std::vec::Vec<#elem>)
+        }
+        Type::Path(path) if path.qself.is_none() && path.path.is_ident("str") => {
+            parse_quote!(::std::string::String)
+        }
+        _ => ty.clone(),
+    }
+}
+
+/// Whether `ty` is `str` or a slice, whose options are passed with `as_deref`.
+fn is_unsized(ty: &Type) -> bool {
+    match ty {
+        Type::Slice(_) => true,
+        Type::Path(path) => path.qself.is_none() && path.path.is_ident("str"),
+        _ => false,
+    }
+}
+
+fn is_unit(ty: &Type) -> bool {
+    matches!(ty, Type::Tuple(tuple) if tuple.elems.is_empty())
+}
+
+/// The only type argument of the last segment of `ty`, if it is called
+/// `name`.
+fn type_argument<'a>(ty: &'a Type, name: &str) -> Option<&'a Type> {
+    let Type::Path(path) = ty else { return None };
+    let segment = path.path.segments.last()?;
+    if segment.ident != name {
+        return None;
+    }
+    let PathArguments::AngleBracketed(arguments) = &segment.arguments else { return None };
+    match arguments.args.first()? {
+        GenericArgument::Type(ty) => Some(ty),
+        _ => None,
+    }
+}
+
+/// `T` for `Option<&T>`.
+fn option_ref(ty: &Type) -> Option<&Type> {
+    match type_argument(ty, "Option")? {
+        Type::Reference(reference) if reference.mutability.is_none() => Some(&reference.elem),
+        _ => None,
+    }
+}
+
+/// `T` for `Result<T>` or `Result<T, E>`.
+fn result_type(ty: &Type) -> Option<Type> {
+    type_argument(ty, "Result").cloned()
+}
+
+/// The name of the trait without its leading `I`, as in `BnFoo` for `IFoo`.
+fn base_name(name: &Ident) -> String {
+    let name = name.to_string();
+    match name.strip_prefix('I') {
+        Some(base) if base.starts_with(|c: char| c.is_ascii_uppercase()) => base.to_owned(),
+        _ => name,
+    }
+}
+
+pub(crate) fn expand_interface(
+    args: InterfaceArgs,
+    mut item: ItemTrait,
+) -> syn::Result<TokenStream> {
+    let InterfaceArgs { descriptor, stability, krate } = args;
+    if !item.generics.params.is_empty() || item.generics.where_clause.is_some() {
+        return Err(syn::Error::new_spanned(&item.generics, "binder interfaces can't be generic"));
+    }
+
+    let mut methods = Vec::new();
+    for trait_item in &mut item.items {
+        let TraitItem::Fn(method) = trait_item else {
+            return Err(syn::Error::new_spanned(
+                trait_item,
+                "binder interfaces can only have methods",
+            ));
+        };
+        methods.push(Method::parse(method)?);
+    }
+
+    // Like AIDL interfaces, require `Interface` and `Send`, unless the trait
+    // already does.
+    let has_interface = item.supertraits.iter().any(|bound| {
+        matches!(bound, TypeParamBound::Trait(bound)
+            if bound.path.segments.last().map_or(false, |segment| segment.ident == "Interface"))
+    });
+    if !has_interface {
+        item.supertraits.push(parse_quote!(#krate::Interface));
+    }
+    item.supertraits.push(parse_quote!(::std::marker::Send));
+    item.items.insert(
+        0,
+        parse_quote! {
+            /// The binder descriptor of this interface.
+            fn get_descriptor() -> &'static str
+            where
+                Self: Sized,
+            {
+                #descriptor
+            }
+        },
+    );
+
+    let vis = &item.vis;
+    let interface = &item.ident;
+    let base = base_name(interface);
+    let native = format_ident!("Bn{}", base);
+    let proxy = format_ident!("Bp{}", base);
+    let async_interface = format_ident!("{}Async", interface);
+    let async_server = format_ident!("{}AsyncServer", interface);
+    // Only VINTF interfaces may be called from the other partition.
+    let private_local = match &stability {
+        Some(stability) if stability == "Vintf" => quote!(0),
+        _ => quote!(#krate::binder_impl::FLAG_PRIVATE_LOCAL),
+    };
+    let stability = match stability {
+        Some(stability) => quote!(#krate::binder_impl::Stability::#stability),
+        None => quote!(#krate::binder_impl::Stability::default()),
+    };
+
+    let codes = methods.iter().enumerate().map(|(index, Method { code, name, .. })| {
+        let index = index as u32;
+        let doc = format!("Transaction code of [`{}::{}`].", interface, name);
+        quote! {
+            #[doc = #doc]
+            pub const #code: #krate::binder_impl::TransactionCode =
+                #krate::binder_impl::FIRST_CALL_TRANSACTION + #index;
+        }
+    });
+
+    let dispatch_arms = methods.iter().map(|method| {
+        let Method { name, code, .. } = method;
+        let reads = method.args.iter().map(Arg::read);
+        let passes = method.args.iter().map(Arg::pass);
+        let call = quote! {
+            #(#reads)*
+            let _aidl_return = _aidl_service.#name(#(#passes),*);
+        };
+        if method.oneway {
+            return quote! {
+                Self::#code => {
+                    #call
+                    Ok(())
+                }
+            };
+        }
+        let write_return = if is_unit(&method.result) {
+            None
+        } else {
+            Some(quote! { _aidl_reply.write(_aidl_return)?; })
+        };
+        let write_mut_args = method.mut_args().map(|Arg { name, .. }| {
+            quote! { _aidl_reply.write(&#name)?; }
+        });
+        quote! {
+            Self::#code => {
+                #call
+                match &_aidl_return {
+                    Ok(_aidl_return) => {
+                        _aidl_reply.write(&#krate::Status::from(#krate::StatusCode::OK))?;
+                        #write_return
+                        #(#write_mut_args)*
+                    }
+                    Err(_aidl_status) => _aidl_reply.write(_aidl_status)?,
+                }
+                Ok(())
+            }
+        }
+    });
+
+    let proxy_helpers = methods.iter().map(|method| {
+        let Method { name, result, .. } = method;
+        let build_parcel = format_ident!("build_parcel_{}", name);
+        let read_response = format_ident!("read_response_{}", name);
+        let params = method.params();
+        let writes = method.args.iter().filter_map(Arg::write);
+        let mut_params = method.mut_args().map(|Arg { name, ty, .. }| quote!(#name: #ty));
+        let response = if method.oneway {
+            quote! {
+                _aidl_reply?;
+                Ok(())
+            }
+        } else {
+            let read_mut_args = method.mut_args().map(|Arg { name, .. }| {
+                quote! { _aidl_reply.read_onto(#name)?; }
+            });
+            let (read_return, ret) = if is_unit(result) {
+                (None, quote!(()))
+            } else {
+                (
+                    Some(quote! { let _aidl_return: #result = _aidl_reply.read()?; }),
+                    quote!(_aidl_return),
+                )
+            };
+            quote! {
+                let _aidl_reply = _aidl_reply?;
+                let _aidl_status: #krate::Status = _aidl_reply.read()?;
+                if !_aidl_status.is_ok() {
+                    return Err(_aidl_status);
+                }
+                #read_return
+                #(#read_mut_args)*
+                Ok(#ret)
+            }
+        };
+        quote! {
+            #[allow(unused_mut)]
+            fn #build_parcel(&self, #params) -> #krate::Result<#krate::binder_impl::Parcel> {
+                let mut _aidl_data = self.binder.prepare_transact()?;
+                #(#writes)*
+                Ok(_aidl_data)
+            }
+
+            fn #read_response(
+                &self,
+                #(#mut_params,)*
+                _aidl_reply: ::std::result::Result<
+                    #krate::binder_impl::Parcel,
+                    #krate::StatusCode,
+                >,
+            ) -> #krate::Result<#result> {
+                #response
+            }
+        }
+    });
+
+    let flags = |method: &Method| {
+        if method.oneway {
+            quote!(#krate::binder_impl::FLAG_ONEWAY | #private_local)
+        } else {
+            private_local.clone()
+        }
+    };
+
+    let proxy_methods = methods.iter().map(|method| {
+        let Method { name, code, output, .. } = method;
+        let build_parcel = format_ident!("build_parcel_{}", name);
+        let read_response = format_ident!("read_response_{}", name);
+        let params = method.params();
+        let arg_names = method.arg_names();
+        let mut_args = method.mut_args().map(|arg| &arg.name);
+        let flags = flags(method);
+        quote! {
+            fn #name(&self, #params) #output {
+                let _aidl_data = self.#build_parcel(#(#arg_names),*)?;
+                let _aidl_reply =
+                    self.binder.submit_transact(#native::#code, _aidl_data, #flags);
+                self.#read_response(#(#mut_args,)* _aidl_reply)
+            }
+        }
+    });
+
+    let async_proxy_methods = methods.iter().map(|method| {
+        let Method { name, code, result, .. } = method;
+        let build_parcel = format_ident!("build_parcel_{}", name);
+        let read_response = format_ident!("read_response_{}", name);
+        let params = method.async_params();
+        let arg_names = method.arg_names();
+        let mut_args = method.mut_args().map(|arg| &arg.name).collect::<Vec<_>>();
+        let flags = flags(method);
+        let submit = if method.oneway {
+            // Oneway transactions don't block, so don't need another thread.
+            quote! {
+                let _aidl_reply = self.binder.submit_transact(#native::#code, _aidl_data, #flags);
+                Box::pin(::std::future::ready(self.#read_response(_aidl_reply)))
+            }
+        } else {
+            quote! {
+                let binder = self.binder.clone();
+                P::spawn(
+                    move || binder.submit_transact(#native::#code, _aidl_data, #flags),
+                    move |_aidl_reply| async move {
+                        self.#read_response(#(#mut_args,)* _aidl_reply)
+                    },
+                )
+            }
+        };
+        quote! {
+            fn #name<'a>(&'a self, #params) -> #krate::BoxFuture<'a, #krate::Result<#result>> {
+                let _aidl_data = match self.#build_parcel(#(#arg_names),*) {
+                    Ok(_aidl_data) => _aidl_data,
+                    Err(err) => return Box::pin(::std::future::ready(Err(err))),
+                };
+                #submit
+            }
+        }
+    });
+
+    let forwarding_methods = methods.iter().map(|method| {
+        let Method { name, output, .. } = method;
+        let params = method.params();
+        let arg_names = method.arg_names();
+        quote! {
+            fn #name(&self, #params) #output {
+                self.0.#name(#(#arg_names),*)
+            }
+        }
+    });
+
+    let blocking_methods = methods.iter().map(|method| {
+        let Method { name, output, .. } = method;
+        let params = method.params();
+        let arg_names = method.arg_names();
+        quote! {
+            fn #name(&self, #params) #output {
+                self._rt.block_on(self._inner.#name(#(#arg_names),*))
+            }
+        }
+    });
+
+    let async_declarations = methods.iter().map(|method| {
+        let Method { attrs, name, result, .. } = method;
+        let params = method.async_params();
+        quote! {
+            #(#attrs)*
+            fn #name<'a>(&'a self, #params) -> #krate::BoxFuture<'a, #krate::Result<#result>>;
+        }
+    });
+
+    let async_server_declarations = methods.iter().map(|method| {
+        let Method { attrs, name, result, .. } = method;
+        let params = method.params();
+        quote! {
+            #(#attrs)*
+            async fn #name(&self, #params) -> #krate::Result<#result>;
+        }
+    });
+
+    let async_doc = format!("The async client side of [`{}`].", interface);
+    let async_server_doc = format!("[`{}`], implemented by async services.", interface);
+
+    Ok(quote! {
+        #item
+
+        #[doc = #async_doc]
+        #vis trait #async_interface<P>: #krate::Interface + ::std::marker::Send {
+            /// The binder descriptor of this interface.
+            fn get_descriptor() -> &'static str
+            where
+                Self: Sized,
+            {
+                #descriptor
+            }
+
+            #(#async_declarations)*
+        }
+
+        #[doc = #async_server_doc]
+        #[#krate::async_trait]
+        #vis trait #async_server: #krate::Interface + ::std::marker::Send {
+            /// The binder descriptor of this interface.
+            fn get_descriptor() -> &'static str
+            where
+                Self: Sized,
+            {
+                #descriptor
+            }
+
+            #(#async_server_declarations)*
+        }
+
+        #krate::declare_binder_interface! {
+            #interface[#descriptor] {
+                native: #native(#native::dispatch),
+                proxy: #proxy,
+                async: #async_interface,
+                stability: #stability,
+            }
+        }
+
+        #[allow(non_upper_case_globals)]
+        impl #native {
+            #(#codes)*
+
+            /// Create a new binder service out of an async service, which
+            /// handles each transaction by blocking on `rt`.
+            pub fn new_async_binder<T, R>(
+                inner: T,
+                rt: R,
+                features: #krate::BinderFeatures,
+            ) -> #krate::Strong<dyn #interface>
+            where
+                T: #async_server + #krate::Interface + ::std::marker::Send + ::std::marker::Sync + 'static,
+                R: #krate::binder_impl::BinderAsyncRuntime + ::std::marker::Send + ::std::marker::Sync + 'static,
+            {
+                struct Wrapper<T, R> {
+                    _inner: T,
+                    _rt: R,
+                }
+
+                impl<T, R> #krate::Interface for Wrapper<T, R>
+                where
+                    T: #krate::Interface,
+                    R: ::std::marker::Send + ::std::marker::Sync,
+                {
+                    fn as_binder(&self) -> #krate::SpIBinder {
+                        self._inner.as_binder()
+                    }
+
+                    fn dump(
+                        &self,
+                        file: &::std::fs::File,
+                        args: &[&::std::ffi::CStr],
+                    ) -> ::std::result::Result<(), #krate::StatusCode> {
+                        self._inner.dump(file, args)
+                    }
+
+                    fn handle_shell_command(
+                        &self,
+                        stdin: &::std::fs::File,
+                        stdout: &::std::fs::File,
+                        stderr: &::std::fs::File,
+                        args: &[&::std::ffi::CStr],
+                    ) -> ::std::result::Result<(), #krate::StatusCode> {
+                        self._inner.handle_shell_command(stdin, stdout, stderr, args)
+                    }
+                }
+
+                impl<T, R> #interface for Wrapper<T, R>
+                where
+                    T: #async_server + ::std::marker::Send + ::std::marker::Sync + 'static,
+                    R: #krate::binder_impl::BinderAsyncRuntime + ::std::marker::Send + ::std::marker::Sync + 'static,
+                {
+                    #(#blocking_methods)*
+                }
+
+                Self::new_binder(Wrapper { _inner: inner, _rt: rt }, features)
+            }
+
+            fn dispatch(
+                _aidl_service: &dyn #interface,
+                _aidl_code: #krate::binder_impl::TransactionCode,
+                _aidl_data: &#krate::binder_impl::BorrowedParcel<'_>,
+                _aidl_reply: &mut #krate::binder_impl::BorrowedParcel<'_>,
+            ) -> ::std::result::Result<(), #krate::StatusCode> {
+                match _aidl_code {
+                    #(#dispatch_arms)*
+                    _ => Err(#krate::StatusCode::UNKNOWN_TRANSACTION),
+                }
+            }
+        }
+
+        impl #proxy {
+            #(#proxy_helpers)*
+        }
+
+        impl #interface for #proxy {
+            #(#proxy_methods)*
+        }
+
+        impl<P: #krate::BinderAsyncPool> #async_interface<P> for #proxy {
+            #(#async_proxy_methods)*
+        }
+
+        impl #interface for #krate::binder_impl::Binder<#native> {
+            #(#forwarding_methods)*
+        }
+    })
+}
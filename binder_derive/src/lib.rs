@@ -0,0 +1,150 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Macros for binder parcelables and interfaces written in Rust, without AIDL.
+//!
+//! # Parcelables
+//!
+//! `#[derive(Parcelable)]` writes the fields of a struct in declaration order,
+//! prefixed by their total size, the same way AIDL-generated parcelables do.
+//! A reader skips any trailing fields it does not know about, and leaves the
+//! fields that an older writer did not send at their current value, or at the
+//! value given with `#[parcel(default = ...)]`.
+//!
+//! ```text
+//! use binder::binder_impl::{Deserialize, Serialize};
+//! use binder::Parcelable;
+//!
+//! #[derive(Debug, Default, Parcelable, Serialize, Deserialize)]
+//! #[parcel(descriptor = "com.example.Point", stability = "vintf")]
+//! struct Point {
+//!     x: i32,
+//!     y: i32,
+//!     // Added later on; older writers don't send it.
+//!     #[parcel(default = 1)]
+//!     z: i32,
+//!     // Never sent, always `Default::default()` when read.
+//!     #[parcel(skip)]
+//!     cached_norm: Option<f64>,
+//! }
+//! ```
+//!
+//! Container attributes:
+//! - `descriptor = "..."`: the [`ParcelableMetadata`] descriptor. Defaults to
+//!   the Rust path of the type.
+//! - `stability = "local" | "vendor" | "system" | "vintf"`: the stability
+//!   reported by [`ParcelableMetadata`], `local` by default.
+//! - `crate = "..."`: the path of the binder crate, `::binder` by default.
+//!
+//! Field attributes:
+//! - `skip`: neither write nor read the field.
+//! - `default` or `default = expr`: the value of the field when the parcelable
+//!   ends before it, instead of leaving it unchanged.
+//!
+//! # Interfaces
+//!
+//! `#[interface("descriptor")]` turns a trait into a binder interface, with the
+//! same items and wire format the AIDL Rust backend generates for the same
+//! methods. For a trait `IFoo` these are:
+//! - `BnFoo` and `BpFoo`, the native and proxy types of
+//!   `declare_binder_interface!`, and `BnFoo::new_async_binder`;
+//! - `BnFoo::TRANSACTION_*`, the transaction codes, numbered from
+//!   `FIRST_CALL_TRANSACTION` in declaration order;
+//! - `IFooAsync<P>`, implemented by `BpFoo` for async clients;
+//! - `IFooAsyncServer`, an `async_trait` for async services.
+//!
+//! ```text
+//! #[binder::interface("com.example.IFoo")]
+//! pub trait IFoo {
+//!     fn greet(&self, name: &str) -> binder::Result<String>;
+//!     fn sort(&self, values: &mut Vec<i32>, #[out] count: &mut i32) -> binder::Result<()>;
+//!     #[oneway]
+//!     fn notify(&self, event: i32) -> binder::Result<()>;
+//! }
+//! ```
+//!
+//! Methods take `&self` and return `binder::Result`. Arguments are passed by
+//! value, by reference or as `Option<&T>` for AIDL `in` arguments, as `&mut T`
+//! for `inout` arguments, and as `#[out] &mut T` for `out` arguments. `#[oneway]`
+//! methods don't wait for the service, and can't have results.
+//!
+//! Arguments after the descriptor:
+//! - `stability = "..."`: the stability of the native binders, as for
+//!   parcelables. Interfaces other than `vintf` ones are only usable within
+//!   their partition, like AIDL interfaces without `@VintfStability`.
+//! - `crate = "..."`: the path of the binder crate, `::binder` by default.
+//!
+//! [`ParcelableMetadata`]: https://docs.rs/binder_ndk/latest/binder_ndk/binder_impl/trait.ParcelableMetadata.html
+
+mod interface;
+mod parcelable;
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput, Ident, ItemTrait, LitStr};
+
+/// Implement `Parcelable` and `ParcelableMetadata` for a struct.
+#[proc_macro_derive(Parcelable, attributes(parcel))]
+pub fn derive_parcelable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    parcelable::expand_parcelable(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Implement `Serialize` for a `Parcelable` struct, with
+/// `impl_serialize_for_parcelable!`.
+#[proc_macro_derive(Serialize, attributes(parcel))]
+pub fn derive_serialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    parcelable::expand_impl_macro(&input, "impl_serialize_for_parcelable")
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Implement `Deserialize` for a `Parcelable` struct, with
+/// `impl_deserialize_for_parcelable!`. The struct must implement `Default`.
+#[proc_macro_derive(Deserialize, attributes(parcel))]
+pub fn derive_deserialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    parcelable::expand_impl_macro(&input, "impl_deserialize_for_parcelable")
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Define a binder interface from a trait.
+///
+/// See the [crate documentation](crate) for what it generates.
+#[proc_macro_attribute]
+pub fn interface(args: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(args as interface::InterfaceArgs);
+    let item = parse_macro_input!(item as ItemTrait);
+    interface::expand_interface(args, item).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Parse the value of a `stability` argument into a `Stability` variant.
+fn parse_stability(stability: &LitStr) -> syn::Result<Ident> {
+    let variant = match stability.value().as_str() {
+        "local" => "Local",
+        "vendor" => "Vendor",
+        "system" => "System",
+        "vintf" => "Vintf",
+        _ => {
+            return Err(syn::Error::new(
+                stability.span(),
+                "expected one of \"local\", \"vendor\", \"system\" or \"vintf\"",
+            ))
+        }
+    };
+    Ok(Ident::new(variant, stability.span()))
+}
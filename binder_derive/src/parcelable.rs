@@ -0,0 +1,186 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! `#[derive(Parcelable)]` and friends.
+
+use crate::parse_stability;
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{Data, DeriveInput, Expr, Field, Ident, Index, LitStr, Member, Path};
+
+/// The `#[parcel(...)]` attributes of the struct itself.
+struct ContainerAttrs {
+    krate: Path,
+    descriptor: Option<LitStr>,
+    stability: Option<Ident>,
+}
+
+impl ContainerAttrs {
+    fn parse(input: &DeriveInput) -> syn::Result<Self> {
+        let mut attrs = ContainerAttrs {
+            krate: syn::parse_quote!(::binder),
+            descriptor: None,
+            stability: None,
+        };
+        for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("parcel")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("crate") {
+                    attrs.krate = meta.value()?.parse::<LitStr>()?.parse()?;
+                } else if meta.path.is_ident("descriptor") {
+                    attrs.descriptor = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("stability") {
+                    attrs.stability = Some(parse_stability(&meta.value()?.parse()?)?);
+                } else {
+                    return Err(meta.error("unknown parcel attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(attrs)
+    }
+}
+
+/// The `#[parcel(...)]` attributes of a field.
+#[derive(Default)]
+struct FieldAttrs {
+    skip: bool,
+    default: Option<Expr>,
+}
+
+impl FieldAttrs {
+    fn parse(field: &Field) -> syn::Result<Self> {
+        let mut attrs = FieldAttrs::default();
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("parcel")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("skip") {
+                    attrs.skip = true;
+                } else if meta.path.is_ident("default") {
+                    attrs.default = Some(if meta.input.peek(syn::Token![=]) {
+                        meta.value()?.parse()?
+                    } else {
+                        syn::parse_quote!(::std::default::Default::default())
+                    });
+                } else {
+                    return Err(meta.error("unknown parcel attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        if attrs.skip && attrs.default.is_some() {
+            return Err(syn::Error::new_spanned(field, "skipped fields can't have a default"));
+        }
+        Ok(attrs)
+    }
+}
+
+/// Check that the input is a struct the generated code can handle.
+fn check_struct(input: &DeriveInput) -> syn::Result<&syn::DataStruct> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "binder parcelables can only be derived for structs",
+        ));
+    };
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "binder parcelables can't be derived for generic structs",
+        ));
+    }
+    Ok(data)
+}
+
+pub(crate) fn expand_parcelable(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let data = check_struct(input)?;
+    let attrs = ContainerAttrs::parse(input)?;
+    let krate = &attrs.krate;
+    let name = &input.ident;
+
+    let mut writes = Vec::new();
+    let mut reads = Vec::new();
+    for (index, field) in data.fields.iter().enumerate() {
+        let field_attrs = FieldAttrs::parse(field)?;
+        if field_attrs.skip {
+            continue;
+        }
+        let member = match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(index)),
+        };
+        writes.push(quote! { subparcel.write(&self.#member)?; });
+        let missing =
+            field_attrs.default.map(|default| quote! { else { self.#member = #default; } });
+        reads.push(quote! {
+            if subparcel.has_more_data() {
+                self.#member = subparcel.read()?;
+            } #missing
+        });
+    }
+    // Don't leave an unused closure argument behind for empty structs.
+    let subparcel = if writes.is_empty() { quote!(_) } else { quote!(subparcel) };
+
+    let descriptor = match &attrs.descriptor {
+        Some(descriptor) => quote!(#descriptor),
+        None => quote!(concat!(module_path!(), "::", stringify!(#name))),
+    };
+    let stability = attrs.stability.as_ref().map(|stability| {
+        quote! {
+            fn get_stability(&self) -> #krate::binder_impl::Stability {
+                #krate::binder_impl::Stability::#stability
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #krate::Parcelable for #name {
+            fn write_to_parcel(
+                &self,
+                parcel: &mut #krate::binder_impl::BorrowedParcel<'_>,
+            ) -> ::std::result::Result<(), #krate::StatusCode> {
+                parcel.sized_write(|#subparcel| {
+                    #(#writes)*
+                    Ok(())
+                })
+            }
+
+            fn read_from_parcel(
+                &mut self,
+                parcel: &#krate::binder_impl::BorrowedParcel<'_>,
+            ) -> ::std::result::Result<(), #krate::StatusCode> {
+                parcel.sized_read(|#subparcel| {
+                    #(#reads)*
+                    Ok(())
+                })
+            }
+        }
+
+        impl #krate::binder_impl::ParcelableMetadata for #name {
+            fn get_descriptor() -> &'static str {
+                #descriptor
+            }
+
+            #stability
+        }
+    })
+}
+
+pub(crate) fn expand_impl_macro(input: &DeriveInput, macro_name: &str) -> syn::Result<TokenStream> {
+    check_struct(input)?;
+    let krate = ContainerAttrs::parse(input)?.krate;
+    let macro_name = Ident::new(macro_name, Span::call_site());
+    let name = &input.ident;
+    Ok(quote! { #krate::#macro_name!(#name); })
+}
@@ -67,6 +67,12 @@ fn main() {
     println!("cargo:rerun-if-changed=src/BinderBindings.hpp");
     println!("cargo:rerun-if-changed=src/symbols.txt");
 
+    // The host backend is written in Rust, so there is nothing to link
+    // against or generate bindings for.
+    if env::var_os("CARGO_FEATURE_HOST").is_some() {
+        return;
+    }
+
     build_stub().unwrap();
 
     // The bindgen::Builder is the main entry point
@@ -24,6 +24,18 @@
 #include <android/binder_stability.h>
 #include <android/binder_status.h>
 
+// binder_stability.h only declares the stability functions for the partition
+// being built for, but libbinder_ndk exports those of both.
+extern "C" {
+#if defined(__ANDROID_VENDOR__)
+__attribute__((weak)) void AIBinder_markSystemStability(AIBinder* binder);
+void AIBinder_forceDowngradeToSystemStability(AIBinder* binder);
+#else
+void AIBinder_markVendorStability(AIBinder* binder);
+void AIBinder_forceDowngradeToVendorStability(AIBinder* binder);
+#endif
+}
+
 namespace android {
 
 namespace c_interface {
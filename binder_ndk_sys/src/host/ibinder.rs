@@ -0,0 +1,1133 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! `AIBinder` and friends.
+//!
+//! Binder objects are reference counted with an `Arc`: a `*mut AIBinder`
+//! handed out by this module is always one strong count of that `Arc`, and an
+//! `AIBinder_Weak` is a `std::sync::Weak` to it.
+//!
+//! A binder is either local, hosted by the process that created it, or a
+//! proxy held by another process. Transactions on a proxy are delivered to
+//! its local binder through the loopback transport in [`super::process`].
+
+use super::manager::clients_changed;
+use super::parcel::{to_status_t, AParcel, Result};
+use super::process::{current_process, security_context, Process};
+use super::status::prune_status;
+use super::{
+    binder_flags_t, binder_status_t, pid_t, transaction_code_t, uid_t, AIBinder_Class_onCreate,
+    AIBinder_Class_onDestroy, AIBinder_Class_onTransact, AIBinder_DeathRecipient_onBinderDied,
+    AIBinder_DeathRecipient_onBinderUnlinked, AIBinder_handleShellCommand, AIBinder_onDump,
+    StatusCode, FIRST_CALL_TRANSACTION, FLAG_CLEAR_BUF, FLAG_ONEWAY, FLAG_PRIVATE_VENDOR,
+    LAST_CALL_TRANSACTION,
+};
+
+use std::ffi::{CStr, CString};
+use std::mem::ManuallyDrop;
+use std::os::raw::{c_char, c_int, c_void};
+use std::os::unix::io::{AsRawFd, BorrowedFd};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicPtr, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+const fn b_pack_chars(c1: u8, c2: u8, c3: u8, c4: u8) -> u32 {
+    (c1 as u32) << 24 | (c2 as u32) << 16 | (c3 as u32) << 8 | c4 as u32
+}
+
+pub(super) const PING_TRANSACTION: u32 = b_pack_chars(b'_', b'P', b'N', b'G');
+pub(super) const DUMP_TRANSACTION: u32 = b_pack_chars(b'_', b'D', b'M', b'P');
+pub(super) const SHELL_COMMAND_TRANSACTION: u32 = b_pack_chars(b'_', b'C', b'M', b'D');
+pub(super) const INTERFACE_TRANSACTION: u32 = b_pack_chars(b'_', b'N', b'T', b'F');
+pub(super) const EXTENSION_TRANSACTION: u32 = b_pack_chars(b'_', b'E', b'X', b'T');
+pub(super) const DEBUG_PID_TRANSACTION: u32 = b_pack_chars(b'_', b'P', b'I', b'D');
+
+/// `Parcel::kHeader` for system code, written in every interface token.
+const INTERFACE_HEADER: i32 = b_pack_chars(b'S', b'Y', b'S', b'T') as i32;
+const STRICT_MODE_PENALTY_GATHER: i32 = 1 << 31;
+const UNSET_WORK_SOURCE: i32 = -1;
+
+/// The interface `cmd` passes to receive the result of a shell command.
+const RESULT_RECEIVER_DESCRIPTOR: &CStr =
+    unsafe { CStr::from_bytes_with_nul_unchecked(b"com.android.internal.os.IResultReceiver\0") };
+
+/// Wire format version of the stability written after every binder.
+const BINDER_WIRE_FORMAT_VERSION: i32 = 1;
+
+/// Stability levels, as in libbinder's `Stability::Level`.
+pub(super) const STABILITY_UNDECLARED: i32 = 0;
+pub(super) const STABILITY_VENDOR: i32 = 0b000011;
+pub(super) const STABILITY_SYSTEM: i32 = 0b001100;
+pub(super) const STABILITY_VINTF: i32 = 0b111111;
+
+fn is_user_command(code: transaction_code_t) -> bool {
+    (FIRST_CALL_TRANSACTION..=LAST_CALL_TRANSACTION).contains(&code)
+}
+
+pub struct AIBinder_Class {
+    descriptor: CString,
+    on_create: AIBinder_Class_onCreate,
+    on_destroy: AIBinder_Class_onDestroy,
+    on_transact: AIBinder_Class_onTransact,
+    on_dump: AIBinder_onDump,
+    handle_shell_command: AIBinder_handleShellCommand,
+    write_header: bool,
+}
+
+impl AIBinder_Class {
+    pub(super) fn descriptor(&self) -> &CStr {
+        &self.descriptor
+    }
+}
+
+/// An owned strong reference to an `AIBinder`.
+#[derive(Debug)]
+pub(super) struct Sp(*mut AIBinder);
+
+// Safety: `Sp` is a strong count of an `Arc<AIBinder>`, and `AIBinder` is
+// `Send` and `Sync`.
+unsafe impl Send for Sp {}
+unsafe impl Sync for Sp {}
+
+impl Sp {
+    /// Take a new strong reference to `binder`, which must be live.
+    pub(super) unsafe fn new(binder: *mut AIBinder) -> Option<Sp> {
+        if binder.is_null() {
+            None
+        } else {
+            AIBinder_incStrong(binder);
+            Some(Sp(binder))
+        }
+    }
+
+    /// Adopt a strong reference owned by the caller.
+    pub(super) unsafe fn from_raw(binder: *mut AIBinder) -> Option<Sp> {
+        if binder.is_null() {
+            None
+        } else {
+            Some(Sp(binder))
+        }
+    }
+
+    pub(super) fn from_arc(binder: Arc<AIBinder>) -> Sp {
+        Sp(Arc::into_raw(binder) as *mut AIBinder)
+    }
+
+    pub(super) fn as_ptr(&self) -> *mut AIBinder {
+        self.0
+    }
+
+    pub(super) fn into_raw(self) -> *mut AIBinder {
+        ManuallyDrop::new(self).0
+    }
+
+    fn as_arc(&self) -> ManuallyDrop<Arc<AIBinder>> {
+        // Safety: `self` holds a strong reference, so the binder is live.
+        unsafe { borrow_arc(self.0) }
+    }
+
+    /// The local binder behind this binder: itself if it is local, or the
+    /// binder a proxy refers to.
+    pub(super) fn node(&self) -> Result<Sp> {
+        match &self.kind {
+            Kind::Local(_) => Ok(self.clone()),
+            Kind::Proxy(proxy) => proxy.node.lock().unwrap().clone().ok_or(StatusCode::DEAD_OBJECT),
+        }
+    }
+
+    /// Send a transaction to this binder, wherever it is hosted.
+    pub(super) fn transact(
+        &self,
+        code: transaction_code_t,
+        data: &AParcel,
+        flags: binder_flags_t,
+    ) -> Result<AParcel> {
+        match &self.kind {
+            Kind::Local(_) => {
+                let mut reply = AParcel::new(self.0);
+                match prune_status(self.as_arc().transact(code, data, &mut reply, flags)) {
+                    0 => Ok(reply),
+                    status => Err(super::parcel::status_code(status)),
+                }
+            }
+            Kind::Proxy(_) => self.proxy_transact(code, data, flags),
+        }
+    }
+
+    /// Equivalent of `BpBinder::transact`: deliver the transaction to the
+    /// process hosting the binder, and the reply back to this one.
+    fn proxy_transact(
+        &self,
+        code: transaction_code_t,
+        data: &AParcel,
+        flags: binder_flags_t,
+    ) -> Result<AParcel> {
+        if is_user_command(code) {
+            let required =
+                if flags & FLAG_PRIVATE_VENDOR != 0 { STABILITY_VENDOR } else { STABILITY_SYSTEM };
+            if self.stability.load(Ordering::Acquire) & required != required {
+                return Err(StatusCode::BAD_TYPE);
+            }
+        }
+        let node = self.node()?;
+        let process = node.owner.clone();
+        if !process.is_alive() {
+            return Err(StatusCode::DEAD_OBJECT);
+        }
+        let data = data.translate(node.as_ptr(), &process)?;
+        let requesting_sid = match &node.kind {
+            Kind::Local(local) => local.requesting_sid.load(Ordering::Acquire),
+            Kind::Proxy(_) => false,
+        };
+        let sid = if requesting_sid { Some(security_context()) } else { None };
+        let reply = process.dispatch_transaction(sid, || node.transact(code, &data, flags));
+        if flags & FLAG_ONEWAY != 0 {
+            // One-way transactions never report how they were handled.
+            return Ok(AParcel::new(self.0));
+        }
+        reply?.translate(self.0, &current_process())
+    }
+
+    /// Equivalent of `IBinder::getInterfaceDescriptor`.
+    fn interface_descriptor(&self) -> Result<String> {
+        let reply = self.transact(INTERFACE_TRANSACTION, &AParcel::new(self.0), 0)?;
+        reply.read_str()
+    }
+}
+
+impl Clone for Sp {
+    fn clone(&self) -> Sp {
+        // Safety: `self` holds a strong reference, so the binder is live.
+        unsafe { Sp::new(self.0).unwrap() }
+    }
+}
+
+impl Drop for Sp {
+    fn drop(&mut self) {
+        // Safety: `self` owns one strong reference.
+        unsafe { AIBinder_decStrong(self.0) }
+    }
+}
+
+impl std::ops::Deref for Sp {
+    type Target = AIBinder;
+
+    fn deref(&self) -> &AIBinder {
+        // Safety: `self` holds a strong reference, so the binder is live.
+        unsafe { &*self.0 }
+    }
+}
+
+pub struct AIBinder {
+    class: AtomicPtr<AIBinder_Class>,
+    stability: AtomicI32,
+    /// The process hosting a local binder, or holding a proxy.
+    owner: Arc<Process>,
+    kind: Kind,
+}
+
+enum Kind {
+    Local(Local),
+    Proxy(Proxy),
+}
+
+/// A binder hosted by its owner, `ABBinder`.
+struct Local {
+    user_data: *mut c_void,
+    extension: Mutex<Option<Sp>>,
+    requesting_sid: AtomicBool,
+}
+
+/// A reference to a binder hosted by another process, `ABpBinder`.
+struct Proxy {
+    /// The binder this refers to, until its process dies.
+    node: Mutex<Option<Sp>>,
+    links: Mutex<Vec<DeathLink>>,
+}
+
+/// A death recipient linked to a proxy, `TransferDeathRecipient`.
+struct DeathLink {
+    /// Gone once the `AIBinder_DeathRecipient` is deleted.
+    on_binder_died: Weak<AIBinder_DeathRecipient_onBinderDied>,
+    on_binder_unlinked: AIBinder_DeathRecipient_onBinderUnlinked,
+    cookie: *mut c_void,
+}
+
+impl Drop for DeathLink {
+    fn drop(&mut self) {
+        if let Some(on_binder_unlinked) = self.on_binder_unlinked {
+            // Safety: the cookie was handed to us by `AIBinder_linkToDeath`,
+            // and this is the last use of it.
+            unsafe { on_binder_unlinked(self.cookie) };
+        }
+    }
+}
+
+// Safety: The user data of a binder and the cookies of death recipients are
+// only ever handed back to their callbacks, which the `binder` crate requires
+// to be thread-safe. Everything else is behind atomics or locks.
+unsafe impl Send for AIBinder {}
+unsafe impl Sync for AIBinder {}
+
+impl AIBinder {
+    fn new_local(clazz: *const AIBinder_Class, user_data: *mut c_void, owner: Arc<Process>) -> Sp {
+        Sp::from_arc(Arc::new(AIBinder {
+            class: AtomicPtr::new(clazz as *mut _),
+            stability: AtomicI32::new(STABILITY_UNDECLARED),
+            owner,
+            kind: Kind::Local(Local {
+                user_data,
+                extension: Mutex::new(None),
+                requesting_sid: AtomicBool::new(false),
+            }),
+        }))
+    }
+
+    /// Create a proxy held by the current process for the local binder
+    /// `node`.
+    pub(super) fn new_proxy(node: Sp, owner: Arc<Process>) -> Arc<AIBinder> {
+        let stability = (node.stability_repr() >> 8) & 0xff;
+        Arc::new(AIBinder {
+            class: AtomicPtr::new(ptr::null_mut()),
+            stability: AtomicI32::new(stability),
+            owner,
+            kind: Kind::Proxy(Proxy {
+                node: Mutex::new(Some(node)),
+                links: Mutex::new(Vec::new()),
+            }),
+        })
+    }
+
+    fn class(&self) -> Option<&AIBinder_Class> {
+        // Safety: classes are never freed.
+        unsafe { self.class.load(Ordering::Acquire).as_ref() }
+    }
+
+    pub(super) fn owner(&self) -> &Arc<Process> {
+        &self.owner
+    }
+
+    pub(super) fn is_proxy(&self) -> bool {
+        matches!(self.kind, Kind::Proxy(_))
+    }
+
+    /// Whether this is a live proxy for `node`.
+    pub(super) fn is_proxy_for(&self, node: &Sp) -> bool {
+        match &self.kind {
+            Kind::Local(_) => false,
+            Kind::Proxy(proxy) => proxy
+                .node
+                .lock()
+                .unwrap()
+                .as_ref()
+                .map_or(false, |n| ptr::eq(n.as_ptr(), node.as_ptr())),
+        }
+    }
+
+    fn is_alive(&self) -> bool {
+        match &self.kind {
+            Kind::Local(_) => true,
+            Kind::Proxy(proxy) => {
+                proxy.node.lock().unwrap().as_ref().map_or(false, |node| node.owner.is_alive())
+            }
+        }
+    }
+
+    /// Equivalent of `BpBinder::sendObituary`: the process hosting the binder
+    /// of this proxy died.
+    pub(super) fn binder_died(&self) {
+        let proxy = match &self.kind {
+            Kind::Local(_) => return,
+            Kind::Proxy(proxy) => proxy,
+        };
+        let node = proxy.node.lock().unwrap().take();
+        let links = std::mem::take(&mut *proxy.links.lock().unwrap());
+        for link in &links {
+            if let Some(on_binder_died) = link.on_binder_died.upgrade().and_then(|f| *f) {
+                // Safety: the cookie was handed to us by `AIBinder_linkToDeath`
+                // for this callback.
+                unsafe { on_binder_died(link.cookie) };
+            }
+        }
+        drop(links);
+        drop(node);
+    }
+
+    /// Representation of this binder's stability on the wire, as written by
+    /// `Parcel::finishFlattenBinder`. Sending an undeclared binder marks it
+    /// with the stability of this compilation unit.
+    pub(super) fn stability_repr(&self) -> i32 {
+        let _ = self.stability.compare_exchange(
+            STABILITY_UNDECLARED,
+            STABILITY_SYSTEM,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        );
+        BINDER_WIRE_FORMAT_VERSION | self.stability.load(Ordering::Acquire) << 8
+    }
+
+    /// Equivalent of `Stability::forceDowngradeToStability`.
+    fn force_downgrade(&self, level: i32) {
+        assert!(matches!(self.kind, Kind::Local(_)), "Can only downgrade local binder");
+        self.stability.store(level, Ordering::Release);
+    }
+
+    /// Check the stability read with this binder, as in
+    /// `Parcel::finishUnflattenBinder`.
+    pub(super) fn check_stability_repr(&self, repr: i32) -> Result<()> {
+        if repr & 0xff != BINDER_WIRE_FORMAT_VERSION {
+            return Err(StatusCode::BAD_TYPE);
+        }
+        let level = (repr >> 8) & 0xff;
+        match self.stability.compare_exchange(
+            STABILITY_UNDECLARED,
+            level,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => Ok(()),
+            Err(current) if current == level => Ok(()),
+            Err(_) => Err(StatusCode::BAD_TYPE),
+        }
+    }
+
+    pub(super) fn descriptor(&self) -> Option<&CStr> {
+        self.class().map(AIBinder_Class::descriptor)
+    }
+
+    /// Equivalent of `BBinder::transact`.
+    fn transact(
+        self: &Arc<Self>,
+        code: transaction_code_t,
+        data: &AParcel,
+        reply: &mut AParcel,
+        flags: binder_flags_t,
+    ) -> binder_status_t {
+        data.set_data_position(0);
+        let status = match code {
+            PING_TRANSACTION => {
+                reply.write_i32(StatusCode::OK as i32);
+                StatusCode::OK as i32
+            }
+            EXTENSION_TRANSACTION => {
+                let extension = match &self.kind {
+                    Kind::Local(local) => local.extension.lock().unwrap().clone(),
+                    Kind::Proxy(_) => None,
+                };
+                reply.write_binder(extension.as_ref().map_or(ptr::null_mut(), Sp::as_ptr));
+                StatusCode::OK as i32
+            }
+            DEBUG_PID_TRANSACTION => {
+                reply.write_i32(std::process::id() as i32);
+                StatusCode::OK as i32
+            }
+            _ => self.on_transact(code, data, reply, flags),
+        };
+        reply.set_data_position(0);
+        status
+    }
+
+    /// Equivalent of `ABBinder::onTransact`.
+    fn on_transact(
+        self: &Arc<Self>,
+        code: transaction_code_t,
+        data: &AParcel,
+        reply: &mut AParcel,
+        _flags: binder_flags_t,
+    ) -> binder_status_t {
+        let class = match self.class() {
+            Some(class) => class,
+            None => return StatusCode::UNKNOWN_TRANSACTION as i32,
+        };
+        let this = Arc::as_ptr(self) as *mut AIBinder;
+        if is_user_command(code) {
+            if class.write_header && !check_interface(data, class.descriptor()) {
+                return StatusCode::BAD_TYPE as i32;
+            }
+            // Safety: `this` is live for the duration of the call, and the
+            // class callback expects exactly these parcels.
+            return unsafe { class.on_transact.unwrap()(this, code, data, reply) };
+        }
+        match code {
+            INTERFACE_TRANSACTION => {
+                let descriptor = class.descriptor().to_string_lossy();
+                to_status_t(reply.write_str(&descriptor))
+            }
+            DUMP_TRANSACTION => {
+                match read_dump_args(data) {
+                    // Safety: `this` is live and `fd` is owned by `data` for
+                    // the duration of the call.
+                    Ok((fd, args)) => unsafe { self.dump(this, fd, &args) },
+                    Err(e) => e as i32,
+                }
+            }
+            SHELL_COMMAND_TRANSACTION => {
+                match read_shell_command_args(data) {
+                    // Safety: `this` is live and the fds are owned by `data`
+                    // for the duration of the call.
+                    Ok((fds, args, result_receiver)) => unsafe {
+                        self.shell_command(this, fds, &args, result_receiver)
+                    },
+                    Err(e) => e as i32,
+                }
+            }
+            _ => StatusCode::UNKNOWN_TRANSACTION as i32,
+        }
+    }
+
+    /// Equivalent of `ABBinder::dump`.
+    unsafe fn dump(
+        &self,
+        this: *mut AIBinder,
+        fd: BorrowedFd<'_>,
+        args: &[String],
+    ) -> binder_status_t {
+        let on_dump = match self.class().and_then(|class| class.on_dump) {
+            Some(on_dump) => on_dump,
+            None => return StatusCode::OK as i32,
+        };
+        let args: Vec<CString> =
+            args.iter().map(|arg| CString::new(arg.replace('\0', "")).unwrap()).collect();
+        let mut arg_ptrs: Vec<*const c_char> = args.iter().map(|arg| arg.as_ptr()).collect();
+        on_dump(this, fd.as_raw_fd(), arg_ptrs.as_mut_ptr(), arg_ptrs.len() as u32)
+    }
+
+    /// Equivalent of the `SHELL_COMMAND_TRANSACTION` case of
+    /// `ABBinder::onTransact`, less the check that the caller is root or
+    /// shell: every process on the host runs as the same user.
+    unsafe fn shell_command(
+        &self,
+        this: *mut AIBinder,
+        [stdin, stdout, stderr]: [BorrowedFd<'_>; 3],
+        args: &[String],
+        result_receiver: Option<Sp>,
+    ) -> binder_status_t {
+        let status = match self.class().and_then(|class| class.handle_shell_command) {
+            Some(handle_shell_command) => {
+                let args: Vec<CString> =
+                    args.iter().map(|arg| CString::new(arg.replace('\0', "")).unwrap()).collect();
+                let mut arg_ptrs: Vec<*const c_char> =
+                    args.iter().map(|arg| arg.as_ptr()).collect();
+                handle_shell_command(
+                    this,
+                    stdin.as_raw_fd(),
+                    stdout.as_raw_fd(),
+                    stderr.as_raw_fd(),
+                    arg_ptrs.as_mut_ptr(),
+                    arg_ptrs.len() as u32,
+                )
+            }
+            None => StatusCode::INVALID_OPERATION as i32,
+        };
+        if let Some(result_receiver) = result_receiver {
+            send_result(&result_receiver, status);
+        }
+        status
+    }
+}
+
+impl Drop for AIBinder {
+    fn drop(&mut self) {
+        match &self.kind {
+            Kind::Local(local) => {
+                if let Some(on_destroy) = self.class().and_then(|class| class.on_destroy) {
+                    // Safety: the user data was created by `on_create` of the
+                    // same class and is destroyed exactly once, with the binder.
+                    unsafe { on_destroy(local.user_data) };
+                }
+            }
+            Kind::Proxy(proxy) => {
+                if let Some(node) = proxy.node.lock().unwrap().take() {
+                    clients_changed(node.owner());
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for AIBinder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AIBinder")
+            .field("descriptor", &self.descriptor())
+            .field("remote", &self.is_proxy())
+            .finish()
+    }
+}
+
+/// Read the arguments of a `DUMP_TRANSACTION`, as `BBinder::onTransact` does.
+fn read_dump_args(data: &AParcel) -> Result<(BorrowedFd<'_>, Vec<String>)> {
+    let fd = data.read_fd()?;
+    let argc = data.read_i32()?;
+    let mut args = Vec::new();
+    for _ in 0..argc {
+        if data.data_avail() == 0 {
+            break;
+        }
+        args.push(data.read_str()?);
+    }
+    Ok((fd, args))
+}
+
+/// Read the arguments of a `SHELL_COMMAND_TRANSACTION`, as
+/// `ABBinder::onTransact` does. The shell callback, which `cmd` uses to open
+/// files on behalf of the service, is dropped.
+fn read_shell_command_args(
+    data: &AParcel,
+) -> Result<([BorrowedFd<'_>; 3], Vec<String>, Option<Sp>)> {
+    let fds = [data.read_fd()?, data.read_fd()?, data.read_fd()?];
+    let argc = data.read_i32()?;
+    let mut args = Vec::new();
+    for _ in 0..argc {
+        args.push(data.read_str()?);
+    }
+    // Safety: `read_binder` returns an owned strong reference.
+    unsafe {
+        drop(Sp::from_raw(data.read_binder()?));
+        Ok((fds, args, Sp::from_raw(data.read_binder()?)))
+    }
+}
+
+/// Equivalent of `IResultReceiver::send`, which `cmd` waits on for the result
+/// of a shell command.
+fn send_result(result_receiver: &Sp, result: i32) {
+    let mut data = AParcel::new(result_receiver.as_ptr());
+    if write_interface(&mut data, RESULT_RECEIVER_DESCRIPTOR).is_ok() {
+        data.write_i32(result);
+        // Like libbinder, there is nothing to do if the receiver is gone.
+        let _ = result_receiver.transact(FIRST_CALL_TRANSACTION, &data, FLAG_ONEWAY);
+    }
+}
+
+/// Equivalent of `Parcel::writeInterfaceToken`.
+pub(super) fn write_interface(parcel: &mut AParcel, descriptor: &CStr) -> Result<()> {
+    parcel.write_i32(STRICT_MODE_PENALTY_GATHER);
+    parcel.write_i32(UNSET_WORK_SOURCE);
+    parcel.write_i32(INTERFACE_HEADER);
+    parcel.write_str(&descriptor.to_string_lossy())
+}
+
+/// Equivalent of `Parcel::checkInterface`.
+pub(super) fn check_interface(parcel: &AParcel, descriptor: &CStr) -> bool {
+    let header = (|| {
+        let _strict_mode_policy = parcel.read_i32()?;
+        let _work_source = parcel.read_i32()?;
+        let header = parcel.read_i32()?;
+        Ok::<_, StatusCode>((header, parcel.read_str()?))
+    })();
+    match header {
+        Ok((header, interface)) => {
+            header == INTERFACE_HEADER && interface.as_bytes() == descriptor.to_bytes()
+        }
+        Err(_) => false,
+    }
+}
+
+/// Borrow the `Arc` behind a binder pointer without touching its count.
+unsafe fn borrow_arc(binder: *const AIBinder) -> ManuallyDrop<Arc<AIBinder>> {
+    ManuallyDrop::new(Arc::from_raw(binder))
+}
+
+/// Borrow a binder pointer as an `Sp` without touching its count.
+unsafe fn borrow_sp(binder: *mut AIBinder) -> ManuallyDrop<Sp> {
+    ManuallyDrop::new(Sp(binder))
+}
+
+pub unsafe fn AIBinder_Class_define(
+    interfaceDescriptor: *const c_char,
+    onCreate: AIBinder_Class_onCreate,
+    onDestroy: AIBinder_Class_onDestroy,
+    onTransact: AIBinder_Class_onTransact,
+) -> *mut AIBinder_Class {
+    if interfaceDescriptor.is_null() || onCreate.is_none() || onDestroy.is_none() {
+        return ptr::null_mut();
+    }
+    if onTransact.is_none() {
+        return ptr::null_mut();
+    }
+    // Like libbinder_ndk, classes are never freed.
+    Box::into_raw(Box::new(AIBinder_Class {
+        descriptor: CStr::from_ptr(interfaceDescriptor).into(),
+        on_create: onCreate,
+        on_destroy: onDestroy,
+        on_transact: onTransact,
+        on_dump: None,
+        handle_shell_command: None,
+        write_header: true,
+    }))
+}
+
+pub unsafe fn AIBinder_Class_setOnDump(clazz: *mut AIBinder_Class, onDump: AIBinder_onDump) {
+    (*clazz).on_dump = onDump;
+}
+
+pub unsafe fn AIBinder_Class_setHandleShellCommand(
+    clazz: *mut AIBinder_Class,
+    handleShellCommand: AIBinder_handleShellCommand,
+) {
+    (*clazz).handle_shell_command = handleShellCommand;
+}
+
+pub unsafe fn AIBinder_Class_disableInterfaceTokenHeader(clazz: *mut AIBinder_Class) {
+    (*clazz).write_header = false;
+}
+
+pub unsafe fn AIBinder_Class_getDescriptor(clazz: *const AIBinder_Class) -> *const c_char {
+    (*clazz).descriptor.as_ptr()
+}
+
+/// Create a local binder hosted by `owner` rather than the current process.
+pub(super) unsafe fn new_binder_in(
+    clazz: *const AIBinder_Class,
+    args: *mut c_void,
+    owner: Arc<Process>,
+) -> Sp {
+    let user_data = (*clazz).on_create.unwrap()(args);
+    AIBinder::new_local(clazz, user_data, owner)
+}
+
+pub unsafe fn AIBinder_new(clazz: *const AIBinder_Class, args: *mut c_void) -> *mut AIBinder {
+    if clazz.is_null() {
+        return ptr::null_mut();
+    }
+    new_binder_in(clazz, args, current_process()).into_raw()
+}
+
+pub unsafe fn AIBinder_incStrong(binder: *mut AIBinder) {
+    if !binder.is_null() {
+        Arc::increment_strong_count(binder);
+    }
+}
+
+pub unsafe fn AIBinder_decStrong(binder: *mut AIBinder) {
+    if !binder.is_null() {
+        Arc::decrement_strong_count(binder);
+    }
+}
+
+pub unsafe fn AIBinder_debugGetRefCount(binder: *mut AIBinder) -> i32 {
+    if binder.is_null() {
+        return -1;
+    }
+    Arc::strong_count(&borrow_arc(binder)) as i32
+}
+
+pub unsafe fn AIBinder_isRemote(binder: *const AIBinder) -> bool {
+    !binder.is_null() && (*binder).is_proxy()
+}
+
+pub unsafe fn AIBinder_isAlive(binder: *const AIBinder) -> bool {
+    !binder.is_null() && (*binder).is_alive()
+}
+
+pub unsafe fn AIBinder_ping(binder: *mut AIBinder) -> binder_status_t {
+    if binder.is_null() {
+        return StatusCode::UNEXPECTED_NULL as i32;
+    }
+    let binder = borrow_sp(binder);
+    to_status_t(binder.transact(PING_TRANSACTION, &AParcel::new(binder.as_ptr()), 0).map(drop))
+}
+
+pub unsafe fn AIBinder_dump(
+    binder: *mut AIBinder,
+    fd: c_int,
+    args: *mut *const c_char,
+    numArgs: u32,
+) -> binder_status_t {
+    if binder.is_null() {
+        return StatusCode::UNEXPECTED_NULL as i32;
+    }
+    let args = (0..numArgs as usize)
+        .map(|i| CStr::from_ptr(*args.add(i)).to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    if !(*binder).is_proxy() {
+        return (*binder).dump(binder, BorrowedFd::borrow_raw(fd), &args);
+    }
+    // Equivalent of `BpBinder::dump`.
+    let binder = borrow_sp(binder);
+    let result = (|| {
+        let fd = BorrowedFd::borrow_raw(fd).try_clone_to_owned().or(Err(StatusCode::BAD_VALUE))?;
+        let mut data = AParcel::new(binder.as_ptr());
+        data.write_fd(fd);
+        data.write_i32(args.len() as i32);
+        for arg in &args {
+            data.write_str(arg)?;
+        }
+        binder.transact(DUMP_TRANSACTION, &data, 0).map(drop)
+    })();
+    to_status_t(result)
+}
+
+/// Not in the NDK, where only `cmd` sends shell commands: the equivalent of
+/// `IBinder::shellCommand`, without a shell callback or result receiver. Local
+/// and remote binders both get the command as a transaction, and the result of
+/// the command is returned.
+pub unsafe fn AIBinder_shellCommand(
+    binder: *mut AIBinder,
+    in_: c_int,
+    out: c_int,
+    err: c_int,
+    argv: *mut *const c_char,
+    argc: u32,
+) -> binder_status_t {
+    if binder.is_null() || (argv.is_null() && argc != 0) {
+        return StatusCode::UNEXPECTED_NULL as i32;
+    }
+    let binder = borrow_sp(binder);
+    let result = (|| {
+        let mut data = AParcel::new(binder.as_ptr());
+        for fd in [in_, out, err] {
+            let fd =
+                BorrowedFd::borrow_raw(fd).try_clone_to_owned().or(Err(StatusCode::BAD_VALUE))?;
+            data.write_fd(fd);
+        }
+        data.write_i32(argc as i32);
+        for i in 0..argc as usize {
+            data.write_str(&CStr::from_ptr(*argv.add(i)).to_string_lossy())?;
+        }
+        data.write_binder(ptr::null_mut());
+        data.write_binder(ptr::null_mut());
+        binder.transact(SHELL_COMMAND_TRANSACTION, &data, 0).map(drop)
+    })();
+    to_status_t(result)
+}
+
+pub unsafe fn AIBinder_associateClass(binder: *mut AIBinder, clazz: *const AIBinder_Class) -> bool {
+    if binder.is_null() || clazz.is_null() {
+        return false;
+    }
+    let current = (*binder).class.load(Ordering::Acquire);
+    if ptr::eq(current, clazz) {
+        return true;
+    }
+    // Local binders always have a class, which can never change.
+    if !(*binder).is_proxy() {
+        return false;
+    }
+    // A proxy takes on the first class whose descriptor matches the remote
+    // binder's.
+    match borrow_sp(binder).interface_descriptor() {
+        Ok(descriptor) if descriptor.as_bytes() == (*clazz).descriptor.to_bytes() => (*binder)
+            .class
+            .compare_exchange(ptr::null_mut(), clazz as *mut _, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok(),
+        _ => false,
+    }
+}
+
+pub unsafe fn AIBinder_getClass(binder: *mut AIBinder) -> *const AIBinder_Class {
+    if binder.is_null() {
+        return ptr::null();
+    }
+    (*binder).class.load(Ordering::Acquire)
+}
+
+pub unsafe fn AIBinder_getUserData(binder: *mut AIBinder) -> *mut c_void {
+    match binder.as_ref().map(|binder| &binder.kind) {
+        Some(Kind::Local(local)) => local.user_data,
+        _ => ptr::null_mut(),
+    }
+}
+
+pub unsafe fn AIBinder_lt(lhs: *const AIBinder, rhs: *const AIBinder) -> bool {
+    lhs < rhs
+}
+
+pub unsafe fn AIBinder_prepareTransaction(
+    binder: *mut AIBinder,
+    in_: *mut *mut AParcel,
+) -> binder_status_t {
+    if binder.is_null() || in_.is_null() {
+        return StatusCode::UNEXPECTED_NULL as i32;
+    }
+    let mut parcel = AParcel::new(binder);
+    if let Some(class) = (*binder).class() {
+        if class.write_header {
+            if let Err(e) = write_interface(&mut parcel, class.descriptor()) {
+                *in_ = ptr::null_mut();
+                return e as i32;
+            }
+        }
+    }
+    *in_ = parcel.into_raw();
+    StatusCode::OK as i32
+}
+
+pub unsafe fn AIBinder_transact(
+    binder: *mut AIBinder,
+    code: transaction_code_t,
+    in_: *mut *mut AParcel,
+    out: *mut *mut AParcel,
+    flags: binder_flags_t,
+) -> binder_status_t {
+    if in_.is_null() || (*in_).is_null() {
+        return StatusCode::UNEXPECTED_NULL as i32;
+    }
+    let data = Box::from_raw(*in_);
+    *in_ = ptr::null_mut();
+    if binder.is_null() || out.is_null() {
+        return StatusCode::UNEXPECTED_NULL as i32;
+    }
+    if !ptr::eq(data.binder(), binder) {
+        return StatusCode::BAD_VALUE as i32;
+    }
+    if flags & !(FLAG_PRIVATE_VENDOR | FLAG_ONEWAY | FLAG_CLEAR_BUF) != 0 {
+        return StatusCode::BAD_VALUE as i32;
+    }
+    match borrow_sp(binder).transact(code, &data, flags) {
+        Ok(reply) => {
+            *out = reply.into_raw();
+            StatusCode::OK as i32
+        }
+        Err(e) => {
+            *out = ptr::null_mut();
+            e as i32
+        }
+    }
+}
+
+pub unsafe fn AIBinder_getExtension(
+    binder: *mut AIBinder,
+    outExt: *mut *mut AIBinder,
+) -> binder_status_t {
+    if binder.is_null() || outExt.is_null() {
+        return StatusCode::UNEXPECTED_NULL as i32;
+    }
+    let extension = match &(*binder).kind {
+        Kind::Local(local) => {
+            Ok(local.extension.lock().unwrap().clone().map_or(ptr::null_mut(), Sp::into_raw))
+        }
+        Kind::Proxy(_) => {
+            let binder = borrow_sp(binder);
+            binder
+                .transact(EXTENSION_TRANSACTION, &AParcel::new(binder.as_ptr()), 0)
+                .and_then(|reply| reply.read_binder())
+        }
+    };
+    match extension {
+        Ok(extension) => {
+            *outExt = extension;
+            StatusCode::OK as i32
+        }
+        Err(e) => {
+            *outExt = ptr::null_mut();
+            e as i32
+        }
+    }
+}
+
+pub unsafe fn AIBinder_setExtension(binder: *mut AIBinder, ext: *mut AIBinder) -> binder_status_t {
+    if binder.is_null() || ext.is_null() {
+        return StatusCode::UNEXPECTED_NULL as i32;
+    }
+    match &(*binder).kind {
+        Kind::Local(local) => {
+            *local.extension.lock().unwrap() = Sp::new(ext);
+            StatusCode::OK as i32
+        }
+        Kind::Proxy(_) => StatusCode::INVALID_OPERATION as i32,
+    }
+}
+
+pub unsafe fn AIBinder_markVintfStability(binder: *mut AIBinder) {
+    (*binder).stability.store(STABILITY_VINTF, Ordering::Release);
+}
+
+pub unsafe fn AIBinder_markVendorStability(binder: *mut AIBinder) {
+    (*binder).stability.store(STABILITY_VENDOR, Ordering::Release);
+}
+
+pub unsafe fn AIBinder_markSystemStability(binder: *mut AIBinder) {
+    (*binder).stability.store(STABILITY_SYSTEM, Ordering::Release);
+}
+
+pub unsafe fn AIBinder_forceDowngradeToVendorStability(binder: *mut AIBinder) {
+    (*binder).force_downgrade(STABILITY_VENDOR);
+}
+
+pub unsafe fn AIBinder_forceDowngradeToSystemStability(binder: *mut AIBinder) {
+    (*binder).force_downgrade(STABILITY_SYSTEM);
+}
+
+pub unsafe fn AIBinder_setRequestingSid(binder: *mut AIBinder, requestingSid: bool) {
+    if let Kind::Local(local) = &(*binder).kind {
+        local.requesting_sid.store(requestingSid, Ordering::Release);
+    }
+}
+
+pub unsafe fn AIBinder_setMinSchedulerPolicy(
+    binder: *mut AIBinder,
+    policy: c_int,
+    priority: c_int,
+) {
+    // Like libbinder, abort on values the driver would reject. Transactions
+    // are dispatched on the calling thread, so there is nothing to apply.
+    let priorities = match policy {
+        libc::SCHED_OTHER => -20..=19,
+        libc::SCHED_FIFO | libc::SCHED_RR => 1..=99,
+        _ => panic!("Unrecognized scheduling policy: {}", policy),
+    };
+    assert!(
+        priorities.contains(&priority),
+        "Invalid priority {} for scheduling policy {}",
+        priority,
+        policy
+    );
+    assert!(matches!((*binder).kind, Kind::Local(_)), "setMinSchedulerPolicy on a proxy");
+}
+
+pub unsafe fn AIBinder_setInheritRt(binder: *mut AIBinder, _inheritRt: bool) {
+    assert!(matches!((*binder).kind, Kind::Local(_)), "setInheritRt on a proxy");
+}
+
+pub unsafe fn AIBinder_getCallingUid() -> uid_t {
+    libc::getuid()
+}
+
+pub unsafe fn AIBinder_getCallingPid() -> pid_t {
+    libc::getpid()
+}
+
+pub unsafe fn AIBinder_getCallingSid() -> *const c_char {
+    super::process::calling_sid()
+}
+
+pub unsafe fn AIBinder_isHandlingTransaction() -> bool {
+    super::process::is_handling_transaction()
+}
+
+pub struct AIBinder_Weak(Weak<AIBinder>);
+
+pub unsafe fn AIBinder_Weak_new(binder: *mut AIBinder) -> *mut AIBinder_Weak {
+    if binder.is_null() {
+        return ptr::null_mut();
+    }
+    Box::into_raw(Box::new(AIBinder_Weak(Arc::downgrade(&borrow_arc(binder)))))
+}
+
+pub unsafe fn AIBinder_Weak_delete(weakBinder: *mut AIBinder_Weak) {
+    if !weakBinder.is_null() {
+        drop(Box::from_raw(weakBinder));
+    }
+}
+
+pub unsafe fn AIBinder_Weak_promote(weakBinder: *mut AIBinder_Weak) -> *mut AIBinder {
+    if weakBinder.is_null() {
+        return ptr::null_mut();
+    }
+    (*weakBinder).0.upgrade().map_or(ptr::null_mut(), |b| Arc::into_raw(b) as *mut AIBinder)
+}
+
+pub unsafe fn AIBinder_Weak_clone(weak: *const AIBinder_Weak) -> *mut AIBinder_Weak {
+    if weak.is_null() {
+        return ptr::null_mut();
+    }
+    Box::into_raw(Box::new(AIBinder_Weak((*weak).0.clone())))
+}
+
+pub unsafe fn AIBinder_Weak_lt(lhs: *const AIBinder_Weak, rhs: *const AIBinder_Weak) -> bool {
+    match (lhs.is_null(), rhs.is_null()) {
+        (true, _) | (_, true) => lhs < rhs,
+        _ => (*lhs).0.as_ptr() < (*rhs).0.as_ptr(),
+    }
+}
+
+pub struct AIBinder_DeathRecipient {
+    /// Shared with the links of this recipient, which stop calling it once
+    /// the recipient is deleted.
+    on_binder_died: Arc<AIBinder_DeathRecipient_onBinderDied>,
+    on_binder_unlinked: AIBinder_DeathRecipient_onBinderUnlinked,
+}
+
+pub unsafe fn AIBinder_DeathRecipient_new(
+    onBinderDied: AIBinder_DeathRecipient_onBinderDied,
+) -> *mut AIBinder_DeathRecipient {
+    if onBinderDied.is_none() {
+        return ptr::null_mut();
+    }
+    Box::into_raw(Box::new(AIBinder_DeathRecipient {
+        on_binder_died: Arc::new(onBinderDied),
+        on_binder_unlinked: None,
+    }))
+}
+
+pub unsafe fn AIBinder_DeathRecipient_setOnUnlinked(
+    recipient: *mut AIBinder_DeathRecipient,
+    onUnlinked: AIBinder_DeathRecipient_onBinderUnlinked,
+) {
+    (*recipient).on_binder_unlinked = onUnlinked;
+}
+
+pub unsafe fn AIBinder_DeathRecipient_delete(recipient: *mut AIBinder_DeathRecipient) {
+    if !recipient.is_null() {
+        drop(Box::from_raw(recipient));
+    }
+}
+
+/// Local binders never die, so `BBinder::linkToDeath` always fails. Either
+/// way, the cookie is released through `onUnlinked` once the link is gone.
+pub unsafe fn AIBinder_linkToDeath(
+    binder: *mut AIBinder,
+    recipient: *mut AIBinder_DeathRecipient,
+    cookie: *mut c_void,
+) -> binder_status_t {
+    if binder.is_null() || recipient.is_null() {
+        return StatusCode::UNEXPECTED_NULL as i32;
+    }
+    let link = DeathLink {
+        on_binder_died: Arc::downgrade(&(*recipient).on_binder_died),
+        on_binder_unlinked: (*recipient).on_binder_unlinked,
+        cookie,
+    };
+    let proxy = match &(*binder).kind {
+        Kind::Local(_) => return StatusCode::INVALID_OPERATION as i32,
+        Kind::Proxy(proxy) => proxy,
+    };
+    let mut links = proxy.links.lock().unwrap();
+    if proxy.node.lock().unwrap().is_none() {
+        return StatusCode::DEAD_OBJECT as i32;
+    }
+    links.push(link);
+    StatusCode::OK as i32
+}
+
+pub unsafe fn AIBinder_unlinkToDeath(
+    binder: *mut AIBinder,
+    recipient: *mut AIBinder_DeathRecipient,
+    cookie: *mut c_void,
+) -> binder_status_t {
+    if binder.is_null() || recipient.is_null() {
+        return StatusCode::UNEXPECTED_NULL as i32;
+    }
+    let proxy = match &(*binder).kind {
+        Kind::Local(_) => return StatusCode::INVALID_OPERATION as i32,
+        Kind::Proxy(proxy) => proxy,
+    };
+    let on_binder_died = Arc::downgrade(&(*recipient).on_binder_died);
+    let link = {
+        let mut links = proxy.links.lock().unwrap();
+        let index = links.iter().position(|link| {
+            link.cookie == cookie && Weak::ptr_eq(&link.on_binder_died, &on_binder_died)
+        });
+        index.map(|index| links.remove(index))
+    };
+    match link {
+        Some(link) => {
+            drop(link);
+            StatusCode::OK as i32
+        }
+        None => StatusCode::NAME_NOT_FOUND as i32,
+    }
+}
@@ -0,0 +1,456 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Service manager entry points.
+//!
+//! Services live in an in-process registry standing in for servicemanager,
+//! which itself is registered as `manager`, like on a device. Nothing is
+//! declared in a VINTF manifest.
+//!
+//! Lazy services are told when they gain or lose clients, that is when
+//! another process first takes or releases its last proxy for them.
+//!
+//! Registration notifications are delivered synchronously, on the thread that
+//! registers the service, as an incoming call of the process that asked for
+//! them.
+
+use super::ibinder::{new_binder_in, Sp};
+use super::process::{current_process, Process};
+use super::{
+    binder_exception_t, binder_status_t, transaction_code_t, AIBinder, AIBinder_Class,
+    AIBinder_Class_define, AParcel, AServiceManager_forEachDeclaredInstance_callback,
+    AServiceManager_getUpdatableApexName_callback, AServiceManager_onRegister,
+    AServiceManager_setActiveServicesCallback_callback, ExceptionCode, StatusCode,
+};
+
+use std::collections::BTreeMap;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+
+/// Registered services by name, created on first use.
+static SERVICES: Mutex<Option<BTreeMap<String, Sp>>> = Mutex::new(None);
+/// Notified whenever a service is registered.
+static REGISTERED: Condvar = Condvar::new();
+/// Registrations for service notifications, in registration order.
+static NOTIFICATIONS: Mutex<Vec<Arc<AServiceManager_NotificationRegistration>>> =
+    Mutex::new(Vec::new());
+
+const SERVICE_MANAGER_DESCRIPTOR: &CStr =
+    // Safety: the string is NUL terminated and has no interior NULs.
+    unsafe { CStr::from_bytes_with_nul_unchecked(b"android.os.IServiceManager\0") };
+
+fn services() -> MutexGuard<'static, Option<BTreeMap<String, Sp>>> {
+    let mut services = SERVICES.lock().unwrap();
+    if services.is_none() {
+        let mut registry = BTreeMap::new();
+        registry.insert("manager".to_owned(), new_service_manager());
+        *services = Some(registry);
+    }
+    services
+}
+
+/// Create the binder of servicemanager, hosted by a process of its own.
+fn new_service_manager() -> Sp {
+    unsafe extern "C" fn on_create(_args: *mut c_void) -> *mut c_void {
+        ptr::null_mut()
+    }
+    unsafe extern "C" fn on_destroy(_user_data: *mut c_void) {}
+    unsafe extern "C" fn on_transact(
+        _binder: *mut AIBinder,
+        _code: transaction_code_t,
+        _in: *const AParcel,
+        _out: *mut AParcel,
+    ) -> binder_status_t {
+        StatusCode::UNKNOWN_TRANSACTION as i32
+    }
+
+    // Safety: the descriptor is a valid C string and the callbacks match the
+    // class, which is never freed.
+    unsafe {
+        let class: *const AIBinder_Class = AIBinder_Class_define(
+            SERVICE_MANAGER_DESCRIPTOR.as_ptr(),
+            Some(on_create),
+            Some(on_destroy),
+            Some(on_transact),
+        );
+        new_binder_in(class, ptr::null_mut(), Arc::new(Process::new()))
+    }
+}
+
+/// Unregister every service hosted by `process`, which died.
+pub(super) fn remove_services_of(process: &Arc<Process>) {
+    let removed: Vec<Sp> = {
+        let mut services = services();
+        let services = services.as_mut().unwrap();
+        let names: Vec<String> = services
+            .iter()
+            .filter(|(_, service)| Arc::ptr_eq(service.owner(), process))
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.iter().filter_map(|name| services.remove(name)).collect()
+    };
+    // Released outside of the lock, as dropping them may run arbitrary code.
+    drop(removed);
+}
+
+/// Equivalent of servicemanager's `isValidServiceName`.
+fn is_valid_service_name(name: &[u8]) -> bool {
+    (1..=127).contains(&name.len())
+        && name.iter().all(|c| c.is_ascii_alphanumeric() || b"_-./".contains(c))
+}
+
+unsafe fn add_service(
+    binder: *mut AIBinder,
+    instance: *const c_char,
+) -> Result<(String, Sp), ExceptionCode> {
+    if instance.is_null() {
+        return Err(ExceptionCode::ILLEGAL_ARGUMENT);
+    }
+    let name = CStr::from_ptr(instance).to_bytes();
+    if !is_valid_service_name(name) {
+        return Err(ExceptionCode::ILLEGAL_ARGUMENT);
+    }
+    let node = Sp::new(binder)
+        .ok_or(ExceptionCode::ILLEGAL_ARGUMENT)?
+        .node()
+        .or(Err(ExceptionCode::ILLEGAL_ARGUMENT))?;
+    let name = String::from_utf8_lossy(name).into_owned();
+    register(name.clone(), node.clone());
+    Ok((name, node))
+}
+
+/// Register `node` as the service `name`, and notify everyone waiting for it.
+fn register(name: String, node: Sp) {
+    let registrations: Vec<_> = NOTIFICATIONS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|registration| registration.instance.to_bytes() == name.as_bytes())
+        .cloned()
+        .collect();
+    let previous = services().as_mut().unwrap().insert(name, node.clone());
+    REGISTERED.notify_all();
+    drop(previous);
+
+    for registration in registrations {
+        registration.notify(&node);
+    }
+}
+
+/// Look up a service, as seen by the current process.
+unsafe fn get_service(
+    services: &BTreeMap<String, Sp>,
+    instance: *const c_char,
+) -> Option<*mut AIBinder> {
+    let node = services.get(CStr::from_ptr(instance).to_str().ok()?)?;
+    Some(current_process().import(node).into_raw())
+}
+
+pub unsafe fn AServiceManager_addService(
+    binder: *mut AIBinder,
+    instance: *const c_char,
+) -> binder_exception_t {
+    match add_service(binder, instance) {
+        Ok(_) => ExceptionCode::NONE as i32,
+        Err(e) => e as i32,
+    }
+}
+
+pub unsafe fn AServiceManager_registerLazyService(
+    binder: *mut AIBinder,
+    instance: *const c_char,
+) -> binder_status_t {
+    match add_service(binder, instance) {
+        Ok((name, node)) => {
+            let mut state = node.owner().lazy.state.lock().unwrap();
+            state.services.retain(|(other, _)| *other != name);
+            state.services.push((name, node.clone()));
+            StatusCode::OK as i32
+        }
+        Err(_) => StatusCode::UNKNOWN_ERROR as i32,
+    }
+}
+
+/// The lazy services of a process, and the state of its
+/// `LazyServiceRegistrar`.
+#[derive(Debug, Default)]
+pub(super) struct LazyServices {
+    state: Mutex<LazyState>,
+    /// Held while deciding whether to shut down, so that client changes are
+    /// handled one at a time.
+    checking: Mutex<()>,
+}
+
+#[derive(Debug, Default)]
+struct LazyState {
+    services: Vec<(String, Sp)>,
+    /// Set while the services are unregistered by `tryUnregister`.
+    unregistered: bool,
+    force_persist: bool,
+    callback: Option<ActiveServicesCallback>,
+    /// Whether any service had clients, when last checked.
+    has_clients: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ActiveServicesCallback {
+    callback: unsafe extern "C" fn(bool, *mut c_void) -> bool,
+    context: *mut c_void,
+}
+
+// Safety: the context is only ever handed back to the callback, which must
+// accept it on any thread.
+unsafe impl Send for ActiveServicesCallback {}
+
+/// Report that a binder of `process` gained or lost a client.
+///
+/// Like servicemanager, this is reported to processes with lazy services
+/// asynchronously, as an incoming call.
+pub(super) fn clients_changed(process: &Arc<Process>) {
+    if !process.is_alive() || process.lazy.state.lock().unwrap().services.is_empty() {
+        return;
+    }
+    let process = process.clone();
+    thread::spawn(move || process.dispatch(None, || maybe_try_shutdown(&process)));
+}
+
+/// Equivalent of libbinder's `ClientCounterCallbackImpl::maybeTryShutdown`.
+fn maybe_try_shutdown(process: &Arc<Process>) {
+    let _checking = process.lazy.checking.lock().unwrap();
+    let (services, force_persist) = {
+        let state = process.lazy.state.lock().unwrap();
+        if state.unregistered {
+            return;
+        }
+        let services: Vec<Sp> = state.services.iter().map(|(_, node)| node.clone()).collect();
+        (services, state.force_persist)
+    };
+    if force_persist {
+        return;
+    }
+    let has_clients = services.iter().any(|node| process.has_clients(node));
+    let (callback, changed) = {
+        let mut state = process.lazy.state.lock().unwrap();
+        let changed = has_clients != state.has_clients;
+        state.has_clients = has_clients;
+        (state.callback, changed)
+    };
+
+    let mut handled = false;
+    if let (Some(callback), true) = (callback, changed) {
+        // Safety: the callback was registered along with its context.
+        handled = unsafe { (callback.callback)(has_clients, callback.context) };
+    }
+    if !handled && !has_clients {
+        if try_unregister(process) {
+            process.exit_voluntarily();
+        } else {
+            re_register(process);
+        }
+    }
+}
+
+/// Unregister all lazy services of `process`, unless any has clients.
+fn try_unregister(process: &Arc<Process>) -> bool {
+    let lazy: Vec<(String, Sp)> = {
+        let state = process.lazy.state.lock().unwrap();
+        if state.unregistered {
+            return true;
+        }
+        state.services.clone()
+    };
+    let removed: Vec<Sp> = {
+        // Checked with the registry locked, so that no client gets hold of a
+        // service in the meantime.
+        let mut services = services();
+        if lazy.iter().any(|(_, node)| process.has_clients(node)) {
+            return false;
+        }
+        let services = services.as_mut().unwrap();
+        let mut removed = Vec::new();
+        for (name, node) in &lazy {
+            if services.get(name).map_or(false, |service| service.as_ptr() == node.as_ptr()) {
+                removed.extend(services.remove(name));
+            }
+        }
+        removed
+    };
+    process.lazy.state.lock().unwrap().unregistered = true;
+    drop(removed);
+    true
+}
+
+/// Register again the lazy services of `process` unregistered by
+/// [`try_unregister`].
+fn re_register(process: &Arc<Process>) {
+    let lazy = {
+        let mut state = process.lazy.state.lock().unwrap();
+        if !state.unregistered {
+            return;
+        }
+        state.unregistered = false;
+        state.services.clone()
+    };
+    for (name, node) in lazy {
+        register(name, node);
+    }
+}
+
+pub unsafe fn AServiceManager_forceLazyServicesPersist(persist: bool) {
+    let process = current_process();
+    process.lazy.state.lock().unwrap().force_persist = persist;
+    if !persist {
+        clients_changed(&process);
+    }
+}
+
+pub unsafe fn AServiceManager_setActiveServicesCallback(
+    callback: AServiceManager_setActiveServicesCallback_callback,
+    context: *mut c_void,
+) {
+    current_process().lazy.state.lock().unwrap().callback =
+        callback.map(|callback| ActiveServicesCallback { callback, context });
+}
+
+pub unsafe fn AServiceManager_tryUnregister() -> bool {
+    try_unregister(&current_process())
+}
+
+pub unsafe fn AServiceManager_reRegister() {
+    re_register(&current_process())
+}
+
+pub unsafe fn AServiceManager_getService(instance: *const c_char) -> *mut AIBinder {
+    if instance.is_null() {
+        return ptr::null_mut();
+    }
+    get_service(services().as_ref().unwrap(), instance).unwrap_or(ptr::null_mut())
+}
+
+pub unsafe fn AServiceManager_checkService(instance: *const c_char) -> *mut AIBinder {
+    AServiceManager_getService(instance)
+}
+
+pub unsafe fn AServiceManager_waitForService(instance: *const c_char) -> *mut AIBinder {
+    if instance.is_null() {
+        return ptr::null_mut();
+    }
+    let mut services = services();
+    loop {
+        if let Some(binder) = get_service(services.as_ref().unwrap(), instance) {
+            return binder;
+        }
+        services = REGISTERED.wait(services).unwrap();
+    }
+}
+
+pub struct AServiceManager_NotificationRegistration {
+    instance: CString,
+    on_register: AServiceManager_onRegister,
+    cookie: *mut c_void,
+    /// The process that registered, to which notifications are delivered.
+    process: Arc<Process>,
+    /// Cleared once deleted. Held while delivering a notification, so that
+    /// deleting waits for it to complete.
+    active: Mutex<bool>,
+}
+
+// Safety: the cookie is never dereferenced here, only handed back to the
+// callback, which must accept it on any thread.
+unsafe impl Send for AServiceManager_NotificationRegistration {}
+unsafe impl Sync for AServiceManager_NotificationRegistration {}
+
+impl AServiceManager_NotificationRegistration {
+    /// Notify the registering process that `node` was registered.
+    fn notify(&self, node: &Sp) {
+        let active = self.active.lock().unwrap();
+        let on_register = match self.on_register {
+            Some(on_register) if *active && self.process.is_alive() => on_register,
+            _ => return,
+        };
+        let binder = self.process.import(node);
+        self.process.dispatch(None, || {
+            // Safety: the callback takes ownership of the binder, and was
+            // registered along with the cookie.
+            unsafe { on_register(self.instance.as_ptr(), binder.into_raw(), self.cookie) }
+        });
+    }
+}
+
+pub unsafe fn AServiceManager_registerForServiceNotifications(
+    instance: *const c_char,
+    onRegister: AServiceManager_onRegister,
+    cookie: *mut c_void,
+) -> *mut AServiceManager_NotificationRegistration {
+    if instance.is_null() {
+        return ptr::null_mut();
+    }
+    let instance = CStr::from_ptr(instance);
+    if !is_valid_service_name(instance.to_bytes()) {
+        return ptr::null_mut();
+    }
+    let registration = Arc::new(AServiceManager_NotificationRegistration {
+        instance: instance.to_owned(),
+        on_register: onRegister,
+        cookie,
+        process: current_process(),
+        active: Mutex::new(true),
+    });
+    NOTIFICATIONS.lock().unwrap().push(registration.clone());
+
+    let registered = services().as_ref().unwrap().get(instance.to_str().unwrap()).cloned();
+    if let Some(node) = registered {
+        registration.notify(&node);
+    }
+    Arc::into_raw(registration) as *mut AServiceManager_NotificationRegistration
+}
+
+pub unsafe fn AServiceManager_NotificationRegistration_delete(
+    notification: *mut AServiceManager_NotificationRegistration,
+) {
+    if notification.is_null() {
+        return;
+    }
+    let registration =
+        Arc::from_raw(notification as *const AServiceManager_NotificationRegistration);
+    NOTIFICATIONS.lock().unwrap().retain(|other| !Arc::ptr_eq(other, &registration));
+    *registration.active.lock().unwrap() = false;
+}
+
+pub unsafe fn AServiceManager_isDeclared(_instance: *const c_char) -> bool {
+    false
+}
+
+pub unsafe fn AServiceManager_forEachDeclaredInstance(
+    _interface: *const c_char,
+    _context: *mut c_void,
+    _callback: AServiceManager_forEachDeclaredInstance_callback,
+) {
+}
+
+pub unsafe fn AServiceManager_isUpdatableViaApex(_instance: *const c_char) -> bool {
+    false
+}
+
+pub unsafe fn AServiceManager_getUpdatableApexName(
+    _instance: *const c_char,
+    _context: *mut c_void,
+    _callback: AServiceManager_getUpdatableApexName_callback,
+) {
+}
@@ -0,0 +1,196 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Pure-Rust implementation of the subset of libbinder_ndk used by the
+//! `binder` crate, for running on hosts without `/dev/binder`.
+//!
+//! Every item here mirrors the bindgen output for the corresponding NDK
+//! declaration, so the `binder` crate compiles unchanged against either
+//! backend. Parcels produce the same wire layout as libbinder's `Parcel`.
+//!
+//! The functions follow the contracts documented in the NDK headers under
+//! `src/include_*`; they are not individually documented here.
+#![allow(clippy::missing_safety_doc)]
+
+mod ibinder;
+mod manager;
+mod parcel;
+mod process;
+mod status;
+
+pub use ibinder::*;
+pub use manager::*;
+pub use parcel::*;
+pub use process::*;
+pub use status::*;
+
+use std::os::raw::{c_char, c_int, c_uint, c_void};
+
+pub type binder_status_t = i32;
+pub type binder_exception_t = i32;
+pub type binder_flags_t = u32;
+pub type transaction_code_t = u32;
+pub type char16_t = u16;
+pub type uid_t = c_uint;
+pub type pid_t = c_int;
+
+pub const FIRST_CALL_TRANSACTION: c_uint = 0x00000001;
+pub const LAST_CALL_TRANSACTION: c_uint = 0x00ffffff;
+
+pub const FLAG_ONEWAY: c_uint = 0x01;
+pub const FLAG_CLEAR_BUF: c_uint = 0x20;
+pub const FLAG_PRIVATE_VENDOR: c_uint = 0x10000000;
+pub const FLAG_PRIVATE_LOCAL: c_uint = 0;
+
+#[repr(i32)]
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum android_c_interface_StatusCode {
+    OK = 0,
+    UNKNOWN_ERROR = i32::MIN,
+    NO_MEMORY = -libc::ENOMEM,
+    INVALID_OPERATION = -libc::ENOSYS,
+    BAD_VALUE = -libc::EINVAL,
+    BAD_TYPE = i32::MIN + 1,
+    NAME_NOT_FOUND = -libc::ENOENT,
+    PERMISSION_DENIED = -libc::EPERM,
+    NO_INIT = -libc::ENODEV,
+    ALREADY_EXISTS = -libc::EEXIST,
+    DEAD_OBJECT = -libc::EPIPE,
+    FAILED_TRANSACTION = i32::MIN + 2,
+    BAD_INDEX = -libc::EOVERFLOW,
+    NOT_ENOUGH_DATA = -libc::ENODATA,
+    WOULD_BLOCK = -libc::EWOULDBLOCK,
+    TIMED_OUT = -libc::ETIMEDOUT,
+    UNKNOWN_TRANSACTION = -libc::EBADMSG,
+    FDS_NOT_ALLOWED = i32::MIN + 7,
+    UNEXPECTED_NULL = i32::MIN + 8,
+}
+
+#[repr(i32)]
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum android_c_interface_ExceptionCode {
+    NONE = 0,
+    SECURITY = -1,
+    BAD_PARCELABLE = -2,
+    ILLEGAL_ARGUMENT = -3,
+    NULL_POINTER = -4,
+    ILLEGAL_STATE = -5,
+    NETWORK_MAIN_THREAD = -6,
+    UNSUPPORTED_OPERATION = -7,
+    SERVICE_SPECIFIC = -8,
+    PARCELABLE = -9,
+    TRANSACTION_FAILED = -129,
+}
+
+use android_c_interface_ExceptionCode as ExceptionCode;
+use android_c_interface_StatusCode as StatusCode;
+
+/// Reply header written by Java services, see `EX_HAS_REPLY_HEADER` in
+/// libbinder's `Status.h`.
+const EX_HAS_REPLY_HEADER: i32 = -128;
+const EX_HAS_NOTED_APPOPS_REPLY_HEADER: i32 = -127;
+
+pub type AIBinder_Class_onCreate = Option<unsafe extern "C" fn(args: *mut c_void) -> *mut c_void>;
+pub type AIBinder_Class_onDestroy = Option<unsafe extern "C" fn(userData: *mut c_void)>;
+pub type AIBinder_Class_onTransact = Option<
+    unsafe extern "C" fn(
+        binder: *mut AIBinder,
+        code: transaction_code_t,
+        in_: *const AParcel,
+        out: *mut AParcel,
+    ) -> binder_status_t,
+>;
+pub type AIBinder_onDump = Option<
+    unsafe extern "C" fn(
+        binder: *mut AIBinder,
+        fd: c_int,
+        args: *mut *const c_char,
+        numArgs: u32,
+    ) -> binder_status_t,
+>;
+pub type AIBinder_handleShellCommand = Option<
+    unsafe extern "C" fn(
+        binder: *mut AIBinder,
+        in_: c_int,
+        out: c_int,
+        err: c_int,
+        argv: *mut *const c_char,
+        argc: u32,
+    ) -> binder_status_t,
+>;
+pub type AIBinder_DeathRecipient_onBinderDied = Option<unsafe extern "C" fn(cookie: *mut c_void)>;
+pub type AIBinder_DeathRecipient_onBinderUnlinked =
+    Option<unsafe extern "C" fn(cookie: *mut c_void)>;
+
+pub type AParcel_stringAllocator = Option<
+    unsafe extern "C" fn(stringData: *mut c_void, length: i32, buffer: *mut *mut c_char) -> bool,
+>;
+pub type AParcel_parcelableArrayAllocator =
+    Option<unsafe extern "C" fn(arrayData: *mut c_void, length: i32) -> bool>;
+pub type AParcel_writeParcelableElement = Option<
+    unsafe extern "C" fn(
+        parcel: *mut AParcel,
+        arrayData: *const c_void,
+        index: usize,
+    ) -> binder_status_t,
+>;
+pub type AParcel_readParcelableElement = Option<
+    unsafe extern "C" fn(
+        parcel: *const AParcel,
+        arrayData: *mut c_void,
+        index: usize,
+    ) -> binder_status_t,
+>;
+pub type AParcel_int32ArrayAllocator = Option<
+    unsafe extern "C" fn(arrayData: *mut c_void, length: i32, outBuffer: *mut *mut i32) -> bool,
+>;
+pub type AParcel_uint32ArrayAllocator = Option<
+    unsafe extern "C" fn(arrayData: *mut c_void, length: i32, outBuffer: *mut *mut u32) -> bool,
+>;
+pub type AParcel_int64ArrayAllocator = Option<
+    unsafe extern "C" fn(arrayData: *mut c_void, length: i32, outBuffer: *mut *mut i64) -> bool,
+>;
+pub type AParcel_uint64ArrayAllocator = Option<
+    unsafe extern "C" fn(arrayData: *mut c_void, length: i32, outBuffer: *mut *mut u64) -> bool,
+>;
+pub type AParcel_floatArrayAllocator = Option<
+    unsafe extern "C" fn(arrayData: *mut c_void, length: i32, outBuffer: *mut *mut f32) -> bool,
+>;
+pub type AParcel_doubleArrayAllocator = Option<
+    unsafe extern "C" fn(arrayData: *mut c_void, length: i32, outBuffer: *mut *mut f64) -> bool,
+>;
+pub type AParcel_charArrayAllocator = Option<
+    unsafe extern "C" fn(
+        arrayData: *mut c_void,
+        length: i32,
+        outBuffer: *mut *mut char16_t,
+    ) -> bool,
+>;
+pub type AParcel_byteArrayAllocator = Option<
+    unsafe extern "C" fn(arrayData: *mut c_void, length: i32, outBuffer: *mut *mut i8) -> bool,
+>;
+
+pub type AServiceManager_forEachDeclaredInstance_callback =
+    Option<unsafe extern "C" fn(instance: *const c_char, context: *mut c_void)>;
+pub type AServiceManager_getUpdatableApexName_callback =
+    Option<unsafe extern "C" fn(apexName: *const c_char, context: *mut c_void)>;
+pub type AServiceManager_setActiveServicesCallback_callback =
+    Option<unsafe extern "C" fn(hasClients: bool, context: *mut c_void) -> bool>;
+pub type AServiceManager_onRegister = Option<
+    unsafe extern "C" fn(instance: *const c_char, registered: *mut AIBinder, cookie: *mut c_void),
+>;
@@ -0,0 +1,1044 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! `AParcel`, laid out exactly like libbinder's `Parcel`.
+//!
+//! Primitives are little-endian and every write is padded to 4 bytes. Strings
+//! are UTF-16 with an `i32` length prefix and a NUL terminator, arrays have an
+//! `i32` length prefix, and null strings and arrays have a length of -1.
+//! Binders and file descriptors are written as a 64-bit `flat_binder_object`;
+//! the live object is kept in a side table keyed by its offset, just like
+//! `Parcel::mObjects`.
+
+use super::ibinder::{AIBinder_decStrong, AIBinder_incStrong, Sp};
+use super::process::Process;
+use super::status::{prune_status, AStatus};
+use super::{
+    binder_status_t, char16_t, AIBinder, AParcel_byteArrayAllocator, AParcel_charArrayAllocator,
+    AParcel_doubleArrayAllocator, AParcel_floatArrayAllocator, AParcel_int32ArrayAllocator,
+    AParcel_int64ArrayAllocator, AParcel_parcelableArrayAllocator, AParcel_readParcelableElement,
+    AParcel_stringAllocator, AParcel_uint32ArrayAllocator, AParcel_uint64ArrayAllocator,
+    AParcel_writeParcelableElement, ExceptionCode, StatusCode, EX_HAS_NOTED_APPOPS_REPLY_HEADER,
+    EX_HAS_REPLY_HEADER,
+};
+
+use std::cell::Cell;
+use std::ffi::CString;
+use std::mem;
+use std::os::raw::{c_char, c_int, c_void};
+use std::os::unix::io::{AsRawFd, BorrowedFd, IntoRawFd, OwnedFd};
+use std::ptr;
+use std::slice;
+use std::sync::Arc;
+
+pub(super) type Result<T> = std::result::Result<T, StatusCode>;
+
+const fn b_pack_chars(c1: u8, c2: u8, c3: u8, c4: u8) -> u32 {
+    (c1 as u32) << 24 | (c2 as u32) << 16 | (c3 as u32) << 8 | c4 as u32
+}
+
+const BINDER_TYPE_BINDER: u32 = b_pack_chars(b's', b'b', b'*', 0x85);
+const BINDER_TYPE_HANDLE: u32 = b_pack_chars(b's', b'h', b'*', 0x85);
+const BINDER_TYPE_FD: u32 = b_pack_chars(b'f', b'd', b'*', 0x85);
+
+/// Size of a 64-bit `flat_binder_object`: type, flags, binder/handle and
+/// cookie.
+const FLAT_BINDER_OBJECT_SIZE: usize = 24;
+
+const fn pad_size(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+fn flatten_object(bytes: &mut [u8], kind: u32, value: u64) {
+    bytes[0..4].copy_from_slice(&kind.to_le_bytes());
+    bytes[8..16].copy_from_slice(&value.to_le_bytes());
+    bytes[16..24].copy_from_slice(&value.to_le_bytes());
+}
+
+/// A binder or file descriptor held by a parcel.
+#[derive(Debug)]
+pub(super) enum Object {
+    /// Owns a strong reference.
+    Binder(*mut AIBinder),
+    Fd(OwnedFd),
+}
+
+impl Object {
+    fn try_clone(&self) -> Result<Object> {
+        match self {
+            Object::Binder(binder) => {
+                // Safety: we hold a strong reference, so `binder` is live.
+                unsafe { AIBinder_incStrong(*binder) };
+                Ok(Object::Binder(*binder))
+            }
+            Object::Fd(fd) => fd.try_clone().map(Object::Fd).or(Err(StatusCode::BAD_VALUE)),
+        }
+    }
+}
+
+impl Drop for Object {
+    fn drop(&mut self) {
+        if let Object::Binder(binder) = *self {
+            // Safety: we own one strong reference to `binder`.
+            unsafe { AIBinder_decStrong(binder) };
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AParcel {
+    data: Vec<u8>,
+    position: Cell<usize>,
+    /// Objects sorted by their offset in `data`.
+    objects: Vec<(usize, Object)>,
+    /// The binder this parcel was prepared for by
+    /// `AIBinder_prepareTransaction`, holding a strong reference.
+    binder: *mut AIBinder,
+    sensitive: bool,
+    allow_fds: bool,
+}
+
+impl AParcel {
+    pub(super) fn new(binder: *mut AIBinder) -> AParcel {
+        if !binder.is_null() {
+            // Safety: the caller passes a live binder.
+            unsafe { AIBinder_incStrong(binder) };
+        }
+        AParcel {
+            data: Vec::new(),
+            position: Cell::new(0),
+            objects: Vec::new(),
+            binder,
+            sensitive: false,
+            allow_fds: true,
+        }
+    }
+
+    pub(super) fn into_raw(self) -> *mut AParcel {
+        Box::into_raw(Box::new(self))
+    }
+
+    pub(super) fn binder(&self) -> *mut AIBinder {
+        self.binder
+    }
+
+    pub(super) fn data_size(&self) -> usize {
+        self.data.len()
+    }
+
+    pub(super) fn data_position(&self) -> usize {
+        self.position.get()
+    }
+
+    pub(super) fn set_data_position(&self, position: usize) {
+        self.position.set(position)
+    }
+
+    pub(super) fn data_avail(&self) -> usize {
+        self.data.len().saturating_sub(self.position.get())
+    }
+
+    pub(super) fn objects(&self) -> impl Iterator<Item = &Object> {
+        self.objects.iter().map(|(_, object)| object)
+    }
+
+    fn free_data(&mut self) {
+        if self.sensitive {
+            for byte in self.data.iter_mut() {
+                // Safety: `byte` is a valid, aligned reference. The volatile
+                // write keeps the compiler from eliding the wipe.
+                unsafe { ptr::write_volatile(byte, 0) };
+            }
+        }
+        self.data.clear();
+        self.objects.clear();
+        self.position.set(0);
+    }
+
+    /// Reserve `len` bytes, padded to 4, at the current position and advance
+    /// past them. Objects overlapping the written range are released.
+    fn write_inplace(&mut self, len: usize) -> &mut [u8] {
+        let start = self.position.get();
+        let end = start + pad_size(len);
+        if self.data.len() < end {
+            self.data.resize(end, 0);
+        }
+        self.objects
+            .retain(|(offset, _)| offset + FLAT_BINDER_OBJECT_SIZE <= start || *offset >= end);
+        self.data[start + len..end].fill(0);
+        self.position.set(end);
+        &mut self.data[start..start + len]
+    }
+
+    pub(super) fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_inplace(bytes.len()).copy_from_slice(bytes);
+    }
+
+    pub(super) fn write_i32(&mut self, value: i32) {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    pub(super) fn write_u32(&mut self, value: u32) {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    pub(super) fn write_i64(&mut self, value: i64) {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    pub(super) fn write_u64(&mut self, value: u64) {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    /// Equivalent of `Parcel::writeString16`.
+    pub(super) fn write_string16(&mut self, string: Option<&[u16]>) -> Result<()> {
+        match string {
+            None => self.write_i32(-1),
+            Some(string) => {
+                self.write_i32(string.len().try_into().or(Err(StatusCode::BAD_VALUE))?);
+                let bytes = self.write_inplace((string.len() + 1) * mem::size_of::<u16>());
+                for (dst, c) in bytes.chunks_exact_mut(2).zip(string.iter().chain([0u16].iter())) {
+                    dst.copy_from_slice(&c.to_le_bytes());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn write_str(&mut self, string: &str) -> Result<()> {
+        self.write_string16(Some(&string.encode_utf16().collect::<Vec<_>>()))
+    }
+
+    fn write_object(&mut self, kind: u32, value: u64, object: Option<Object>) {
+        let offset = self.position.get();
+        flatten_object(self.write_inplace(FLAT_BINDER_OBJECT_SIZE), kind, value);
+        if let Some(object) = object {
+            let index = self.objects.partition_point(|(o, _)| *o < offset);
+            self.objects.insert(index, (offset, object));
+        }
+    }
+
+    /// Equivalent of `Parcel::writeStrongBinder`: a flat object followed by
+    /// the stability of the binder.
+    pub(super) fn write_binder(&mut self, binder: *mut AIBinder) {
+        if binder.is_null() {
+            // Null binders are not recorded in the object table.
+            self.write_object(BINDER_TYPE_BINDER, 0, None);
+            self.write_i32(0);
+        } else {
+            // Safety: the caller passes a live binder, and the object table
+            // takes its own strong reference.
+            let stability = unsafe {
+                AIBinder_incStrong(binder);
+                (*binder).stability_repr()
+            };
+            self.write_object(BINDER_TYPE_BINDER, binder as u64, Some(Object::Binder(binder)));
+            self.write_i32(stability);
+        }
+    }
+
+    /// Equivalent of `Parcel::writeFileDescriptor` with `takeOwnership`.
+    pub(super) fn write_fd(&mut self, fd: OwnedFd) {
+        let raw = fd.as_raw_fd();
+        self.write_object(BINDER_TYPE_FD, raw as u64, Some(Object::Fd(fd)));
+    }
+
+    /// Read `len` bytes, padded to 4, at the current position and advance past
+    /// them.
+    pub(super) fn read_inplace(&self, len: usize) -> Option<&[u8]> {
+        let start = self.position.get();
+        let end = start.checked_add(pad_size(len))?;
+        if end > self.data.len() {
+            return None;
+        }
+        self.position.set(end);
+        Some(&self.data[start..start + len])
+    }
+
+    fn read_array<const N: usize>(&self) -> Result<[u8; N]> {
+        match self.read_inplace(N) {
+            Some(bytes) => Ok(bytes.try_into().unwrap()),
+            None => Err(StatusCode::NOT_ENOUGH_DATA),
+        }
+    }
+
+    pub(super) fn read_i32(&self) -> Result<i32> {
+        self.read_array().map(i32::from_le_bytes)
+    }
+
+    pub(super) fn read_u32(&self) -> Result<u32> {
+        self.read_array().map(u32::from_le_bytes)
+    }
+
+    pub(super) fn read_i64(&self) -> Result<i64> {
+        self.read_array().map(i64::from_le_bytes)
+    }
+
+    pub(super) fn read_u64(&self) -> Result<u64> {
+        self.read_array().map(u64::from_le_bytes)
+    }
+
+    /// Equivalent of `Parcel::readString16Inplace`: a malformed string reads
+    /// as null.
+    pub(super) fn read_string16(&self) -> Option<Vec<u16>> {
+        let len = self.read_i32().ok()?;
+        if len < 0 || len == i32::MAX {
+            return None;
+        }
+        let len = len as usize;
+        let bytes = self.read_inplace((len + 1) * mem::size_of::<u16>())?;
+        let string: Vec<u16> =
+            bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+        if string[len] != 0 {
+            return None;
+        }
+        Some(string[..len].to_vec())
+    }
+
+    pub(super) fn read_str(&self) -> Result<String> {
+        let string = self.read_string16().ok_or(StatusCode::UNEXPECTED_NULL)?;
+        String::from_utf16(&string).or(Err(StatusCode::BAD_VALUE))
+    }
+
+    /// Equivalent of `Parcel::readObject`. Returns `Ok(None)` for a null
+    /// binder.
+    fn read_object(&self) -> Result<Option<(u32, &Object)>> {
+        let offset = self.position.get();
+        let bytes = self.read_inplace(FLAT_BINDER_OBJECT_SIZE).ok_or(StatusCode::BAD_TYPE)?;
+        let kind = u32::from_le_bytes(bytes[0..4].try_into().unwrap());
+        if bytes[8..24].iter().all(|b| *b == 0) {
+            return if kind == BINDER_TYPE_BINDER || kind == BINDER_TYPE_HANDLE {
+                Ok(None)
+            } else {
+                Err(StatusCode::BAD_TYPE)
+            };
+        }
+        match self.objects.binary_search_by_key(&offset, |(o, _)| *o) {
+            Ok(index) => Ok(Some((kind, &self.objects[index].1))),
+            Err(_) => Err(StatusCode::BAD_TYPE),
+        }
+    }
+
+    /// Equivalent of `Parcel::readNullableStrongBinder`. Returns an owned
+    /// strong reference.
+    pub(super) fn read_binder(&self) -> Result<*mut AIBinder> {
+        let binder = match self.read_object()? {
+            None => ptr::null_mut(),
+            Some((_, Object::Binder(binder))) => *binder,
+            Some(_) => return Err(StatusCode::BAD_TYPE),
+        };
+        let stability = self.read_i32()?;
+        if binder.is_null() {
+            return if stability == 0 { Ok(binder) } else { Err(StatusCode::BAD_TYPE) };
+        }
+        // Safety: the object table holds a strong reference to `binder`.
+        unsafe {
+            (*binder).check_stability_repr(stability)?;
+            AIBinder_incStrong(binder);
+        }
+        Ok(binder)
+    }
+
+    /// Equivalent of `Parcel::readFileDescriptor`. The descriptor remains
+    /// owned by the parcel.
+    pub(super) fn read_fd(&self) -> Result<BorrowedFd<'_>> {
+        match self.read_object()? {
+            Some((BINDER_TYPE_FD, Object::Fd(fd))) => {
+                // Safety: `fd` is owned by `self.objects` for as long as the
+                // returned borrow.
+                Ok(unsafe { BorrowedFd::borrow_raw(fd.as_raw_fd()) })
+            }
+            _ => Err(StatusCode::BAD_TYPE),
+        }
+    }
+
+    /// Equivalent of `Parcel::readParcelFileDescriptor` for a nullable
+    /// descriptor. Returns a duplicate owned by the caller, or -1 for null.
+    fn read_parcel_fd(&self) -> Result<c_int> {
+        if self.read_i32()? == 0 {
+            return Ok(-1);
+        }
+        let has_comm = self.read_i32()?;
+        let fd = self.read_fd()?.try_clone_to_owned().or(Err(StatusCode::BAD_VALUE))?;
+        if has_comm != 0 {
+            self.read_fd()?;
+        }
+        Ok(fd.into_raw_fd())
+    }
+
+    /// Equivalent of `Parcel::appendFrom`.
+    pub(super) fn append_from(&mut self, from: &AParcel, start: usize, len: usize) -> Result<()> {
+        if len == 0 {
+            return Ok(());
+        }
+        let end = start.checked_add(len).ok_or(StatusCode::BAD_VALUE)?;
+        if end > from.data.len() || len > i32::MAX as usize {
+            return Err(StatusCode::BAD_VALUE);
+        }
+        let objects = from
+            .objects
+            .iter()
+            .filter(|(offset, _)| *offset >= start && offset + FLAT_BINDER_OBJECT_SIZE <= end)
+            .map(|(offset, object)| Ok((offset - start, object.try_clone()?)))
+            .collect::<Result<Vec<_>>>()?;
+        if !self.allow_fds && objects.iter().any(|(_, object)| matches!(object, Object::Fd(_))) {
+            return Err(StatusCode::FDS_NOT_ALLOWED);
+        }
+
+        let base = self.position.get();
+        let new_end = base + len;
+        if self.data.len() < new_end {
+            self.data.resize(new_end, 0);
+        }
+        self.objects
+            .retain(|(offset, _)| offset + FLAT_BINDER_OBJECT_SIZE <= base || *offset >= new_end);
+        self.data[base..new_end].copy_from_slice(&from.data[start..end]);
+        for (offset, object) in objects {
+            let offset = base + offset;
+            let index = self.objects.partition_point(|(o, _)| *o < offset);
+            self.objects.insert(index, (offset, object));
+        }
+        self.position.set(new_end);
+        Ok(())
+    }
+
+    /// Equivalent of `AParcel_marshal`: the raw bytes `start..start + len` of
+    /// a parcel without objects.
+    fn marshal(&self, start: usize, len: usize) -> Result<&[u8]> {
+        if !self.objects.is_empty() {
+            return Err(StatusCode::INVALID_OPERATION);
+        }
+        start
+            .checked_add(len)
+            .and_then(|end| self.data.get(start..end))
+            .ok_or(StatusCode::BAD_VALUE)
+    }
+
+    /// Equivalent of `AParcel_unmarshal`: replace the contents with `bytes`,
+    /// leaving the position after them.
+    fn unmarshal(&mut self, bytes: &[u8]) {
+        self.free_data();
+        self.write_bytes(bytes);
+    }
+
+    /// Copy of the data and objects, for appending a parcel to itself.
+    fn clone_data(&self) -> Result<AParcel> {
+        let mut copy = AParcel::new(ptr::null_mut());
+        copy.data = self.data.clone();
+        copy.objects = self
+            .objects
+            .iter()
+            .map(|(offset, object)| Ok((*offset, object.try_clone()?)))
+            .collect::<Result<_>>()?;
+        Ok(copy)
+    }
+
+    /// Copy this parcel into `process` for delivery to `binder`, as the kernel
+    /// does for a transaction: every binder becomes the object `process` sees
+    /// for it, and file descriptors are duplicated.
+    pub(super) fn translate(
+        &self,
+        binder: *mut AIBinder,
+        process: &Arc<Process>,
+    ) -> Result<AParcel> {
+        let mut parcel = AParcel::new(binder);
+        parcel.data = self.data.clone();
+        for (offset, object) in &self.objects {
+            let (kind, object) = match object {
+                Object::Binder(binder) => {
+                    // Safety: the object table holds a strong reference.
+                    let node = unsafe { Sp::new(*binder) }.unwrap().node()?;
+                    let binder = process.import(&node);
+                    let kind =
+                        if binder.is_proxy() { BINDER_TYPE_HANDLE } else { BINDER_TYPE_BINDER };
+                    (kind, Object::Binder(binder.into_raw()))
+                }
+                Object::Fd(fd) => {
+                    let fd = fd.try_clone().or(Err(StatusCode::BAD_VALUE))?;
+                    (BINDER_TYPE_FD, Object::Fd(fd))
+                }
+            };
+            let value = match &object {
+                Object::Binder(binder) => *binder as u64,
+                Object::Fd(fd) => fd.as_raw_fd() as u64,
+            };
+            flatten_object(
+                &mut parcel.data[*offset..*offset + FLAT_BINDER_OBJECT_SIZE],
+                kind,
+                value,
+            );
+            parcel.objects.push((*offset, object));
+        }
+        Ok(parcel)
+    }
+
+    /// Equivalent of `Status::writeToParcel`.
+    pub(super) fn write_status(&mut self, status: &AStatus) -> Result<()> {
+        if status.exception == ExceptionCode::TRANSACTION_FAILED as i32 {
+            return Err(status_code(status.error_code));
+        }
+        self.write_i32(status.exception);
+        if status.exception == ExceptionCode::NONE as i32 {
+            return Ok(());
+        }
+        self.write_str(&status.message.to_string_lossy())?;
+        // Empty remote stack trace header.
+        self.write_i32(0);
+        if status.exception == ExceptionCode::SERVICE_SPECIFIC as i32 {
+            self.write_i32(status.error_code);
+        } else if status.exception == ExceptionCode::PARCELABLE as i32 {
+            self.write_i32(0);
+        }
+        Ok(())
+    }
+
+    /// Equivalent of `Status::readFromParcel`.
+    pub(super) fn read_status(&self) -> Result<AStatus> {
+        let mut exception = self.read_i32()?;
+        if exception == EX_HAS_NOTED_APPOPS_REPLY_HEADER {
+            self.skip_header()?;
+            exception = self.read_i32()?;
+        }
+        if exception == EX_HAS_REPLY_HEADER {
+            self.skip_header()?;
+            exception = ExceptionCode::NONE as i32;
+        }
+        if exception == ExceptionCode::NONE as i32 {
+            return Ok(AStatus::ok());
+        }
+        let message = self.read_str()?;
+        // Skip over the remote stack trace.
+        self.skip_header()?;
+        let error_code = if exception == ExceptionCode::SERVICE_SPECIFIC as i32 {
+            self.read_i32()?
+        } else {
+            if exception == ExceptionCode::PARCELABLE as i32 {
+                self.skip_header()?;
+            }
+            0
+        };
+        let message = CString::new(message.replace('\0', "")).unwrap();
+        Ok(AStatus::new(exception, error_code, message))
+    }
+
+    /// Skip over a header which starts with its own `i32` size. A size of
+    /// zero means an empty header of just the size field.
+    fn skip_header(&self) -> Result<()> {
+        let start = self.position.get();
+        let size = self.read_i32()?;
+        if size < 0 || size as usize > self.data.len() - start {
+            return Err(StatusCode::BAD_VALUE);
+        }
+        if size != 0 {
+            self.position.set(start + size as usize);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for AParcel {
+    fn drop(&mut self) {
+        self.free_data();
+        if !self.binder.is_null() {
+            // Safety: `AParcel::new` took a strong reference to the binder.
+            unsafe { AIBinder_decStrong(self.binder) };
+        }
+    }
+}
+
+/// Convert a `binder_status_t` into a `StatusCode`, mapping unknown values to
+/// `UNKNOWN_ERROR`.
+pub(super) fn status_code(status: binder_status_t) -> StatusCode {
+    let status = prune_status(status);
+    // Safety: `prune_status` only returns values of `StatusCode` variants.
+    unsafe { mem::transmute::<i32, StatusCode>(status) }
+}
+
+pub(super) fn to_status_t(result: Result<()>) -> binder_status_t {
+    match result {
+        Ok(()) => StatusCode::OK as i32,
+        Err(e) => e as i32,
+    }
+}
+
+unsafe fn parcel_ref<'a>(parcel: *const AParcel) -> &'a AParcel {
+    &*parcel
+}
+
+unsafe fn parcel_mut<'a>(parcel: *mut AParcel) -> &'a mut AParcel {
+    &mut *parcel
+}
+
+pub unsafe fn AParcel_create() -> *mut AParcel {
+    AParcel::new(ptr::null_mut()).into_raw()
+}
+
+pub unsafe fn AParcel_delete(parcel: *mut AParcel) {
+    if !parcel.is_null() {
+        drop(Box::from_raw(parcel));
+    }
+}
+
+pub unsafe fn AParcel_reset(parcel: *mut AParcel) -> binder_status_t {
+    parcel_mut(parcel).free_data();
+    StatusCode::OK as i32
+}
+
+pub unsafe fn AParcel_markSensitive(parcel: *const AParcel) {
+    // The NDK takes a const parcel here as well; the flag only affects how the
+    // data is freed.
+    (*(parcel as *mut AParcel)).sensitive = true;
+}
+
+pub unsafe fn AParcel_setDataPosition(parcel: *const AParcel, position: i32) -> binder_status_t {
+    if position < 0 {
+        return StatusCode::BAD_VALUE as i32;
+    }
+    parcel_ref(parcel).set_data_position(position as usize);
+    StatusCode::OK as i32
+}
+
+pub unsafe fn AParcel_getDataPosition(parcel: *const AParcel) -> i32 {
+    parcel_ref(parcel).data_position() as i32
+}
+
+pub unsafe fn AParcel_getDataSize(parcel: *const AParcel) -> i32 {
+    parcel_ref(parcel).data_size() as i32
+}
+
+pub unsafe fn AParcel_appendFrom(
+    from: *const AParcel,
+    to: *mut AParcel,
+    start: i32,
+    size: i32,
+) -> binder_status_t {
+    if start < 0 || size < 0 {
+        return StatusCode::BAD_VALUE as i32;
+    }
+    if ptr::eq(from, to) {
+        let result = parcel_ref(from)
+            .clone_data()
+            .and_then(|from| parcel_mut(to).append_from(&from, start as usize, size as usize));
+        return to_status_t(result);
+    }
+    to_status_t(parcel_mut(to).append_from(parcel_ref(from), start as usize, size as usize))
+}
+
+pub unsafe fn AParcel_marshal(
+    parcel: *const AParcel,
+    buffer: *mut u8,
+    start: usize,
+    len: usize,
+) -> binder_status_t {
+    match parcel_ref(parcel).marshal(start, len) {
+        Ok(data) => {
+            if !data.is_empty() {
+                ptr::copy_nonoverlapping(data.as_ptr(), buffer, data.len());
+            }
+            StatusCode::OK as i32
+        }
+        Err(e) => e as i32,
+    }
+}
+
+pub unsafe fn AParcel_unmarshal(
+    parcel: *mut AParcel,
+    buffer: *const u8,
+    len: usize,
+) -> binder_status_t {
+    let bytes = if len == 0 { &[][..] } else { slice::from_raw_parts(buffer, len) };
+    parcel_mut(parcel).unmarshal(bytes);
+    StatusCode::OK as i32
+}
+
+pub unsafe fn AParcel_getAllowFds(parcel: *const AParcel) -> bool {
+    parcel_ref(parcel).allow_fds
+}
+
+/// Not in the NDK, which can only query it: the equivalent of
+/// `Parcel::setAllowFds`. Once disallowed, writing or appending a file
+/// descriptor fails with `FDS_NOT_ALLOWED`.
+pub unsafe fn AParcel_setAllowFds(parcel: *mut AParcel, allow_fds: bool) {
+    parcel_mut(parcel).allow_fds = allow_fds;
+}
+
+/// Not in the NDK, which has no way to read without copying: the equivalent of
+/// `Parcel::readInplace`. Returns the next `len` bytes and advances past them,
+/// padded to 4, or returns null if there aren't that many.
+pub unsafe fn AParcel_readInplace(parcel: *const AParcel, len: usize) -> *const c_void {
+    match parcel_ref(parcel).read_inplace(len) {
+        Some(bytes) => bytes.as_ptr() as *const c_void,
+        None => ptr::null(),
+    }
+}
+
+pub unsafe fn AParcel_writeStrongBinder(
+    parcel: *mut AParcel,
+    binder: *mut AIBinder,
+) -> binder_status_t {
+    parcel_mut(parcel).write_binder(binder);
+    StatusCode::OK as i32
+}
+
+pub unsafe fn AParcel_readStrongBinder(
+    parcel: *const AParcel,
+    binder: *mut *mut AIBinder,
+) -> binder_status_t {
+    match parcel_ref(parcel).read_binder() {
+        Ok(b) => {
+            *binder = b;
+            StatusCode::OK as i32
+        }
+        Err(e) => e as i32,
+    }
+}
+
+pub unsafe fn AParcel_writeParcelFileDescriptor(
+    parcel: *mut AParcel,
+    fd: c_int,
+) -> binder_status_t {
+    let parcel = parcel_mut(parcel);
+    if fd < 0 {
+        if fd != -1 {
+            return StatusCode::UNKNOWN_ERROR as i32;
+        }
+        parcel.write_i32(0);
+        return StatusCode::OK as i32;
+    }
+    if !parcel.allow_fds {
+        return StatusCode::FDS_NOT_ALLOWED as i32;
+    }
+    let fd = match BorrowedFd::borrow_raw(fd).try_clone_to_owned() {
+        Ok(fd) => fd,
+        Err(_) => return StatusCode::BAD_VALUE as i32,
+    };
+    parcel.write_i32(1);
+    // No comm channel.
+    parcel.write_i32(0);
+    parcel.write_fd(fd);
+    StatusCode::OK as i32
+}
+
+pub unsafe fn AParcel_readParcelFileDescriptor(
+    parcel: *const AParcel,
+    fd: *mut c_int,
+) -> binder_status_t {
+    let result = parcel_ref(parcel).read_parcel_fd();
+    match result {
+        Ok(value) => {
+            *fd = value;
+            StatusCode::OK as i32
+        }
+        Err(e) => e as i32,
+    }
+}
+
+pub unsafe fn AParcel_writeStatusHeader(
+    parcel: *mut AParcel,
+    status: *const AStatus,
+) -> binder_status_t {
+    to_status_t(parcel_mut(parcel).write_status(&*status))
+}
+
+pub unsafe fn AParcel_readStatusHeader(
+    parcel: *const AParcel,
+    status: *mut *mut AStatus,
+) -> binder_status_t {
+    match parcel_ref(parcel).read_status() {
+        Ok(s) => {
+            *status = Box::into_raw(Box::new(s));
+            StatusCode::OK as i32
+        }
+        Err(e) => e as i32,
+    }
+}
+
+pub unsafe fn AParcel_writeString(
+    parcel: *mut AParcel,
+    string: *const c_char,
+    length: i32,
+) -> binder_status_t {
+    let parcel = parcel_mut(parcel);
+    if string.is_null() {
+        if length != -1 {
+            return StatusCode::UNEXPECTED_NULL as i32;
+        }
+        parcel.write_i32(-1);
+        return StatusCode::OK as i32;
+    }
+    if length < 0 {
+        return StatusCode::BAD_VALUE as i32;
+    }
+    let bytes = slice::from_raw_parts(string as *const u8, length as usize);
+    match std::str::from_utf8(bytes) {
+        Ok(s) => to_status_t(parcel.write_str(s)),
+        Err(_) => StatusCode::BAD_VALUE as i32,
+    }
+}
+
+pub unsafe fn AParcel_readString(
+    parcel: *const AParcel,
+    stringData: *mut c_void,
+    allocator: AParcel_stringAllocator,
+) -> binder_status_t {
+    let allocator = allocator.unwrap();
+    let mut buffer = ptr::null_mut();
+    let string = match parcel_ref(parcel).read_string16() {
+        Some(string) => string,
+        None => {
+            return if allocator(stringData, -1, &mut buffer) {
+                StatusCode::OK as i32
+            } else {
+                StatusCode::UNEXPECTED_NULL as i32
+            }
+        }
+    };
+    let string = match String::from_utf16(&string) {
+        Ok(s) => s,
+        Err(_) => return StatusCode::BAD_VALUE as i32,
+    };
+    let len = string.len() + 1;
+    if len >= i32::MAX as usize {
+        return StatusCode::BAD_VALUE as i32;
+    }
+    if !allocator(stringData, len as i32, &mut buffer) {
+        return StatusCode::NO_MEMORY as i32;
+    }
+    if buffer.is_null() {
+        return StatusCode::UNEXPECTED_NULL as i32;
+    }
+    ptr::copy_nonoverlapping(string.as_ptr(), buffer as *mut u8, string.len());
+    *buffer.add(string.len()) = 0;
+    StatusCode::OK as i32
+}
+
+/// Write an array length, validating it against the nullness of the array.
+fn write_array_size(parcel: &mut AParcel, is_null: bool, length: i32) -> Result<()> {
+    if length < -1 || (!is_null && length < 0) {
+        return Err(StatusCode::BAD_VALUE);
+    }
+    if is_null && length > 0 {
+        return Err(StatusCode::UNEXPECTED_NULL);
+    }
+    parcel.write_i32(length);
+    Ok(())
+}
+
+/// Read an array length, checking that `element_size` bytes per element are
+/// available before anything is allocated for it.
+fn read_array_size(parcel: &AParcel, element_size: usize) -> Result<i32> {
+    let length = parcel.read_i32()?;
+    if length < -1 {
+        return Err(StatusCode::BAD_VALUE);
+    }
+    if length > 0 {
+        let size = (length as usize).checked_mul(element_size).ok_or(StatusCode::NO_MEMORY)?;
+        if size > parcel.data_avail() {
+            return Err(StatusCode::NOT_ENOUGH_DATA);
+        }
+    }
+    Ok(length)
+}
+
+/// Equivalent of the NDK's `WriteArray`, for element types which are copied
+/// into the parcel as-is.
+unsafe fn write_packed_array<T: Copy>(
+    parcel: *mut AParcel,
+    array: *const T,
+    length: i32,
+) -> Result<()> {
+    let parcel = parcel_mut(parcel);
+    write_array_size(parcel, array.is_null(), length)?;
+    if length <= 0 {
+        return Ok(());
+    }
+    let size = mem::size_of::<T>().checked_mul(length as usize).ok_or(StatusCode::NO_MEMORY)?;
+    parcel.write_bytes(slice::from_raw_parts(array as *const u8, size));
+    Ok(())
+}
+
+/// Equivalent of the NDK's `ReadArray`, for element types which are copied
+/// out of the parcel as-is.
+unsafe fn read_packed_array<T: Copy>(
+    parcel: *const AParcel,
+    array_data: *mut c_void,
+    allocator: unsafe extern "C" fn(*mut c_void, i32, *mut *mut T) -> bool,
+) -> Result<()> {
+    let parcel = parcel_ref(parcel);
+    let length = read_array_size(parcel, mem::size_of::<T>())?;
+    let mut buffer = ptr::null_mut();
+    if !allocator(array_data, length, &mut buffer) {
+        return Err(StatusCode::NO_MEMORY);
+    }
+    if length <= 0 {
+        return Ok(());
+    }
+    let size = mem::size_of::<T>() * length as usize;
+    let data = parcel.read_inplace(size).ok_or(StatusCode::NO_MEMORY)?;
+    ptr::copy_nonoverlapping(data.as_ptr(), buffer as *mut u8, size);
+    Ok(())
+}
+
+macro_rules! parcel_primitives {
+    ($($ty:ty, $write:ident, $read:ident, $to:expr, $from:expr, $raw:ident;)*) => {
+        $(
+            pub unsafe fn $write(parcel: *mut AParcel, value: $ty) -> binder_status_t {
+                let to: fn($ty) -> _ = $to;
+                parcel_mut(parcel).write_bytes(&to(value));
+                StatusCode::OK as i32
+            }
+
+            pub unsafe fn $read(parcel: *const AParcel, value: *mut $ty) -> binder_status_t {
+                let from: fn(_) -> $ty = $from;
+                match parcel_ref(parcel).$raw() {
+                    Ok(v) => {
+                        *value = from(v);
+                        StatusCode::OK as i32
+                    }
+                    Err(e) => e as i32,
+                }
+            }
+        )*
+    };
+}
+
+parcel_primitives! {
+    i32, AParcel_writeInt32, AParcel_readInt32, i32::to_le_bytes, |v: i32| v, read_i32;
+    u32, AParcel_writeUint32, AParcel_readUint32, u32::to_le_bytes, |v: u32| v, read_u32;
+    i64, AParcel_writeInt64, AParcel_readInt64, i64::to_le_bytes, |v: i64| v, read_i64;
+    u64, AParcel_writeUint64, AParcel_readUint64, u64::to_le_bytes, |v: u64| v, read_u64;
+    f32, AParcel_writeFloat, AParcel_readFloat, f32::to_le_bytes, f32::from_bits, read_u32;
+    f64, AParcel_writeDouble, AParcel_readDouble, f64::to_le_bytes, f64::from_bits, read_u64;
+    bool, AParcel_writeBool, AParcel_readBool, |v: bool| (v as i32).to_le_bytes(), |v: i32| v != 0, read_i32;
+    char16_t, AParcel_writeChar, AParcel_readChar, |v: u16| (v as i32).to_le_bytes(), |v: i32| v as u16, read_i32;
+    i8, AParcel_writeByte, AParcel_readByte, |v: i8| (v as i32).to_le_bytes(), |v: i32| v as i8, read_i32;
+}
+
+macro_rules! packed_arrays {
+    ($($ty:ty, $write:ident, $read:ident, $allocator:ty;)*) => {
+        $(
+            pub unsafe fn $write(
+                parcel: *mut AParcel,
+                arrayData: *const $ty,
+                length: i32,
+            ) -> binder_status_t {
+                to_status_t(write_packed_array(parcel, arrayData, length))
+            }
+
+            pub unsafe fn $read(
+                parcel: *const AParcel,
+                arrayData: *mut c_void,
+                allocator: $allocator,
+            ) -> binder_status_t {
+                to_status_t(read_packed_array(parcel, arrayData, allocator.unwrap()))
+            }
+        )*
+    };
+}
+
+packed_arrays! {
+    i32, AParcel_writeInt32Array, AParcel_readInt32Array, AParcel_int32ArrayAllocator;
+    u32, AParcel_writeUint32Array, AParcel_readUint32Array, AParcel_uint32ArrayAllocator;
+    i64, AParcel_writeInt64Array, AParcel_readInt64Array, AParcel_int64ArrayAllocator;
+    u64, AParcel_writeUint64Array, AParcel_readUint64Array, AParcel_uint64ArrayAllocator;
+    f32, AParcel_writeFloatArray, AParcel_readFloatArray, AParcel_floatArrayAllocator;
+    f64, AParcel_writeDoubleArray, AParcel_readDoubleArray, AParcel_doubleArrayAllocator;
+    i8, AParcel_writeByteArray, AParcel_readByteArray, AParcel_byteArrayAllocator;
+}
+
+// Unlike the other primitive arrays, each element of a char16_t array is
+// widened to an int32_t.
+
+pub unsafe fn AParcel_writeCharArray(
+    parcel: *mut AParcel,
+    arrayData: *const char16_t,
+    length: i32,
+) -> binder_status_t {
+    let parcel = parcel_mut(parcel);
+    let result = write_array_size(parcel, arrayData.is_null(), length);
+    if result.is_err() || length <= 0 {
+        return to_status_t(result);
+    }
+    for c in slice::from_raw_parts(arrayData, length as usize) {
+        parcel.write_i32(*c as i32);
+    }
+    StatusCode::OK as i32
+}
+
+pub unsafe fn AParcel_readCharArray(
+    parcel: *const AParcel,
+    arrayData: *mut c_void,
+    allocator: AParcel_charArrayAllocator,
+) -> binder_status_t {
+    let parcel = parcel_ref(parcel);
+    let result = (|| {
+        let length = read_array_size(parcel, mem::size_of::<i32>())?;
+        let mut buffer = ptr::null_mut();
+        if !allocator.unwrap()(arrayData, length, &mut buffer) {
+            return Err(StatusCode::NO_MEMORY);
+        }
+        for i in 0..length.max(0) as usize {
+            *buffer.add(i) = parcel.read_i32()? as char16_t;
+        }
+        Ok(())
+    })();
+    to_status_t(result)
+}
+
+pub unsafe fn AParcel_writeParcelableArray(
+    parcel: *mut AParcel,
+    arrayData: *const c_void,
+    length: i32,
+    elementWriter: AParcel_writeParcelableElement,
+) -> binder_status_t {
+    let result = write_array_size(parcel_mut(parcel), length < 0, length);
+    if result.is_err() || length <= 0 {
+        return to_status_t(result);
+    }
+    let element_writer = elementWriter.unwrap();
+    for i in 0..length as usize {
+        let status = element_writer(parcel, arrayData, i);
+        if status != StatusCode::OK as i32 {
+            return status;
+        }
+    }
+    StatusCode::OK as i32
+}
+
+pub unsafe fn AParcel_readParcelableArray(
+    parcel: *const AParcel,
+    arrayData: *mut c_void,
+    allocator: AParcel_parcelableArrayAllocator,
+    elementReader: AParcel_readParcelableElement,
+) -> binder_status_t {
+    // Every element AIDL can put in an array takes at least 4 bytes, so this
+    // rejects impossible lengths before they are allocated.
+    let length = match read_array_size(parcel_ref(parcel), mem::size_of::<i32>()) {
+        Ok(length) => length,
+        Err(e) => return e as i32,
+    };
+    if !allocator.unwrap()(arrayData, length) {
+        return StatusCode::NO_MEMORY as i32;
+    }
+    let element_reader = elementReader.unwrap();
+    for i in 0..length.max(0) as usize {
+        let status = element_reader(parcel, arrayData, i);
+        if status != StatusCode::OK as i32 {
+            return status;
+        }
+    }
+    StatusCode::OK as i32
+}
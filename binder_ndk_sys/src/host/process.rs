@@ -0,0 +1,521 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Simulated processes of the loopback transport, and the thread pool entry
+//! points.
+//!
+//! A [`HostProcess`] is a thread standing in for a separate process: binders
+//! created on it belong to it, and every other process only ever sees proxies
+//! for them. All remaining threads belong to the main process. Transactions
+//! are dispatched on the calling thread, which takes on the identity of the
+//! target process for the duration of the call, unless a thread of the target
+//! process set up polling: they are then queued for that thread.
+
+use super::ibinder::Sp;
+use super::manager::{clients_changed, remove_services_of, LazyServices};
+use super::{binder_status_t, AIBinder, StatusCode};
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::ffi::CString;
+use std::fmt;
+use std::os::raw::{c_int, c_void};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::thread::{self, JoinHandle, ThreadId};
+
+#[derive(Debug, Default)]
+struct Lifecycle {
+    /// Set once the process has finished starting up, by joining its thread
+    /// pool or returning.
+    started: bool,
+    alive: bool,
+    /// Set once the process has been torn down by [`Process::exit`].
+    exited: bool,
+}
+
+#[derive(Debug)]
+pub(super) struct Process {
+    lifecycle: Mutex<Lifecycle>,
+    changed: Condvar,
+    /// Proxies held by other processes for binders owned by this one.
+    remote_refs: Mutex<Vec<Weak<AIBinder>>>,
+    /// The proxies this process holds, by the address of their local binder.
+    proxies: Mutex<BTreeMap<usize, Weak<AIBinder>>>,
+    pub(super) lazy: LazyServices,
+    /// Set up by `ABinderProcess_setupPolling`.
+    polling: Mutex<Option<Polling>>,
+}
+
+/// The state of polling for commands in a process.
+#[derive(Debug)]
+struct Polling {
+    /// The eventfd handed out by `ABinderProcess_setupPolling`, which is
+    /// readable while commands are queued, and stays open once the polling
+    /// thread is gone.
+    fd: OwnedFd,
+    /// The thread handling commands, until it exits.
+    thread: Option<ThreadId>,
+    commands: VecDeque<Arc<PolledCommand>>,
+}
+
+impl Polling {
+    /// Make the eventfd readable.
+    fn signal(&self) {
+        let one = 1u64;
+        // Safety: `one` is valid for reads of its size. Writing only fails
+        // if the counter would overflow, in which case it's readable anyway.
+        unsafe { libc::write(self.fd.as_raw_fd(), &one as *const u64 as *const c_void, 8) };
+    }
+}
+
+/// A job run for an incoming transaction. The lifetime of its borrows is
+/// erased, see [`Process::run_transaction`].
+type Job = Box<dyn FnOnce() + Send>;
+
+/// An incoming transaction queued for the polling thread of its process.
+struct PolledCommand {
+    state: Mutex<CommandState>,
+    changed: Condvar,
+}
+
+enum CommandState {
+    Queued(Job),
+    Running,
+    Done,
+    /// The polling thread exited before taking the command, which is handed
+    /// back to the caller.
+    Abandoned(Job),
+}
+
+impl PolledCommand {
+    /// Run the command on the polling thread.
+    fn run(&self) {
+        let job = match std::mem::replace(&mut *self.state.lock().unwrap(), CommandState::Running) {
+            CommandState::Queued(job) => job,
+            _ => unreachable!("Polled command taken twice"),
+        };
+        job();
+        *self.state.lock().unwrap() = CommandState::Done;
+        self.changed.notify_all();
+    }
+
+    /// Hand a command still in the queue back to its caller.
+    fn abandon(&self) {
+        let mut state = self.state.lock().unwrap();
+        match std::mem::replace(&mut *state, CommandState::Running) {
+            CommandState::Queued(job) => *state = CommandState::Abandoned(job),
+            _ => unreachable!("Abandoned a polled command which was taken"),
+        }
+        self.changed.notify_all();
+    }
+
+    /// Wait for the command to be handled, returning its job if it was
+    /// abandoned instead.
+    fn wait(&self) -> Option<Job> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            match std::mem::replace(&mut *state, CommandState::Done) {
+                CommandState::Done => return None,
+                CommandState::Abandoned(job) => return Some(job),
+                pending => *state = pending,
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+}
+
+impl fmt::Debug for PolledCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PolledCommand").finish_non_exhaustive()
+    }
+}
+
+impl Process {
+    pub(super) fn new() -> Process {
+        Process {
+            lifecycle: Mutex::new(Lifecycle { alive: true, ..Default::default() }),
+            changed: Condvar::new(),
+            remote_refs: Mutex::new(Vec::new()),
+            proxies: Mutex::new(BTreeMap::new()),
+            lazy: LazyServices::default(),
+            polling: Mutex::new(None),
+        }
+    }
+
+    pub(super) fn is_alive(&self) -> bool {
+        self.lifecycle.lock().unwrap().alive
+    }
+
+    fn set_started(&self) {
+        self.lifecycle.lock().unwrap().started = true;
+        self.changed.notify_all();
+    }
+
+    fn is_main(self: &Arc<Self>) -> bool {
+        MAIN.lock().unwrap().as_ref().map_or(false, |main| Arc::ptr_eq(main, self))
+    }
+
+    /// Make the process exit, as it would by calling `exit()`. The main
+    /// process is the test binary itself, which keeps running.
+    pub(super) fn exit_voluntarily(self: &Arc<Self>) {
+        if !self.is_main() {
+            self.kill();
+        }
+    }
+
+    fn kill(&self) {
+        self.lifecycle.lock().unwrap().alive = false;
+        self.changed.notify_all();
+        // Wake up the polling thread, for it to find out.
+        if let Some(polling) = &*self.polling.lock().unwrap() {
+            polling.signal();
+        }
+    }
+
+    /// Tear down a dead process, as the kernel does once the last thread of a
+    /// process exits: its services are unregistered and every proxy for one
+    /// of its binders is told that it died.
+    fn exit(self: &Arc<Self>) {
+        {
+            let mut lifecycle = self.lifecycle.lock().unwrap();
+            if lifecycle.exited {
+                return;
+            }
+            lifecycle.alive = false;
+            lifecycle.exited = true;
+        }
+        self.changed.notify_all();
+        remove_services_of(self);
+        let remote_refs = std::mem::take(&mut *self.remote_refs.lock().unwrap());
+        for proxy in remote_refs.iter().filter_map(Weak::upgrade) {
+            proxy.binder_died();
+        }
+    }
+
+    /// Return the binder this process sees for the local binder `node`: the
+    /// binder itself if this process owns it, otherwise a proxy for it.
+    pub(super) fn import(self: &Arc<Self>, node: &Sp) -> Sp {
+        if Arc::ptr_eq(node.owner(), self) {
+            return node.clone();
+        }
+        let mut proxies = self.proxies.lock().unwrap();
+        let key = node.as_ptr() as usize;
+        if let Some(proxy) = proxies.get(&key).and_then(Weak::upgrade) {
+            if proxy.is_proxy_for(node) {
+                return Sp::from_arc(proxy);
+            }
+        }
+        let proxy = AIBinder::new_proxy(node.clone(), self.clone());
+        proxies.insert(key, Arc::downgrade(&proxy));
+        proxies.retain(|_, proxy| proxy.strong_count() > 0);
+        node.owner().remote_refs.lock().unwrap().push(Arc::downgrade(&proxy));
+        drop(proxies);
+        clients_changed(node.owner());
+        Sp::from_arc(proxy)
+    }
+
+    /// Whether another process holds a proxy for `node`, one of the binders
+    /// of this process.
+    pub(super) fn has_clients(&self, node: &Sp) -> bool {
+        let proxies: Vec<Arc<AIBinder>> = {
+            let mut remote_refs = self.remote_refs.lock().unwrap();
+            remote_refs.retain(|proxy| proxy.strong_count() > 0);
+            remote_refs.iter().filter_map(Weak::upgrade).collect()
+        };
+        // Checked and released outside of the lock, as releasing the last
+        // reference to a proxy reports that its node lost a client.
+        proxies.iter().any(|proxy| proxy.is_proxy_for(node))
+    }
+
+    /// Run `f` as an incoming transaction of this process, on behalf of a
+    /// caller with security context `sid`.
+    pub(super) fn dispatch<T>(self: &Arc<Self>, sid: Option<CString>, f: impl FnOnce() -> T) -> T {
+        let previous = CURRENT.with(|current| current.replace(Some(self.clone())));
+        CALLS.with(|calls| calls.borrow_mut().push(CallContext { sid }));
+        let result = f();
+        CALLS.with(|calls| calls.borrow_mut().pop());
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+        result
+    }
+
+    /// Run `f` as an incoming binder transaction of this process, like
+    /// [`dispatch`](Self::dispatch), but on the polling thread of the process
+    /// if it has one.
+    pub(super) fn dispatch_transaction<T>(
+        self: &Arc<Self>,
+        sid: Option<CString>,
+        f: impl FnOnce() -> T,
+    ) -> T {
+        let mut result = None;
+        self.run_transaction(Box::new(|| result = Some(self.dispatch(sid, f))));
+        result.expect("Polled command was not run")
+    }
+
+    /// Run `job` on the polling thread if there is one, otherwise on the
+    /// calling thread.
+    fn run_transaction<'a>(&self, job: Box<dyn FnOnce() + 'a>) {
+        let command = {
+            let mut polling = self.polling.lock().unwrap();
+            match &mut *polling {
+                Some(polling) if polling.thread.map_or(false, |t| t != thread::current().id()) => {
+                    // Safety: this doesn't return before the job is run and
+                    // dropped, either by the polling thread before `wait`
+                    // returns or here once handed back, so its borrows
+                    // outlive it. The caller waits meanwhile, as it would for
+                    // the transaction.
+                    let job: Job = unsafe { std::mem::transmute(job) };
+                    let command = Arc::new(PolledCommand {
+                        state: Mutex::new(CommandState::Queued(job)),
+                        changed: Condvar::new(),
+                    });
+                    polling.commands.push_back(command.clone());
+                    polling.signal();
+                    Ok(command)
+                }
+                _ => Err(job),
+            }
+        };
+        match command {
+            Ok(command) => {
+                if let Some(job) = command.wait() {
+                    job();
+                }
+            }
+            Err(job) => job(),
+        }
+    }
+
+    /// Take the next command queued for the polling thread.
+    fn next_polled_command(&self) -> Option<Arc<PolledCommand>> {
+        self.polling.lock().unwrap().as_mut()?.commands.pop_front()
+    }
+
+    /// Stop polling on `thread`, which is exiting, handing its pending
+    /// commands back to their callers.
+    fn stop_polling(&self, thread: ThreadId) {
+        let commands = match &mut *self.polling.lock().unwrap() {
+            Some(polling) if polling.thread == Some(thread) => {
+                polling.thread = None;
+                std::mem::take(&mut polling.commands)
+            }
+            _ => return,
+        };
+        for command in commands {
+            command.abandon();
+        }
+    }
+}
+
+/// Stops polling when the polling thread exits.
+struct PollingGuard {
+    process: Arc<Process>,
+    thread: ThreadId,
+}
+
+impl Drop for PollingGuard {
+    fn drop(&mut self) {
+        self.process.stop_polling(self.thread);
+    }
+}
+
+/// The caller of an incoming transaction.
+struct CallContext {
+    sid: Option<CString>,
+}
+
+thread_local! {
+    /// The process this thread is running as, if not the main process.
+    static CURRENT: RefCell<Option<Arc<Process>>> = const { RefCell::new(None) };
+    /// Incoming transactions being handled by this thread, innermost last.
+    static CALLS: RefCell<Vec<CallContext>> = const { RefCell::new(Vec::new()) };
+    /// Set if this thread set up polling.
+    static POLLING: RefCell<Option<PollingGuard>> = const { RefCell::new(None) };
+}
+
+static MAIN: Mutex<Option<Arc<Process>>> = Mutex::new(None);
+
+/// The process the calling thread is running as.
+pub(super) fn current_process() -> Arc<Process> {
+    CURRENT.with(|current| current.borrow().clone()).unwrap_or_else(|| {
+        MAIN.lock().unwrap().get_or_insert_with(|| Arc::new(Process::new())).clone()
+    })
+}
+
+pub(super) fn is_handling_transaction() -> bool {
+    CALLS.with(|calls| !calls.borrow().is_empty())
+}
+
+/// Security context of the caller of the innermost incoming transaction. The
+/// pointer is valid until that transaction returns.
+pub(super) fn calling_sid() -> *const std::os::raw::c_char {
+    CALLS.with(|calls| {
+        calls
+            .borrow()
+            .last()
+            .and_then(|call| call.sid.as_ref())
+            .map_or(std::ptr::null(), |sid| sid.as_ptr())
+    })
+}
+
+/// Security context of this process, which every simulated process shares.
+pub(super) fn security_context() -> CString {
+    let context = std::fs::read("/proc/self/attr/current").unwrap_or_default();
+    let context: Vec<u8> = context.into_iter().take_while(|b| *b != 0 && *b != b'\n').collect();
+    if context.is_empty() {
+        CString::new("unconfined").unwrap()
+    } else {
+        CString::new(context).unwrap()
+    }
+}
+
+/// A thread standing in for a separate process, for the loopback transport.
+///
+/// The process dies when it is killed, or when the function it was spawned
+/// with returns. Its services are then unregistered and death notifications
+/// are sent for all of its binders.
+#[derive(Debug)]
+pub struct HostProcess {
+    process: Arc<Process>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HostProcess {
+    /// Spawn a process running `main`, and wait for it to start serving
+    /// transactions with `ABinderProcess_joinThreadPool` or to return.
+    pub fn spawn<F: FnOnce() + Send + 'static>(main: F) -> HostProcess {
+        struct ExitGuard(Arc<Process>);
+
+        impl Drop for ExitGuard {
+            fn drop(&mut self) {
+                self.0.set_started();
+                self.0.exit();
+            }
+        }
+
+        let process = Arc::new(Process::new());
+        let thread = {
+            let process = process.clone();
+            thread::spawn(move || {
+                CURRENT.with(|current| *current.borrow_mut() = Some(process.clone()));
+                let _guard = ExitGuard(process);
+                main();
+            })
+        };
+        let mut lifecycle = process.lifecycle.lock().unwrap();
+        while !lifecycle.started {
+            lifecycle = process.changed.wait(lifecycle).unwrap();
+        }
+        drop(lifecycle);
+        HostProcess { process, thread: Some(thread) }
+    }
+
+    /// Whether the process is still running.
+    pub fn is_alive(&self) -> bool {
+        self.process.is_alive()
+    }
+
+    /// Kill the process and wait for it to be torn down.
+    pub fn kill(&mut self) {
+        self.process.kill();
+        if let Some(thread) = self.thread.take() {
+            // A panic on the process thread is just another way for the
+            // process to die.
+            let _ = thread.join();
+        }
+        self.process.exit();
+    }
+}
+
+impl Drop for HostProcess {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+pub unsafe fn ABinderProcess_startThreadPool() {}
+
+pub unsafe fn ABinderProcess_setThreadPoolMaxThreadCount(_numThreads: u32) -> bool {
+    true
+}
+
+pub unsafe fn ABinderProcess_isThreadPoolStarted() -> bool {
+    true
+}
+
+/// Serve transactions until the process dies. The main process never dies,
+/// so on it this never returns.
+pub unsafe fn ABinderProcess_joinThreadPool() {
+    let process = current_process();
+    process.set_started();
+    let mut lifecycle = process.lifecycle.lock().unwrap();
+    while lifecycle.alive {
+        lifecycle = process.changed.wait(lifecycle).unwrap();
+    }
+}
+
+/// Transactions for the process are queued for the calling thread from now
+/// on, until it exits. Setting up polling also counts as the process having
+/// started up.
+pub unsafe fn ABinderProcess_setupPolling(fd: *mut c_int) -> binder_status_t {
+    let process = current_process();
+    let thread = thread::current().id();
+    // Replace any previous guard first, as dropping it stops polling.
+    let previous = POLLING.with(|guard| {
+        guard.borrow_mut().replace(PollingGuard { process: process.clone(), thread })
+    });
+    drop(previous);
+    {
+        let mut polling = process.polling.lock().unwrap();
+        if polling.is_none() {
+            let eventfd = libc::eventfd(0, libc::EFD_CLOEXEC);
+            if eventfd < 0 {
+                return StatusCode::UNKNOWN_ERROR as i32;
+            }
+            let fd = OwnedFd::from_raw_fd(eventfd);
+            *polling = Some(Polling { fd, thread: None, commands: VecDeque::new() });
+        }
+        let polling = polling.as_mut().unwrap();
+        polling.thread = Some(thread);
+        *fd = polling.fd.as_raw_fd();
+    }
+    process.set_started();
+    StatusCode::OK as i32
+}
+
+/// Blocks until commands are queued, or the process is killed, in which case
+/// this fails with `DEAD_OBJECT`.
+pub unsafe fn ABinderProcess_handlePolledCommands() -> binder_status_t {
+    let process = current_process();
+    let fd = match &*process.polling.lock().unwrap() {
+        Some(polling) if polling.thread == Some(thread::current().id()) => polling.fd.as_raw_fd(),
+        _ => return StatusCode::INVALID_OPERATION as i32,
+    };
+    let mut count = 0u64;
+    // Safety: `count` is valid for writes of its size, and the eventfd stays
+    // open for the lifetime of the process.
+    if libc::read(fd, &mut count as *mut u64 as *mut c_void, 8) < 0 {
+        return StatusCode::UNKNOWN_ERROR as i32;
+    }
+    while let Some(command) = process.next_polled_command() {
+        command.run();
+    }
+    if process.is_alive() {
+        StatusCode::OK as i32
+    } else {
+        StatusCode::DEAD_OBJECT as i32
+    }
+}
@@ -0,0 +1,226 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! `AStatus`, the equivalent of libbinder's `binder::Status`.
+
+use super::{binder_exception_t, binder_status_t, ExceptionCode, StatusCode};
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AStatus {
+    pub(super) exception: binder_exception_t,
+    /// Service specific error for `EX_SERVICE_SPECIFIC`, transaction error for
+    /// `EX_TRANSACTION_FAILED` and zero otherwise.
+    pub(super) error_code: i32,
+    pub(super) message: CString,
+}
+
+impl AStatus {
+    pub(super) fn new(exception: binder_exception_t, error_code: i32, message: CString) -> Self {
+        AStatus { exception, error_code, message }
+    }
+
+    pub(super) fn ok() -> Self {
+        Self::new(ExceptionCode::NONE as i32, 0, CString::default())
+    }
+
+    pub(super) fn from_status(status: binder_status_t) -> Self {
+        match prune_status(status) {
+            0 => Self::ok(),
+            status => {
+                Self::new(ExceptionCode::TRANSACTION_FAILED as i32, status, CString::default())
+            }
+        }
+    }
+
+    pub(super) fn from_exception(exception: binder_exception_t, message: CString) -> Self {
+        match prune_exception(exception) {
+            e if e == ExceptionCode::TRANSACTION_FAILED as i32 => {
+                Self::new(e, StatusCode::FAILED_TRANSACTION as i32, message)
+            }
+            e => Self::new(e, 0, message),
+        }
+    }
+
+    fn into_raw(self) -> *mut AStatus {
+        Box::into_raw(Box::new(self))
+    }
+
+    fn description(&self) -> String {
+        if self.exception == ExceptionCode::NONE as i32 {
+            return "No error".into();
+        }
+        let detail = if self.exception == ExceptionCode::SERVICE_SPECIFIC as i32 {
+            format!("{}: ", self.error_code)
+        } else if self.exception == ExceptionCode::TRANSACTION_FAILED as i32 {
+            format!("{}: ", status_name(self.error_code).unwrap_or("UNKNOWN_ERROR"))
+        } else {
+            String::new()
+        };
+        format!(
+            "Status({}, {}): '{}{}'",
+            self.exception,
+            exception_name(self.exception).unwrap_or("EX_TRANSACTION_FAILED"),
+            detail,
+            self.message.to_string_lossy()
+        )
+    }
+}
+
+const STATUS_NAMES: [(StatusCode, &str); 19] = [
+    (StatusCode::OK, "OK"),
+    (StatusCode::UNKNOWN_ERROR, "UNKNOWN_ERROR"),
+    (StatusCode::NO_MEMORY, "NO_MEMORY"),
+    (StatusCode::INVALID_OPERATION, "INVALID_OPERATION"),
+    (StatusCode::BAD_VALUE, "BAD_VALUE"),
+    (StatusCode::BAD_TYPE, "BAD_TYPE"),
+    (StatusCode::NAME_NOT_FOUND, "NAME_NOT_FOUND"),
+    (StatusCode::PERMISSION_DENIED, "PERMISSION_DENIED"),
+    (StatusCode::NO_INIT, "NO_INIT"),
+    (StatusCode::ALREADY_EXISTS, "ALREADY_EXISTS"),
+    (StatusCode::DEAD_OBJECT, "DEAD_OBJECT"),
+    (StatusCode::FAILED_TRANSACTION, "FAILED_TRANSACTION"),
+    (StatusCode::BAD_INDEX, "BAD_INDEX"),
+    (StatusCode::NOT_ENOUGH_DATA, "NOT_ENOUGH_DATA"),
+    (StatusCode::WOULD_BLOCK, "WOULD_BLOCK"),
+    (StatusCode::TIMED_OUT, "TIMED_OUT"),
+    (StatusCode::UNKNOWN_TRANSACTION, "UNKNOWN_TRANSACTION"),
+    (StatusCode::FDS_NOT_ALLOWED, "FDS_NOT_ALLOWED"),
+    (StatusCode::UNEXPECTED_NULL, "UNEXPECTED_NULL"),
+];
+
+const EXCEPTION_NAMES: [(ExceptionCode, &str); 11] = [
+    (ExceptionCode::NONE, "EX_NONE"),
+    (ExceptionCode::SECURITY, "EX_SECURITY"),
+    (ExceptionCode::BAD_PARCELABLE, "EX_BAD_PARCELABLE"),
+    (ExceptionCode::ILLEGAL_ARGUMENT, "EX_ILLEGAL_ARGUMENT"),
+    (ExceptionCode::NULL_POINTER, "EX_NULL_POINTER"),
+    (ExceptionCode::ILLEGAL_STATE, "EX_ILLEGAL_STATE"),
+    (ExceptionCode::NETWORK_MAIN_THREAD, "EX_NETWORK_MAIN_THREAD"),
+    (ExceptionCode::UNSUPPORTED_OPERATION, "EX_UNSUPPORTED_OPERATION"),
+    (ExceptionCode::SERVICE_SPECIFIC, "EX_SERVICE_SPECIFIC"),
+    (ExceptionCode::PARCELABLE, "EX_PARCELABLE"),
+    (ExceptionCode::TRANSACTION_FAILED, "EX_TRANSACTION_FAILED"),
+];
+
+fn status_name(status: binder_status_t) -> Option<&'static str> {
+    STATUS_NAMES.iter().find(|(code, _)| *code as i32 == status).map(|(_, name)| *name)
+}
+
+fn exception_name(exception: binder_exception_t) -> Option<&'static str> {
+    EXCEPTION_NAMES.iter().find(|(code, _)| *code as i32 == exception).map(|(_, name)| *name)
+}
+
+/// Map unknown status codes to `STATUS_UNKNOWN_ERROR`, as libbinder_ndk does
+/// for every status that crosses the API boundary.
+pub(super) fn prune_status(status: binder_status_t) -> binder_status_t {
+    match status_name(status) {
+        Some(_) => status,
+        None => StatusCode::UNKNOWN_ERROR as i32,
+    }
+}
+
+fn prune_exception(exception: binder_exception_t) -> binder_exception_t {
+    match exception_name(exception) {
+        Some(_) => exception,
+        None => ExceptionCode::TRANSACTION_FAILED as i32,
+    }
+}
+
+unsafe fn message_from_ptr(message: *const c_char) -> CString {
+    if message.is_null() {
+        CString::default()
+    } else {
+        CStr::from_ptr(message).into()
+    }
+}
+
+pub unsafe fn AStatus_newOk() -> *mut AStatus {
+    AStatus::ok().into_raw()
+}
+
+pub unsafe fn AStatus_fromExceptionCode(exception: binder_exception_t) -> *mut AStatus {
+    AStatus::from_exception(exception, CString::default()).into_raw()
+}
+
+pub unsafe fn AStatus_fromExceptionCodeWithMessage(
+    exception: binder_exception_t,
+    message: *const c_char,
+) -> *mut AStatus {
+    AStatus::from_exception(exception, message_from_ptr(message)).into_raw()
+}
+
+pub unsafe fn AStatus_fromServiceSpecificError(serviceSpecific: i32) -> *mut AStatus {
+    AStatus_fromServiceSpecificErrorWithMessage(serviceSpecific, std::ptr::null())
+}
+
+pub unsafe fn AStatus_fromServiceSpecificErrorWithMessage(
+    serviceSpecific: i32,
+    message: *const c_char,
+) -> *mut AStatus {
+    AStatus::new(ExceptionCode::SERVICE_SPECIFIC as i32, serviceSpecific, message_from_ptr(message))
+        .into_raw()
+}
+
+pub unsafe fn AStatus_fromStatus(status: binder_status_t) -> *mut AStatus {
+    AStatus::from_status(status).into_raw()
+}
+
+pub unsafe fn AStatus_isOk(status: *const AStatus) -> bool {
+    (*status).exception == ExceptionCode::NONE as i32
+}
+
+pub unsafe fn AStatus_getExceptionCode(status: *const AStatus) -> binder_exception_t {
+    (*status).exception
+}
+
+pub unsafe fn AStatus_getServiceSpecificError(status: *const AStatus) -> i32 {
+    if (*status).exception == ExceptionCode::SERVICE_SPECIFIC as i32 {
+        (*status).error_code
+    } else {
+        0
+    }
+}
+
+pub unsafe fn AStatus_getStatus(status: *const AStatus) -> binder_status_t {
+    if (*status).exception == ExceptionCode::TRANSACTION_FAILED as i32 {
+        prune_status((*status).error_code)
+    } else {
+        StatusCode::OK as i32
+    }
+}
+
+pub unsafe fn AStatus_getMessage(status: *const AStatus) -> *const c_char {
+    (*status).message.as_ptr()
+}
+
+pub unsafe fn AStatus_getDescription(status: *const AStatus) -> *const c_char {
+    // An interior NUL can only come from the message, which was itself read
+    // from a C string, so this cannot fail.
+    CString::new((*status).description()).unwrap_or_default().into_raw()
+}
+
+pub unsafe fn AStatus_deleteDescription(description: *const c_char) {
+    drop(CString::from_raw(description as *mut c_char));
+}
+
+pub unsafe fn AStatus_delete(status: *mut AStatus) {
+    if !status.is_null() {
+        drop(Box::from_raw(status));
+    }
+}
@@ -23,8 +23,14 @@
 use std::error::Error;
 use std::fmt;
 
+#[cfg(not(feature = "host"))]
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
+#[cfg(feature = "host")]
+mod host;
+#[cfg(feature = "host")]
+pub use host::*;
+
 impl Error for android_c_interface_StatusCode {}
 
 impl fmt::Display for android_c_interface_StatusCode {
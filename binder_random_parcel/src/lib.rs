@@ -0,0 +1,106 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Helpers for fuzzing binder code with arbitrary parcels.
+//!
+//! A fuzz target for a service only needs to hand it the fuzzer input:
+//! ```text
+//! fuzz_target!(|data: &[u8]| {
+//!     let service = BnFoo::new_binder(Foo::default(), BinderFeatures::default());
+//!     binder_random_parcel::fuzz_service(&mut service.as_binder(), data);
+//! });
+//! ```
+
+use arbitrary::Unstructured;
+use binder::binder_impl::{
+    BorrowedParcel, IBinderInternal, Parcel, TransactionCode, FIRST_CALL_TRANSACTION, FLAG_ONEWAY,
+};
+use binder::{ParcelFileDescriptor, SpIBinder};
+use std::fs::File;
+use std::slice;
+
+/// Transaction codes past `FIRST_CALL_TRANSACTION` tried most often; AIDL
+/// interfaces rarely have more methods than this.
+const MAX_CALL_INDEX: u32 = 100;
+
+/// Create a parcel out of fuzzer input, positioned at its start.
+///
+/// The parcel mostly holds raw bytes from `fuzzer_data`, and the odd file
+/// descriptor.
+pub fn create_random_parcel(fuzzer_data: &[u8]) -> Parcel {
+    let mut parcel = Parcel::new();
+    fill_random_parcel(&mut parcel.borrowed(), &mut Unstructured::new(fuzzer_data), &[]);
+    unsafe {
+        // Safety: 0 is always a valid position.
+        parcel.set_data_position(0).expect("Could not rewind parcel");
+    }
+    parcel
+}
+
+/// Send transactions made up of fuzzer input to `binder`, until the input
+/// runs out.
+///
+/// Transactions start with the interface token, so they reach the service's
+/// `on_transact`. Their data may contain `binder` itself, and file
+/// descriptors. Errors are expected for most inputs and ignored: only crashes
+/// and hangs are bugs.
+pub fn fuzz_service(binder: &mut SpIBinder, fuzzer_data: &[u8]) {
+    let mut data = Unstructured::new(fuzzer_data);
+    while !data.is_empty() {
+        let code: TransactionCode = if data.ratio(1, 8).unwrap_or(false) {
+            data.arbitrary().unwrap_or_default()
+        } else {
+            FIRST_CALL_TRANSACTION + data.int_in_range(0..=MAX_CALL_INDEX).unwrap_or_default()
+        };
+        let flags = if data.ratio(1, 4).unwrap_or(false) { FLAG_ONEWAY } else { 0 };
+        let len = data.arbitrary_len::<u8>().unwrap_or_default();
+        let mut transaction = Unstructured::new(data.bytes(len).unwrap_or_default());
+
+        let mut parcel = match binder.prepare_transact() {
+            Ok(parcel) => parcel,
+            Err(_) => return,
+        };
+        fill_random_parcel(&mut parcel.borrowed(), &mut transaction, slice::from_ref(binder));
+        let _ = binder.submit_transact(code, parcel, flags);
+    }
+}
+
+/// Append the remaining `data` to `parcel`, interspersed with objects.
+fn fill_random_parcel(
+    parcel: &mut BorrowedParcel<'_>,
+    data: &mut Unstructured<'_>,
+    binders: &[SpIBinder],
+) {
+    while !data.is_empty() {
+        let _ = match data.int_in_range(0..=7).unwrap_or_default() {
+            0 => match File::open("/dev/null") {
+                Ok(file) => parcel.write(&ParcelFileDescriptor::new(file)),
+                Err(_) => Ok(()),
+            },
+            1 => match data.choose(binders) {
+                Ok(binder) => parcel.write(binder),
+                Err(_) => Ok(()),
+            },
+            // Lengths and flags in parcels are mostly small, or -1 for null.
+            2 => parcel.write(&data.int_in_range(-1..=16).unwrap_or_default()),
+            _ => {
+                let len = data.arbitrary_len::<u8>().unwrap_or_default();
+                let bytes = data.bytes(len).unwrap_or_default();
+                Parcel::unmarshal(bytes).and_then(|bytes| parcel.append_all_from(&bytes))
+            }
+        };
+    }
+}
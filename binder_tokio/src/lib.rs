@@ -29,8 +29,21 @@
 //! [`Tokio`]: crate::Tokio
 
 use binder::binder_impl::BinderAsyncRuntime;
-use binder::{BinderAsyncPool, BoxFuture, FromIBinder, StatusCode, Strong};
+use binder::{
+    BinderAsyncPool, BoxFuture, DeathRecipient, FromIBinder, IBinder, ProcessState,
+    ServiceNotificationRegistration, SpIBinder, StatusCode, Strong,
+};
+use futures_core::Stream;
+use std::collections::BTreeMap;
 use std::future::Future;
+use std::marker::PhantomData;
+use std::os::unix::io::AsRawFd;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use tokio::io::unix::AsyncFd;
+use tokio::io::Interest;
+use tokio::sync::{mpsc, oneshot};
 
 /// Retrieve an existing service for a particular interface, sleeping for a few
 /// seconds if it doesn't yet exist.
@@ -80,6 +93,383 @@ pub async fn wait_for_interface<T: FromIBinder + ?Sized + 'static>(
     }
 }
 
+/// A client's handle to a service for a particular interface, like
+/// [`binder::ServiceHandle`], which connects on the Tokio blocking pool rather
+/// than blocking the runtime.
+///
+/// Clones share the cached service. To retry calls on `DEAD_OBJECT` or run a
+/// callback on reconnection, configure a handle with
+/// [`binder::ServiceHandle::builder`] and convert it with `From`:
+///
+/// ```text
+/// let handle: ServiceHandle<dyn IFoo> = binder::ServiceHandle::builder("foo")
+///     .retry_on_dead_object(true)
+///     .on_reconnect(|foo| register_listener(foo))
+///     .build()
+///     .into();
+/// ```
+pub struct ServiceHandle<T: FromIBinder + ?Sized>(binder::ServiceHandle<T>);
+
+impl<T: FromIBinder + ?Sized + 'static> ServiceHandle<T> {
+    /// Create a handle to the service `name`, without connecting to it yet.
+    pub fn new(name: &str) -> Self {
+        Self(binder::ServiceHandle::new(name))
+    }
+
+    /// The blocking handle this wraps, which shares the cached service.
+    pub fn blocking(&self) -> &binder::ServiceHandle<T> {
+        &self.0
+    }
+
+    /// The service, connecting to it first if needed.
+    pub async fn get(&self) -> Result<Strong<T>, StatusCode> {
+        if let Some(service) = self.0.cached() {
+            return Ok(service);
+        }
+        if binder::is_handling_transaction() {
+            // See comment in the BinderAsyncPool impl.
+            return self.0.get();
+        }
+
+        let handle = self.0.clone();
+        let res = tokio::task::spawn_blocking(move || handle.get()).await;
+
+        // The `is_panic` branch is not actually reachable in Android as we compile
+        // with `panic = abort`.
+        match res {
+            Ok(Ok(service)) => Ok(service),
+            Ok(Err(err)) => Err(err),
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            Err(e) if e.is_cancelled() => Err(StatusCode::FAILED_TRANSACTION),
+            Err(_) => Err(StatusCode::UNKNOWN_ERROR),
+        }
+    }
+
+    /// Call `f` with the service, connecting to it first if needed, and
+    /// handling `DEAD_OBJECT` like [`binder::ServiceHandle::call`].
+    pub async fn call<R, F, Fut>(&self, f: F) -> binder::Result<R>
+    where
+        F: Fn(Strong<T>) -> Fut,
+        Fut: Future<Output = binder::Result<R>>,
+    {
+        match self.call_once(&f).await {
+            Err(status) if self.0.retries_on_dead_object() && is_dead_object(&status) => {
+                self.call_once(&f).await
+            }
+            result => result,
+        }
+    }
+
+    async fn call_once<R, F, Fut>(&self, f: &F) -> binder::Result<R>
+    where
+        F: Fn(Strong<T>) -> Fut,
+        Fut: Future<Output = binder::Result<R>>,
+    {
+        let service = self.get().await?;
+        let result = f(service.clone()).await;
+        if matches!(&result, Err(status) if is_dead_object(status)) {
+            self.0.invalidate(&service);
+        }
+        result
+    }
+}
+
+fn is_dead_object(status: &binder::Status) -> bool {
+    status.transaction_error() == StatusCode::DEAD_OBJECT
+}
+
+impl<T: FromIBinder + ?Sized> From<binder::ServiceHandle<T>> for ServiceHandle<T> {
+    fn from(handle: binder::ServiceHandle<T>) -> Self {
+        Self(handle)
+    }
+}
+
+impl<T: FromIBinder + ?Sized> Clone for ServiceHandle<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: FromIBinder + ?Sized> std::fmt::Debug for ServiceHandle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// A stream of a service for a particular interface, which yields the service
+/// every time it is registered.
+///
+/// Created by [`interface_notifications`]. The stream never ends; dropping it
+/// stops the notifications.
+pub struct InterfaceNotifications<T: FromIBinder + ?Sized> {
+    services: mpsc::UnboundedReceiver<Strong<T>>,
+    _registration: ServiceNotificationRegistration,
+}
+
+/// Watch a service for a particular interface, yielding it every time it is
+/// registered, starting right away if it is already registered.
+///
+/// This lets clients reconnect whenever the service restarts, without polling.
+pub fn interface_notifications<T: FromIBinder + ?Sized + 'static>(
+    name: &str,
+) -> Result<InterfaceNotifications<T>, StatusCode> {
+    let (sender, services) = mpsc::unbounded_channel();
+    let registration =
+        binder::register_for_interface_notifications(name, move |service: Strong<T>| {
+            // The receiver only goes away along with the registration.
+            let _ = sender.send(service);
+        })?;
+    Ok(InterfaceNotifications { services, _registration: registration })
+}
+
+impl<T: FromIBinder + ?Sized> InterfaceNotifications<T> {
+    /// Wait for the next registration of the service.
+    pub async fn next(&mut self) -> Option<Strong<T>> {
+        self.services.recv().await
+    }
+}
+
+impl<T: FromIBinder + ?Sized> Stream for InterfaceNotifications<T> {
+    type Item = Strong<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Strong<T>>> {
+        self.services.poll_recv(cx)
+    }
+}
+
+/// A death recipient linked to a binder, which is unlinked on drop.
+struct DeathLink {
+    binder: SpIBinder,
+    recipient: DeathRecipient,
+}
+
+impl DeathLink {
+    /// Link `callback` to the death of `binder`.
+    ///
+    /// Returns `Ok(None)` for a local binder, which never dies.
+    fn new<F>(binder: &SpIBinder, callback: F) -> Result<Option<DeathLink>, StatusCode>
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        let mut binder = binder.clone();
+        let mut recipient = DeathRecipient::new(callback);
+        match binder.link_to_death(&mut recipient) {
+            Ok(()) => Ok(Some(DeathLink { binder, recipient })),
+            Err(StatusCode::INVALID_OPERATION) if !binder.is_remote() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Drop for DeathLink {
+    fn drop(&mut self) {
+        // This fails if the binder already died, which unlinked it.
+        let _ = self.binder.unlink_to_death(&mut self.recipient);
+    }
+}
+
+/// A future which completes when a binder dies, created by [`died`].
+///
+/// Dropping it unlinks the death notification, so it can be used in
+/// `select!`.
+pub struct Died(DiedState);
+
+enum DiedState {
+    Linked { receiver: oneshot::Receiver<()>, _link: DeathLink },
+    Dead,
+    Local,
+    Failed(StatusCode),
+}
+
+/// Wait for `binder` to die, completing right away if it is already dead.
+///
+/// A local binder never dies, so neither does its future. If the death
+/// notification can't be linked for any other reason than the binder being
+/// dead, e.g. `NO_MEMORY`, the future completes with that error.
+pub fn died(binder: &SpIBinder) -> Died {
+    let (sender, receiver) = oneshot::channel();
+    let sender = Mutex::new(Some(sender));
+    let link = DeathLink::new(binder, move || {
+        if let Some(sender) = sender.lock().unwrap().take() {
+            // The receiver may have been dropped while this was called.
+            let _ = sender.send(());
+        }
+    });
+    Died(match link {
+        Ok(Some(link)) => DiedState::Linked { receiver, _link: link },
+        Ok(None) => DiedState::Local,
+        Err(StatusCode::DEAD_OBJECT) => DiedState::Dead,
+        Err(e) => DiedState::Failed(e),
+    })
+}
+
+impl Future for Died {
+    type Output = Result<(), StatusCode>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match &mut self.0 {
+            // The sender is only dropped after sending, or with the link.
+            DiedState::Linked { receiver, .. } => Pin::new(receiver).poll(cx).map(|_| Ok(())),
+            DiedState::Dead => Poll::Ready(Ok(())),
+            DiedState::Local => Poll::Pending,
+            DiedState::Failed(e) => Poll::Ready(Err(*e)),
+        }
+    }
+}
+
+impl std::fmt::Debug for Died {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad("Died")
+    }
+}
+
+/// A stream of the binders which died, out of a set of watched binders.
+///
+/// A binder is no longer watched once it has been yielded. The stream never
+/// ends; dropping it unlinks every death notification.
+pub struct DeathWatcher {
+    sender: mpsc::UnboundedSender<SpIBinder>,
+    receiver: mpsc::UnboundedReceiver<SpIBinder>,
+    links: BTreeMap<SpIBinder, Option<DeathLink>>,
+}
+
+impl DeathWatcher {
+    /// Create a watcher which isn't watching anything yet.
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Self { sender, receiver, links: BTreeMap::new() }
+    }
+
+    /// Watch `binder`, which is yielded right away if it is already dead.
+    /// Watching a binder twice has no effect.
+    ///
+    /// Fails, without watching `binder`, if the death notification can't be
+    /// linked for any other reason than the binder being dead.
+    pub fn watch(&mut self, binder: &SpIBinder) -> Result<(), StatusCode> {
+        if self.links.contains_key(binder) {
+            return Ok(());
+        }
+        let sender = self.sender.clone();
+        let dead = binder.clone();
+        // The receiver only goes away along with the links.
+        let link = match DeathLink::new(binder, move || drop(sender.send(dead.clone()))) {
+            Ok(link) => link,
+            Err(StatusCode::DEAD_OBJECT) => {
+                let _ = self.sender.send(binder.clone());
+                None
+            }
+            Err(e) => return Err(e),
+        };
+        self.links.insert(binder.clone(), link);
+        Ok(())
+    }
+
+    /// Stop watching `binder`, returning whether it was watched.
+    pub fn unwatch(&mut self, binder: &SpIBinder) -> bool {
+        self.links.remove(binder).is_some()
+    }
+
+    /// Whether `binder` is watched.
+    pub fn is_watching(&self, binder: &SpIBinder) -> bool {
+        self.links.contains_key(binder)
+    }
+
+    /// Wait for the next watched binder to die.
+    pub async fn next(&mut self) -> Option<SpIBinder> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+}
+
+impl Default for DeathWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Stream for DeathWatcher {
+    type Item = SpIBinder;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<SpIBinder>> {
+        loop {
+            let binder = match self.receiver.poll_recv(cx) {
+                Poll::Ready(Some(binder)) => binder,
+                other => return other,
+            };
+            // Skip binders which were unwatched after they died.
+            if self.links.remove(&binder).is_some() {
+                return Poll::Ready(Some(binder));
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for DeathWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeathWatcher").field("watching", &self.links.len()).finish()
+    }
+}
+
+/// Serve incoming binder transactions from the current thread, whenever the
+/// binder driver has commands for it, instead of from a binder thread pool.
+///
+/// This is meant for single-threaded processes, which should not also start a
+/// thread pool. Polling is set up for the thread first polling the returned
+/// future, which then handles every command, so the future is not `Send`: run
+/// it with `block_on` or `spawn_local` on a current-thread runtime. It only
+/// completes if handling commands fails.
+pub fn serve_polled_commands() -> ServePolledCommands {
+    ServePolledCommands {
+        inner: Box::pin(serve_polled_commands_inner()),
+        _thread_bound: PhantomData,
+    }
+}
+
+/// Future returned by [`serve_polled_commands`].
+#[must_use = "futures do nothing unless polled"]
+pub struct ServePolledCommands {
+    inner: Pin<Box<dyn Future<Output = Result<(), StatusCode>>>>,
+    /// Commands must be handled on the thread which set up polling.
+    _thread_bound: PhantomData<*const ()>,
+}
+
+impl Future for ServePolledCommands {
+    type Output = Result<(), StatusCode>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.inner.as_mut().poll(cx)
+    }
+}
+
+impl std::fmt::Debug for ServePolledCommands {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ServePolledCommands").finish_non_exhaustive()
+    }
+}
+
+async fn serve_polled_commands_inner() -> Result<(), StatusCode> {
+    let fd = AsyncFd::with_interest(ProcessState::setup_polling()?, Interest::READABLE)
+        .or(Err(StatusCode::UNKNOWN_ERROR))?;
+    loop {
+        let mut ready = fd.readable().await.or(Err(StatusCode::UNKNOWN_ERROR))?;
+        // Clear the readiness first, so that no command arriving meanwhile is
+        // missed. Handling commands blocks if there are none, so only do it
+        // while some are pending.
+        ready.clear_ready();
+        while is_readable(fd.get_ref()) {
+            ProcessState::handle_polled_commands()?;
+        }
+    }
+}
+
+/// Check whether `fd` is readable, without waiting.
+fn is_readable(fd: &impl AsRawFd) -> bool {
+    let mut pollfd = libc::pollfd { fd: fd.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+    // Safety: `pollfd` is a single valid `pollfd`, borrowed for the duration of
+    // the call.
+    let ready = unsafe { libc::poll(&mut pollfd, 1, 0) };
+    ready > 0 && pollfd.revents & libc::POLLIN != 0
+}
+
 /// Use the Tokio `spawn_blocking` pool with AIDL.
 pub enum Tokio {}
 
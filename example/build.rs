@@ -0,0 +1,3 @@
+fn main() {
+    binder_aidl::Builder::new().source("aidl/IRemoteService.aidl").generate().unwrap();
+}
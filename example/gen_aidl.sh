@@ -1,2 +0,0 @@
-# $ANDROID_SDK_ROOT/build-tools/33.0.1/aidl
-aidl --lang=rust aidl/IRemoteService.aidl -o src
\ No newline at end of file
@@ -2,7 +2,10 @@
 mod cli;
 mod server;
 mod client;
-mod IRemoteService;
+mod aidl {
+    include!(concat!(env!("OUT_DIR"), "/aidl.rs"));
+}
+use aidl::IRemoteService;
 
 fn main() -> anyhow::Result<()>{
     cli::run()
@@ -0,0 +1,76 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Read values of a type chosen by the first byte of the input out of a
+//! parcel made from the rest, and write them back.
+
+#![no_main]
+
+use binder::binder_impl::{Deserialize, Parcel, Serialize};
+use binder::{ParcelFileDescriptor, ParcelableHolder, SpIBinder, Status};
+use binder_random_parcel::create_random_parcel;
+use libfuzzer_sys::fuzz_target;
+
+/// Read `T`s until the parcel runs out of them.
+fn read_all<T: Deserialize + Serialize>(parcel: &Parcel) {
+    let mut copy = Parcel::new();
+    loop {
+        let start = parcel.get_data_position();
+        match parcel.read::<T>() {
+            // Some values, e.g. a null string, can be read from an empty
+            // parcel.
+            Ok(value) if parcel.get_data_position() > start => {
+                let _ = copy.write(&value);
+            }
+            _ => break,
+        }
+    }
+}
+
+const READERS: &[fn(&Parcel)] = &[
+    read_all::<bool>,
+    read_all::<i8>,
+    read_all::<u16>,
+    read_all::<i32>,
+    read_all::<i64>,
+    read_all::<f32>,
+    read_all::<f64>,
+    read_all::<String>,
+    read_all::<Option<String>>,
+    read_all::<Vec<u8>>,
+    read_all::<Vec<bool>>,
+    read_all::<Vec<i32>>,
+    read_all::<Vec<String>>,
+    read_all::<Vec<Option<String>>>,
+    read_all::<Option<Vec<Option<String>>>>,
+    read_all::<[i32; 3]>,
+    read_all::<[[u8; 4]; 2]>,
+    read_all::<[Option<String>; 2]>,
+    read_all::<Option<[i64; 2]>>,
+    read_all::<Vec<[u16; 3]>>,
+    read_all::<Status>,
+    read_all::<ParcelableHolder>,
+    read_all::<ParcelFileDescriptor>,
+    read_all::<Option<ParcelFileDescriptor>>,
+    read_all::<Vec<ParcelFileDescriptor>>,
+    read_all::<Option<SpIBinder>>,
+    read_all::<Vec<Option<SpIBinder>>>,
+];
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&kind, data)) = data.split_first() else { return };
+    READERS[kind as usize % READERS.len()](&create_random_parcel(data));
+});
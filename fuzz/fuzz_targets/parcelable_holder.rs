@@ -0,0 +1,84 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Read a `ParcelableHolder` out of a parcel, unpack the parcelable it holds
+//! and pack it up again.
+
+#![no_main]
+
+use binder::binder_impl::{BorrowedParcel, Parcel, ParcelableMetadata};
+use binder::{
+    impl_deserialize_for_parcelable, impl_serialize_for_parcelable, ParcelFileDescriptor,
+    Parcelable, ParcelableHolder, StatusCode,
+};
+use binder_random_parcel::create_random_parcel;
+use libfuzzer_sys::fuzz_target;
+use std::sync::Arc;
+
+/// A parcelable as generated by the AIDL compiler.
+#[derive(Debug, Default)]
+struct FuzzParcelable {
+    value: i32,
+    names: Vec<Option<String>>,
+    fd: Option<ParcelFileDescriptor>,
+}
+
+impl Parcelable for FuzzParcelable {
+    fn write_to_parcel(&self, parcel: &mut BorrowedParcel<'_>) -> Result<(), StatusCode> {
+        parcel.sized_write(|subparcel| {
+            subparcel.write(&self.value)?;
+            subparcel.write(&self.names)?;
+            subparcel.write(&self.fd)
+        })
+    }
+
+    fn read_from_parcel(&mut self, parcel: &BorrowedParcel<'_>) -> Result<(), StatusCode> {
+        parcel.sized_read(|subparcel| {
+            if subparcel.has_more_data() {
+                self.value = subparcel.read()?;
+            }
+            if subparcel.has_more_data() {
+                self.names = subparcel.read()?;
+            }
+            if subparcel.has_more_data() {
+                self.fd = subparcel.read()?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl_serialize_for_parcelable!(FuzzParcelable);
+impl_deserialize_for_parcelable!(FuzzParcelable);
+
+impl ParcelableMetadata for FuzzParcelable {
+    fn get_descriptor() -> &'static str {
+        "android.binder.fuzz.FuzzParcelable"
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let parcel = create_random_parcel(data);
+    let Ok(holder) = parcel.read::<ParcelableHolder>() else { return };
+
+    if let Ok(Some(parcelable)) = holder.get_parcelable::<FuzzParcelable>() {
+        let mut repacked = ParcelableHolder::new(holder.get_stability());
+        if repacked.set_parcelable(Arc::clone(&parcelable)).is_ok() {
+            let _ = Parcel::new().write(&repacked);
+        }
+    }
+    let _ = Parcel::new().write(&holder);
+});
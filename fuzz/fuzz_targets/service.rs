@@ -0,0 +1,160 @@
+/*
+ * Copyright (C) 2023 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Send arbitrary transactions to the `on_transact` generated by
+//! `declare_binder_interface!`.
+
+#![no_main]
+
+use binder::binder_impl::{
+    Binder, BorrowedParcel, IBinderInternal, TransactionCode, FIRST_CALL_TRANSACTION,
+};
+use binder::{
+    declare_binder_interface, BinderFeatures, Interface, ParcelFileDescriptor, ParcelableHolder,
+    SpIBinder, StatusCode,
+};
+use binder_random_parcel::fuzz_service;
+use libfuzzer_sys::fuzz_target;
+
+const ECHO: TransactionCode = FIRST_CALL_TRANSACTION;
+const SUM: TransactionCode = FIRST_CALL_TRANSACTION + 1;
+const COUNT_NAMES: TransactionCode = FIRST_CALL_TRANSACTION + 2;
+const TAKE_OBJECTS: TransactionCode = FIRST_CALL_TRANSACTION + 3;
+
+pub trait IFuzzService: Interface {
+    fn echo(&self, value: &str) -> Result<String, StatusCode>;
+    fn sum(&self, values: &[i32; 4]) -> Result<i64, StatusCode>;
+    fn count_names(&self, names: Option<&[Option<String>]>) -> Result<i32, StatusCode>;
+    fn take_objects(
+        &self,
+        binder: Option<&SpIBinder>,
+        fds: &[ParcelFileDescriptor],
+        holder: &ParcelableHolder,
+    ) -> Result<(), StatusCode>;
+}
+
+declare_binder_interface! {
+    IFuzzService["android.binder.fuzz.IFuzzService"] {
+        native: BnFuzzService(on_transact),
+        proxy: BpFuzzService,
+    }
+}
+
+fn on_transact(
+    service: &dyn IFuzzService,
+    code: TransactionCode,
+    data: &BorrowedParcel<'_>,
+    reply: &mut BorrowedParcel<'_>,
+) -> Result<(), StatusCode> {
+    match code {
+        ECHO => reply.write(&service.echo(&data.read::<String>()?)?),
+        SUM => reply.write(&service.sum(&data.read()?)?),
+        COUNT_NAMES => {
+            let names: Option<Vec<Option<String>>> = data.read()?;
+            reply.write(&service.count_names(names.as_deref())?)
+        }
+        TAKE_OBJECTS => {
+            let binder: Option<SpIBinder> = data.read()?;
+            let fds: Vec<ParcelFileDescriptor> = data.read()?;
+            service.take_objects(binder.as_ref(), &fds, &data.read()?)
+        }
+        _ => Err(StatusCode::UNKNOWN_TRANSACTION),
+    }
+}
+
+// Transactions are only ever sent by `fuzz_service`, so there is no need for a
+// working proxy.
+impl IFuzzService for BpFuzzService {
+    fn echo(&self, _value: &str) -> Result<String, StatusCode> {
+        Err(StatusCode::INVALID_OPERATION)
+    }
+
+    fn sum(&self, _values: &[i32; 4]) -> Result<i64, StatusCode> {
+        Err(StatusCode::INVALID_OPERATION)
+    }
+
+    fn count_names(&self, _names: Option<&[Option<String>]>) -> Result<i32, StatusCode> {
+        Err(StatusCode::INVALID_OPERATION)
+    }
+
+    fn take_objects(
+        &self,
+        _binder: Option<&SpIBinder>,
+        _fds: &[ParcelFileDescriptor],
+        _holder: &ParcelableHolder,
+    ) -> Result<(), StatusCode> {
+        Err(StatusCode::INVALID_OPERATION)
+    }
+}
+
+struct FuzzService;
+
+impl Interface for FuzzService {}
+
+impl IFuzzService for FuzzService {
+    fn echo(&self, value: &str) -> Result<String, StatusCode> {
+        Ok(value.to_owned())
+    }
+
+    fn sum(&self, values: &[i32; 4]) -> Result<i64, StatusCode> {
+        Ok(values.iter().map(|&v| i64::from(v)).sum())
+    }
+
+    fn count_names(&self, names: Option<&[Option<String>]>) -> Result<i32, StatusCode> {
+        let names = names.ok_or(StatusCode::UNEXPECTED_NULL)?;
+        Ok(names.iter().flatten().count() as i32)
+    }
+
+    fn take_objects(
+        &self,
+        binder: Option<&SpIBinder>,
+        _fds: &[ParcelFileDescriptor],
+        _holder: &ParcelableHolder,
+    ) -> Result<(), StatusCode> {
+        match binder {
+            Some(binder) if !binder.is_binder_alive() => Err(StatusCode::DEAD_OBJECT),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl IFuzzService for Binder<BnFuzzService> {
+    fn echo(&self, value: &str) -> Result<String, StatusCode> {
+        self.0.echo(value)
+    }
+
+    fn sum(&self, values: &[i32; 4]) -> Result<i64, StatusCode> {
+        self.0.sum(values)
+    }
+
+    fn count_names(&self, names: Option<&[Option<String>]>) -> Result<i32, StatusCode> {
+        self.0.count_names(names)
+    }
+
+    fn take_objects(
+        &self,
+        binder: Option<&SpIBinder>,
+        fds: &[ParcelFileDescriptor],
+        holder: &ParcelableHolder,
+    ) -> Result<(), StatusCode> {
+        self.0.take_objects(binder, fds, holder)
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let service = BnFuzzService::new_binder(FuzzService, BinderFeatures::default());
+    fuzz_service(&mut service.as_binder(), data);
+});
@@ -0,0 +1,11 @@
+fn main() {
+    // Other types are found from their use in the interface.
+    binder_aidl::Builder::new()
+        .source("aidl/android/binder/test/IAidlTest.aidl")
+        .source("aidl/android/binder/test/Extendable.aidl")
+        .source("aidl/android/binder/test/IOutArrays.aidl")
+        .include_dir("aidl")
+        .async_server(true)
+        .generate()
+        .unwrap();
+}
@@ -16,8 +16,8 @@
 
 //! Rust Binder crate integration tests
 
-use binder::{declare_binder_enum, declare_binder_interface};
-use binder::{BinderFeatures, Interface, StatusCode, ThreadState};
+use binder::{declare_binder_enum, declare_binder_interface, declare_binder_union};
+use binder::{BinderFeatures, Interface, ShellArgs, StatusCode, ThreadState};
 // Import from internal API for testing only, do not use this module in
 // production.
 use binder::binder_impl::{
@@ -27,6 +27,7 @@ use binder::binder_impl::{
 use std::convert::{TryFrom, TryInto};
 use std::ffi::CStr;
 use std::fs::File;
+use std::io::Write;
 use std::sync::Mutex;
 
 /// Name of service runner.
@@ -54,22 +55,24 @@ pub fn main() -> Result<(), &'static str> {
     })?;
     let extension_name = args.next();
 
-    {
-        let mut service = Binder::new(BnTest(Box::new(TestService::new(&service_name))));
-        service.set_requesting_sid(true);
-        if let Some(extension_name) = extension_name {
-            let extension =
-                BnTest::new_binder(TestService::new(&extension_name), BinderFeatures::default());
-            service.set_extension(&mut extension.as_binder()).expect("Could not add extension");
-        }
-        binder::add_service(&service_name, service.as_binder())
-            .expect("Could not register service");
-    }
+    add_test_service(&service_name, extension_name.as_deref());
 
     binder::ProcessState::join_thread_pool();
     Err("Unexpected exit after join_thread_pool")
 }
 
+/// Register a test service named `service_name`, optionally with an extension.
+fn add_test_service(service_name: &str, extension_name: Option<&str>) {
+    let mut service = Binder::new(BnTest(Box::new(TestService::new(service_name))));
+    service.set_requesting_sid(true);
+    if let Some(extension_name) = extension_name {
+        let extension =
+            BnTest::new_binder(TestService::new(extension_name), BinderFeatures::default());
+        service.set_extension(&mut extension.as_binder()).expect("Could not add extension");
+    }
+    binder::add_service(service_name, service.as_binder()).expect("Could not register service");
+}
+
 fn print_usage() {
     eprintln!("Usage: {} SERVICE_NAME [EXTENSION_NAME]", RUST_SERVICE_BINARY);
     eprintln!(concat!(
@@ -123,6 +126,29 @@ impl Interface for TestService {
         dump_args.extend(args.iter().map(|s| s.to_str().unwrap().to_owned()));
         Ok(())
     }
+
+    fn handle_shell_command(
+        &self,
+        _stdin: &File,
+        mut stdout: &File,
+        mut stderr: &File,
+        args: &[&CStr],
+    ) -> Result<(), StatusCode> {
+        let mut args = ShellArgs::new(args);
+        match args.subcommand()? {
+            Some("echo") => {
+                let newline = !args.flag("-n");
+                let words: Vec<_> = args.rest().iter().map(|s| s.to_string_lossy()).collect();
+                let end = if newline { "\n" } else { "" };
+                write!(stdout, "{}{}", words.join(" "), end).or(Err(StatusCode::UNKNOWN_ERROR))
+            }
+            _ => {
+                writeln!(stderr, "usage: echo [-n] <words>...")
+                    .or(Err(StatusCode::UNKNOWN_ERROR))?;
+                Err(StatusCode::BAD_VALUE)
+            }
+        }
+    }
 }
 
 impl ITest for TestService {
@@ -354,31 +380,84 @@ declare_binder_enum! {
     }
 }
 
+declare_binder_union! {
+    #[derive(Debug, Clone, PartialEq)]
+    TestUnion["android.os.TestUnion"] {
+        tag: TestUnionTag,
+        fields: {
+            number = 0 => Number(i32) = 42,
+            text = 1 => Text(Option<String>),
+            values = 2 => Values(Vec<TestEnum>),
+        },
+        stability: binder::binder_impl::Stability::Vintf,
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
     use std::ffi::CStr;
     use std::fs::File;
+    #[cfg(feature = "host")]
+    use std::io::Read;
+    #[cfg(feature = "host")]
+    use std::os::unix::io::AsFd;
+    #[cfg(feature = "host")]
+    use std::os::unix::net::UnixStream;
+    #[cfg(not(feature = "host"))]
     use std::process::{Child, Command};
     use std::ptr;
-    use std::sync::atomic::{AtomicBool, Ordering};
-    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::{mpsc, Arc, Mutex};
     use std::thread;
     use std::time::Duration;
 
     use binder::{
-        BinderFeatures, DeathRecipient, FromIBinder, IBinder, Interface, SpIBinder, StatusCode,
-        Strong,
+        BinderFeatures, DeathRecipient, Dumper, FromIBinder, IBinder, Interface,
+        MinSchedulerPolicy, RemoteCallbackList, SchedulerPolicy, ServiceHandle, SpIBinder,
+        StatusCode, Strong,
     };
     // Import from impl API for testing only, should not be necessary as long as
     // you are using AIDL.
-    use binder::binder_impl::{Binder, IBinderInternal, TransactionCode};
+    use binder::binder_impl::{
+        Binder, IBinderInternal, Remotable, TransactionCode, FIRST_CALL_TRANSACTION,
+    };
 
-    use binder_tokio::Tokio;
+    use binder_tokio::{DeathWatcher, Tokio, TokioRuntime};
 
-    use super::{BnTest, IATest, ITest, ITestSameDescriptor, TestService, RUST_SERVICE_BINARY};
+    #[cfg(not(feature = "host"))]
+    use super::RUST_SERVICE_BINARY;
+    use super::{BnTest, IATest, ITest, ITestSameDescriptor, TestEnum, TestService};
+    use super::{TestUnion, TestUnionTag};
 
+    #[cfg(not(feature = "host"))]
     pub struct ScopedServiceProcess(Child);
 
+    /// On the host, the service runs in-process instead, and dies with this.
+    #[cfg(feature = "host")]
+    pub struct ScopedServiceProcess(#[allow(dead_code)] binder::ServiceProcess);
+
+    #[cfg(feature = "host")]
+    impl ScopedServiceProcess {
+        pub fn new(identifier: &str) -> Self {
+            Self::new_internal(identifier, None)
+        }
+
+        pub fn new_with_extension(identifier: &str, extension: &str) -> Self {
+            Self::new_internal(identifier, Some(extension))
+        }
+
+        fn new_internal(identifier: &str, extension: Option<&str>) -> Self {
+            let identifier = identifier.to_owned();
+            let extension = extension.map(str::to_owned);
+            Self(binder::ServiceProcess::spawn(move || {
+                super::add_test_service(&identifier, extension.as_deref());
+                binder::ProcessState::join_thread_pool();
+            }))
+        }
+    }
+
+    #[cfg(not(feature = "host"))]
     impl ScopedServiceProcess {
         pub fn new(identifier: &str) -> Self {
             Self::new_internal(identifier, None)
@@ -404,6 +483,7 @@ mod tests {
         }
     }
 
+    #[cfg(not(feature = "host"))]
     impl Drop for ScopedServiceProcess {
         fn drop(&mut self) {
             self.0.kill().expect("Could not kill child process");
@@ -482,6 +562,39 @@ mod tests {
         );
     }
 
+    #[test]
+    fn check_service() {
+        let service_name = "check_service_test";
+        assert!(binder::check_service(service_name).is_none());
+        assert_eq!(
+            binder::check_interface::<dyn ITest>(service_name).err(),
+            Some(StatusCode::NAME_NOT_FOUND)
+        );
+
+        let _process = ScopedServiceProcess::new(service_name);
+        binder::wait_for_service(service_name).expect("Did not get test service");
+        let test_client: Strong<dyn ITest> =
+            binder::check_interface(service_name).expect("Did not get test service");
+        assert_eq!(test_client.test().unwrap(), "check_service_test");
+
+        // The service manager service isn't an ITest, so this must fail.
+        assert!(binder::check_service("manager").is_some());
+        assert_eq!(
+            binder::check_interface::<dyn ITest>("manager").err(),
+            Some(StatusCode::BAD_TYPE)
+        );
+    }
+
+    #[test]
+    fn updatable_via_apex() {
+        // The service manager is part of the platform, not of an APEX.
+        assert!(!binder::is_updatable_via_apex("manager").expect("Could not check for APEX"));
+        assert_eq!(
+            binder::get_updatable_apex_name("manager").expect("Could not get APEX name"),
+            None
+        );
+    }
+
     #[test]
     fn get_declared_instances() {
         // At the time of writing this test, there is no good VINTF interface
@@ -535,6 +648,197 @@ mod tests {
         assert_eq!(test_client.test().await.unwrap(), "wait_for_trivial_client_test");
     }
 
+    #[test]
+    fn service_handle() {
+        binder::ProcessState::start_thread_pool();
+
+        let service_name = "service_handle_test";
+        let reconnects = Arc::new(AtomicUsize::new(0));
+        let handle = ServiceHandle::<dyn ITest>::builder(service_name)
+            .on_reconnect({
+                let reconnects = reconnects.clone();
+                move |service| {
+                    assert_eq!(service.test().unwrap(), "service_handle_test");
+                    reconnects.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+            .build();
+        assert_eq!(handle.name(), service_name);
+        assert!(handle.cached().is_none());
+
+        let process = ScopedServiceProcess::new(service_name);
+        assert_eq!(handle.call(|service| Ok(service.test()?)).unwrap(), service_name);
+        let service = handle.cached().expect("Handle did not cache the service");
+        assert_eq!(handle.clone().get().unwrap().as_binder(), service.as_binder());
+
+        drop(process);
+        // Pause to ensure the death notification gets delivered
+        thread::sleep(Duration::from_secs(1));
+        assert!(handle.cached().is_none());
+        assert_eq!(reconnects.load(Ordering::SeqCst), 0);
+
+        let _process = ScopedServiceProcess::new(service_name);
+        assert_eq!(handle.call(|service| Ok(service.test()?)).unwrap(), service_name);
+        assert_ne!(handle.cached().unwrap().as_binder(), service.as_binder());
+        assert_eq!(reconnects.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn service_handle_retry() {
+        binder::ProcessState::start_thread_pool();
+
+        let service_name = "service_handle_retry_test";
+        let handle =
+            ServiceHandle::<dyn ITest>::builder(service_name).retry_on_dead_object(true).build();
+        let process = ScopedServiceProcess::new(service_name);
+        let service = handle.get().unwrap();
+
+        // Whether or not the death notification arrives first, the call goes to
+        // the new service.
+        drop(process);
+        let _process = ScopedServiceProcess::new(service_name);
+        assert_eq!(handle.call(|service| Ok(service.test()?)).unwrap(), service_name);
+        assert_ne!(handle.cached().unwrap().as_binder(), service.as_binder());
+
+        // Calls are repeated once, and only if retries are enabled.
+        for (handle, expected_calls) in [(handle, 2), (ServiceHandle::new(service_name), 1)] {
+            let calls = AtomicUsize::new(0);
+            let result = handle.call(|_| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err::<(), _>(StatusCode::DEAD_OBJECT.into())
+            });
+            assert_eq!(result.unwrap_err().transaction_error(), StatusCode::DEAD_OBJECT);
+            assert_eq!(calls.load(Ordering::SeqCst), expected_calls);
+            // Each failure forgets the service, including after reconnecting.
+            assert!(handle.cached().is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn service_handle_async() {
+        binder::ProcessState::start_thread_pool();
+
+        let service_name = "service_handle_async_test";
+        let handle: binder_tokio::ServiceHandle<dyn IATest<Tokio>> =
+            ServiceHandle::builder(service_name).retry_on_dead_object(true).build().into();
+        let process = ScopedServiceProcess::new(service_name);
+        let name = handle.call(|service| async move { Ok(service.test().await?) }).await;
+        assert_eq!(name.unwrap(), service_name);
+        let service = handle.blocking().cached().expect("Handle did not cache the service");
+
+        // Once the service dies, the handle connects to the new one.
+        drop(process);
+        // Pause to ensure the death notification gets delivered
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!(handle.blocking().cached().is_none());
+        let process = ScopedServiceProcess::new(service_name);
+        let name = handle.call(|service| async move { Ok(service.test().await?) }).await;
+        assert_eq!(name.unwrap(), service_name);
+        let reconnected = handle.get().await.unwrap();
+        assert_ne!(reconnected.as_binder(), service.as_binder());
+
+        // Whether or not the death notification arrives first, the call goes to
+        // the new service.
+        drop(process);
+        let _process = ScopedServiceProcess::new(service_name);
+        let name = handle.call(|service| async move { Ok(service.test().await?) }).await;
+        assert_eq!(name.unwrap(), service_name);
+        assert_ne!(handle.blocking().cached().unwrap().as_binder(), reconnected.as_binder());
+
+        // Calls failing with DEAD_OBJECT are repeated once.
+        let calls = AtomicUsize::new(0);
+        let result = handle
+            .call(|_| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                async { Err::<(), _>(StatusCode::DEAD_OBJECT.into()) }
+            })
+            .await;
+        assert_eq!(result.unwrap_err().transaction_error(), StatusCode::DEAD_OBJECT);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(handle.blocking().cached().is_none());
+    }
+
+    #[test]
+    fn service_notifications() {
+        binder::ProcessState::start_thread_pool();
+
+        let service_name = "service_notifications_test";
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let _registration = binder::register_for_interface_notifications(
+            service_name,
+            move |service: Strong<dyn ITest>| {
+                let _ = sender.lock().unwrap().send(service);
+            },
+        )
+        .expect("Could not register for service notifications");
+
+        // Every time the service restarts, we should be told about it.
+        for _ in 0..2 {
+            let service_process = ScopedServiceProcess::new(service_name);
+            let service = receiver
+                .recv_timeout(Duration::from_secs(5))
+                .expect("Did not get notified of the service");
+            assert_eq!(service.test().unwrap(), service_name);
+            drop(service_process);
+        }
+    }
+
+    /// The callback can unregister itself to be notified only once.
+    #[test]
+    fn service_notifications_unregister_in_callback() {
+        binder::ProcessState::start_thread_pool();
+
+        let service_name = "service_notifications_unregister_in_callback_test";
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let slot = Arc::new(Mutex::new(None));
+        let callback_slot = slot.clone();
+        let registration = binder::register_for_interface_notifications(
+            service_name,
+            move |service: Strong<dyn ITest>| {
+                drop(callback_slot.lock().unwrap().take());
+                let _ = sender.lock().unwrap().send(service);
+            },
+        )
+        .expect("Could not register for service notifications");
+        *slot.lock().unwrap() = Some(registration);
+
+        let service_process = ScopedServiceProcess::new(service_name);
+        let service = receiver
+            .recv_timeout(Duration::from_secs(5))
+            .expect("Did not get notified of the service");
+        assert_eq!(service.test().unwrap(), service_name);
+        assert!(slot.lock().unwrap().is_none());
+        drop(service_process);
+
+        let _service_process = ScopedServiceProcess::new(service_name);
+        assert!(receiver.recv_timeout(Duration::from_millis(500)).is_err());
+    }
+
+    #[tokio::test]
+    async fn service_notifications_async() {
+        let service_name = "service_notifications_async_test";
+        let _process = ScopedServiceProcess::new(service_name);
+        let mut services = binder_tokio::interface_notifications::<dyn IATest<Tokio>>(service_name)
+            .expect("Could not register for service notifications");
+        let service = services.next().await.expect("Did not get notified of the service");
+        assert_eq!(service.test().await.unwrap(), service_name);
+    }
+
+    /// On the host, every service runs with the context of the test process.
+    #[cfg(feature = "host")]
+    fn get_expected_selinux_context() -> String {
+        let context = std::fs::read("/proc/self/attr/current").unwrap_or_default();
+        let context: Vec<u8> = context.into_iter().take_while(|b| *b != 0 && *b != b'\n').collect();
+        if context.is_empty() {
+            "unconfined".to_owned()
+        } else {
+            String::from_utf8(context).expect("context was invalid UTF-8")
+        }
+    }
+
+    #[cfg(not(feature = "host"))]
     fn get_expected_selinux_context() -> &'static str {
         // unsafe {
         //     let mut out_ptr = ptr::null_mut();
@@ -700,6 +1004,58 @@ mod tests {
         bools.assert_dropped();
     }
 
+    /// Death notifications can be awaited, and are unlinked when dropped.
+    #[tokio::test]
+    async fn test_async_death_notifications() {
+        binder::ProcessState::start_thread_pool();
+
+        let first_name = "test_async_death_notifications_first";
+        let second_name = "test_async_death_notifications_second";
+        let first_process = ScopedServiceProcess::new(first_name);
+        let second_process = ScopedServiceProcess::new(second_name);
+        let first = binder::get_service(first_name).expect("Could not retrieve service");
+        let second = binder::get_service(second_name).expect("Could not retrieve service");
+
+        let died = binder_tokio::died(&first);
+        tokio::pin!(died);
+        let mut watcher = DeathWatcher::new();
+        watcher.watch(&first).unwrap();
+        watcher.watch(&second).unwrap();
+        assert!(watcher.is_watching(&second));
+
+        tokio::select! {
+            _ = &mut died => panic!("Service died too soon"),
+            _ = watcher.next() => panic!("Service died too soon"),
+            _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+        }
+        // Dropping a future unlinks it.
+        drop(binder_tokio::died(&second));
+
+        drop(first_process);
+        tokio::time::timeout(Duration::from_secs(1), died)
+            .await
+            .expect("Did not receive death notification")
+            .expect("Could not link to death");
+        let dead = tokio::time::timeout(Duration::from_secs(1), watcher.next()).await;
+        assert_eq!(dead.expect("Did not receive death notification"), Some(first.clone()));
+        assert!(!watcher.is_watching(&first));
+
+        // An unwatched binder isn't yielded.
+        assert!(watcher.unwatch(&second));
+        drop(second_process);
+        let dead = tokio::time::timeout(Duration::from_millis(100), watcher.next()).await;
+        assert!(dead.is_err(), "Received unexpected death notification after unwatching");
+
+        // Binders which are already dead complete right away.
+        tokio::time::timeout(Duration::from_secs(1), binder_tokio::died(&second))
+            .await
+            .expect("Dead binder did not complete")
+            .expect("Dead binder failed");
+        watcher.watch(&second).unwrap();
+        let dead = tokio::time::timeout(Duration::from_secs(1), watcher.next()).await;
+        assert_eq!(dead.unwrap(), Some(second));
+    }
+
     /// Dropping a remote handle should unregister any death notifications.
     #[test]
     fn test_death_notification_registration_lifetime() {
@@ -731,6 +1087,269 @@ mod tests {
         bools.assert_dropped();
     }
 
+    /// Start a process with a lazy service, which reports every call to its
+    /// active services callback and answers it with `on_clients`.
+    #[cfg(feature = "host")]
+    fn start_lazy_service<F>(
+        service_name: &'static str,
+        on_clients: F,
+    ) -> (binder::ServiceProcess, mpsc::Receiver<bool>)
+    where
+        F: Fn(bool) -> bool + Send + Sync + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let process = binder::ServiceProcess::spawn(move || {
+            binder::set_active_services_callback(move |has_clients| {
+                let handled = on_clients(has_clients);
+                let _ = sender.lock().unwrap().send(has_clients);
+                handled
+            });
+            let service =
+                BnTest::new_binder(TestService::new(service_name), BinderFeatures::default());
+            binder::register_lazy_service(service_name, service.as_binder())
+                .expect("Could not register service");
+            binder::ProcessState::join_thread_pool();
+        });
+        (process, receiver)
+    }
+
+    /// Test that a lazy service exits once it has no clients.
+    #[cfg(feature = "host")]
+    #[test]
+    fn lazy_service_shutdown() {
+        let service_name = "lazy_service_shutdown_test";
+        let (process, receiver) = start_lazy_service(service_name, |_| false);
+
+        let test_client: Strong<dyn ITest> =
+            binder::get_interface(service_name).expect("Did not get test service");
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)), Ok(true));
+        assert!(process.is_alive());
+        assert_eq!(test_client.test().unwrap(), service_name);
+
+        drop(test_client);
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)), Ok(false));
+        for _ in 0..50 {
+            if !process.is_alive() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        assert!(!process.is_alive(), "Service should have exited");
+        assert!(binder::check_service(service_name).is_none());
+    }
+
+    /// Test that a lazy service can unregister itself and change its mind.
+    #[cfg(feature = "host")]
+    #[test]
+    fn lazy_service_re_register() {
+        let service_name = "lazy_service_re_register_test";
+        let unregistered = Arc::new(Mutex::new(Vec::new()));
+        let (process, receiver) = start_lazy_service(service_name, {
+            let unregistered = unregistered.clone();
+            move |_| {
+                let success = binder::try_unregister_lazy_services();
+                unregistered.lock().unwrap().push(success);
+                if success {
+                    binder::re_register_lazy_services();
+                }
+                true
+            }
+        });
+
+        let test_client: Strong<dyn ITest> =
+            binder::get_interface(service_name).expect("Did not get test service");
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)), Ok(true));
+        drop(test_client);
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)), Ok(false));
+
+        // Unregistering fails while the service has a client.
+        assert_eq!(*unregistered.lock().unwrap(), [false, true]);
+        assert!(process.is_alive());
+        assert!(binder::check_service(service_name).is_some());
+    }
+
+    #[test]
+    fn stability_order() {
+        use binder::binder_impl::Stability;
+
+        assert!(Stability::Local < Stability::Vendor);
+        assert!(Stability::Vendor < Stability::System);
+        assert!(Stability::System < Stability::Vintf);
+
+        assert!(Stability::Vendor.can_be_called_from(Stability::Local));
+        assert!(Stability::Vendor.can_be_called_from(Stability::Vendor));
+        assert!(!Stability::Vendor.can_be_called_from(Stability::System));
+        assert!(!Stability::System.can_be_called_from(Stability::Vendor));
+        assert!(Stability::Vintf.can_be_called_from(Stability::System));
+        assert!(!Stability::Local.can_be_called_from(Stability::Vintf));
+    }
+
+    /// Test that a service downgraded to vendor stability can't be called from
+    /// the system partition.
+    #[cfg(feature = "host")]
+    #[test]
+    fn force_downgrade_stability() {
+        let service_name = "force_downgrade_stability_test";
+        let _process = binder::ServiceProcess::spawn(move || {
+            let service =
+                BnTest::new_binder(TestService::new(service_name), BinderFeatures::default());
+            let mut binder = service.as_binder();
+            binder.force_downgrade_to_vendor_stability().expect("Could not downgrade service");
+            binder::add_service(service_name, binder).expect("Could not register service");
+            binder::ProcessState::join_thread_pool();
+        });
+
+        let test_client: Strong<dyn ITest> =
+            binder::get_interface(service_name).expect("Did not get test service");
+        assert_eq!(test_client.test(), Err(StatusCode::BAD_TYPE));
+        assert_eq!(
+            test_client.as_binder().force_downgrade_to_system_stability(),
+            Err(StatusCode::INVALID_OPERATION)
+        );
+    }
+
+    #[test]
+    fn serve_polled_commands() {
+        // Polling is set up for a thread, so don't leave it to the test harness.
+        thread::spawn(|| {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("Could not create runtime");
+            runtime.block_on(async {
+                tokio::select! {
+                    result = binder_tokio::serve_polled_commands() => {
+                        panic!("Stopped serving polled commands: {:?}", result)
+                    }
+                    _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+                }
+            });
+        })
+        .join()
+        .expect("Polling thread panicked");
+    }
+
+    /// Testing binder interface for checking which thread handles transactions
+    #[binder::interface("android.binder.test.IPolledTest")]
+    pub trait IPolledTest {
+        /// Returns whether the transaction is handled on the polling thread
+        fn on_polling_thread(&self) -> binder::Result<bool>;
+    }
+
+    struct PolledTestService {
+        polling_thread: thread::ThreadId,
+    }
+
+    impl Interface for PolledTestService {}
+
+    impl IPolledTest for PolledTestService {
+        fn on_polling_thread(&self) -> binder::Result<bool> {
+            Ok(binder::is_handling_transaction() && thread::current().id() == self.polling_thread)
+        }
+    }
+
+    #[cfg(feature = "host")]
+    #[test]
+    fn serve_polled_commands_on_polling_thread() {
+        let service_name = "serve_polled_commands_test";
+        let _process = binder::ServiceProcess::spawn(move || {
+            let service = BnPolledTest::new_binder(
+                PolledTestService { polling_thread: thread::current().id() },
+                BinderFeatures::default(),
+            );
+            binder::add_service(service_name, service.as_binder())
+                .expect("Could not register service");
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("Could not create runtime");
+            // Only fails once the process is killed.
+            let _ = runtime.block_on(binder_tokio::serve_polled_commands());
+        });
+
+        let service: Strong<dyn IPolledTest> =
+            binder::get_interface(service_name).expect("Did not get polled service");
+        assert_eq!(service.on_polling_thread(), Ok(true));
+        // Calls from several threads are all handled on the polling thread.
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let service = service.clone();
+                thread::spawn(move || service.on_polling_thread())
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().expect("Client thread panicked"), Ok(true));
+        }
+    }
+
+    /// Callbacks are dropped from a `RemoteCallbackList` when their process
+    /// dies, whether or not the death notification arrived first.
+    #[test]
+    fn test_remote_callback_list() {
+        binder::ProcessState::start_thread_pool();
+
+        let first_name = "test_remote_callback_list_first";
+        let second_name = "test_remote_callback_list_second";
+        let first_process = ScopedServiceProcess::new(first_name);
+        let second_process = ScopedServiceProcess::new(second_name);
+        let first: Strong<dyn ITest> =
+            binder::get_interface(first_name).expect("Did not get first service");
+        let second: Strong<dyn ITest> =
+            binder::get_interface(second_name).expect("Did not get second service");
+        let local = BnTest::new_binder(
+            TestService::new("test_remote_callback_list_local"),
+            BinderFeatures::default(),
+        );
+
+        let callbacks = RemoteCallbackList::<dyn ITest, &str>::new();
+        callbacks.register_with_cookie(first.clone(), "first").unwrap();
+        callbacks.register_with_cookie(second.clone(), "second").unwrap();
+        callbacks.register_with_cookie(local.clone(), "local").unwrap();
+        callbacks.register_with_cookie(first.clone(), "first again").unwrap();
+        assert_eq!(callbacks.len(), 3);
+
+        let mut called = vec![];
+        let errors = callbacks.broadcast(|callback, cookie| {
+            called.push((callback.test()?, *cookie));
+            Ok(())
+        });
+        assert!(errors.is_empty());
+        called.sort();
+        assert_eq!(
+            called,
+            [
+                (first_name.to_owned(), "first again"),
+                ("test_remote_callback_list_local".to_owned(), "local"),
+                (second_name.to_owned(), "second"),
+            ]
+        );
+
+        let errors = callbacks.broadcast(|_, cookie| match *cookie {
+            "local" => Err(StatusCode::BAD_VALUE.into()),
+            _ => Ok(()),
+        });
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].1, "local");
+        assert_eq!(errors[0].2.transaction_error(), StatusCode::BAD_VALUE);
+
+        drop(first_process);
+        // Pause to ensure the death notification gets delivered
+        thread::sleep(Duration::from_secs(1));
+        assert_eq!(callbacks.len(), 2);
+        assert!(!callbacks.contains(&first));
+
+        // Without waiting, the callback may only be found dead by the broadcast.
+        drop(second_process);
+        let errors = callbacks.broadcast(|callback, _| Ok(callback.test().map(drop)?));
+        assert!(errors.is_empty());
+        assert_eq!(callbacks.len(), 1);
+
+        assert_eq!(callbacks.unregister(&local), Some("local"));
+        assert_eq!(callbacks.unregister(&local), None);
+        assert!(callbacks.is_empty());
+    }
+
     /// Test IBinder interface methods not exercised elsewhere.
     #[test]
     fn test_misc_ibinder() {
@@ -908,6 +1527,874 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "host")]
+    #[test]
+    fn transact_without_fds() {
+        let service = BnTest::new_binder(
+            TestService::new("testing_service_no_fds"),
+            BinderFeatures::default(),
+        );
+        let service = service.as_binder();
+
+        let mut parcel = service.prepare_transact().unwrap();
+        assert!(parcel.allows_fds());
+        assert!(parcel.write(&std::io::stdin().as_fd()).is_ok());
+
+        let mut parcel = service.prepare_transact_without_fds().unwrap();
+        assert!(!parcel.allows_fds());
+        assert_eq!(parcel.write(&std::io::stdin().as_fd()), Err(StatusCode::FDS_NOT_ALLOWED));
+        assert!(parcel.write(&None::<binder::ParcelFileDescriptor>).is_ok());
+    }
+
+    /// Testing binder interface for a service which only dumps
+    #[binder::interface("android.binder.test.IDumpTest")]
+    pub trait IDumpTest {}
+
+    struct DumpTestService;
+
+    impl Interface for DumpTestService {
+        fn dump(&self, file: &File, args: &[&CStr]) -> Result<(), StatusCode> {
+            Dumper::new(args)?
+                .section("service", |s| {
+                    s.field("name", "dump_test");
+                    if s.all() {
+                        s.field("owner", None::<&str>);
+                    }
+                })
+                .dump(file)
+        }
+    }
+
+    impl IDumpTest for DumpTestService {}
+
+    #[test]
+    fn dump_to_string() {
+        let service = BnDumpTest::new_binder(DumpTestService, BinderFeatures::default());
+        let binder = service.as_binder();
+        assert_eq!(
+            binder::dump_to_string(&binder, &[]).as_deref(),
+            Ok("service:\n  name: dump_test\n")
+        );
+        assert_eq!(
+            binder::dump_to_string(&binder, &["--json", "-a"]).as_deref(),
+            Ok("{\"service\":{\"name\":\"dump_test\",\"owner\":null}}\n")
+        );
+        assert_eq!(
+            binder::dump_to_string(&binder, &["--section", "missing"]),
+            Err(StatusCode::BAD_VALUE)
+        );
+    }
+
+    #[cfg(feature = "host")]
+    #[test]
+    fn shell_command() {
+        let service_name = "rust_test_shell_command";
+        let _process = ScopedServiceProcess::new(service_name);
+        let mut remote = binder::get_service(service_name).expect("Did not get service");
+
+        let (mut stdout, stdout_writer) = UnixStream::pair().unwrap();
+        let (mut stderr, stderr_writer) = UnixStream::pair().unwrap();
+        stdout.set_nonblocking(true).unwrap();
+        stderr.set_nonblocking(true).unwrap();
+        let read = |stream: &mut UnixStream| {
+            let mut buf = [0; 64];
+            let len = stream.read(&mut buf).unwrap_or(0);
+            String::from_utf8_lossy(&buf[..len]).into_owned()
+        };
+
+        let stdin = File::open("/dev/null").unwrap();
+        assert_eq!(
+            remote.shell_command(&stdin, &stdout_writer, &stderr_writer, &["echo", "hi", "there"]),
+            Ok(())
+        );
+        assert_eq!(read(&mut stdout), "hi there\n");
+
+        assert_eq!(
+            remote.shell_command(&stdin, &stdout_writer, &stderr_writer, &["frobnicate"]),
+            Err(StatusCode::BAD_VALUE)
+        );
+        assert_eq!(read(&mut stderr), "usage: echo [-n] <words>...\n");
+        assert_eq!(read(&mut stdout), "");
+        assert_eq!(
+            remote.shell_command(&stdin, &stdout_writer, &stderr_writer, &["echo", "a\0b"]),
+            Err(StatusCode::UNEXPECTED_NULL)
+        );
+
+        // Local binders get the command too.
+        let mut service = BnTest::new_binder(
+            TestService::new("testing_service_shell_command"),
+            BinderFeatures::default(),
+        )
+        .as_binder();
+        assert_eq!(
+            service.shell_command(&stdin, &stdout_writer, &stderr_writer, &["echo", "-n", "hi"]),
+            Ok(())
+        );
+        assert_eq!(read(&mut stdout), "hi");
+
+        // Services that don't handle shell commands reject them.
+        let mut service =
+            BnMacroTest::new_binder(MacroTestService::default(), BinderFeatures::default())
+                .as_binder();
+        assert_eq!(
+            service.shell_command(&stdin, &stdout_writer, &stderr_writer, &["echo"]),
+            Err(StatusCode::INVALID_OPERATION)
+        );
+    }
+
+    #[test]
+    fn scheduler_policy() {
+        assert_eq!(
+            MinSchedulerPolicy::new(SchedulerPolicy::Normal, 20),
+            Err(StatusCode::BAD_VALUE)
+        );
+        assert_eq!(MinSchedulerPolicy::new(SchedulerPolicy::Fifo, 0), Err(StatusCode::BAD_VALUE));
+        assert_eq!(
+            MinSchedulerPolicy::new(SchedulerPolicy::RoundRobin, 100),
+            Err(StatusCode::BAD_VALUE)
+        );
+
+        let policy = MinSchedulerPolicy::new(SchedulerPolicy::Fifo, 10).unwrap();
+        assert_eq!(policy.policy(), SchedulerPolicy::Fifo);
+        assert_eq!(policy.priority(), 10);
+
+        let service = BnTest::new_binder(
+            TestService::new("scheduler_policy"),
+            BinderFeatures {
+                min_scheduler_policy: Some(policy),
+                inherit_rt: true,
+                ..BinderFeatures::default()
+            },
+        );
+        assert_eq!(service.test().unwrap(), "scheduler_policy");
+    }
+
+    #[test]
+    fn fuzz_service() {
+        let service =
+            BnTest::new_binder(TestService::new("fuzz_service"), BinderFeatures::default());
+        let data: Vec<u8> =
+            (0..4096u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect();
+        binder_random_parcel::fuzz_service(&mut service.as_binder(), &data);
+        assert_eq!(service.test().unwrap(), "fuzz_service");
+    }
+
+    /// Testing binder interface declared with `#[binder::interface]`
+    #[binder::interface("android.binder.test.IMacroTest")]
+    pub trait IMacroTest {
+        /// Returns `value`
+        fn echo(&self, value: &str) -> binder::Result<String>;
+
+        /// Returns the sum of `values` and `bias`
+        fn sum(&self, values: &[i32], bias: i64) -> binder::Result<i64>;
+
+        /// Returns a greeting for `name`, if any
+        fn greet(&self, name: Option<&str>) -> binder::Result<Option<String>>;
+
+        /// Appends the number of `values` to them, and returns it in `count` too
+        fn push_len(&self, values: &mut Vec<i32>, #[out] count: &mut i32) -> binder::Result<()>;
+
+        /// Always fails with a service-specific error
+        fn fail(&self) -> binder::Result<bool>;
+
+        /// Remembers `value`, without waiting for the service
+        #[oneway]
+        fn set_last(&self, value: i32) -> binder::Result<()>;
+
+        /// Returns the last value passed to `set_last`
+        fn last(&self) -> binder::Result<i32>;
+    }
+
+    #[derive(Default)]
+    struct MacroTestService {
+        last: Mutex<i32>,
+    }
+
+    impl Interface for MacroTestService {}
+
+    impl IMacroTest for MacroTestService {
+        fn echo(&self, value: &str) -> binder::Result<String> {
+            Ok(value.to_owned())
+        }
+
+        fn sum(&self, values: &[i32], bias: i64) -> binder::Result<i64> {
+            Ok(values.iter().map(|&v| i64::from(v)).sum::<i64>() + bias)
+        }
+
+        fn greet(&self, name: Option<&str>) -> binder::Result<Option<String>> {
+            Ok(name.map(|name| format!("Hello, {}!", name)))
+        }
+
+        fn push_len(&self, values: &mut Vec<i32>, count: &mut i32) -> binder::Result<()> {
+            *count = values.len() as i32;
+            values.push(*count);
+            Ok(())
+        }
+
+        fn fail(&self) -> binder::Result<bool> {
+            let cause = std::io::Error::new(std::io::ErrorKind::Other, "disk full");
+            Err(binder::Status::builder().service_specific_error(42).source(cause).build())
+        }
+
+        fn set_last(&self, value: i32) -> binder::Result<()> {
+            *self.last.lock().unwrap() = value;
+            Ok(())
+        }
+
+        fn last(&self) -> binder::Result<i32> {
+            Ok(*self.last.lock().unwrap())
+        }
+    }
+
+    #[binder::async_trait]
+    impl IMacroTestAsyncServer for MacroTestService {
+        async fn echo(&self, value: &str) -> binder::Result<String> {
+            IMacroTest::echo(self, value)
+        }
+
+        async fn sum(&self, values: &[i32], bias: i64) -> binder::Result<i64> {
+            IMacroTest::sum(self, values, bias)
+        }
+
+        async fn greet(&self, name: Option<&str>) -> binder::Result<Option<String>> {
+            IMacroTest::greet(self, name)
+        }
+
+        async fn push_len(&self, values: &mut Vec<i32>, count: &mut i32) -> binder::Result<()> {
+            IMacroTest::push_len(self, values, count)
+        }
+
+        async fn fail(&self) -> binder::Result<bool> {
+            IMacroTest::fail(self)
+        }
+
+        async fn set_last(&self, value: i32) -> binder::Result<()> {
+            IMacroTest::set_last(self, value)
+        }
+
+        async fn last(&self) -> binder::Result<i32> {
+            IMacroTest::last(self)
+        }
+    }
+
+    fn check_macro_interface(service: &dyn IMacroTest) {
+        assert_eq!(service.echo("echo").unwrap(), "echo");
+        assert_eq!(service.sum(&[1, 2, 3], 10).unwrap(), 16);
+        assert_eq!(service.greet(Some("binder")).unwrap().as_deref(), Some("Hello, binder!"));
+        assert_eq!(service.greet(None).unwrap(), None);
+        let mut values = vec![7, 8];
+        let mut count = -1;
+        service.push_len(&mut values, &mut count).unwrap();
+        assert_eq!((values, count), (vec![7, 8, 2], 2));
+        let status = service.fail().unwrap_err();
+        assert_eq!(status.service_specific_error(), 42);
+        assert_eq!(status.message().as_deref(), Some("disk full"));
+        service.set_last(5).unwrap();
+        assert_eq!(service.last().unwrap(), 5);
+    }
+
+    #[test]
+    fn interface_macro() {
+        assert_eq!(BnMacroTest::TRANSACTION_echo, FIRST_CALL_TRANSACTION);
+        assert_eq!(BnMacroTest::TRANSACTION_last, FIRST_CALL_TRANSACTION + 6);
+        assert_eq!(<BnMacroTest as Remotable>::get_descriptor(), "android.binder.test.IMacroTest");
+
+        let service =
+            BnMacroTest::new_binder(MacroTestService::default(), BinderFeatures::default());
+        check_macro_interface(&*service);
+
+        let runtime = TokioRuntime(tokio::runtime::Runtime::new().unwrap());
+        let service = BnMacroTest::new_async_binder(
+            MacroTestService::default(),
+            runtime,
+            BinderFeatures::default(),
+        );
+        check_macro_interface(&*service);
+
+        // Transactions are laid out the way AIDL lays them out.
+        let reply = service
+            .as_binder()
+            .transact(BnMacroTest::TRANSACTION_push_len, 0, |mut data| {
+                // `count` is an out argument, so isn't sent.
+                data.write(&vec![1, 2, 3])
+            })
+            .unwrap();
+        assert!(reply.read::<binder::Status>().unwrap().is_ok());
+        assert_eq!(reply.read::<Vec<i32>>().unwrap(), [1, 2, 3, 3]);
+        assert_eq!(reply.read::<i32>().unwrap(), 3);
+    }
+
+    #[cfg(feature = "host")]
+    fn spawn_macro_test_service(service_name: &'static str) -> binder::ServiceProcess {
+        binder::ServiceProcess::spawn(move || {
+            let service =
+                BnMacroTest::new_binder(MacroTestService::default(), BinderFeatures::default());
+            binder::add_service(service_name, service.as_binder())
+                .expect("Could not register service");
+            binder::ProcessState::join_thread_pool();
+        })
+    }
+
+    #[cfg(feature = "host")]
+    #[test]
+    fn interface_macro_remote() {
+        let service_name = "interface_macro_remote";
+        let _process = spawn_macro_test_service(service_name);
+        let service: Strong<dyn IMacroTest> =
+            binder::get_interface(service_name).expect("Did not get test service");
+        assert!(service.as_binder().is_remote());
+        check_macro_interface(&*service);
+    }
+
+    #[cfg(feature = "host")]
+    #[tokio::test]
+    async fn interface_macro_remote_async() {
+        let service_name = "interface_macro_remote_async";
+        let _process = spawn_macro_test_service(service_name);
+        let service: Strong<dyn IMacroTestAsync<Tokio>> =
+            binder_tokio::get_interface(service_name).await.expect("Did not get test service");
+        assert_eq!(service.echo("echo").await.unwrap(), "echo");
+        assert_eq!(service.greet(None).await.unwrap(), None);
+        let mut values = vec![];
+        let mut count = -1;
+        service.push_len(&mut values, &mut count).await.unwrap();
+        assert_eq!((values, count), (vec![0], 0));
+        assert_eq!(service.fail().await.unwrap_err().service_specific_error(), 42);
+        service.set_last(9).await.unwrap();
+        assert_eq!(service.last().await.unwrap(), 9);
+    }
+
+    #[test]
+    fn derive_parcelable() {
+        use binder::binder_impl::{Deserialize, Parcel, ParcelableMetadata, Serialize, Stability};
+        use binder::Parcelable;
+
+        #[derive(Debug, Default, PartialEq, Parcelable, Serialize, Deserialize)]
+        #[parcel(descriptor = "android.binder.test.Point")]
+        struct PointV1 {
+            x: i32,
+            y: i32,
+        }
+
+        #[derive(Debug, Default, PartialEq, Parcelable, Serialize, Deserialize)]
+        #[parcel(descriptor = "android.binder.test.Point", stability = "vintf")]
+        struct PointV2 {
+            x: i32,
+            y: i32,
+            #[parcel(default = Some("origin".to_owned()))]
+            label: Option<String>,
+            #[parcel(skip)]
+            cached: u64,
+        }
+
+        #[derive(Debug, Default, PartialEq, Parcelable, Serialize, Deserialize)]
+        struct Pair(String, #[parcel(default)] Vec<i32>);
+
+        assert_eq!(PointV2::get_descriptor(), "android.binder.test.Point");
+        assert_eq!(PointV2::default().get_stability(), Stability::Vintf);
+        assert_eq!(PointV1::default().get_stability(), Stability::Local);
+        assert!(Pair::get_descriptor().ends_with("::Pair"));
+
+        let point = PointV2 { x: 1, y: 2, label: Some("p".to_owned()), cached: 42 };
+        let mut parcel = Parcel::new();
+        parcel.write(&point).unwrap();
+        // An older reader skips the fields it doesn't know about.
+        parcel.write(&7i32).unwrap();
+        unsafe {
+            parcel.set_data_position(0).unwrap();
+        }
+        assert_eq!(parcel.read::<PointV1>().unwrap(), PointV1 { x: 1, y: 2 });
+        assert_eq!(parcel.read::<i32>().unwrap(), 7);
+        unsafe {
+            parcel.set_data_position(0).unwrap();
+        }
+        assert_eq!(
+            parcel.read::<PointV2>().unwrap(),
+            PointV2 { x: 1, y: 2, label: Some("p".to_owned()), cached: 0 }
+        );
+
+        // A newer reader fills in what an older writer didn't send.
+        let mut parcel = Parcel::new();
+        parcel.write(&PointV1 { x: 3, y: 4 }).unwrap();
+        unsafe {
+            parcel.set_data_position(0).unwrap();
+        }
+        assert_eq!(
+            parcel.read::<PointV2>().unwrap(),
+            PointV2 { x: 3, y: 4, label: Some("origin".to_owned()), cached: 0 }
+        );
+        let mut pair = Pair("a".to_owned(), vec![1]);
+        let mut empty = Parcel::new();
+        empty.sized_write(|subparcel| subparcel.write("b")).unwrap();
+        unsafe {
+            empty.set_data_position(0).unwrap();
+        }
+        pair.read_from_parcel(empty.borrowed_ref()).unwrap();
+        assert_eq!(pair, Pair("b".to_owned(), vec![]));
+    }
+
+    /// Types compiled from `aidl/` by `binder_aidl`, see `build.rs`.
+    mod aidl {
+        include!(concat!(env!("OUT_DIR"), "/aidl.rs"));
+    }
+
+    use aidl::android::binder::test as aidl_test;
+    use aidl_test::common::Label::Label;
+    use aidl_test::Color::Color;
+    use aidl_test::Extendable::Extendable;
+    use aidl_test::IAidlCallback::{BnAidlCallback, IAidlCallback};
+    use aidl_test::IAidlTest::{BnAidlTest, IAidlTest, IAidlTestAsync, IAidlTestAsyncServer};
+    use aidl_test::IOutArrays::{BnOutArrays, BpOutArrays, IOutArrays};
+    use aidl_test::Point::Point;
+    use aidl_test::Shape::Shape;
+
+    #[derive(Default)]
+    struct AidlTestService {
+        callback: Mutex<Option<Strong<dyn IAidlCallback>>>,
+    }
+
+    impl Interface for AidlTestService {}
+
+    impl IAidlTest for AidlTestService {
+        fn echo(&self, value: &str) -> binder::Result<String> {
+            Ok(value.to_owned())
+        }
+
+        fn greet(&self, name: Option<&str>) -> binder::Result<Option<String>> {
+            Ok(name.map(|name| format!("Hello, {}!", name)))
+        }
+
+        fn r#move(&self, point: &Point, dx: i32, dy: i32) -> binder::Result<Point> {
+            Ok(Point { x: point.x + dx, y: point.y + dy, ..point.clone() })
+        }
+
+        fn grow(&self, shape: &Shape) -> binder::Result<Shape> {
+            Ok(match shape {
+                Shape::Radius(radius) => Shape::Radius(radius * 2),
+                Shape::Polygon(points) => Shape::Polygon(
+                    points.iter().map(|p| Point { x: p.x * 2, y: p.y * 2, ..p.clone() }).collect(),
+                ),
+                Shape::Name(name) => Shape::Name(name.repeat(2)),
+            })
+        }
+
+        fn next(&self, color: Color) -> binder::Result<Color> {
+            Ok(match color {
+                Color::RED => Color::GREEN,
+                Color::GREEN => Color::BLUE,
+                _ => Color::RED,
+            })
+        }
+
+        fn reverse(
+            &self,
+            values: &mut Vec<i32>,
+            copy: &mut Vec<i32>,
+            label: &mut Label,
+        ) -> binder::Result<()> {
+            values.reverse();
+            for (copy, value) in copy.iter_mut().zip(values.iter()) {
+                *copy = *value;
+            }
+            label.text = format!("{} values", values.len());
+            Ok(())
+        }
+
+        fn split(&self, text: &str) -> binder::Result<Vec<String>> {
+            Ok(text.split_whitespace().map(str::to_owned).collect())
+        }
+
+        fn maybe(&self, some: bool) -> binder::Result<Option<Vec<i32>>> {
+            Ok(some.then(|| vec![1, 2]))
+        }
+
+        fn count(&self, points: Option<&[Option<Point>]>) -> binder::Result<i32> {
+            Ok(points.map_or(-1, |points| points.iter().flatten().count() as i32))
+        }
+
+        fn register(&self, callback: &Strong<dyn IAidlCallback>) -> binder::Result<()> {
+            *self.callback.lock().unwrap() = Some(callback.clone());
+            Ok(())
+        }
+
+        fn notify(&self, event: i32) -> binder::Result<()> {
+            match &*self.callback.lock().unwrap() {
+                Some(callback) => callback.onEvent(event),
+                None => Ok(()),
+            }
+        }
+
+        fn echoBinder(&self, binder: Option<&SpIBinder>) -> binder::Result<Option<SpIBinder>> {
+            Ok(binder.cloned())
+        }
+
+        fn lengths(&self, words: &[String]) -> binder::Result<HashMap<String, i32>> {
+            Ok(words.iter().map(|word| (word.clone(), word.len() as i32)).collect())
+        }
+
+        fn chunks(&self, values: &[i32], size: i32) -> binder::Result<Vec<Vec<i32>>> {
+            Ok(values.chunks(size as usize).map(<[i32]>::to_vec).collect())
+        }
+
+        fn tally(
+            &self,
+            points: Option<&HashMap<String, Point>>,
+            totals: &mut HashMap<String, i32>,
+        ) -> binder::Result<()> {
+            for (name, point) in points.into_iter().flatten() {
+                *totals.entry(name.clone()).or_default() += point.x;
+            }
+            Ok(())
+        }
+
+        fn r#type(&self) -> binder::Result<String> {
+            Ok("aidl".to_owned())
+        }
+    }
+
+    #[binder::async_trait]
+    impl IAidlTestAsyncServer for AidlTestService {
+        async fn echo(&self, value: &str) -> binder::Result<String> {
+            IAidlTest::echo(self, value)
+        }
+
+        async fn greet(&self, name: Option<&str>) -> binder::Result<Option<String>> {
+            IAidlTest::greet(self, name)
+        }
+
+        async fn r#move(&self, point: &Point, dx: i32, dy: i32) -> binder::Result<Point> {
+            IAidlTest::r#move(self, point, dx, dy)
+        }
+
+        async fn grow(&self, shape: &Shape) -> binder::Result<Shape> {
+            IAidlTest::grow(self, shape)
+        }
+
+        async fn next(&self, color: Color) -> binder::Result<Color> {
+            IAidlTest::next(self, color)
+        }
+
+        async fn reverse(
+            &self,
+            values: &mut Vec<i32>,
+            copy: &mut Vec<i32>,
+            label: &mut Label,
+        ) -> binder::Result<()> {
+            IAidlTest::reverse(self, values, copy, label)
+        }
+
+        async fn split(&self, text: &str) -> binder::Result<Vec<String>> {
+            IAidlTest::split(self, text)
+        }
+
+        async fn maybe(&self, some: bool) -> binder::Result<Option<Vec<i32>>> {
+            IAidlTest::maybe(self, some)
+        }
+
+        async fn count(&self, points: Option<&[Option<Point>]>) -> binder::Result<i32> {
+            IAidlTest::count(self, points)
+        }
+
+        async fn register(&self, callback: &Strong<dyn IAidlCallback>) -> binder::Result<()> {
+            IAidlTest::register(self, callback)
+        }
+
+        async fn notify(&self, event: i32) -> binder::Result<()> {
+            IAidlTest::notify(self, event)
+        }
+
+        async fn echoBinder(
+            &self,
+            binder: Option<&SpIBinder>,
+        ) -> binder::Result<Option<SpIBinder>> {
+            IAidlTest::echoBinder(self, binder)
+        }
+
+        async fn lengths(&self, words: &[String]) -> binder::Result<HashMap<String, i32>> {
+            IAidlTest::lengths(self, words)
+        }
+
+        async fn chunks(&self, values: &[i32], size: i32) -> binder::Result<Vec<Vec<i32>>> {
+            IAidlTest::chunks(self, values, size)
+        }
+
+        async fn tally(
+            &self,
+            points: Option<&HashMap<String, Point>>,
+            totals: &mut HashMap<String, i32>,
+        ) -> binder::Result<()> {
+            IAidlTest::tally(self, points, totals)
+        }
+
+        async fn r#type(&self) -> binder::Result<String> {
+            IAidlTest::r#type(self)
+        }
+    }
+
+    struct AidlCallback(Mutex<mpsc::Sender<i32>>);
+
+    impl Interface for AidlCallback {}
+
+    impl IAidlCallback for AidlCallback {
+        fn onEvent(&self, event: i32) -> binder::Result<()> {
+            self.0.lock().unwrap().send(event).unwrap();
+            Ok(())
+        }
+    }
+
+    fn check_aidl_interface(service: &dyn IAidlTest) {
+        assert_eq!(service.echo("echo").unwrap(), "echo");
+        assert_eq!(service.greet(Some("aidl")).unwrap().as_deref(), Some("Hello, aidl!"));
+        assert_eq!(service.greet(None).unwrap(), None);
+        let point = Point {
+            label: Some(Label::default()),
+            palette: HashMap::from([("sky".to_owned(), Color::BLUE)]),
+            ..Point::default()
+        };
+        assert_eq!(service.r#move(&point, 1, 2).unwrap(), Point { x: 1, y: 1, ..point.clone() });
+        assert_eq!(service.grow(&Shape::default()).unwrap(), Shape::Radius(2));
+        assert_eq!(
+            service.grow(&Shape::Polygon(vec![point.clone()])).unwrap(),
+            Shape::Polygon(vec![Point { x: 0, y: -2, ..point.clone() }])
+        );
+        assert_eq!(
+            service.grow(&Shape::Name("ab".to_owned())).unwrap(),
+            Shape::Name("abab".into())
+        );
+        assert_eq!(service.next(Color::GREEN).unwrap(), Color::BLUE);
+
+        let mut values = vec![1, 2, 3];
+        let mut copy = vec![0; 2];
+        let mut label = Label::default();
+        service.reverse(&mut values, &mut copy, &mut label).unwrap();
+        assert_eq!((values, copy), (vec![3, 2, 1], vec![3, 2]));
+        // `out` parcelables start out with their defaults on the service side.
+        assert_eq!(label, Label { text: "3 values".to_owned(), ..Label::default() });
+
+        assert_eq!(service.split("a b  c").unwrap(), ["a", "b", "c"]);
+        assert_eq!(service.maybe(true).unwrap(), Some(vec![1, 2]));
+        assert_eq!(service.maybe(false).unwrap(), None);
+        assert_eq!(service.count(Some(&[None, Some(point.clone()), Some(point)])).unwrap(), 2);
+        assert_eq!(service.count(None).unwrap(), -1);
+
+        let (sender, receiver) = mpsc::channel();
+        let callback =
+            BnAidlCallback::new_binder(AidlCallback(Mutex::new(sender)), BinderFeatures::default());
+        service.register(&callback).unwrap();
+        service.notify(7).unwrap();
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)), Ok(7));
+
+        assert_eq!(service.echoBinder(None).unwrap(), None);
+        let binder = callback.as_binder();
+        assert_eq!(service.echoBinder(Some(&binder)).unwrap(), Some(binder));
+
+        let words = ["a".to_owned(), "bcd".to_owned()];
+        assert_eq!(
+            service.lengths(&words).unwrap(),
+            HashMap::from([("a".to_owned(), 1), ("bcd".to_owned(), 3)])
+        );
+        assert_eq!(service.chunks(&[1, 2, 3], 2).unwrap(), [vec![1, 2], vec![3]]);
+        let points = HashMap::from([
+            ("a".to_owned(), Point { x: 2, ..Point::default() }),
+            ("b".to_owned(), Point { x: 5, ..Point::default() }),
+        ]);
+        let mut totals = HashMap::from([("a".to_owned(), 1)]);
+        service.tally(Some(&points), &mut totals).unwrap();
+        service.tally(None, &mut totals).unwrap();
+        assert_eq!(totals, HashMap::from([("a".to_owned(), 3), ("b".to_owned(), 5)]));
+
+        assert_eq!(service.r#type().unwrap(), "aidl");
+    }
+
+    #[test]
+    // The constants are checked to have the values the generator computed.
+    #[allow(clippy::assertions_on_constants)]
+    fn aidl_types() {
+        use binder::binder_impl::{Parcel, ParcelableMetadata, Stability};
+
+        assert_eq!(aidl_test::IAidlTest::VERSION, 3);
+        assert_eq!(aidl_test::IAidlTest::BIG, 1 << 40);
+        assert_eq!(aidl_test::IAidlTest::GREETING, "Hello, AIDL");
+        assert!(aidl_test::IAidlTest::NEW);
+        assert_eq!(aidl_test::IAidlTest::ALL, -1);
+        assert_eq!(aidl_test::Point::DIMENSIONS, 2);
+        assert_eq!(aidl_test::Point::NAME, "point");
+        assert_eq!(Color::enum_values(), [Color(1), Color(2), Color(16)]);
+        assert_eq!(aidl_test::IAidlCallback::transactions::onEvent, FIRST_CALL_TRANSACTION + 10);
+        assert_eq!(<BnAidlTest as Remotable>::get_descriptor(), "android.binder.test.IAidlTest");
+
+        let point = Point::default();
+        assert_eq!((point.x, point.y, point.scale, point.color), (0, -1, 1.5, Color::GREEN));
+        assert_eq!((point.name, point.label), (None, None));
+        assert_eq!(
+            (point.bytes, point.pair, point.tags),
+            (vec![1, 255], [3, 4], vec!["a".to_owned(), "b".into()])
+        );
+        let label = Label::default();
+        assert_eq!((label.text.as_str(), label.color, label.mask), ("label", Color::BLUE, -1));
+        assert_eq!((label.initial, label.visible, label.size), (u16::from(b'L'), true, 12.0));
+        assert_eq!(
+            <Label as ParcelableMetadata>::get_descriptor(),
+            "android.binder.test.common.Label"
+        );
+
+        let extendable = Extendable::default();
+        assert_eq!(extendable.id, 7);
+        assert_eq!(extendable.get_stability(), Stability::Vintf);
+
+        let mut parcel = Parcel::new();
+        let shape = Shape::Polygon(vec![Point { x: 5, ..Point::default() }]);
+        parcel.write(&shape).unwrap();
+        parcel.write(&extendable).unwrap();
+        // A non-null union whose tag is past the last field.
+        parcel.write(&1i32).unwrap();
+        parcel.write(&(aidl_test::Shape::Tag::name + 1)).unwrap();
+        unsafe {
+            parcel.set_data_position(0).unwrap();
+        }
+        assert_eq!(parcel.read::<Shape>().unwrap(), shape);
+        assert_eq!(parcel.read::<Extendable>().unwrap().id, 7);
+        assert_eq!(parcel.read::<Shape>(), Err(StatusCode::BAD_VALUE));
+    }
+
+    #[test]
+    fn aidl_interface() {
+        let service = BnAidlTest::new_binder(AidlTestService::default(), BinderFeatures::default());
+        check_aidl_interface(&*service);
+
+        let runtime = TokioRuntime(tokio::runtime::Runtime::new().unwrap());
+        let service = BnAidlTest::new_async_binder(
+            AidlTestService::default(),
+            runtime,
+            BinderFeatures::default(),
+        );
+        check_aidl_interface(&*service);
+    }
+
+    #[cfg(feature = "host")]
+    fn spawn_aidl_test_service(service_name: &'static str) -> binder::ServiceProcess {
+        binder::ServiceProcess::spawn(move || {
+            let service =
+                BnAidlTest::new_binder(AidlTestService::default(), BinderFeatures::default());
+            binder::add_service(service_name, service.as_binder())
+                .expect("Could not register service");
+            binder::ProcessState::join_thread_pool();
+        })
+    }
+
+    #[cfg(feature = "host")]
+    #[test]
+    fn aidl_interface_remote() {
+        let service_name = "aidl_interface_remote";
+        let _process = spawn_aidl_test_service(service_name);
+        let service: Strong<dyn IAidlTest> =
+            binder::get_interface(service_name).expect("Did not get test service");
+        assert!(service.as_binder().is_remote());
+        check_aidl_interface(&*service);
+    }
+
+    #[cfg(feature = "host")]
+    #[tokio::test]
+    async fn aidl_interface_remote_async() {
+        let service_name = "aidl_interface_remote_async";
+        let _process = spawn_aidl_test_service(service_name);
+        let service: Strong<dyn IAidlTestAsync<Tokio>> =
+            binder_tokio::get_interface(service_name).await.expect("Did not get test service");
+        assert_eq!(service.echo("echo").await.unwrap(), "echo");
+        let mut values = vec![1, 2];
+        let mut copy = vec![0; 2];
+        let mut label = Label::default();
+        service.reverse(&mut values, &mut copy, &mut label).await.unwrap();
+        assert_eq!((values, copy), (vec![2, 1], vec![2, 1]));
+        assert_eq!(service.grow(&Shape::Radius(2)).await.unwrap(), Shape::Radius(4));
+        service.notify(1).await.unwrap();
+    }
+
+    /// `IOutArrays` declared with `#[binder::interface]`, for checking that
+    /// both generators agree on the wire.
+    #[binder::interface("android.binder.test.IOutArrays")]
+    pub trait IMacroOutArrays {
+        /// Sets each of `values` to `value`, keeping as many as the caller has
+        fn fill(&self, value: i32, #[out] values: &mut Vec<i32>) -> binder::Result<()>;
+    }
+
+    struct OutArraysService;
+
+    impl Interface for OutArraysService {}
+
+    impl IOutArrays for OutArraysService {
+        fn fill(&self, value: i32, values: &mut Vec<i32>) -> binder::Result<()> {
+            values.iter_mut().for_each(|v| *v = value);
+            Ok(())
+        }
+    }
+
+    impl IMacroOutArrays for OutArraysService {
+        fn fill(&self, value: i32, values: &mut Vec<i32>) -> binder::Result<()> {
+            IOutArrays::fill(self, value, values)
+        }
+    }
+
+    #[test]
+    fn out_arrays_across_generators() {
+        use binder::binder_impl::Proxy;
+
+        let service = BnOutArrays::new_binder(OutArraysService, BinderFeatures::default());
+        let proxy = BpMacroOutArrays::from_binder(service.as_binder()).unwrap();
+        let mut values = vec![0; 3];
+        IMacroOutArrays::fill(&proxy, 7, &mut values).unwrap();
+        assert_eq!(values, [7, 7, 7]);
+
+        let service = BnMacroOutArrays::new_binder(OutArraysService, BinderFeatures::default());
+        let proxy = BpOutArrays::from_binder(service.as_binder()).unwrap();
+        let mut values = vec![0; 2];
+        IOutArrays::fill(&proxy, 5, &mut values).unwrap();
+        assert_eq!(values, [5, 5]);
+        let mut values = vec![];
+        IOutArrays::fill(&proxy, 5, &mut values).unwrap();
+        assert!(values.is_empty());
+    }
+
+    #[test]
+    fn binder_union() {
+        use binder::binder_impl::{Parcel, ParcelableMetadata, Stability};
+
+        assert_eq!(TestUnion::default(), TestUnion::Number(42));
+        assert_eq!((TestUnionTag::number, TestUnionTag::values), (0, 2));
+        assert_eq!(<TestUnion as ParcelableMetadata>::get_descriptor(), "android.os.TestUnion");
+        assert_eq!(TestUnion::default().get_stability(), Stability::Vintf);
+
+        let unions = [
+            TestUnion::Number(-1),
+            TestUnion::Text(None),
+            TestUnion::Text(Some("text".to_owned())),
+            TestUnion::Values(vec![TestEnum::BAR, TestEnum::FOO]),
+        ];
+        let mut parcel = Parcel::new();
+        for union in &unions {
+            parcel.write(union).unwrap();
+        }
+        parcel.write(&Some(unions[1].clone())).unwrap();
+        parcel.write(&None::<TestUnion>).unwrap();
+        // A non-null union with the tag written first, then the value.
+        parcel.write(&1i32).unwrap();
+        parcel.write(&TestUnionTag::values).unwrap();
+        parcel.write(&[TestEnum::BAZ][..]).unwrap();
+        // And one with an unknown tag.
+        parcel.write(&1i32).unwrap();
+        parcel.write(&3i32).unwrap();
+        unsafe {
+            parcel.set_data_position(0).unwrap();
+        }
+        for union in &unions {
+            assert_eq!(&parcel.read::<TestUnion>().unwrap(), union);
+        }
+        assert_eq!(parcel.read::<Option<TestUnion>>().unwrap(), Some(unions[1].clone()));
+        assert_eq!(parcel.read::<Option<TestUnion>>().unwrap(), None);
+        assert_eq!(parcel.read::<TestUnion>().unwrap(), TestUnion::Values(vec![TestEnum::BAZ]));
+        assert_eq!(parcel.read::<TestUnion>(), Err(StatusCode::BAD_VALUE));
+    }
+
     #[test]
     fn get_is_handling_transaction() {
         let service_name = "get_is_handling_transaction";